@@ -1,7 +1,7 @@
 use near_accounts::Account;
 use near_crypto::InMemorySigner;
 use near_primitives::types::Balance;
-use near_providers::JsonRpcProvider;
+use near_providers::{JsonRpcProvider, Network};
 use std::sync::Arc;
 mod utils;
 use near_primitives::types::AccountId;
@@ -13,10 +13,10 @@ async fn add_full_access() -> Result<(), Box<dyn std::error::Error>> {
 
     let new_secret_key = near_crypto::SecretKey::from_random(near_crypto::KeyType::ED25519);
 
-    let provider = Arc::new(JsonRpcProvider::new("https://rpc.testnet.near.org"));
+    let provider = Arc::new(JsonRpcProvider::for_network(&Network::Testnet));
     let signer = Arc::new(signer);
 
-    let account = Account::new(signer_account_id, signer, provider);
+    let account = Account::new(signer_account_id, signer, provider).with_network(Network::Testnet);
 
     let result = account
         .add_key(new_secret_key.public_key(), None, None, None)
@@ -39,10 +39,10 @@ async fn add_function_call_key() -> Result<(), Box<dyn std::error::Error>> {
 
     let new_secret_key = near_crypto::SecretKey::from_random(near_crypto::KeyType::ED25519);
 
-    let provider = Arc::new(JsonRpcProvider::new("https://rpc.testnet.near.org"));
+    let provider = Arc::new(JsonRpcProvider::for_network(&Network::Testnet));
     let signer = Arc::new(signer);
 
-    let account = Account::new(signer_account_id, signer, provider);
+    let account = Account::new(signer_account_id, signer, provider).with_network(Network::Testnet);
 
     let allowance: Balance = 1_000_000_000_000_000_000_000_000; // Example amount in yoctoNEAR
     let contract_id = "contract.near-api-rs.testnet".to_string();
@@ -4,8 +4,8 @@ use near_crypto::{InMemorySigner, SecretKey};
 use near_primitives::views::TxExecutionStatus;
 use near_primitives::{types::Gas, views::FinalExecutionOutcomeViewEnum};
 use near_providers::jsonrpc_primitives::types::transactions::TransactionInfo;
-use near_providers::JsonRpcProvider;
 use near_providers::Provider;
+use near_providers::{JsonRpcProvider, Network};
 use std::sync::Arc;
 mod utils;
 use near_primitives::types::AccountId;
@@ -23,10 +23,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let gas: Gas = 100_000_000_000_000; // Example amount in yoctoNEAR
 
-    let provider = Arc::new(JsonRpcProvider::new("https://rpc.testnet.near.org"));
+    let provider = Arc::new(JsonRpcProvider::for_network(&Network::Testnet));
     let signer = Arc::new(signer);
 
-    let account = Account::new(signer_account_id, signer, provider.clone());
+    let account =
+        Account::new(signer_account_id, signer, provider.clone()).with_network(Network::Testnet);
     let method_name = "set_status".to_string();
 
     let args_json = json!({"message": "working1"});
@@ -1,7 +1,7 @@
 use near_accounts::Account;
 use near_crypto::InMemorySigner;
 use near_primitives::{types::Gas, views::FinalExecutionOutcomeViewEnum};
-use near_providers::JsonRpcProvider;
+use near_providers::{JsonRpcProvider, Network};
 use std::sync::Arc;
 mod utils;
 use near_primitives::types::{AccountId, Balance};
@@ -23,10 +23,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let amount: Balance = 10_000_000_000_000_000_000_000; // Example amount in yoctoNEAR
 
     let new_secret_key = near_crypto::SecretKey::from_random(near_crypto::KeyType::ED25519);
-    let provider = Arc::new(JsonRpcProvider::new("https://rpc.testnet.near.org"));
+    let provider = Arc::new(JsonRpcProvider::for_network(&Network::Testnet));
     let signer = Arc::new(signer);
 
-    let account = Account::new(signer_account_id, signer, provider);
+    let account = Account::new(signer_account_id, signer, provider).with_network(Network::Testnet);
 
     let contract_id: AccountId = "testnet".parse::<AccountId>()?;
     let method_name = "create_account".to_string();
@@ -2,7 +2,7 @@
 use near_accounts::Account;
 use near_crypto::InMemorySigner;
 use near_primitives::types::Balance;
-use near_providers::JsonRpcProvider;
+use near_providers::{JsonRpcProvider, Network};
 use std::sync::Arc;
 mod utils;
 use near_primitives::types::AccountId;
@@ -21,10 +21,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let amount: Balance = 10_000_000_000_000_000_000_000; // Example amount in yoctoNEAR
 
     let new_key_pair = near_crypto::SecretKey::from_random(near_crypto::KeyType::ED25519);
-    let provider = Arc::new(JsonRpcProvider::new("https://rpc.testnet.near.org"));
+    let provider = Arc::new(JsonRpcProvider::for_network(&Network::Testnet));
     let signer = Arc::new(signer);
 
-    let account = Account::new(signer_account_id, signer, provider);
+    let account = Account::new(signer_account_id, signer, provider).with_network(Network::Testnet);
     // Call create_account
     let result = account
         .create_account(&new_account_id, new_key_pair.public_key(), amount)
@@ -1,6 +1,6 @@
 use near_accounts::Account;
 use near_crypto::InMemorySigner;
-use near_providers::JsonRpcProvider;
+use near_providers::{JsonRpcProvider, Network};
 use std::sync::Arc;
 mod utils;
 use near_primitives::types::AccountId;
@@ -15,10 +15,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         utils::input("Enter the account name which need to be deleted ")?.parse()?;
     let signer = InMemorySigner::from_secret_key(signer_account_id.clone(), signer_secret_key);
 
-    let provider = Arc::new(JsonRpcProvider::new("https://rpc.testnet.near.org"));
+    let provider = Arc::new(JsonRpcProvider::for_network(&Network::Testnet));
     let signer = Arc::new(signer);
 
-    let account = Account::new(signer_account_id, signer, provider);
+    let account = Account::new(signer_account_id, signer, provider).with_network(Network::Testnet);
 
     let response = account.delete_account(user_account_id.clone()).await;
 
@@ -1,7 +1,7 @@
 use near_accounts::Account;
 use near_crypto::{InMemorySigner, SecretKey};
 use near_primitives::{types::Gas, views::FinalExecutionOutcomeViewEnum};
-use near_providers::JsonRpcProvider;
+use near_providers::{JsonRpcProvider, Network};
 use std::sync::Arc;
 mod utils;
 use near_primitives::types::{AccountId, Balance};
@@ -16,13 +16,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let signer_account_id: AccountId = "near-api-rs.testnet".parse::<AccountId>()?;
     let signer_secret_key = "ed25519:29nYmQCZMsQeYtztXZzm57ayQt2uBHXdn2SAjK4ccMGSQaNUFNJ7Aoteno81eKTex9cGBbk1FuDuqJRsdzx34xDY".parse::<SecretKey>()?;
 
-    let provider = Arc::new(JsonRpcProvider::new("https://rpc.testnet.near.org"));
+    let provider = Arc::new(JsonRpcProvider::for_network(&Network::Testnet));
     //let provider = JsonRpcProvider::new("https://rpc.testnet.near.org");
     let signer = Arc::new(InMemorySigner::from_secret_key(
         signer_account_id.clone(),
         signer_secret_key,
     ));
-    let account = Account::new(signer_account_id, signer.clone(), provider.clone());
+    let account = Account::new(signer_account_id, signer.clone(), provider.clone())
+        .with_network(Network::Testnet);
 
     let contract_id: AccountId = "contract.near-api-rs.testnet".parse::<AccountId>()?;
 
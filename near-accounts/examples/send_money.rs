@@ -1,7 +1,7 @@
 use near_accounts::Account;
 use near_crypto::InMemorySigner;
 use near_primitives::types::Balance;
-use near_providers::JsonRpcProvider;
+use near_providers::{JsonRpcProvider, Network};
 use std::sync::Arc;
 mod utils;
 use near_primitives::types::AccountId;
@@ -19,10 +19,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Amount to transfer to the receiver account
     let amount: Balance = 10_000_000_000; // Example amount in yoctoNEAR
 
-    let provider = Arc::new(JsonRpcProvider::new("https://rpc.testnet.near.org"));
+    let provider = Arc::new(JsonRpcProvider::for_network(&Network::Testnet));
     let signer = Arc::new(signer);
 
-    let account = Account::new(signer_account_id, signer, provider);
+    let account = Account::new(signer_account_id, signer, provider).with_network(Network::Testnet);
     // Call create_account
     let result = account.send_money(&receiver_account_id, amount).await;
 
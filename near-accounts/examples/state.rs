@@ -1,6 +1,6 @@
 use near_accounts::accounts::state;
 use near_primitives::types::AccountId;
-use near_providers::JsonRpcProvider;
+use near_providers::{JsonRpcProvider, Network};
 use std::sync::Arc;
 
 #[tokio::main]
@@ -9,9 +9,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let account_id: AccountId = "near-api-rs.testnet".parse::<AccountId>()?;
 
-    let provider = Arc::new(JsonRpcProvider::new("https://rpc.testnet.near.org"));
+    let provider = Arc::new(JsonRpcProvider::for_network(&Network::Testnet));
 
-    let result = state(provider, account_id).await;
+    let result = state(provider, account_id, None).await;
 
     println!("response: {:#?}", result);
 
@@ -0,0 +1,19 @@
+use near_accounts::accounts::verified_view_state;
+use near_providers::{JsonRpcProvider, Network};
+use std::sync::Arc;
+mod utils;
+use near_primitives::types::AccountId;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+
+    let provider = Arc::new(JsonRpcProvider::for_network(&Network::Testnet));
+
+    let contract_id: AccountId = "contract.near-api-rs.testnet".parse::<AccountId>()?;
+
+    let result = verified_view_state(provider, contract_id, None).await;
+    println!("response: {:#?}", result);
+
+    Ok(())
+}
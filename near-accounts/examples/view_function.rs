@@ -1,5 +1,5 @@
 use near_accounts::accounts::view_function;
-use near_providers::JsonRpcProvider;
+use near_providers::{JsonRpcProvider, Network};
 use std::sync::Arc;
 mod utils;
 use near_primitives::types::AccountId;
@@ -8,12 +8,12 @@ use serde_json::json;
 async fn single_thread() -> Result<(), Box<dyn std::error::Error>> {
     let contract_id: AccountId = "contract.near-api-rs.testnet".parse::<AccountId>()?;
 
-    let provider = Arc::new(JsonRpcProvider::new("https://rpc.testnet.near.org"));
+    let provider = Arc::new(JsonRpcProvider::for_network(&Network::Testnet));
 
     let args_json = json!({"account_id": "contract.near-api-rs.testnet"});
     let method_name = "get_status".to_string();
 
-    let result = view_function(provider, contract_id, method_name, args_json).await;
+    let result = view_function(provider, contract_id, method_name, args_json, None).await;
 
     println!("response: {:#?}", result);
 
@@ -23,13 +23,13 @@ async fn single_thread() -> Result<(), Box<dyn std::error::Error>> {
 async fn multi_thread() -> Result<(), Box<dyn std::error::Error>> {
     let contract_id: AccountId = "contract.near-api-rs.testnet".parse::<AccountId>()?;
 
-    let provider = Arc::new(JsonRpcProvider::new("https://rpc.testnet.near.org"));
+    let provider = Arc::new(JsonRpcProvider::for_network(&Network::Testnet));
 
     let args_json = json!({"account_id": "contract.near-api-rs.testnet"});
     let method_name = "get_status".to_string();
 
     let handle = tokio::spawn(async move {
-        let result = view_function(provider, contract_id, method_name, args_json).await;
+        let result = view_function(provider, contract_id, method_name, args_json, None).await;
         println!("response: {:#?}", result);
     });
 
@@ -1,5 +1,5 @@
 use near_accounts::accounts::view_state;
-use near_providers::JsonRpcProvider;
+use near_providers::{JsonRpcProvider, Network};
 use std::sync::Arc;
 mod utils;
 use near_primitives::types::AccountId;
@@ -8,11 +8,11 @@ use near_primitives::types::AccountId;
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init();
 
-    let provider = Arc::new(JsonRpcProvider::new("https://rpc.testnet.near.org"));
+    let provider = Arc::new(JsonRpcProvider::for_network(&Network::Testnet));
 
     let contract_id: AccountId = "contract.near-api-rs.testnet".parse::<AccountId>()?;
 
-    let result = view_state(provider, contract_id, None).await;
+    let result = view_state(provider, contract_id, None, None).await;
     println!("response: {:#?}", result);
 
     Ok(())
@@ -2,21 +2,30 @@
 //! allowing for operations such as account creation, access key management, contract deployment, making change and view function calls,
 //! and transaction execution. It abstracts the complexities of transaction construction and signing,
 //! making it easier to perform account-related operations.
+//!
+//! Every operation of an `Account` runs in a `tracing` span named after it, with the nonce fetch, block fetch and
+//! transaction send it makes as child spans, so a single `send_money` call can be followed end to end. Stacking a
+//! `TracingLayer` on the provider adds a span per RPC call below them.
 
 use crate::access_keys::{full_access_key, function_call_access_key};
+use crate::errors::AccountError;
 use crate::transaction_sender::TransactionSender;
 use near_crypto::{PublicKey, Signer};
 use near_primitives::account::AccessKey;
+use near_primitives::hash::CryptoHash;
+use near_primitives::shard_layout::account_id_to_shard_id;
 use near_primitives::transaction::{SignedTransaction, Transaction};
-use near_primitives::types::{AccountId, Balance, BlockReference, Finality, Gas};
+use near_primitives::types::{AccountId, Balance, BlockId, BlockReference, Finality, Gas};
 use near_primitives::views::{FinalExecutionOutcomeView, QueryRequest};
 
+use near_providers::state_proof::{self, VerifiedViewStateResult};
 use near_providers::types::query::{QueryResponseKind, RpcQueryResponse};
-use near_providers::Provider;
+use near_providers::{Network, Provider};
 use near_transactions::ActionBuilder;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::ops::{Add, Mul, Sub};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 pub type ArcProviderSendSync = Arc<dyn Provider + Send + Sync>;
@@ -27,6 +36,8 @@ pub struct Account {
     pub account_id: AccountId,
     pub signer: ArcSignerSendSync,     // Use your Signer abstraction
     pub provider: ArcProviderSendSync, // Use your Provider abstraction
+    network: Option<Network>,
+    network_verified: AtomicBool,
 }
 
 /// Represents the balance details of a NEAR account.
@@ -59,9 +70,39 @@ impl Account {
             account_id,
             signer,
             provider,
+            network: None,
+            network_verified: AtomicBool::new(false),
         }
     }
 
+    /// Binds the account to `network`. Before signing its first transaction, the account checks that the provider
+    /// reports the chain id of the network and refuses to sign with `AccountError::Network` otherwise.
+    ///
+    /// The check is made once per `Account`, so a provider switching between endpoints of different networks
+    /// afterwards is not detected.
+    pub fn with_network(mut self, network: Network) -> Self {
+        self.network = Some(network);
+        self.network_verified = AtomicBool::new(false);
+        self
+    }
+
+    /// Returns the network the account is bound to, if any.
+    pub fn network(&self) -> Option<&Network> {
+        self.network.as_ref()
+    }
+
+    /// Checks that the provider is connected to the network of the account, if it is bound to one.
+    async fn verify_network(&self) -> Result<(), AccountError> {
+        let Some(network) = &self.network else {
+            return Ok(());
+        };
+        if !self.network_verified.load(Ordering::Acquire) {
+            network.verify(&*self.provider).await?;
+            self.network_verified.store(true, Ordering::Release);
+        }
+        Ok(())
+    }
+
     /// Create a `SignedTransaction` for constructing a signed transaction.
     ///
     /// # Arguments
@@ -76,14 +117,12 @@ impl Account {
         &self,
         receiver_id: &AccountId,
         actions: &ActionBuilder,
-    ) -> Result<SignedTransaction, Box<dyn std::error::Error>> {
+    ) -> Result<SignedTransaction, AccountError> {
+        self.verify_network().await?;
+
         // Fetch the current nonce for the signer account and latest block hash
         let nonce = self.fetch_nonce().await?;
-
-        //Block hash
-        let block_reference = BlockReference::Finality(Finality::Final);
-        let block = self.provider.block(block_reference).await?;
-        let block_hash = block.header.hash;
+        let block_hash = self.fetch_block_hash().await?;
 
         let txn = Transaction {
             signer_id: self.account_id.clone(),
@@ -98,6 +137,26 @@ impl Account {
         Ok(signed_transaction)
     }
 
+    /// Fetches the hash of the latest final block, which transactions are signed against.
+    #[tracing::instrument(name = "fetch_block", skip_all)]
+    async fn fetch_block_hash(&self) -> Result<CryptoHash, AccountError> {
+        let block = self
+            .provider
+            .block(BlockReference::Finality(Finality::Final))
+            .await
+            .map_err(|err| AccountError::BlockFetch(Box::new(err)))?;
+        Ok(block.header.hash)
+    }
+
+    /// Sends a signed transaction and waits for its final execution outcome.
+    #[tracing::instrument(name = "send_transaction", skip_all, fields(tx_hash = %signed_tx.get_hash()))]
+    async fn send(
+        &self,
+        signed_tx: SignedTransaction,
+    ) -> Result<FinalExecutionOutcomeView, AccountError> {
+        Ok(self.provider.send_transaction(signed_tx).await?)
+    }
+
     /// Fetches the current nonce for an account's access key.
     ///
     /// # Arguments
@@ -108,20 +167,24 @@ impl Account {
     /// # Returns
     ///
     /// A result containing the nonce or an error if the query failed.
-    pub async fn fetch_nonce(&self) -> Result<u64, Box<dyn std::error::Error>> {
+    #[tracing::instrument(skip_all, fields(account_id = %self.account_id))]
+    pub async fn fetch_nonce(&self) -> Result<u64, AccountError> {
         let query_request = QueryRequest::ViewAccessKey {
             account_id: self.account_id.clone(),
             public_key: self.signer.public_key(),
         };
 
         // Send the query to the NEAR blockchain
-        let response: RpcQueryResponse = self.provider.query(query_request).await?;
+        let response: RpcQueryResponse = self
+            .provider
+            .query(query_request, BlockReference::Finality(Finality::Final))
+            .await
+            .map_err(|err| AccountError::NonceFetch(Box::new(err)))?;
 
         // Extract the access key view from the response
-        if let QueryResponseKind::AccessKey(access_key_view) = response.kind {
-            Ok(access_key_view.nonce)
-        } else {
-            Err("Unexpected response kind".into())
+        match response.kind {
+            QueryResponseKind::AccessKey(access_key_view) => Ok(access_key_view.nonce),
+            kind => Err(AccountError::unexpected_response("AccessKey", kind)),
         }
     }
 
@@ -138,12 +201,13 @@ impl Account {
     /// A final execution outcome of the transaction.
     ///
     /// # Note: The accounts created by this function will be of the form *.signer_account_id.near/testnet
+    #[tracing::instrument(skip_all, fields(account_id = %self.account_id, new_account_id = %new_account_id))]
     pub async fn create_account(
         &self,
         new_account_id: &AccountId,
         public_key: PublicKey,
         amount: Balance,
-    ) -> Result<FinalExecutionOutcomeView, Box<dyn std::error::Error>> {
+    ) -> Result<FinalExecutionOutcomeView, AccountError> {
         let mut action_builder = ActionBuilder::new();
         action_builder
             .create_account()
@@ -153,11 +217,7 @@ impl Account {
         let signed_txn = self
             .create_signed_transaction(new_account_id, &action_builder)
             .await?;
-        // Use TransactionBuilder to construct the transaction
-
-        // Send the transaction
-        let transaction_result = self.provider.send_transaction(signed_txn.clone()).await?;
-        Ok(transaction_result)
+        self.send(signed_txn).await
     }
 
     /// Adds a full or function call access key to an account
@@ -172,19 +232,20 @@ impl Account {
     /// # Returns
     ///
     /// A final execution outcome of the transaction.
+    #[tracing::instrument(skip_all, fields(account_id = %self.account_id, public_key = %public_key))]
     pub async fn add_key(
         &self,
         public_key: PublicKey,
         allowance: Option<Balance>,
         contract_id: Option<String>,
         method_names: Option<Vec<String>>,
-    ) -> Result<FinalExecutionOutcomeView, Box<dyn std::error::Error>> {
+    ) -> Result<FinalExecutionOutcomeView, AccountError> {
         let access_key: AccessKey = match contract_id {
             Some(cid) => {
                 if let Some(m_names) = method_names {
                     function_call_access_key(allowance, cid, m_names)
                 } else {
-                    return Err("No method_names argument provided for function call access keys. You should at-least provide an empty vector.".into());
+                    return Err(AccountError::MissingMethodNames);
                 }
             }
             None => full_access_key(),
@@ -196,14 +257,7 @@ impl Account {
                 ActionBuilder::new().add_key(public_key, access_key),
             )
             .await?;
-        // Use TransactionBuilder to construct the transaction
-
-        // Send the transaction
-        let transaction_result = self.provider.send_transaction(signed_tx).await;
-        match transaction_result {
-            Ok(transaction_result) => Ok(transaction_result),
-            Err(err) => Err(Box::new(err)),
-        }
+        self.send(signed_tx).await
     }
 
     /// Delete a key from an account
@@ -215,23 +269,18 @@ impl Account {
     /// # Returns
     ///
     /// A final execution outcome of the transaction.
+    #[tracing::instrument(skip_all, fields(account_id = %self.account_id, public_key = %public_key))]
     pub async fn delete_key(
         &self,
         public_key: PublicKey,
-    ) -> Result<FinalExecutionOutcomeView, Box<dyn std::error::Error>> {
+    ) -> Result<FinalExecutionOutcomeView, AccountError> {
         let signed_tx = self
             .create_signed_transaction(
                 &self.account_id,
                 ActionBuilder::new().delete_key(public_key),
             )
             .await?;
-
-        // Send the transaction
-        let transaction_result = self.provider.send_transaction(signed_tx).await;
-        match transaction_result {
-            Ok(transaction_result) => Ok(transaction_result),
-            Err(err) => Err(Box::new(err)),
-        }
+        self.send(signed_tx).await
     }
 
     /// Deploys a contract to the account associated with this `Account` instance.
@@ -243,23 +292,18 @@ impl Account {
     /// # Returns
     ///
     /// A `Result` containing the final execution outcome of the contract deployment or an error if the operation fails.
+    #[tracing::instrument(skip_all, fields(account_id = %self.account_id, code_len = byte_code.len()))]
     pub async fn deploy_contract(
         &self,
         byte_code: &[u8],
-    ) -> Result<FinalExecutionOutcomeView, Box<dyn std::error::Error>> {
+    ) -> Result<FinalExecutionOutcomeView, AccountError> {
         let signed_tx = self
             .create_signed_transaction(
                 &self.account_id,
                 ActionBuilder::new().deploy_contract(byte_code),
             )
             .await?;
-
-        // Send the transaction
-        let transaction_result = self.provider.send_transaction(signed_tx).await;
-        match transaction_result {
-            Ok(transaction_result) => Ok(transaction_result),
-            Err(err) => Err(Box::new(err)),
-        }
+        self.send(signed_tx).await
     }
 
     /// Deletes the specified account and transfers any remaining tokens to the beneficiary account.
@@ -271,22 +315,18 @@ impl Account {
     /// # Returns
     ///
     /// A `Result` containing the final execution outcome of the account deletion or an error if the operation fails.
+    #[tracing::instrument(skip_all, fields(account_id = %self.account_id, beneficiary_id = %beneficiary_id))]
     pub async fn delete_account(
         &self,
         beneficiary_id: AccountId,
-    ) -> Result<FinalExecutionOutcomeView, Box<dyn std::error::Error>> {
+    ) -> Result<FinalExecutionOutcomeView, AccountError> {
         let signed_tx = self
             .create_signed_transaction(
                 &self.account_id,
                 ActionBuilder::new().delete_account(beneficiary_id),
             )
             .await?;
-        // Send the transaction
-        let transaction_result = self.provider.send_transaction(signed_tx).await;
-        match transaction_result {
-            Ok(transaction) => Ok(transaction),
-            Err(err) => Err(Box::new(err)),
-        }
+        self.send(signed_tx).await
     }
 
     /// Transfers a specified amount of NEAR tokens from this account to another account.
@@ -300,20 +340,16 @@ impl Account {
     ///
     /// A `Result` containing the final execution outcome of the transfer or an error if the operation fails.
     ///
+    #[tracing::instrument(skip_all, fields(account_id = %self.account_id, receiver_id = %receiver_id, amount))]
     pub async fn send_money(
         &self,
         receiver_id: &AccountId,
         amount: Balance,
-    ) -> Result<FinalExecutionOutcomeView, Box<dyn std::error::Error>> {
+    ) -> Result<FinalExecutionOutcomeView, AccountError> {
         let signed_tx = self
             .create_signed_transaction(receiver_id, ActionBuilder::new().transfer(amount))
             .await?;
-        // Send the transaction
-        let transaction_result = self.provider.send_transaction(signed_tx).await;
-        match transaction_result {
-            Ok(transaction_result) => Ok(transaction_result),
-            Err(err) => Err(Box::new(err)),
-        }
+        self.send(signed_tx).await
     }
 
     /// Calls a function on a smart contract deployed on the NEAR blockchain.
@@ -329,6 +365,7 @@ impl Account {
     /// # Returns
     ///
     /// A `Result` containing the TransactionSender consisting of the signed transaction and the provider(sends transactions to the blockchain)) for the function call or an error if the operation fails.
+    #[tracing::instrument(skip_all, fields(account_id = %self.account_id, contract_id = %contract_id, method_name = %method_name))]
     pub async fn function_call(
         &self,
         contract_id: &AccountId,
@@ -336,9 +373,9 @@ impl Account {
         args: Value,
         gas: Gas,
         deposit: Balance,
-    ) -> Result<TransactionSender, Box<dyn std::error::Error>> {
+    ) -> Result<TransactionSender, AccountError> {
         // Serialize the JSON to a Vec<u8>
-        let args = serde_json::to_vec(&args)?;
+        let args = serde_json::to_vec(&args).map_err(AccountError::Arguments)?;
 
         let signed_tx = self
             .create_signed_transaction(
@@ -351,6 +388,11 @@ impl Account {
     }
 }
 
+/// Resolves the block reference used by the view helpers, defaulting to the latest final block.
+fn block_reference_or_final(block_reference: Option<BlockReference>) -> BlockReference {
+    block_reference.unwrap_or(BlockReference::Finality(Finality::Final))
+}
+
 /// Calls a view function on a contract deployed on the NEAR blockchain.
 ///
 /// View functions are read-only and do not modify state. They're free to call.
@@ -360,6 +402,7 @@ impl Account {
 /// * `contract_id` - The account ID of the contract.
 /// * `method_name` - The name of the view function to call.
 /// * `args` - The arguments to the function call, typically in the form of a serialized byte array (`FunctionArgs`).
+/// * `block_reference` - The block at which to query. Defaults to `Finality::Final` if `None`.
 ///
 /// # Returns
 ///
@@ -369,8 +412,11 @@ pub async fn view_function(
     contract_id: AccountId,
     method_name: String,
     args: Value,
-) -> Result<near_primitives::views::CallResult, Box<dyn std::error::Error>> {
-    let args_vec = serde_json::to_vec(&args)?.into();
+    block_reference: Option<BlockReference>,
+) -> Result<near_primitives::views::CallResult, AccountError> {
+    let args_vec = serde_json::to_vec(&args)
+        .map_err(AccountError::Arguments)?
+        .into();
 
     let query_request = QueryRequest::CallFunction {
         account_id: contract_id.clone(),
@@ -379,12 +425,13 @@ pub async fn view_function(
     };
 
     // Send the query to the NEAR blockchain
-    let response: RpcQueryResponse = provider.query(query_request).await?;
+    let response: RpcQueryResponse = provider
+        .query(query_request, block_reference_or_final(block_reference))
+        .await?;
 
-    if let QueryResponseKind::CallResult(result) = response.kind {
-        Ok(result)
-    } else {
-        Err("Unexpected response kind".into())
+    match response.kind {
+        QueryResponseKind::CallResult(result) => Ok(result),
+        kind => Err(AccountError::unexpected_response("CallResult", kind)),
     }
 }
 
@@ -397,6 +444,7 @@ pub async fn view_function(
 /// * `provider` - The provider through which to query the blockchain.
 /// * `contract_id` - The account ID of the contract whose state is being queried.
 /// * `prefix` - An optional key prefix to filter the state by. If `None`, all state is returned.
+/// * `block_reference` - The block at which to query. Defaults to `Finality::Final` if `None`.
 ///
 /// # Returns
 ///
@@ -405,21 +453,119 @@ pub async fn view_state(
     provider: ArcProviderSendSync,
     contract_id: AccountId,
     prefix: Option<String>,
-) -> Result<near_primitives::views::ViewStateResult, Box<dyn std::error::Error>> {
-    let prefix_op = prefix.unwrap_or("".to_string());
+    block_reference: Option<BlockReference>,
+) -> Result<near_primitives::views::ViewStateResult, AccountError> {
+    query_view_state(
+        provider,
+        contract_id,
+        prefix.unwrap_or_default().into_bytes(),
+        block_reference_or_final(block_reference),
+        false,
+    )
+    .await
+}
+
+/// Queries the state of a contract like `view_state`, requesting the proof of the returned values.
+///
+/// The proof holds the trie nodes read by the node and can be verified with
+/// `near_providers::state_proof::verify_view_state`, see also `verified_view_state`.
+///
+/// # Arguments
+///
+/// * `provider` - The provider through which to query the blockchain.
+/// * `contract_id` - The account ID of the contract whose state is being queried.
+/// * `prefix` - An optional key prefix to filter the state by. If `None`, all state is returned.
+/// * `block_reference` - The block at which to query. Defaults to `Finality::Final` if `None`.
+///
+/// # Returns
+///
+/// A `Result` containing the contract's state filtered by the specified prefix and its proof, or an error if the
+/// query fails.
+pub async fn view_state_with_proof(
+    provider: ArcProviderSendSync,
+    contract_id: AccountId,
+    prefix: Option<String>,
+    block_reference: Option<BlockReference>,
+) -> Result<near_primitives::views::ViewStateResult, AccountError> {
+    query_view_state(
+        provider,
+        contract_id,
+        prefix.unwrap_or_default().into_bytes(),
+        block_reference_or_final(block_reference),
+        true,
+    )
+    .await
+}
+
+/// Queries the state of a contract and verifies it against the state root of its shard, so the values are
+/// authenticated instead of trusted.
+///
+/// The state is read at the parent of the latest final block, whose chunk for the shard of the contract records the
+/// state root to verify against. The query fails with `StateProofError::ChunkMissing` if that block has no new chunk
+/// for the shard, in which case it can be retried once the next block is final.
+///
+/// # Arguments
+///
+/// * `provider` - The provider through which to query the blockchain.
+/// * `contract_id` - The account ID of the contract whose state is being queried.
+/// * `prefix` - An optional key prefix to filter the state by. If `None`, all state is returned.
+///
+/// # Returns
+///
+/// A `Result` containing the verified state of the contract filtered by the specified prefix, or an error if the
+/// query or the verification fails.
+pub async fn verified_view_state(
+    provider: ArcProviderSendSync,
+    contract_id: AccountId,
+    prefix: Option<String>,
+) -> Result<VerifiedViewStateResult, AccountError> {
+    let block = provider
+        .block(BlockReference::Finality(Finality::Final))
+        .await
+        .map_err(|err| AccountError::BlockFetch(Box::new(err)))?;
+    let block_reference = BlockReference::BlockId(BlockId::Hash(block.header.hash));
+    let protocol_config = provider
+        .experimental_protocol_config(block_reference)
+        .await?;
+    let shard_id = account_id_to_shard_id(&contract_id, &protocol_config.shard_layout);
+    let state_root = state_proof::parent_state_root(&block, shard_id)?;
+
+    let prefix = prefix.unwrap_or_default().into_bytes();
+    let result = query_view_state(
+        provider,
+        contract_id.clone(),
+        prefix.clone(),
+        BlockReference::BlockId(BlockId::Hash(block.header.prev_hash)),
+        true,
+    )
+    .await?;
+    Ok(state_proof::verify_view_state(
+        &contract_id,
+        &prefix,
+        result,
+        state_root,
+    )?)
+}
+
+async fn query_view_state(
+    provider: ArcProviderSendSync,
+    contract_id: AccountId,
+    prefix: Vec<u8>,
+    block_reference: BlockReference,
+    include_proof: bool,
+) -> Result<near_primitives::views::ViewStateResult, AccountError> {
     let query_request = QueryRequest::ViewState {
         account_id: contract_id,
-        prefix: near_primitives::types::StoreKey::from(prefix_op.into_bytes()),
-        include_proof: false,
+        prefix: near_primitives::types::StoreKey::from(prefix),
+        include_proof,
     };
 
     // Send the query to the NEAR blockchain
-    let response: RpcQueryResponse = provider.query(query_request).await?;
+    let response: RpcQueryResponse = provider.query(query_request, block_reference).await?;
 
-    if let QueryResponseKind::ViewState(result) = response.kind {
-        Ok(result)
-    } else {
-        Err("Unexpected response kind".into())
+    match response.kind {
+        QueryResponseKind::ViewState(result) => Ok(result),
+        kind => Err(AccountError::unexpected_response("ViewState", kind)),
     }
 }
 
@@ -429,6 +575,7 @@ pub async fn view_state(
 ///
 /// * `provider` - The provider through which to query the blockchain.
 /// * `account_id` - The account ID for which to retrieve access keys.
+/// * `block_reference` - The block at which to query. Defaults to `Finality::Final` if `None`.
 ///
 /// # Returns
 ///
@@ -436,16 +583,18 @@ pub async fn view_state(
 pub async fn get_access_key(
     provider: ArcProviderSendSync,
     account_id: AccountId,
-) -> Result<near_primitives::views::AccessKeyList, Box<dyn std::error::Error>> {
+    block_reference: Option<BlockReference>,
+) -> Result<near_primitives::views::AccessKeyList, AccountError> {
     let query_request = QueryRequest::ViewAccessKeyList { account_id };
 
     // Send the query to the NEAR blockchain
-    let response: RpcQueryResponse = provider.query(query_request).await?;
+    let response: RpcQueryResponse = provider
+        .query(query_request, block_reference_or_final(block_reference))
+        .await?;
 
-    if let QueryResponseKind::AccessKeyList(result) = response.kind {
-        Ok(result)
-    } else {
-        Err("Unexpected response kind".into())
+    match response.kind {
+        QueryResponseKind::AccessKeyList(result) => Ok(result),
+        kind => Err(AccountError::unexpected_response("AccessKeyList", kind)),
     }
 }
 
@@ -455,6 +604,7 @@ pub async fn get_access_key(
 ///
 /// * `provider` - The provider through which to query the blockchain.
 /// * `account_id` - The account ID whose state is being queried.
+/// * `block_reference` - The block at which to query. Defaults to `Finality::Final` if `None`.
 ///
 /// # Returns
 ///
@@ -462,16 +612,18 @@ pub async fn get_access_key(
 pub async fn state(
     provider: ArcProviderSendSync,
     account_id: AccountId,
-) -> Result<near_primitives::views::AccountView, Box<dyn std::error::Error>> {
+    block_reference: Option<BlockReference>,
+) -> Result<near_primitives::views::AccountView, AccountError> {
     let query_request = QueryRequest::ViewAccount { account_id };
 
     // Send the query to the NEAR blockchain
-    let response: RpcQueryResponse = provider.query(query_request).await?;
+    let response: RpcQueryResponse = provider
+        .query(query_request, block_reference_or_final(block_reference))
+        .await?;
 
-    if let QueryResponseKind::ViewAccount(result) = response.kind {
-        Ok(result)
-    } else {
-        Err("Unexpected response kind".into())
+    match response.kind {
+        QueryResponseKind::ViewAccount(result) => Ok(result),
+        kind => Err(AccountError::unexpected_response("ViewAccount", kind)),
     }
 }
 
@@ -481,6 +633,7 @@ pub async fn state(
 ///
 /// * `provider` - The provider through which to query the blockchain.
 /// * `account_id` - The account ID whose balance details are being queried.
+/// * `block_reference` - The block at which to query. Defaults to `Finality::Final` if `None`.
 ///
 /// # Returns
 ///
@@ -488,15 +641,16 @@ pub async fn state(
 pub async fn get_account_balance(
     provider: ArcProviderSendSync,
     account_id: AccountId,
-) -> Result<AccountBalance, Box<dyn std::error::Error>> {
-    // Assuming `experimental_protocol_config` and `state` are async functions you can call on the provider
-    let block_reference = BlockReference::Finality(Finality::Final);
+    block_reference: Option<BlockReference>,
+) -> Result<AccountBalance, AccountError> {
+    // Both the protocol config and the account state are read at the same block
+    let block_reference = block_reference_or_final(block_reference);
     let protocol_config = provider
-        .experimental_protocol_config(block_reference)
+        .experimental_protocol_config(block_reference.clone())
         .await?;
     // let cost_per_byte = BigInt::from(protocol_config.runtime_config.storage_amount_per_byte);
 
-    let state = state(provider, account_id).await?;
+    let state = state(provider, account_id, Some(block_reference)).await?;
 
     let staked = state.locked;
     let state_staked = protocol_config
@@ -519,3 +673,333 @@ pub async fn get_account_balance(
         available: available_balance.to_string(),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_crypto::{InMemorySigner, KeyType};
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::transaction::Action;
+    use near_primitives::trie_key::TrieKey;
+    use near_primitives::views::{StateItem, ViewStateResult};
+    use near_providers::network::NetworkError;
+    use near_providers::state_proof::StateProofError;
+    use near_providers::testing::{
+        access_key_response, block_view, chunk_header, internal_error, query_response,
+        status_response, trie_proof, MockProvider,
+    };
+    use near_providers::ProviderMethod;
+
+    fn account(provider: Arc<MockProvider>) -> Account {
+        let account_id: AccountId = "alice.testnet".parse().unwrap();
+        let signer = InMemorySigner::from_seed(account_id.clone(), KeyType::ED25519, "alice");
+        Account::new(account_id, Arc::new(signer), provider)
+    }
+
+    #[tokio::test]
+    async fn test_fetch_nonce() {
+        let provider = Arc::new(MockProvider::new());
+        provider.push_query(Ok(access_key_response(41)));
+        let account = account(provider.clone());
+
+        assert_eq!(account.fetch_nonce().await.unwrap(), 41);
+        match &provider.calls()[0] {
+            near_providers::ProviderRequest::Query { request, .. } => assert_eq!(
+                request,
+                &QueryRequest::ViewAccessKey {
+                    account_id: account.account_id.clone(),
+                    public_key: account.signer.public_key(),
+                }
+            ),
+            call => panic!("unexpected call {:?}", call),
+        }
+
+        provider.push_query(Err(internal_error("node is down")));
+        assert!(matches!(
+            account.fetch_nonce().await,
+            Err(AccountError::NonceFetch(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_send_money_signs_with_next_nonce() {
+        let provider = Arc::new(MockProvider::new());
+        let block_hash = near_primitives::hash::CryptoHash::hash_bytes(b"final");
+        provider.push_query(Ok(access_key_response(41)));
+        provider.push_block(Ok(block_view(10, block_hash)));
+        provider.push_send_transaction(Err(internal_error("node is down")));
+        let account = account(provider.clone());
+
+        let receiver_id: AccountId = "bob.testnet".parse().unwrap();
+        let result = account.send_money(&receiver_id, 100).await;
+        assert!(matches!(result, Err(AccountError::Transaction(_))));
+        assert_eq!(provider.call_count(ProviderMethod::SendTransaction), 1);
+
+        let signed_transactions = provider.signed_transactions();
+        let transaction = &signed_transactions[0].transaction;
+        assert_eq!(transaction.nonce, 42);
+        assert_eq!(transaction.block_hash, block_hash);
+        assert_eq!(transaction.receiver_id, receiver_id);
+        assert!(matches!(
+            transaction.actions.as_slice(),
+            [Action::Transfer(transfer)] if transfer.deposit == 100
+        ));
+        assert!(signed_transactions[0].signature.verify(
+            signed_transactions[0].get_hash().as_ref(),
+            &account.signer.public_key()
+        ));
+    }
+
+    /// The name of a span and the name of its parent.
+    type SpanEdge = (String, Option<String>);
+
+    /// Records every span opened, in order.
+    #[derive(Clone, Default)]
+    struct SpanTree(Arc<std::sync::Mutex<Vec<SpanEdge>>>);
+
+    impl<S> tracing_subscriber::Layer<S> for SpanTree
+    where
+        S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        fn on_new_span(
+            &self,
+            _attrs: &tracing::span::Attributes<'_>,
+            id: &tracing::span::Id,
+            ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let span = ctx.span(id).unwrap();
+            let parent = span.parent().map(|parent| parent.name().to_string());
+            self.0
+                .lock()
+                .unwrap()
+                .push((span.name().to_string(), parent));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_money_spans() {
+        use near_providers::instrumentation::TracingLayer;
+        use near_providers::ProviderBuilder;
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let spans = SpanTree::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(spans.clone()));
+        let provider = Arc::new(
+            ProviderBuilder::new()
+                .with_layer(TracingLayer::new())
+                .build(MockProvider::new()),
+        );
+        provider.inner().push_query(Ok(access_key_response(41)));
+        provider
+            .inner()
+            .push_block(Ok(block_view(10, CryptoHash::default())));
+        provider
+            .inner()
+            .push_send_transaction(Err(internal_error("node is down")));
+        let account_id: AccountId = "alice.testnet".parse().unwrap();
+        let signer = InMemorySigner::from_seed(account_id.clone(), KeyType::ED25519, "alice");
+        let account = Account::new(account_id, Arc::new(signer), provider);
+
+        let receiver_id: AccountId = "bob.testnet".parse().unwrap();
+        let _ = account.send_money(&receiver_id, 100).await;
+
+        let span = |name: &str, parent: Option<&str>| (name.to_string(), parent.map(String::from));
+        assert_eq!(
+            *spans.0.lock().unwrap(),
+            vec![
+                span("send_money", None),
+                span("fetch_nonce", Some("send_money")),
+                span("rpc", Some("fetch_nonce")),
+                span("fetch_block", Some("send_money")),
+                span("rpc", Some("fetch_block")),
+                span("send_transaction", Some("send_money")),
+                span("rpc", Some("send_transaction")),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refuses_to_sign_for_other_network() {
+        let provider = Arc::new(MockProvider::new());
+        provider.push_status(Ok(status_response("testnet")));
+        let mainnet_account = account(provider.clone()).with_network(Network::Mainnet);
+
+        let receiver_id: AccountId = "bob.near".parse().unwrap();
+        assert!(matches!(
+            mainnet_account.send_money(&receiver_id, 100).await,
+            Err(AccountError::Network(err)) if matches!(*err, NetworkError::ChainIdMismatch { .. })
+        ));
+        assert_eq!(provider.call_count(ProviderMethod::Query), 0);
+        assert!(provider.signed_transactions().is_empty());
+
+        // The chain id is checked once, before the first transaction.
+        let provider = Arc::new(MockProvider::new());
+        provider.push_status(Ok(status_response("testnet")));
+        for nonce in [41, 42] {
+            provider.push_query(Ok(access_key_response(nonce)));
+            provider.push_block(Ok(block_view(10, CryptoHash::default())));
+            provider.push_send_transaction(Err(internal_error("node is down")));
+        }
+        let testnet_account = account(provider.clone()).with_network(Network::Testnet);
+        for _ in 0..2 {
+            assert!(matches!(
+                testnet_account.send_money(&receiver_id, 100).await,
+                Err(AccountError::Transaction(_))
+            ));
+        }
+        assert_eq!(provider.call_count(ProviderMethod::Status), 1);
+        assert_eq!(provider.signed_transactions().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_views_query_at_block_reference() {
+        let account_id: AccountId = "alice.testnet".parse().unwrap();
+        let at_height = BlockReference::BlockId(BlockId::Height(42));
+        let final_block = BlockReference::Finality(Finality::Final);
+        for (block_reference, expected) in
+            [(Some(at_height.clone()), at_height), (None, final_block)]
+        {
+            // Unscripted queries fail, only the block references they were made at matter.
+            let provider = Arc::new(MockProvider::new());
+            provider.push_experimental_protocol_config(Ok(serde_json::from_value(
+                near_rpc_test_server::fixtures::protocol_config(),
+            )
+            .unwrap()));
+            let _ = view_function(
+                provider.clone(),
+                account_id.clone(),
+                "get".to_string(),
+                serde_json::json!({}),
+                block_reference.clone(),
+            )
+            .await;
+            let _ = state(
+                provider.clone(),
+                account_id.clone(),
+                block_reference.clone(),
+            )
+            .await;
+            let _ = get_access_key(
+                provider.clone(),
+                account_id.clone(),
+                block_reference.clone(),
+            )
+            .await;
+            let _ =
+                get_account_balance(provider.clone(), account_id.clone(), block_reference).await;
+
+            let block_references: Vec<_> = provider
+                .calls()
+                .into_iter()
+                .map(|call| match call {
+                    near_providers::ProviderRequest::Query {
+                        block_reference, ..
+                    }
+                    | near_providers::ProviderRequest::ExperimentalProtocolConfig {
+                        block_reference,
+                    } => block_reference,
+                    call => panic!("unexpected call {:?}", call),
+                })
+                .collect();
+            assert_eq!(block_references, vec![expected; 5]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unexpected_query_response() {
+        let provider = Arc::new(MockProvider::new());
+        provider.on_query(|_, _| {
+            Some(Ok(query_response(QueryResponseKind::ViewState(
+                near_primitives::views::ViewStateResult {
+                    values: vec![],
+                    proof: vec![],
+                },
+            ))))
+        });
+        let account = account(provider);
+
+        assert!(matches!(
+            account.fetch_nonce().await,
+            Err(AccountError::UnexpectedResponse {
+                expected: "AccessKey",
+                ..
+            })
+        ));
+    }
+
+    /// Answers the requests of `verified_view_state` for the contract `contract.testnet`, whose state holds `a` and
+    /// `ab`, returning `values` as the result of the `view_state` query.
+    fn verified_view_state_provider(
+        values: Vec<(&'static [u8], &'static [u8])>,
+    ) -> Arc<MockProvider> {
+        let contract_id: AccountId = "contract.testnet".parse().unwrap();
+        let state: Vec<_> = [(b"a".as_slice(), b"1".as_slice()), (b"ab", b"2")]
+            .into_iter()
+            .map(|(key, value)| {
+                let key = TrieKey::ContractData {
+                    account_id: contract_id.clone(),
+                    key: key.to_vec(),
+                };
+                (key.to_vec(), value.to_vec())
+            })
+            .collect();
+        let (state_root, proof) = trie_proof(&state);
+
+        let parent_hash = CryptoHash::hash_bytes(b"parent");
+        let mut block = block_view(11, CryptoHash::hash_bytes(b"final"));
+        block.header.prev_hash = parent_hash;
+        block.chunks = vec![chunk_header(0, 11, state_root)];
+
+        let provider = Arc::new(MockProvider::new());
+        provider.push_block(Ok(block));
+        provider.push_experimental_protocol_config(Ok(serde_json::from_value(
+            near_rpc_test_server::fixtures::protocol_config(),
+        )
+        .unwrap()));
+        provider.on_query(move |request, block_reference| match request {
+            QueryRequest::ViewState {
+                include_proof: true,
+                ..
+            } if *block_reference == BlockReference::BlockId(BlockId::Hash(parent_hash)) => {
+                let values = values
+                    .iter()
+                    .map(|(key, value)| StateItem {
+                        key: key.to_vec().into(),
+                        value: value.to_vec().into(),
+                    })
+                    .collect();
+                Some(Ok(query_response(QueryResponseKind::ViewState(
+                    ViewStateResult {
+                        values,
+                        proof: proof.clone(),
+                    },
+                ))))
+            }
+            _ => None,
+        });
+        provider
+    }
+
+    #[tokio::test]
+    async fn test_verified_view_state() {
+        let contract_id: AccountId = "contract.testnet".parse().unwrap();
+        let provider = verified_view_state_provider(vec![(b"a", b"1"), (b"ab", b"2")]);
+        let verified = verified_view_state(provider, contract_id.clone(), Some("a".to_string()))
+            .await
+            .unwrap();
+        let keys: Vec<_> = verified
+            .values
+            .iter()
+            .map(|item| item.key.to_vec())
+            .collect();
+        assert_eq!(keys, vec![b"a".to_vec(), b"ab".to_vec()]);
+
+        // A node omitting a key of the requested range is caught.
+        let provider = verified_view_state_provider(vec![(b"ab", b"2")]);
+        assert!(matches!(
+            verified_view_state(provider, contract_id, Some("a".to_string())).await,
+            Err(AccountError::StateProof(StateProofError::MissingKey(key))) if key == b"a"
+        ));
+    }
+}
@@ -0,0 +1,133 @@
+//! The `errors` module defines the error type returned by the `near-accounts` crate.
+//!
+//! `AccountError` keeps the typed `JsonRpcError` values produced by `near-providers` instead of
+//! erasing them, so callers can tell an RPC failure apart from a nonce fetch failure or a response
+//! of an unexpected kind, and decide whether to retry, alert or surface the error to a user.
+//! The `JsonRpcError`s and `NetworkError`s are boxed to keep `AccountError` small, and convert into the variant of their method with `?`.
+//! Every variant is `Send + Sync`, so results can be moved across `tokio::spawn` boundaries. The message of a
+//! variant wrapping another error does not repeat it: the wrapped error is its `source`, which error reporters such
+//! as `anyhow` print after it.
+
+use near_providers::jsonrpc_client::errors::JsonRpcError;
+use near_providers::network::NetworkError;
+use near_providers::state_proof::StateProofError;
+use near_providers::types::{
+    blocks::RpcBlockError, config::RpcProtocolConfigError, query::QueryResponseKind,
+    query::RpcQueryError, receipts::RpcReceiptError, transactions::RpcTransactionError,
+};
+use thiserror::Error;
+
+/// Errors returned by `Account` methods, the account view helpers and `TransactionSender`.
+#[derive(Debug, Error)]
+pub enum AccountError {
+    /// Fetching the current nonce of the signer's access key failed.
+    #[error("failed to fetch the access key nonce")]
+    NonceFetch(#[source] Box<JsonRpcError<RpcQueryError>>),
+
+    /// Fetching the block whose hash is used as the transaction's reference block failed.
+    #[error("failed to fetch the reference block")]
+    BlockFetch(#[source] Box<JsonRpcError<RpcBlockError>>),
+
+    /// A query for account, access key, contract state or view function data failed.
+    #[error("query failed")]
+    Query(#[source] Box<JsonRpcError<RpcQueryError>>),
+
+    /// Fetching the protocol configuration failed.
+    #[error("failed to fetch the protocol config")]
+    ProtocolConfig(#[source] Box<JsonRpcError<RpcProtocolConfigError>>),
+
+    /// Sending a transaction, or waiting for its outcome, failed.
+    #[error("transaction failed")]
+    Transaction(#[source] Box<JsonRpcError<RpcTransactionError>>),
+
+    /// Fetching a receipt by its id failed.
+    #[error("failed to fetch the receipt")]
+    Receipt(#[source] Box<JsonRpcError<RpcReceiptError>>),
+
+    /// The contract state returned by the RPC does not match the state root it was verified against, or the state
+    /// root is not available.
+    #[error("failed to verify the contract state")]
+    StateProof(#[from] StateProofError),
+
+    /// The provider is not connected to the network the account is bound to, or its chain id could not be fetched.
+    #[error("refusing to sign for the network")]
+    Network(#[source] Box<NetworkError>),
+
+    /// Function call arguments could not be serialized to JSON.
+    #[error("failed to serialize function call arguments")]
+    Arguments(#[source] serde_json::Error),
+
+    /// The `wait_until` string passed to `TransactionSender::transact_advanced` is not a valid `TxExecutionStatus`.
+    #[error("invalid wait_until value `{value}`")]
+    InvalidWaitUntil {
+        value: String,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    /// A function call access key was requested without any method names.
+    #[error("no method_names argument provided for function call access keys, provide at least an empty vector")]
+    MissingMethodNames,
+
+    /// The RPC answered a query with a response of a different kind than the one requested.
+    #[error("unexpected query response kind, expected {expected}")]
+    UnexpectedResponse {
+        expected: &'static str,
+        received: Box<QueryResponseKind>,
+    },
+}
+
+impl AccountError {
+    pub(crate) fn unexpected_response(expected: &'static str, received: QueryResponseKind) -> Self {
+        Self::UnexpectedResponse {
+            expected,
+            received: Box::new(received),
+        }
+    }
+}
+
+macro_rules! from_rpc_error {
+    ($($error:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl From<JsonRpcError<$error>> for AccountError {
+                fn from(err: JsonRpcError<$error>) -> Self {
+                    Self::$variant(Box::new(err))
+                }
+            }
+        )*
+    };
+}
+
+impl From<NetworkError> for AccountError {
+    fn from(err: NetworkError) -> Self {
+        Self::Network(Box::new(err))
+    }
+}
+
+from_rpc_error! {
+    RpcQueryError => Query,
+    RpcProtocolConfigError => ProtocolConfig,
+    RpcTransactionError => Transaction,
+    RpcReceiptError => Receipt,
+}
+
+#[cfg(test)]
+#[test]
+fn test_account_error_is_send_sync() {
+    fn assert_send_sync<T: Send + Sync + 'static>() {}
+    assert_send_sync::<AccountError>();
+}
+
+#[cfg(test)]
+#[test]
+fn test_account_error_does_not_repeat_its_source() {
+    use std::error::Error as _;
+
+    let source = serde_json::from_str::<u8>("not json").unwrap_err();
+    let err = AccountError::Arguments(source);
+    assert_eq!(
+        err.to_string(),
+        "failed to serialize function call arguments"
+    );
+    assert!(err.source().is_some());
+}
@@ -27,7 +27,7 @@
 //! let provider = Arc::new(JsonRpcProvider::new("https://rpc.testnet.near.org"));
 //! let account_id = "example_account.testnet".parse()?;
 //!
-//! let result = state(provider, account_id).await?;
+//! let result = state(provider, account_id, None).await?;
 //! println!("Account state: {:?}", result);
 //! # Ok(())
 //! # }
@@ -35,45 +35,53 @@
 //!
 //! # Example - `function_call`
 //! ```no_run
-//! use near_accounts::Account;
+//! use near_accounts::{Account, AccountError};
 //! use near_crypto::InMemorySigner;
 //! use near_primitives::types::Gas;
 //! use near_providers::JsonRpcProvider;
 //! use std::sync::Arc;
 //! use near_primitives::types::AccountId;
 //! use serde_json::json;
-
+//!
 //! #[tokio::main]
 //! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
 //!     env_logger::init();
-
-//!     let signer_account_id: AccountId = ("your_account_id")?.parse()?;
-//!     let signer_secret_key = utils::input("your_private_key")?.parse()?;
+//!
+//!     let signer_account_id: AccountId = "your_account_id".parse()?;
+//!     let signer_secret_key = "your_private_key".parse()?;
 //!     let contract_id: AccountId = "contract.near-api-rs.testnet".parse::<AccountId>()?;
 //!     let signer = InMemorySigner::from_secret_key(signer_account_id.clone(), signer_secret_key);
-
+//!
 //!     // Amount to transfer to the new account
 //!     let gas: Gas = 100_000_000_000_000; // Example amount in yoctoNEAR
-
+//!
 //!     let provider = Arc::new(JsonRpcProvider::new("https://rpc.testnet.near.org"));
 //!     let signer = Arc::new(signer);
-
+//!
 //!     let account = Account::new(signer_account_id, signer, provider);
 //!     let method_name = "set_status".to_string();
-
+//!
 //!     let args_json = json!({"message": "working1"});
 //!     let result = account
-//!         .function_call(contract_id, method_name, args_json, gas, 0)
+//!         .function_call(&contract_id, method_name, args_json, gas, 0)
+//!         .await?
+//!         .transact()
 //!         .await;
-
-//!     println!("response: {:#?}", result);
-
+//!
+//!     match result {
+//!         Ok(response) => println!("response: {:#?}", response),
+//!         Err(AccountError::Transaction(err)) => println!("transaction failed: {}", err),
+//!         Err(err) => return Err(err.into()),
+//!     }
+//!
 //! #    Ok(())
-//! #}
+//! # }
 //! ```
 
 pub use crate::accounts::Account;
+pub use crate::errors::AccountError;
 
 mod access_keys;
 pub mod accounts;
+pub mod errors;
 mod transaction_sender;
@@ -1,8 +1,10 @@
 use crate::accounts::ArcProviderSendSync;
+use crate::errors::AccountError;
 use near_primitives::hash::CryptoHash;
 use near_primitives::transaction::SignedTransaction;
-use near_primitives::views::TxExecutionStatus;
-use near_providers::types::transactions::RpcTransactionResponse;
+use near_primitives::views::{ReceiptView, TxExecutionStatus};
+use near_providers::tx_status::RpcTransactionWithReceiptsResponse;
+use near_providers::types::transactions::{RpcTransactionResponse, TransactionInfo};
 
 ///This struct represent a Transaction Sender used specifically if you want to send transactions manually.
 /// This gives user more control over how they want to send their transactions to the NEAR network for examples, asyn, sync or advanced.
@@ -32,63 +34,159 @@ impl TransactionSender {
     }
 
     ///Send your transaction to the NEAR blockchain synchronously using the send_tx RPC end point and default wait_until value
-    pub async fn transact(self) -> Result<RpcTransactionResponse, Box<dyn std::error::Error>> {
+    pub async fn transact(self) -> Result<RpcTransactionResponse, AccountError> {
         self.provider
             .send_tx(self.signed_transaction, TxExecutionStatus::default())
             .await
-            .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
+            .map_err(AccountError::from)
     }
 
     ///Send your transaction to the NEAR blockchain asynchronously using the send_tx RPC end point and default wait_until None.
-    pub async fn transact_async(
-        self,
-    ) -> Result<RpcTransactionResponse, Box<dyn std::error::Error>> {
+    pub async fn transact_async(self) -> Result<RpcTransactionResponse, AccountError> {
         self.provider
             .send_tx(self.signed_transaction, TxExecutionStatus::None)
             .await
-            .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
+            .map_err(AccountError::from)
     }
 
     ///Send your transaction to the NEAR blockchain using the send_tx RPC end point and custom wait_until value.
     /// Different wait_until values and what they mean:
     ///
     /// * None
-    /// Transaction is waiting to be included into the block
+    ///   Transaction is waiting to be included into the block
     ///
     /// * Included
-    /// Transaction is included into the block. The block may be not finalised yet
+    ///   Transaction is included into the block. The block may be not finalised yet
     ///
     /// * ExecutedOptimistic,
-    /// Transaction is included into the block +
-    /// All the transaction receipts finished their execution.
-    /// The corresponding blocks for tx and each receipt may be not finalized yet
-    /// It is also the default value unless defined otherwise.
+    ///   Transaction is included into the block +
+    ///   All the transaction receipts finished their execution.
+    ///   The corresponding blocks for tx and each receipt may be not finalized yet
+    ///   It is also the default value unless defined otherwise.
     ///
     /// * IncludedFinal
-    /// Transaction is included into finalized block
+    ///   Transaction is included into finalized block
     ///
     /// * Executed
-    /// Transaction is included into finalized block +
-    /// All the transaction receipts finished their execution.
-    /// The corresponding blocks for each receipt may be not finalized yet
+    ///   Transaction is included into finalized block +
+    ///   All the transaction receipts finished their execution.
+    ///   The corresponding blocks for each receipt may be not finalized yet
     ///
     /// * Final
-    /// Transaction is included into finalize block +
-    /// Execution of transaction receipts is finalized
+    ///   Transaction is included into finalize block +
+    ///   Execution of transaction receipts is finalized
     pub async fn transact_advanced(
         self,
         wait_until_str: &str,
-    ) -> Result<RpcTransactionResponse, Box<dyn std::error::Error>> {
+    ) -> Result<RpcTransactionResponse, AccountError> {
         let wait_until: TxExecutionStatus =
-            serde_json::from_value(serde_json::json!(wait_until_str))?;
+            serde_json::from_value(serde_json::json!(wait_until_str)).map_err(|source| {
+                AccountError::InvalidWaitUntil {
+                    value: wait_until_str.to_string(),
+                    source,
+                }
+            })?;
         self.provider
             .send_tx(self.signed_transaction, wait_until)
             .await
-            .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
+            .map_err(AccountError::from)
+    }
+
+    /// Fetches the status of the transaction once it reaches `wait_until`, with the receipts it created included
+    /// next to their execution outcomes. Use it after sending the transaction to follow its cross-contract calls.
+    pub async fn status_with_receipts(
+        &self,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionWithReceiptsResponse, AccountError> {
+        let transaction_info = TransactionInfo::TransactionId {
+            tx_hash: self.signed_transaction.get_hash(),
+            sender_account_id: self.signed_transaction.transaction.signer_id.clone(),
+        };
+        self.provider
+            .tx_status_with_receipts(transaction_info, wait_until)
+            .await
+            .map_err(AccountError::from)
+    }
+
+    /// Fetches a receipt by its id, e.g. one of the receipt ids listed in the outcome of the transaction.
+    pub async fn receipt(&self, receipt_id: CryptoHash) -> Result<ReceiptView, AccountError> {
+        self.provider
+            .receipt(receipt_id)
+            .await
+            .map_err(AccountError::from)
     }
 
     /// Returns transaction hash for a given signed transaction
-    pub fn get_transaction_hash(self) -> Result<CryptoHash, Box<dyn std::error::Error>> {
+    pub fn get_transaction_hash(self) -> Result<CryptoHash, AccountError> {
         Ok(self.signed_transaction.get_hash())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_crypto::{InMemorySigner, KeyType};
+    use near_providers::testing::{handler_error, MockProvider};
+    use near_providers::types::receipts::RpcReceiptError;
+    use near_providers::ProviderRequest;
+    use std::sync::Arc;
+
+    fn transaction_sender(provider: Arc<MockProvider>) -> TransactionSender {
+        let signer =
+            InMemorySigner::from_seed("alice.testnet".parse().unwrap(), KeyType::ED25519, "alice");
+        let signed_transaction = SignedTransaction::send_money(
+            1,
+            "alice.testnet".parse().unwrap(),
+            "bob.testnet".parse().unwrap(),
+            &signer,
+            100,
+            CryptoHash::default(),
+        );
+        TransactionSender::new(signed_transaction, provider)
+    }
+
+    #[tokio::test]
+    async fn test_status_with_receipts() {
+        let provider = Arc::new(MockProvider::new());
+        provider.push_tx_status_with_receipts(Ok(RpcTransactionWithReceiptsResponse {
+            final_execution_outcome: None,
+            final_execution_status: TxExecutionStatus::Final,
+        }));
+        let sender = transaction_sender(provider.clone());
+
+        let response = sender
+            .status_with_receipts(TxExecutionStatus::Final)
+            .await
+            .unwrap();
+        assert_eq!(response.final_execution_status, TxExecutionStatus::Final);
+        match &provider.calls()[0] {
+            ProviderRequest::TxStatusWithReceipts {
+                transaction_info:
+                    TransactionInfo::TransactionId {
+                        tx_hash,
+                        sender_account_id,
+                    },
+                wait_until: TxExecutionStatus::Final,
+            } => {
+                assert_eq!(*tx_hash, sender.signed_transaction.get_hash());
+                assert_eq!(sender_account_id, "alice.testnet");
+            }
+            call => panic!("unexpected call {:?}", call),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_receipt_error() {
+        let provider = Arc::new(MockProvider::new());
+        let receipt_id = CryptoHash::hash_bytes(b"receipt");
+        provider.push_receipt(Err(handler_error(RpcReceiptError::UnknownReceipt {
+            receipt_id,
+        })));
+        let sender = transaction_sender(provider);
+
+        assert!(matches!(
+            sender.receipt(receipt_id).await,
+            Err(AccountError::Receipt(_))
+        ));
+    }
+}
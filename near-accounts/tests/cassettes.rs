@@ -0,0 +1,237 @@
+//! Golden file tests of the `Account` flows shown in the examples. Each flow is replayed through a `ReplayProvider`
+//! from the cassette of the same name under `tests/cassettes`, so a change to the requests a flow makes, or to how
+//! it reads the responses, fails here with the request that no longer matches the recording.
+//!
+//! The cassettes are recorded against a `TestRpcServer` serving the fixtures. After an intended change to the
+//! requests of a flow, record them again with `cargo test -p near-accounts --test cassettes -- --ignored` and
+//! review the diff of the JSON files.
+
+use near_accounts::accounts::{
+    get_access_key, get_account_balance, state, view_function, AccountBalance, ArcProviderSendSync,
+};
+use near_accounts::Account;
+use near_crypto::{InMemorySigner, KeyType, SecretKey};
+use near_primitives::types::{AccountId, Balance, Gas};
+use near_primitives::views::{AccessKeyPermissionView, FinalExecutionStatus};
+use near_providers::cassette::{RecordingProvider, ReplayProvider};
+use near_providers::{JsonRpcProvider, Network};
+use near_rpc_test_server::{fixtures, RpcResponse, TestRpcServer};
+use serde_json::{json, Value};
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const GAS: Gas = 100_000_000_000_000;
+
+fn cassette(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/cassettes")
+        .join(format!("{}.json", name))
+}
+
+fn signer_account_id() -> AccountId {
+    "near-api-rs.testnet".parse().unwrap()
+}
+
+fn contract_id() -> AccountId {
+    "contract.near-api-rs.testnet".parse().unwrap()
+}
+
+fn account(provider: ArcProviderSendSync) -> Account {
+    let signer =
+        InMemorySigner::from_seed(signer_account_id(), KeyType::ED25519, "near-api-rs.testnet");
+    Account::new(signer_account_id(), Arc::new(signer), provider).with_network(Network::Localnet)
+}
+
+async fn send_money(provider: ArcProviderSendSync) -> FinalExecutionStatus {
+    let receiver_id: AccountId = "receiver.testnet".parse().unwrap();
+    let amount: Balance = 10_000_000_000;
+    let outcome = account(provider)
+        .send_money(&receiver_id, amount)
+        .await
+        .unwrap();
+    outcome.status
+}
+
+async fn function_call(provider: ArcProviderSendSync) -> Option<FinalExecutionStatus> {
+    let response = account(provider)
+        .function_call(
+            &contract_id(),
+            "set_status".to_string(),
+            json!({"message": "working1"}),
+            GAS,
+            0,
+        )
+        .await
+        .unwrap()
+        .transact()
+        .await
+        .unwrap();
+    response
+        .final_execution_outcome
+        .map(|outcome| outcome.into_outcome().status)
+}
+
+async fn create_account(provider: ArcProviderSendSync) -> Option<FinalExecutionStatus> {
+    let new_account_id: AccountId = "new.near-api-rs.testnet".parse().unwrap();
+    let new_secret_key = SecretKey::from_seed(KeyType::ED25519, "new.near-api-rs.testnet");
+    let amount: Balance = 10_000_000_000_000_000_000_000;
+    let response = account(provider)
+        .function_call(
+            &"testnet".parse().unwrap(),
+            "create_account".to_string(),
+            json!({
+                "new_account_id": new_account_id,
+                "new_public_key": new_secret_key.public_key(),
+            }),
+            GAS,
+            amount,
+        )
+        .await
+        .unwrap()
+        .transact()
+        .await
+        .unwrap();
+    response
+        .final_execution_outcome
+        .map(|outcome| outcome.into_outcome().status)
+}
+
+/// The results of the view calls of the examples, read from the contract account.
+#[derive(Debug, PartialEq)]
+struct Views {
+    status: Value,
+    amount: Balance,
+    access_keys: usize,
+    balance: AccountBalance,
+}
+
+async fn view_calls(provider: ArcProviderSendSync) -> Views {
+    let call = view_function(
+        provider.clone(),
+        contract_id(),
+        "get_status".to_string(),
+        json!({"account_id": contract_id()}),
+        None,
+    )
+    .await
+    .unwrap();
+    let account = state(provider.clone(), contract_id(), None).await.unwrap();
+    let access_keys = get_access_key(provider.clone(), contract_id(), None)
+        .await
+        .unwrap();
+    assert!(access_keys
+        .keys
+        .iter()
+        .all(|key| key.access_key.permission == AccessKeyPermissionView::FullAccess));
+    let balance = get_account_balance(provider, contract_id(), None)
+        .await
+        .unwrap();
+    Views {
+        status: serde_json::from_slice(&call.result).unwrap(),
+        amount: account.amount,
+        access_keys: access_keys.keys.len(),
+        balance,
+    }
+}
+
+/// Starts a server answering the fixtures, and the view calls of the examples that the fixtures do not cover.
+fn server() -> TestRpcServer {
+    let server = TestRpcServer::start().with_fixtures();
+    let block_hash = near_primitives::hash::CryptoHash::hash_bytes(b"block");
+    // The fixture dates the genesis at the current time, which would change the recording on every run.
+    let mut protocol_config = fixtures::protocol_config();
+    protocol_config["genesis_time"] = json!("2024-03-01T00:00:00Z");
+    server.on_result("EXPERIMENTAL_protocol_config", protocol_config);
+    server.on("query", move |params| {
+        let result = match params["request_type"].as_str() {
+            Some("view_access_key") => fixtures::access_key(0, 100, block_hash),
+            Some("view_account") => fixtures::account(10_u128.pow(24), 100, block_hash),
+            Some("call_function") => json!({
+                "result": serde_json::to_vec(&json!("working1")).unwrap(),
+                "logs": [],
+                "block_height": 100,
+                "block_hash": block_hash,
+            }),
+            Some("view_access_key_list") => {
+                let public_key = SecretKey::from_seed(KeyType::ED25519, "contract").public_key();
+                json!({
+                    "keys": [{
+                        "public_key": public_key,
+                        "access_key": { "nonce": 0, "permission": "FullAccess" },
+                    }],
+                    "block_height": 100,
+                    "block_hash": block_hash,
+                })
+            }
+            _ => return RpcResponse::internal_error("unsupported query request type"),
+        };
+        RpcResponse::Result(result)
+    });
+    server
+}
+
+/// Runs `flow` against the fixture server and writes the interactions to the cassette `name`.
+async fn record<F, Fut>(name: &str, flow: F)
+where
+    F: FnOnce(ArcProviderSendSync) -> Fut,
+    Fut: Future,
+{
+    let server = server();
+    let recorder = Arc::new(RecordingProvider::new(JsonRpcProvider::new(&server.url())));
+    flow(recorder.clone()).await;
+    recorder.save(cassette(name)).unwrap();
+}
+
+/// Runs `flow` against the cassette `name`, checking that it makes every recorded request.
+async fn replay<F, Fut>(name: &str, flow: F) -> Fut::Output
+where
+    F: FnOnce(ArcProviderSendSync) -> Fut,
+    Fut: Future,
+{
+    let replay = Arc::new(ReplayProvider::from_file(cassette(name)).unwrap());
+    let output = flow(replay.clone()).await;
+    assert!(
+        replay.remaining().is_empty(),
+        "{} was not replayed in full: {:?}",
+        name,
+        replay.remaining()
+    );
+    output
+}
+
+#[tokio::test]
+async fn test_send_money() {
+    let status = replay("send_money", send_money).await;
+    assert_eq!(status, FinalExecutionStatus::SuccessValue(vec![]));
+}
+
+#[tokio::test]
+async fn test_function_call() {
+    let status = replay("function_call", function_call).await;
+    assert_eq!(status, Some(FinalExecutionStatus::SuccessValue(vec![])));
+}
+
+#[tokio::test]
+async fn test_create_account() {
+    let status = replay("create_account", create_account).await;
+    assert_eq!(status, Some(FinalExecutionStatus::SuccessValue(vec![])));
+}
+
+#[tokio::test]
+async fn test_view_calls() {
+    let views = replay("view_calls", view_calls).await;
+    assert_eq!(views.status, json!("working1"));
+    assert_eq!(views.amount, 10_u128.pow(24));
+    assert_eq!(views.access_keys, 1);
+    assert_eq!(views.balance.total, 10_u128.pow(24).to_string());
+}
+
+#[tokio::test]
+#[ignore = "records the cassettes under tests/cassettes"]
+async fn record_cassettes() {
+    record("send_money", send_money).await;
+    record("function_call", function_call).await;
+    record("create_account", create_account).await;
+    record("view_calls", view_calls).await;
+}
@@ -1,7 +1,7 @@
 use near_api_lib::primitives::types::{AccountId, Balance, Gas};
 use near_api_lib::Account;
 use near_api_lib::InMemorySigner;
-use near_api_lib::JsonRpcProvider;
+use near_api_lib::{JsonRpcProvider, Network};
 
 use serde_json::json;
 use std::sync::Arc;
@@ -24,10 +24,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let amount: Balance = 10_000_000_000_000_000_000_000; // Example amount in yoctoNEAR
 
     let new_secret_key = near_crypto::SecretKey::from_random(near_crypto::KeyType::ED25519);
-    let provider = Arc::new(JsonRpcProvider::new("https://rpc.testnet.near.org"));
+    let provider = Arc::new(JsonRpcProvider::for_network(&Network::Testnet));
     let signer = Arc::new(signer);
 
-    let account = Account::new(signer_account_id, signer, provider);
+    let account = Account::new(signer_account_id, signer, provider).with_network(Network::Testnet);
 
     let contract_id: AccountId = "testnet".parse::<AccountId>()?;
     let method_name = "create_account".to_string();
@@ -1,6 +1,6 @@
 use near_api_lib::accounts::get_access_key;
 use near_api_lib::primitives::types::AccountId;
-use near_api_lib::JsonRpcProvider;
+use near_api_lib::{JsonRpcProvider, Network};
 use std::sync::Arc;
 
 #[tokio::main]
@@ -9,9 +9,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let account_id: AccountId = "near-api-rs.testnet".parse::<AccountId>()?;
 
-    let provider = Arc::new(JsonRpcProvider::new("https://rpc.testnet.near.org"));
+    let provider = Arc::new(JsonRpcProvider::for_network(&Network::Testnet));
 
-    let result = get_access_key(provider, account_id).await;
+    let result = get_access_key(provider, account_id, None).await;
 
     println!("response: {:#?}", result);
 
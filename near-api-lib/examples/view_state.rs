@@ -1,5 +1,5 @@
 use near_api_lib::accounts::view_state;
-use near_api_lib::JsonRpcProvider;
+use near_api_lib::{JsonRpcProvider, Network};
 use near_primitives::types::AccountId;
 use std::sync::Arc;
 
@@ -7,11 +7,11 @@ use std::sync::Arc;
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init();
 
-    let provider = Arc::new(JsonRpcProvider::new("https://rpc.testnet.near.org"));
+    let provider = Arc::new(JsonRpcProvider::for_network(&Network::Testnet));
 
     let contract_id: AccountId = "contract.near-api-rs.testnet".parse::<AccountId>()?;
 
-    let result = view_state(provider, contract_id, None).await;
+    let result = view_state(provider, contract_id, None, None).await;
     println!("response: {:#?}", result);
 
     Ok(())
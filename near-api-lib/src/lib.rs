@@ -24,6 +24,7 @@ pub use near_accounts::Account;
 
 pub use near_providers as providers;
 pub use near_providers::JsonRpcProvider;
+pub use near_providers::Network;
 
 pub use near_crypto::InMemorySigner;
 
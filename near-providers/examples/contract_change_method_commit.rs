@@ -3,7 +3,7 @@ use near_primitives::types::BlockReference;
 use near_providers::jsonrpc_client::{methods, JsonRpcClient};
 use near_providers::types::query::QueryResponseKind;
 
-use near_providers::JsonRpcProvider;
+use near_providers::{JsonRpcProvider, Network};
 
 // items from traits can only be used if the trait is in scope
 // can we change it somehow with better crate design?
@@ -19,7 +19,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let client = JsonRpcClient::connect("https://rpc.testnet.near.org");
 
-    let provider = JsonRpcProvider::new("https://rpc.testnet.near.org");
+    let provider = JsonRpcProvider::for_network(&Network::Testnet);
 
     let signer_account_id = utils::input("Enter the signer Account ID: ")?.parse()?;
     let signer_secret_key = utils::input("Enter the signer's private key: ")?.parse()?;
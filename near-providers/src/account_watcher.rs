@@ -0,0 +1,434 @@
+//! The `account_watcher` module reacts to the activity of a set of accounts, such as incoming transfers and function
+//! calls, without running an indexer.
+//!
+//! The `AccountWatcher` follows the chain with a `BlockStream` fetching the new chunks of every block, and scans their
+//! transactions and receipts for the watched accounts:
+//!
+//! - A receipt whose receiver is watched yields an event per transfer, function call, added key and deleted key in
+//!   it. Gas refunds, whose predecessor is `system`, are left out. A receipt is seen when it is included in a chunk,
+//!   before it is executed, so its actions may still fail: a transfer is an `IncludedIncomingTransfer`, not money
+//!   received yet. Use a `PaymentConfirmation` of the `payment` module to wait for a transfer to be executed.
+//! - A transaction signed by a watched account to another account yields an `OutgoingTransaction`. The receipt it is
+//!   converted to is reported on the receiver side, if the receiver is watched as well.
+//! - A transaction of a watched account to itself, e.g. to add a key, is executed without an outgoing receipt, so its
+//!   actions are reported from the transaction.
+//!
+//! The events of a block are yielded in the order of its chunks, transactions first. `AccountWatcher::checkpoint`
+//! returns the last block whose events were all yielded, to persist and resume from with `StartAt::Checkpoint`, so
+//! that no event is missed after a restart. Blocks without events move the checkpoint forward as well.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use futures::StreamExt;
+//! use near_primitives::types::Finality;
+//! use near_providers::account_watcher::{AccountEventKind, AccountWatcher};
+//! use near_providers::block_stream::StartAt;
+//! use near_providers::JsonRpcProvider;
+//! use std::sync::Arc;
+//!
+//! # async fn run() {
+//! let provider = Arc::new(JsonRpcProvider::new("https://rpc.testnet.near.org"));
+//! let account_id = "merchant.testnet".parse().unwrap();
+//! let mut events = AccountWatcher::new(provider, [account_id], StartAt::LatestFinal)
+//!     .with_finality(Finality::DoomSlug);
+//! while let Some(event) = events.next().await {
+//!     match event {
+//!         Ok(event) => {
+//!             if let AccountEventKind::IncludedIncomingTransfer { sender_id, amount } = &event.kind {
+//!                 println!("{} is sending {} yoctoNEAR to {}", sender_id, amount, event.account_id);
+//!             }
+//!         }
+//!         Err(err) => eprintln!("{}", err),
+//!     }
+//! }
+//! # }
+//! ```
+
+use crate::block_stream::{BlockCheckpoint, BlockStream, BlockStreamError, StartAt, StreamedBlock};
+use crate::Provider;
+use futures::stream::{Stream, StreamExt};
+use near_crypto::PublicKey;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{AccountId, Balance, BlockHeight, Finality, Gas};
+use near_primitives::views::{AccessKeyView, ActionView, ReceiptEnumView};
+use std::collections::{HashSet, VecDeque};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+/// The predecessor of the receipts refunding gas and deposits.
+const SYSTEM_ACCOUNT: &str = "system";
+
+/// Something that happened to a watched account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountEvent {
+    /// The watched account the event is about.
+    pub account_id: AccountId,
+    /// The height of the block the transaction or receipt is included in.
+    pub block_height: BlockHeight,
+    /// The hash of the block the transaction or receipt is included in.
+    pub block_hash: CryptoHash,
+    pub origin: EventOrigin,
+    pub kind: AccountEventKind,
+}
+
+/// The transaction or receipt an `AccountEvent` was found in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventOrigin {
+    Transaction(CryptoHash),
+    Receipt(CryptoHash),
+}
+
+/// The kinds of `AccountEvent`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccountEventKind {
+    /// `amount` yoctoNEAR sent to the account by `sender_id`, in a receipt included in a chunk but not executed yet.
+    /// The transfer may still fail, so the amount is not received until the receipt is executed successfully.
+    IncludedIncomingTransfer {
+        sender_id: AccountId,
+        amount: Balance,
+    },
+    /// A call of `method_name` on the contract of the account by `caller_id`, attaching `deposit` yoctoNEAR.
+    FunctionCall {
+        caller_id: AccountId,
+        method_name: String,
+        args: Vec<u8>,
+        gas: Gas,
+        deposit: Balance,
+    },
+    /// An access key added to the account.
+    AccessKeyAdded {
+        public_key: PublicKey,
+        access_key: AccessKeyView,
+    },
+    /// An access key deleted from the account.
+    AccessKeyDeleted { public_key: PublicKey },
+    /// A transaction signed by the account to `receiver_id`.
+    OutgoingTransaction {
+        receiver_id: AccountId,
+        actions: Vec<ActionView>,
+    },
+}
+
+/// Returns the events of `account_ids` in the chunks of `block`, in the order described in the module documentation.
+pub fn block_events(block: &StreamedBlock, account_ids: &HashSet<AccountId>) -> Vec<AccountEvent> {
+    let mut events = Vec::new();
+    let mut push = |account_id: &AccountId, origin: EventOrigin, kind: AccountEventKind| {
+        events.push(AccountEvent {
+            account_id: account_id.clone(),
+            block_height: block.height(),
+            block_hash: block.hash(),
+            origin,
+            kind,
+        })
+    };
+
+    for chunk in &block.chunks {
+        for transaction in &chunk.transactions {
+            if !account_ids.contains(&transaction.signer_id) {
+                continue;
+            }
+            let origin = EventOrigin::Transaction(transaction.hash);
+            if transaction.receiver_id == transaction.signer_id {
+                for kind in action_events(&transaction.signer_id, &transaction.actions) {
+                    push(&transaction.signer_id, origin, kind);
+                }
+            } else {
+                let kind = AccountEventKind::OutgoingTransaction {
+                    receiver_id: transaction.receiver_id.clone(),
+                    actions: transaction.actions.clone(),
+                };
+                push(&transaction.signer_id, origin, kind);
+            }
+        }
+
+        for receipt in &chunk.receipts {
+            let ReceiptEnumView::Action { actions, .. } = &receipt.receipt else {
+                continue;
+            };
+            if !account_ids.contains(&receipt.receiver_id)
+                || receipt.predecessor_id.as_str() == SYSTEM_ACCOUNT
+            {
+                continue;
+            }
+            let origin = EventOrigin::Receipt(receipt.receipt_id);
+            for kind in action_events(&receipt.predecessor_id, actions) {
+                push(&receipt.receiver_id, origin, kind);
+            }
+        }
+    }
+    events
+}
+
+/// Returns the events of the actions sent by `sender_id` to a watched account.
+fn action_events(sender_id: &AccountId, actions: &[ActionView]) -> Vec<AccountEventKind> {
+    actions
+        .iter()
+        .filter_map(|action| match action {
+            ActionView::Transfer { deposit } => Some(AccountEventKind::IncludedIncomingTransfer {
+                sender_id: sender_id.clone(),
+                amount: *deposit,
+            }),
+            ActionView::FunctionCall {
+                method_name,
+                args,
+                gas,
+                deposit,
+            } => Some(AccountEventKind::FunctionCall {
+                caller_id: sender_id.clone(),
+                method_name: method_name.clone(),
+                args: args.to_vec(),
+                gas: *gas,
+                deposit: *deposit,
+            }),
+            ActionView::AddKey {
+                public_key,
+                access_key,
+            } => Some(AccountEventKind::AccessKeyAdded {
+                public_key: public_key.clone(),
+                access_key: access_key.clone(),
+            }),
+            ActionView::DeleteKey { public_key } => Some(AccountEventKind::AccessKeyDeleted {
+                public_key: public_key.clone(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// An async `Stream` of the events of a set of accounts, see the module documentation.
+pub struct AccountWatcher<P: ?Sized> {
+    blocks: BlockStream<P>,
+    account_ids: HashSet<AccountId>,
+    /// The events of the last block scanned which were not yielded yet.
+    pending: VecDeque<AccountEvent>,
+    /// The last block scanned.
+    scanned: Option<BlockCheckpoint>,
+    /// The last block whose events were all yielded.
+    checkpoint: Option<BlockCheckpoint>,
+}
+
+impl<P: Provider + Send + Sync + ?Sized + 'static> AccountWatcher<P> {
+    /// Creates a watcher of `account_ids` scanning the final blocks fetched from `provider`, from `start` on.
+    pub fn new(
+        provider: Arc<P>,
+        account_ids: impl IntoIterator<Item = AccountId>,
+        start: StartAt,
+    ) -> Self {
+        let checkpoint = match &start {
+            StartAt::Checkpoint(checkpoint) => Some(*checkpoint),
+            _ => None,
+        };
+        Self {
+            blocks: BlockStream::new(provider, start).with_chunks(true),
+            account_ids: account_ids.into_iter().collect(),
+            pending: VecDeque::new(),
+            scanned: checkpoint,
+            checkpoint,
+        }
+    }
+
+    /// Scans the blocks once they reach `finality` instead of `Finality::Final`, see `BlockStream::with_finality`.
+    pub fn with_finality(mut self, finality: Finality) -> Self {
+        self.blocks = self.blocks.with_finality(finality);
+        self
+    }
+
+    /// Sets the time to wait for a new block once the watcher caught up with the chain, 1 second by default.
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.blocks = self.blocks.with_poll_interval(poll_interval);
+        self
+    }
+
+    /// Returns the checkpoint to resume the watcher from without missing an event, or `None` if no block was scanned
+    /// since the watcher started at the latest block or at a height.
+    pub fn checkpoint(&self) -> Option<BlockCheckpoint> {
+        self.checkpoint
+    }
+}
+
+impl<P: Provider + Send + Sync + ?Sized + 'static> Stream for AccountWatcher<P> {
+    type Item = Result<AccountEvent, BlockStreamError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(event) = this.pending.pop_front() {
+                if this.pending.is_empty() {
+                    this.checkpoint = this.scanned;
+                }
+                return Poll::Ready(Some(Ok(event)));
+            }
+            let block = match ready!(this.blocks.poll_next_unpin(cx)) {
+                Some(Ok(block)) => block,
+                Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+                None => return Poll::Ready(None),
+            };
+            this.pending.extend(block_events(&block, &this.account_ids));
+            this.scanned = Some(block.checkpoint());
+            if this.pending.is_empty() {
+                this.checkpoint = this.scanned;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::chain::{action_receipt_view, serve_chain, transaction_view};
+    use crate::testing::MockProvider;
+    use crate::ProviderRequest;
+    use near_crypto::KeyType;
+    use near_primitives::types::BlockReference;
+    use near_primitives::views::AccessKeyPermissionView;
+    use std::collections::HashMap;
+
+    fn alice() -> AccountId {
+        "alice.near".parse().unwrap()
+    }
+
+    fn full_access_key() -> AccessKeyView {
+        AccessKeyView {
+            nonce: 0,
+            permission: AccessKeyPermissionView::FullAccess,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_events_and_checkpoint() {
+        let provider = Arc::new(MockProvider::new());
+        let key = PublicKey::empty(KeyType::ED25519);
+        let transfer = ActionView::Transfer { deposit: 5 };
+        let call = ActionView::FunctionCall {
+            method_name: "ping".to_string(),
+            args: b"{}".to_vec().into(),
+            gas: 30,
+            deposit: 0,
+        };
+        let add_key = ActionView::AddKey {
+            public_key: key.clone(),
+            access_key: full_access_key(),
+        };
+        let hash = CryptoHash::hash_bytes;
+        let transactions = vec![
+            transaction_view(hash(b"t1"), "alice.near", "alice.near", vec![add_key]),
+            transaction_view(
+                hash(b"t2"),
+                "alice.near",
+                "bob.near",
+                vec![transfer.clone()],
+            ),
+            transaction_view(
+                hash(b"t3"),
+                "bob.near",
+                "alice.near",
+                vec![transfer.clone()],
+            ),
+        ];
+        let receipts = vec![
+            action_receipt_view(hash(b"r1"), "bob.near", "alice.near", vec![transfer, call]),
+            action_receipt_view(hash(b"r2"), "system", "alice.near", vec![]),
+            action_receipt_view(hash(b"r3"), "bob.near", "carol.near", vec![]),
+        ];
+        let delete_key = ActionView::DeleteKey {
+            public_key: key.clone(),
+        };
+        let contents = HashMap::from([
+            (10, (transactions, receipts)),
+            (
+                13,
+                (
+                    vec![],
+                    vec![action_receipt_view(
+                        hash(b"r4"),
+                        "bob.near",
+                        "alice.near",
+                        vec![delete_key],
+                    )],
+                ),
+            ),
+        ]);
+        serve_chain(&provider, &[10, 11, 13], contents);
+
+        let mut watcher = AccountWatcher::new(provider.clone(), [alice()], StartAt::Height(10));
+        let mut events = Vec::new();
+        for _ in 0..4 {
+            events.push(watcher.next().await.unwrap().unwrap());
+            // The checkpoint moves past block 10 once all its events are yielded.
+            assert_eq!(watcher.checkpoint().is_some(), events.len() == 4);
+        }
+        let kinds: Vec<_> = events.iter().map(|event| event.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                AccountEventKind::AccessKeyAdded {
+                    public_key: key.clone(),
+                    access_key: full_access_key(),
+                },
+                AccountEventKind::OutgoingTransaction {
+                    receiver_id: "bob.near".parse().unwrap(),
+                    actions: vec![ActionView::Transfer { deposit: 5 }],
+                },
+                AccountEventKind::IncludedIncomingTransfer {
+                    sender_id: "bob.near".parse().unwrap(),
+                    amount: 5,
+                },
+                AccountEventKind::FunctionCall {
+                    caller_id: "bob.near".parse().unwrap(),
+                    method_name: "ping".to_string(),
+                    args: b"{}".to_vec(),
+                    gas: 30,
+                    deposit: 0,
+                },
+            ]
+        );
+        assert_eq!(events[0].origin, EventOrigin::Transaction(hash(b"t1")));
+        assert_eq!(events[3].origin, EventOrigin::Receipt(hash(b"r1")));
+        assert!(events
+            .iter()
+            .all(|event| event.block_height == 10 && event.account_id == alice()));
+        let checkpoint = watcher.checkpoint().unwrap();
+        assert_eq!(checkpoint.height, 10);
+
+        // Resuming from the checkpoint passes over the empty block 11.
+        let mut watcher =
+            AccountWatcher::new(provider.clone(), [alice()], StartAt::Checkpoint(checkpoint));
+        let event = watcher.next().await.unwrap().unwrap();
+        assert_eq!(event.block_height, 13);
+        assert_eq!(
+            event.kind,
+            AccountEventKind::AccessKeyDeleted { public_key: key }
+        );
+        assert_eq!(watcher.checkpoint().unwrap().height, 13);
+    }
+
+    #[tokio::test]
+    async fn test_finality() {
+        let provider = Arc::new(MockProvider::new());
+        let receipt = action_receipt_view(
+            CryptoHash::hash_bytes(b"r1"),
+            "bob.near",
+            "alice.near",
+            vec![ActionView::Transfer { deposit: 1 }],
+        );
+        serve_chain(
+            &provider,
+            &[20],
+            HashMap::from([(20, (vec![], vec![receipt]))]),
+        );
+
+        let mut watcher = AccountWatcher::new(provider.clone(), [alice()], StartAt::LatestFinal)
+            .with_finality(Finality::None);
+        let event = watcher.next().await.unwrap().unwrap();
+        assert_eq!(event.block_height, 20);
+        assert!(matches!(
+            provider.calls()[0],
+            ProviderRequest::Block {
+                block_reference: BlockReference::Finality(Finality::None)
+            }
+        ));
+    }
+}
@@ -0,0 +1,629 @@
+//! The `ArchivalProvider` module offers a `Provider` that routes requests between a regular node and an archival node.
+//!
+//! Regular nodes garbage collect blocks, chunks, state and transactions older than a few epochs, and answer requests
+//! for them with errors such as `UNKNOWN_BLOCK` or `GARBAGE_COLLECTED_BLOCK`. Requests go to the regular node first;
+//! when it fails with an error meaning the data may have been garbage collected, the request is sent again to the
+//! archival node. Requests for blocks below a configurable height threshold go straight to the archival node, and
+//! requests that do not depend on historical data, such as `status` or sending transactions, always go to the regular
+//! node.
+
+use crate::batch;
+use crate::client_config::{RpcClientConfigError, RpcClientConfigResponse};
+use crate::jsonrpc_client::{
+    errors::JsonRpcError,
+    methods::{self, status::RpcStatusResponse},
+};
+use crate::tx_status::RpcTransactionWithReceiptsResponse;
+use crate::types::{
+    blocks::RpcBlockError,
+    changes::{
+        RpcStateChangesError, RpcStateChangesInBlockByTypeResponse, RpcStateChangesInBlockResponse,
+    },
+    chunks::{ChunkReference, RpcChunkError},
+    config::RpcProtocolConfigError,
+    gas_price::RpcGasPriceError,
+    light_client::{
+        RpcLightClientExecutionProofResponse, RpcLightClientNextBlockError,
+        RpcLightClientProofError,
+    },
+    network_info::{RpcNetworkInfoError, RpcNetworkInfoResponse},
+    query::{RpcQueryError, RpcQueryResponse},
+    receipts::RpcReceiptError,
+    status::{RpcHealthResponse, RpcStatusError},
+    transactions::{RpcTransactionError, TransactionInfo},
+    validator::RpcValidatorError,
+};
+use crate::utils::{clone_epoch_reference, clone_state_changes_request};
+use crate::{JsonRpcProvider, Network, Provider, ProviderRequest, ProviderResponse};
+use async_trait::async_trait;
+use near_chain_configs::{GenesisConfig, ProtocolConfigView};
+use near_jsonrpc_client::methods::tx::RpcTransactionResponse;
+use near_primitives::{
+    hash::CryptoHash,
+    transaction::SignedTransaction,
+    types::{BlockHeight, BlockId, BlockReference, EpochReference, TransactionOrReceiptId},
+    views::{
+        BlockView, ChunkView, EpochValidatorInfo, FinalExecutionOutcomeView, GasPriceView,
+        LightClientBlockView, QueryRequest, ReceiptView, StateChangesRequestView,
+        TxExecutionStatus,
+    },
+};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Classifies the handler errors of an RPC method that reads historical data.
+pub trait MissingDataError {
+    /// Returns true if the error may mean that the node garbage collected the requested data, so an archival node
+    /// may still have it.
+    fn is_missing_data(&self) -> bool;
+}
+
+impl MissingDataError for RpcQueryError {
+    fn is_missing_data(&self) -> bool {
+        matches!(
+            self,
+            RpcQueryError::UnknownBlock { .. } | RpcQueryError::GarbageCollectedBlock { .. }
+        )
+    }
+}
+
+impl MissingDataError for RpcBlockError {
+    fn is_missing_data(&self) -> bool {
+        matches!(self, RpcBlockError::UnknownBlock { .. })
+    }
+}
+
+impl MissingDataError for RpcChunkError {
+    fn is_missing_data(&self) -> bool {
+        matches!(
+            self,
+            RpcChunkError::UnknownBlock { .. } | RpcChunkError::UnknownChunk { .. }
+        )
+    }
+}
+
+impl MissingDataError for RpcProtocolConfigError {
+    fn is_missing_data(&self) -> bool {
+        matches!(self, RpcProtocolConfigError::UnknownBlock { .. })
+    }
+}
+
+impl MissingDataError for RpcValidatorError {
+    fn is_missing_data(&self) -> bool {
+        matches!(self, RpcValidatorError::UnknownEpoch)
+    }
+}
+
+impl MissingDataError for RpcGasPriceError {
+    fn is_missing_data(&self) -> bool {
+        matches!(self, RpcGasPriceError::UnknownBlock { .. })
+    }
+}
+
+impl MissingDataError for RpcStateChangesError {
+    fn is_missing_data(&self) -> bool {
+        matches!(self, RpcStateChangesError::UnknownBlock { .. })
+    }
+}
+
+impl MissingDataError for RpcTransactionError {
+    fn is_missing_data(&self) -> bool {
+        matches!(self, RpcTransactionError::UnknownTransaction { .. })
+    }
+}
+
+impl MissingDataError for RpcReceiptError {
+    fn is_missing_data(&self) -> bool {
+        matches!(self, RpcReceiptError::UnknownReceipt { .. })
+    }
+}
+
+impl MissingDataError for RpcLightClientProofError {
+    fn is_missing_data(&self) -> bool {
+        matches!(
+            self,
+            RpcLightClientProofError::UnknownBlock { .. }
+                | RpcLightClientProofError::UnknownTransactionOrReceipt { .. }
+        )
+    }
+}
+
+impl MissingDataError for RpcLightClientNextBlockError {
+    fn is_missing_data(&self) -> bool {
+        matches!(self, RpcLightClientNextBlockError::UnknownBlock { .. })
+    }
+}
+
+/// Returns true if a failed call may succeed against an archival node. Only handler errors are considered: an
+/// unavailable regular node is the job of the `FailoverProvider`.
+pub fn is_missing_data<E: MissingDataError>(err: &JsonRpcError<E>) -> bool {
+    err.handler_error()
+        .is_some_and(|handler_error| handler_error.is_missing_data())
+}
+
+/// Represents a provider that sends requests for historical data to an archival provider when the regular provider
+/// no longer has it.
+pub struct ArchivalProvider<R, A> {
+    regular: R,
+    archival: A,
+    // Requests for blocks below this height go straight to the archival provider. `0` disables the routing by height.
+    height_threshold: AtomicU64,
+}
+
+impl<R: Provider, A: Provider> ArchivalProvider<R, A> {
+    /// Constructs a new `ArchivalProvider`, sending requests to `regular` first and falling back to `archival`.
+    pub fn new(regular: R, archival: A) -> Self {
+        Self {
+            regular,
+            archival,
+            height_threshold: AtomicU64::new(0),
+        }
+    }
+
+    /// Sends requests for blocks below `height` straight to the archival provider, without trying the regular one.
+    pub fn with_height_threshold(self, height: BlockHeight) -> Self {
+        self.set_height_threshold(height);
+        self
+    }
+
+    /// Moves the height below which requests go straight to the archival provider, e.g. as the chain advances and
+    /// the regular provider garbage collects more blocks.
+    pub fn set_height_threshold(&self, height: BlockHeight) {
+        self.height_threshold.store(height, Ordering::Relaxed);
+    }
+
+    /// Returns the height below which requests go straight to the archival provider, if any.
+    pub fn height_threshold(&self) -> Option<BlockHeight> {
+        match self.height_threshold.load(Ordering::Relaxed) {
+            0 => None,
+            height => Some(height),
+        }
+    }
+
+    /// Returns a reference to the regular provider.
+    pub fn regular(&self) -> &R {
+        &self.regular
+    }
+
+    /// Returns a reference to the archival provider.
+    pub fn archival(&self) -> &A {
+        &self.archival
+    }
+}
+
+impl ArchivalProvider<JsonRpcProvider, JsonRpcProvider> {
+    /// Constructs a new `ArchivalProvider` using the default RPC and archival RPC endpoints of `network`. Returns
+    /// `None` if the network has no archival endpoint.
+    pub fn for_network(network: &Network) -> Option<Self> {
+        let archival = JsonRpcProvider::new(network.archival_rpc_url()?);
+        Some(Self::new(JsonRpcProvider::for_network(network), archival))
+    }
+}
+
+impl<R: Provider + Send + Sync, A: Provider + Send + Sync> ArchivalProvider<R, A> {
+    /// Calls `request` on the archival provider if `height` is below the threshold. Otherwise calls it on the regular
+    /// provider, and again on the archival provider if the regular one is missing the data.
+    async fn call<'a, T, E, F, Fut>(
+        &'a self,
+        height: Option<BlockHeight>,
+        request: F,
+    ) -> Result<T, JsonRpcError<E>>
+    where
+        E: MissingDataError,
+        F: Fn(&'a (dyn Provider + Send + Sync)) -> Fut,
+        Fut: Future<Output = Result<T, JsonRpcError<E>>>,
+    {
+        let threshold = self.height_threshold();
+        if matches!((height, threshold), (Some(height), Some(threshold)) if height < threshold) {
+            return request(&self.archival).await;
+        }
+        match request(&self.regular).await {
+            Err(err) if is_missing_data(&err) => request(&self.archival).await,
+            result => result,
+        }
+    }
+}
+
+/// Returns the height of the block referenced by `block_id`, if it is referenced by height.
+fn block_id_height(block_id: &BlockId) -> Option<BlockHeight> {
+    match block_id {
+        BlockId::Height(height) => Some(*height),
+        BlockId::Hash(_) => None,
+    }
+}
+
+fn block_reference_height(block_reference: &BlockReference) -> Option<BlockHeight> {
+    match block_reference {
+        BlockReference::BlockId(block_id) => block_id_height(block_id),
+        BlockReference::Finality(_) | BlockReference::SyncCheckpoint(_) => None,
+    }
+}
+
+/// Returns true if the response is a failure that the archival provider may answer. The responses of the requests
+/// that always go to the regular provider never are.
+fn is_missing_data_response(response: &ProviderResponse) -> bool {
+    match response {
+        ProviderResponse::TxStatus(Err(err)) => is_missing_data(err),
+        ProviderResponse::Chunk(Err(err)) => is_missing_data(err),
+        ProviderResponse::Block(Err(err)) => is_missing_data(err),
+        ProviderResponse::Validators(Err(err)) => is_missing_data(err),
+        ProviderResponse::Query(Err(err)) => is_missing_data(err),
+        ProviderResponse::ExperimentalProtocolConfig(Err(err)) => is_missing_data(err),
+        ProviderResponse::GasPrice(Err(err)) => is_missing_data(err),
+        ProviderResponse::ChangesInBlock(Err(err)) => is_missing_data(err),
+        ProviderResponse::Changes(Err(err)) => is_missing_data(err),
+        ProviderResponse::TxStatusWithReceipts(Err(err)) => is_missing_data(err),
+        ProviderResponse::Receipt(Err(err)) => is_missing_data(err),
+        ProviderResponse::LightClientProof(Err(err)) => is_missing_data(err),
+        ProviderResponse::NextLightClientBlock(Err(err)) => is_missing_data(err),
+        _ => false,
+    }
+}
+
+#[async_trait]
+impl<R: Provider + Send + Sync, A: Provider + Send + Sync> Provider for ArchivalProvider<R, A> {
+    /// Retrieves the current status of the NEAR blockchain from the regular provider.
+    async fn status(&self) -> Result<RpcStatusResponse, JsonRpcError<RpcStatusError>> {
+        self.regular.status().await
+    }
+
+    /// Executes a query, falling back to the archival provider if the block is unknown to the regular provider.
+    async fn query(
+        &self,
+        request: QueryRequest,
+        block_reference: BlockReference,
+    ) -> Result<RpcQueryResponse, JsonRpcError<RpcQueryError>> {
+        self.call(block_reference_height(&block_reference), |provider| {
+            provider.query(request.clone(), block_reference.clone())
+        })
+        .await
+    }
+
+    /// Sends a signed transaction to the regular provider and waits for its final execution outcome.
+    async fn send_transaction(
+        &self,
+        signed_transaction: SignedTransaction,
+    ) -> Result<FinalExecutionOutcomeView, JsonRpcError<RpcTransactionError>> {
+        self.regular.send_transaction(signed_transaction).await
+    }
+
+    /// Sends a signed transaction asynchronously to the regular provider.
+    async fn send_transaction_async(
+        &self,
+        signed_transaction: SignedTransaction,
+    ) -> Result<CryptoHash, JsonRpcError<methods::broadcast_tx_async::RpcBroadcastTxAsyncError>>
+    {
+        self.regular
+            .send_transaction_async(signed_transaction)
+            .await
+    }
+
+    /// Sends a signed transaction to the regular provider with the given `wait_until`.
+    async fn send_tx(
+        &self,
+        signed_transaction: SignedTransaction,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionResponse, JsonRpcError<RpcTransactionError>> {
+        self.regular.send_tx(signed_transaction, wait_until).await
+    }
+
+    /// Retrieves the status of a transaction, falling back to the archival provider if it is unknown.
+    async fn tx_status(
+        &self,
+        transaction_info: TransactionInfo,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionResponse, JsonRpcError<RpcTransactionError>> {
+        self.call(None, |provider| {
+            provider.tx_status(transaction_info.clone(), wait_until.clone())
+        })
+        .await
+    }
+
+    /// Fetches a chunk, falling back to the archival provider if it is unknown to the regular provider.
+    async fn chunk(
+        &self,
+        chunk_reference: ChunkReference,
+    ) -> Result<ChunkView, JsonRpcError<RpcChunkError>> {
+        let height = match &chunk_reference {
+            ChunkReference::BlockShardId { block_id, .. } => block_id_height(block_id),
+            ChunkReference::ChunkHash { .. } => None,
+        };
+        self.call(height, |provider| provider.chunk(chunk_reference.clone()))
+            .await
+    }
+
+    /// Retrieves a block, falling back to the archival provider if it is unknown to the regular provider.
+    async fn block(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<BlockView, JsonRpcError<RpcBlockError>> {
+        self.call(block_reference_height(&block_reference), |provider| {
+            provider.block(block_reference.clone())
+        })
+        .await
+    }
+
+    /// Fetches the protocol configuration, falling back to the archival provider if the block is unknown.
+    async fn experimental_protocol_config(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<ProtocolConfigView, JsonRpcError<RpcProtocolConfigError>> {
+        self.call(block_reference_height(&block_reference), |provider| {
+            provider.experimental_protocol_config(block_reference.clone())
+        })
+        .await
+    }
+
+    /// Retrieves information about validators, falling back to the archival provider if the epoch is unknown.
+    async fn validators(
+        &self,
+        epoch_reference: EpochReference,
+    ) -> Result<EpochValidatorInfo, JsonRpcError<RpcValidatorError>> {
+        let height = match &epoch_reference {
+            EpochReference::BlockId(block_id) => block_id_height(block_id),
+            EpochReference::EpochId(_) | EpochReference::Latest => None,
+        };
+        self.call(height, |provider| {
+            provider.validators(clone_epoch_reference(&epoch_reference))
+        })
+        .await
+    }
+
+    /// Fetches the gas price, falling back to the archival provider if the block is unknown.
+    async fn gas_price(
+        &self,
+        block_id: Option<BlockId>,
+    ) -> Result<GasPriceView, JsonRpcError<RpcGasPriceError>> {
+        self.call(block_id.as_ref().and_then(block_id_height), |provider| {
+            provider.gas_price(block_id.clone())
+        })
+        .await
+    }
+
+    /// Checks the health of the regular provider.
+    async fn health(&self) -> Result<RpcHealthResponse, JsonRpcError<RpcStatusError>> {
+        self.regular.health().await
+    }
+
+    /// Fetches the network information of the regular provider.
+    async fn network_info(
+        &self,
+    ) -> Result<RpcNetworkInfoResponse, JsonRpcError<RpcNetworkInfoError>> {
+        self.regular.network_info().await
+    }
+
+    /// Fetches the genesis configuration from the regular provider.
+    async fn experimental_genesis_config(
+        &self,
+    ) -> Result<
+        GenesisConfig,
+        JsonRpcError<methods::EXPERIMENTAL_genesis_config::RpcGenesisConfigError>,
+    > {
+        self.regular.experimental_genesis_config().await
+    }
+
+    /// Fetches the client configuration of the regular provider.
+    async fn client_config(
+        &self,
+    ) -> Result<RpcClientConfigResponse, JsonRpcError<RpcClientConfigError>> {
+        self.regular.client_config().await
+    }
+
+    /// Lists the state changes made in a block, falling back to the archival provider if the block is unknown.
+    async fn changes_in_block(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<RpcStateChangesInBlockByTypeResponse, JsonRpcError<RpcStateChangesError>> {
+        self.call(block_reference_height(&block_reference), |provider| {
+            provider.changes_in_block(block_reference.clone())
+        })
+        .await
+    }
+
+    /// Retrieves state changes, falling back to the archival provider if the block is unknown.
+    async fn changes(
+        &self,
+        request: StateChangesRequestView,
+        block_reference: BlockReference,
+    ) -> Result<RpcStateChangesInBlockResponse, JsonRpcError<RpcStateChangesError>> {
+        self.call(block_reference_height(&block_reference), |provider| {
+            provider.changes(
+                clone_state_changes_request(&request),
+                block_reference.clone(),
+            )
+        })
+        .await
+    }
+
+    /// Retrieves the status of a transaction and its receipts, falling back to the archival provider if it is unknown.
+    async fn tx_status_with_receipts(
+        &self,
+        transaction_info: TransactionInfo,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionWithReceiptsResponse, JsonRpcError<RpcTransactionError>> {
+        self.call(None, |provider| {
+            provider.tx_status_with_receipts(transaction_info.clone(), wait_until.clone())
+        })
+        .await
+    }
+
+    /// Retrieves a receipt, falling back to the archival provider if it is unknown.
+    async fn receipt(
+        &self,
+        receipt_id: CryptoHash,
+    ) -> Result<ReceiptView, JsonRpcError<RpcReceiptError>> {
+        self.call(None, |provider| provider.receipt(receipt_id))
+            .await
+    }
+
+    /// Retrieves an execution proof, falling back to the archival provider if the outcome or block is unknown.
+    async fn light_client_proof(
+        &self,
+        id: TransactionOrReceiptId,
+        light_client_head: CryptoHash,
+    ) -> Result<RpcLightClientExecutionProofResponse, JsonRpcError<RpcLightClientProofError>> {
+        self.call(None, |provider| {
+            provider.light_client_proof(id.clone(), light_client_head)
+        })
+        .await
+    }
+
+    /// Retrieves the next light client block, falling back to the archival provider if the block is unknown.
+    async fn next_light_client_block(
+        &self,
+        last_block_hash: CryptoHash,
+    ) -> Result<Option<LightClientBlockView>, JsonRpcError<RpcLightClientNextBlockError>> {
+        self.call(None, |provider| {
+            provider.next_light_client_block(last_block_hash)
+        })
+        .await
+    }
+
+    /// Sends the requests for blocks below the height threshold to the archival provider and the others to the
+    /// regular provider as one batch, then sends the requests the regular provider is missing the data of to the
+    /// archival provider along with them, as another batch.
+    async fn batch(&self, requests: Vec<ProviderRequest>) -> Vec<ProviderResponse> {
+        let count = requests.len();
+        let threshold = self.height_threshold();
+        let (mut archival, regular): (Vec<_>, Vec<_>) =
+            requests.into_iter().enumerate().partition(|(_, request)| {
+                let height = request
+                    .block_reference()
+                    .as_ref()
+                    .and_then(block_reference_height);
+                matches!((height, threshold), (Some(height), Some(threshold)) if height < threshold)
+            });
+
+        let (regular_indices, regular): (Vec<_>, Vec<_>) = regular.into_iter().unzip();
+        let regular_responses = self.regular.batch(regular.clone()).await;
+        let mut responses = Vec::with_capacity(count);
+        for ((index, request), response) in regular_indices
+            .into_iter()
+            .zip(regular)
+            .zip(regular_responses)
+        {
+            if is_missing_data_response(&response) {
+                archival.push((index, request));
+            } else {
+                responses.push((index, response));
+            }
+        }
+
+        let (archival_indices, archival): (Vec<_>, Vec<_>) = archival.into_iter().unzip();
+        let archival_responses = self.archival.batch(archival).await;
+        responses.extend(archival_indices.into_iter().zip(archival_responses));
+        batch::in_request_order(count, responses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{
+        access_key_response, block_view, handler_error, internal_error, status_response,
+        MockProvider,
+    };
+    use crate::ProviderMethod;
+    use near_primitives::types::Finality;
+
+    fn unknown_block() -> JsonRpcError<RpcBlockError> {
+        handler_error(RpcBlockError::UnknownBlock {
+            error_message: "DB Not Found Error: BLOCK HEIGHT: 10".to_string(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_when_data_is_missing() {
+        let provider = ArchivalProvider::new(MockProvider::new(), MockProvider::new());
+        provider.regular().push_block(Err(unknown_block()));
+        provider
+            .archival()
+            .push_block(Ok(block_view(10, CryptoHash::default())));
+
+        let block = provider
+            .block(BlockReference::BlockId(BlockId::Height(10)))
+            .await
+            .unwrap();
+        assert_eq!(block.header.height, 10);
+        assert_eq!(provider.regular().call_count(ProviderMethod::Block), 1);
+        assert_eq!(provider.archival().call_count(ProviderMethod::Block), 1);
+
+        provider
+            .regular()
+            .push_query(Err(handler_error(RpcQueryError::GarbageCollectedBlock {
+                block_height: 10,
+                block_hash: CryptoHash::default(),
+            })));
+        provider.archival().push_query(Ok(access_key_response(1)));
+        let request = QueryRequest::ViewAccessKey {
+            account_id: "alice.near".parse().unwrap(),
+            public_key: near_crypto::PublicKey::empty(near_crypto::KeyType::ED25519),
+        };
+        provider
+            .query(request, BlockReference::BlockId(BlockId::Height(10)))
+            .await
+            .unwrap();
+        assert_eq!(provider.archival().call_count(ProviderMethod::Query), 1);
+    }
+
+    #[tokio::test]
+    async fn test_other_errors_are_returned() {
+        let provider = ArchivalProvider::new(MockProvider::new(), MockProvider::new());
+        provider
+            .regular()
+            .push_block(Err(internal_error("node is down")));
+
+        assert!(provider
+            .block(BlockReference::Finality(Finality::Final))
+            .await
+            .is_err());
+        assert_eq!(provider.archival().call_count(ProviderMethod::Block), 0);
+
+        // Requests that don't read historical data never reach the archival provider.
+        provider
+            .regular()
+            .push_status(Ok(status_response("testnet")));
+        provider.status().await.unwrap();
+        assert!(provider.archival().calls().is_empty());
+    }
+
+    #[test]
+    fn test_for_network() {
+        assert!(ArchivalProvider::for_network(&Network::Testnet).is_some());
+        assert!(ArchivalProvider::for_network(&Network::Localnet).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_routes_by_height() {
+        let provider = ArchivalProvider::new(MockProvider::new(), MockProvider::new())
+            .with_height_threshold(1_000);
+        assert_eq!(provider.height_threshold(), Some(1_000));
+        provider
+            .archival()
+            .push_block(Ok(block_view(999, CryptoHash::default())));
+        provider
+            .regular()
+            .push_block(Ok(block_view(1_000, CryptoHash::default())));
+
+        provider
+            .block(BlockReference::BlockId(BlockId::Height(999)))
+            .await
+            .unwrap();
+        provider
+            .block(BlockReference::BlockId(BlockId::Height(1_000)))
+            .await
+            .unwrap();
+        assert_eq!(provider.regular().call_count(ProviderMethod::Block), 1);
+        assert_eq!(provider.archival().call_count(ProviderMethod::Block), 1);
+
+        provider.set_height_threshold(2_000);
+        provider
+            .archival()
+            .push_block(Ok(block_view(1_000, CryptoHash::default())));
+        provider
+            .block(BlockReference::BlockId(BlockId::Height(1_000)))
+            .await
+            .unwrap();
+        assert_eq!(provider.archival().call_count(ProviderMethod::Block), 2);
+    }
+}
@@ -0,0 +1,475 @@
+//! The `backfill` module processes a range of historical blocks, e.g. heights 100,000,000 to 101,000,000, as fast as
+//! the node allows.
+//!
+//! The `Backfill` fetches several heights of the range at once, at most `DEFAULT_BACKFILL_CONCURRENCY` unless
+//! configured otherwise, and yields their blocks in height order as an async `Stream` of `StreamedBlock`s, like a
+//! `BlockStream`. A height the node has no block at was skipped by the chain and is passed over, which is checked
+//! against the `prev_hash` of the next block: a block that does not follow the last one yielded ends the backfill
+//! with `BackfillError::Discontinuity`. The whole range must be final, which is checked against the latest final
+//! block before the first height is fetched.
+//!
+//! A node also reports the heights it garbage collected as unknown. The `prev_height` of each block tells them apart
+//! from skipped heights: a block following a height after the last block yielded, or after the start of the range,
+//! ends the backfill with `BackfillError::MissingBlock` instead of passing over blocks that exist.
+//!
+//! Every call is retried according to a `RetryPolicy`. A height that still fails is yielded as an error without
+//! moving past it, so polling again fetches it again. Each block has a `BlockCheckpoint` to persist once the block
+//! is processed, and to resume the backfill from after a restart with `Backfill::resume_from`.
+//!
+//! The backfill composes with the other providers of this crate: wrap an `ArchivalProvider` to read blocks that
+//! regular nodes garbage collected, and a `RateLimitProvider` to stay within the limits of the endpoint.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use futures::StreamExt;
+//! use near_providers::backfill::Backfill;
+//! use near_providers::rate_limit_provider::RateLimit;
+//! use near_providers::{ArchivalProvider, Network, RateLimitProvider};
+//! use std::sync::Arc;
+//!
+//! # async fn run() {
+//! let archival = ArchivalProvider::for_network(&Network::Mainnet).unwrap();
+//! let provider = Arc::new(RateLimitProvider::new(archival, RateLimit::per_second(20.0)).unwrap());
+//! let mut blocks = Backfill::new(provider, 100_000_000..=101_000_000)
+//!     .with_concurrency(16)
+//!     .with_chunks(true);
+//! while let Some(block) = blocks.next().await {
+//!     match block {
+//!         Ok(block) => println!("block {} with {} new chunks", block.height(), block.chunks.len()),
+//!         Err(err) => eprintln!("{}", err),
+//!     }
+//! }
+//! # }
+//! ```
+
+use crate::block_stream::{
+    BlockCheckpoint, BlockSource, Continuity, Link, PulledBlocks, StartAt, StreamedBlock,
+};
+use crate::jsonrpc_client::errors::JsonRpcError;
+use crate::retry_provider::{retry, RetryPolicy};
+use crate::types::{
+    blocks::RpcBlockError,
+    chunks::{ChunkReference, RpcChunkError},
+};
+use crate::Provider;
+use async_trait::async_trait;
+use futures::future::{self, BoxFuture};
+use futures::stream::{FuturesOrdered, Stream, StreamExt};
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{BlockHeight, BlockId, BlockReference, Finality};
+use std::ops::RangeInclusive;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// The number of heights a backfill fetches at once, unless configured otherwise.
+pub const DEFAULT_BACKFILL_CONCURRENCY: usize = 8;
+
+/// The reasons a `Backfill` fails to yield a block.
+#[derive(Debug, thiserror::Error)]
+pub enum BackfillError {
+    #[error("Failed to fetch the latest final block: {0}")]
+    Head(JsonRpcError<RpcBlockError>),
+    #[error("Failed to fetch block {height}: {source}")]
+    Block {
+        height: BlockHeight,
+        source: JsonRpcError<RpcBlockError>,
+    },
+    #[error("Failed to fetch chunk {chunk_hash} of block {height}: {source}")]
+    Chunk {
+        height: BlockHeight,
+        chunk_hash: CryptoHash,
+        source: JsonRpcError<RpcChunkError>,
+    },
+    #[error("Block {height} follows block {actual_prev_hash}, not the last block yielded {expected_prev_hash}")]
+    Discontinuity {
+        height: BlockHeight,
+        expected_prev_hash: CryptoHash,
+        actual_prev_hash: CryptoHash,
+    },
+    #[error(
+        "Block {height} exists, as block {next_height} follows it, but the node does not have it"
+    )]
+    MissingBlock {
+        height: BlockHeight,
+        next_height: BlockHeight,
+    },
+    #[error("The range ends at {end}, after the latest final block {final_height}")]
+    NotFinal {
+        end: BlockHeight,
+        final_height: BlockHeight,
+    },
+}
+
+/// The fetch of a height, resolving to `None` if the chain skipped it.
+type Fetch = BoxFuture<'static, (BlockHeight, Result<Option<StreamedBlock>, BackfillError>)>;
+
+/// The heights of a `Backfill` being fetched.
+struct Pipeline<P: ?Sized> {
+    provider: Arc<P>,
+    fetch_chunks: bool,
+    concurrency: usize,
+    policy: RetryPolicy,
+    /// The next height to start fetching, or `None` once the whole range is being fetched.
+    next_height: Option<BlockHeight>,
+    end: BlockHeight,
+    in_flight: FuturesOrdered<Fetch>,
+    /// The last block yielded, which the next block must follow.
+    continuity: Continuity,
+    /// Set once the end of the range was checked to be final.
+    head_checked: bool,
+    /// Set once the backfill yielded a fatal error, after which it ends.
+    finished: bool,
+}
+
+#[async_trait]
+impl<P: Provider + Send + Sync + ?Sized + 'static> BlockSource for Pipeline<P> {
+    type Error = BackfillError;
+
+    /// Fetches the next block of the range in height order, or returns `None` at the end of the range.
+    async fn next_block(&mut self) -> Option<Result<StreamedBlock, BackfillError>> {
+        if self.finished {
+            return None;
+        }
+        if !self.head_checked {
+            if let Err(err) = self.check_head().await {
+                return Some(Err(err));
+            }
+            self.head_checked = true;
+        }
+
+        loop {
+            while self.in_flight.len() < self.concurrency.max(1) {
+                let Some(height) = self.next_height.filter(|height| *height <= self.end) else {
+                    break;
+                };
+                self.in_flight.push_back(self.fetch(height));
+                self.next_height = height.checked_add(1);
+            }
+
+            let (height, result) = self.in_flight.next().await?;
+            match result {
+                Ok(None) => continue,
+                Ok(Some(block)) => {
+                    // The range is final, so a height passed over was garbage collected rather than skipped.
+                    let err = match self.continuity.link(&block.block) {
+                        Link::Follows => {
+                            self.continuity.advance(block.checkpoint());
+                            return Some(Ok(block));
+                        }
+                        Link::Gap { prev_height } => BackfillError::MissingBlock {
+                            height: prev_height,
+                            next_height: height,
+                        },
+                        Link::Discontinuity {
+                            expected_prev_hash,
+                            actual_prev_hash,
+                        } => BackfillError::Discontinuity {
+                            height,
+                            expected_prev_hash,
+                            actual_prev_hash,
+                        },
+                    };
+                    self.finished = true;
+                    return Some(Err(err));
+                }
+                Err(err) => {
+                    self.in_flight.push_front(self.fetch(height));
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
+
+impl<P: Provider + Send + Sync + ?Sized + 'static> Pipeline<P> {
+    /// Checks that the end of the range is final, so that a height the node has no block at was skipped.
+    async fn check_head(&mut self) -> Result<(), BackfillError> {
+        let provider = &self.provider;
+        let head = retry(&self.policy, || {
+            provider.block(BlockReference::Finality(Finality::Final))
+        })
+        .await
+        .map_err(BackfillError::Head)?;
+        if head.header.height < self.end {
+            self.finished = true;
+            return Err(BackfillError::NotFinal {
+                end: self.end,
+                final_height: head.header.height,
+            });
+        }
+        Ok(())
+    }
+
+    fn fetch(&self, height: BlockHeight) -> Fetch {
+        let provider = self.provider.clone();
+        let policy = self.policy.clone();
+        let fetch_chunks = self.fetch_chunks;
+        Box::pin(async move {
+            let result = fetch_height(&*provider, height, fetch_chunks, &policy).await;
+            (height, result)
+        })
+    }
+}
+
+/// Fetches the block at `height` and its new chunks, or returns `None` if the chain skipped the height.
+async fn fetch_height<P: Provider + Send + Sync + ?Sized>(
+    provider: &P,
+    height: BlockHeight,
+    fetch_chunks: bool,
+    policy: &RetryPolicy,
+) -> Result<Option<StreamedBlock>, BackfillError> {
+    let block_reference = BlockReference::BlockId(BlockId::Height(height));
+    let block = match retry(policy, || provider.block(block_reference.clone())).await {
+        Ok(block) => block,
+        Err(err)
+            if matches!(
+                err.handler_error(),
+                Some(RpcBlockError::UnknownBlock { .. })
+            ) =>
+        {
+            return Ok(None);
+        }
+        Err(source) => return Err(BackfillError::Block { height, source }),
+    };
+
+    let mut chunks = Vec::new();
+    if fetch_chunks {
+        let new_chunks = block
+            .chunks
+            .iter()
+            .filter(|chunk| chunk.height_included == height)
+            .map(|chunk| async move {
+                let chunk_reference = ChunkReference::ChunkHash {
+                    chunk_id: chunk.chunk_hash,
+                };
+                retry(policy, || provider.chunk(chunk_reference.clone()))
+                    .await
+                    .map_err(|source| BackfillError::Chunk {
+                        height,
+                        chunk_hash: chunk.chunk_hash,
+                        source,
+                    })
+            });
+        chunks = future::try_join_all(new_chunks).await?;
+    }
+    Ok(Some(StreamedBlock { block, chunks }))
+}
+
+/// An async `Stream` of the blocks of a range of heights in height order, see the module documentation.
+pub struct Backfill<P: ?Sized> {
+    blocks: PulledBlocks<Pipeline<P>, BackfillError>,
+}
+
+impl<P: Provider + Send + Sync + ?Sized + 'static> Backfill<P> {
+    /// Creates a backfill of the blocks at `heights` fetched from `provider`, without their chunks.
+    pub fn new(provider: Arc<P>, heights: RangeInclusive<BlockHeight>) -> Self {
+        let (start, end) = heights.into_inner();
+        Self {
+            blocks: PulledBlocks::new(Pipeline {
+                provider,
+                fetch_chunks: false,
+                concurrency: DEFAULT_BACKFILL_CONCURRENCY,
+                policy: RetryPolicy::default(),
+                next_height: Some(start),
+                end,
+                in_flight: FuturesOrdered::new(),
+                continuity: Continuity::new(&StartAt::Height(start)),
+                head_checked: false,
+                finished: false,
+            }),
+        }
+    }
+
+    /// Fetches the new chunks of every block, see `StreamedBlock::chunks`.
+    pub fn with_chunks(mut self, fetch_chunks: bool) -> Self {
+        if let Some(pipeline) = self.blocks.source_mut() {
+            pipeline.fetch_chunks = fetch_chunks;
+        }
+        self
+    }
+
+    /// Sets the number of heights fetched at once, 8 by default. The chunks of a block are fetched together with it.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        if let Some(pipeline) = self.blocks.source_mut() {
+            pipeline.concurrency = concurrency;
+        }
+        self
+    }
+
+    /// Sets the policy the calls are retried with, `RetryPolicy::default()` by default.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        if let Some(pipeline) = self.blocks.source_mut() {
+            pipeline.policy = policy;
+        }
+        self
+    }
+
+    /// Resumes the backfill after the block of `checkpoint`, which the next block must follow.
+    pub fn resume_from(mut self, checkpoint: BlockCheckpoint) -> Self {
+        if let Some(pipeline) = self.blocks.source_mut() {
+            pipeline.next_height = checkpoint.height.checked_add(1);
+            pipeline.continuity = Continuity::new(&StartAt::Checkpoint(checkpoint));
+        }
+        self
+    }
+}
+
+impl<P: Provider + Send + Sync + ?Sized + 'static> Stream for Backfill<P> {
+    type Item = Result<StreamedBlock, BackfillError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().blocks.poll_next(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::chain::{block_chain, serve_blocks, serve_chain};
+    use crate::testing::{internal_error, MockProvider};
+    use crate::utils::clone_block_view;
+    use crate::{ArchivalProvider, ProviderMethod};
+    use std::collections::HashMap;
+    use std::sync::atomic::AtomicU64;
+    use std::time::Duration;
+
+    fn heights(blocks: &[Result<StreamedBlock, BackfillError>]) -> Vec<BlockHeight> {
+        blocks
+            .iter()
+            .map(|block| block.as_ref().unwrap().height())
+            .collect()
+    }
+
+    fn no_backoff(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_backoff: Duration::ZERO,
+            ..RetryPolicy::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_backfill_in_order() {
+        let provider = Arc::new(MockProvider::new());
+        let chain: Vec<_> = (1..=12).filter(|height| height % 5 != 0).collect();
+        serve_chain(&provider, &chain, HashMap::new());
+
+        let blocks: Vec<_> = Backfill::new(provider.clone(), 1..=12)
+            .with_concurrency(3)
+            .with_chunks(true)
+            .collect()
+            .await;
+        assert_eq!(heights(&blocks), chain);
+        assert!(blocks
+            .iter()
+            .all(|block| block.as_ref().unwrap().chunks.len() == 1));
+        assert_eq!(provider.call_count(ProviderMethod::Block), 13);
+        assert_eq!(provider.call_count(ProviderMethod::Chunk), chain.len());
+    }
+
+    #[tokio::test]
+    async fn test_retry_and_resume() {
+        let provider = Arc::new(MockProvider::new());
+        let head = Arc::new(AtomicU64::new(6));
+        serve_blocks(&provider, block_chain(&[1, 2, 3, 4, 5, 6]), head);
+        let mut blocks = Backfill::new(provider.clone(), 1..=6)
+            .with_concurrency(1)
+            .with_retry_policy(no_backoff(2));
+        let first: Vec<_> = blocks.by_ref().take(2).collect().await;
+        assert_eq!(heights(&first), vec![1, 2]);
+
+        // Height 3 fails twice, running out of attempts, and is fetched again on the next poll.
+        provider.push_block(Err(internal_error("node is down")));
+        provider.push_block(Err(internal_error("node is down")));
+        assert!(matches!(
+            blocks.next().await,
+            Some(Err(BackfillError::Block { height: 3, .. }))
+        ));
+        let block = blocks.next().await.unwrap().unwrap();
+        assert_eq!(block.height(), 3);
+
+        let rest: Vec<_> = Backfill::new(provider.clone(), 1..=6)
+            .resume_from(block.checkpoint())
+            .collect()
+            .await;
+        assert_eq!(heights(&rest), vec![4, 5, 6]);
+
+        // A checkpoint of another chain ends the backfill.
+        let checkpoint = BlockCheckpoint {
+            height: 3,
+            hash: CryptoHash::hash_bytes(b"other chain"),
+        };
+        let mut blocks = Backfill::new(provider.clone(), 1..=6).resume_from(checkpoint);
+        assert!(matches!(
+            blocks.next().await,
+            Some(Err(BackfillError::Discontinuity { height: 4, .. }))
+        ));
+        assert!(blocks.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_not_final() {
+        let provider = Arc::new(MockProvider::new());
+        serve_blocks(&provider, block_chain(&[1, 2]), Arc::new(AtomicU64::new(2)));
+        let mut blocks = Backfill::new(provider.clone(), 1..=3);
+        assert!(matches!(
+            blocks.next().await,
+            Some(Err(BackfillError::NotFinal {
+                end: 3,
+                final_height: 2
+            }))
+        ));
+        assert!(blocks.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_garbage_collected_heights() {
+        // The node garbage collected the blocks 2 and 3, and reports them as unknown like skipped heights.
+        let chain = block_chain(&[1, 2, 3, 4, 5]);
+        let provider = Arc::new(MockProvider::new());
+        let served = [0, 3, 4].map(|index| clone_block_view(&chain[index]));
+        serve_blocks(&provider, served.into(), Arc::new(AtomicU64::new(5)));
+
+        let mut blocks = Backfill::new(provider.clone(), 1..=5);
+        assert_eq!(blocks.next().await.unwrap().unwrap().height(), 1);
+        assert!(matches!(
+            blocks.next().await,
+            Some(Err(BackfillError::MissingBlock {
+                height: 3,
+                next_height: 4
+            }))
+        ));
+        assert!(blocks.next().await.is_none());
+
+        // The same goes for the heights at the start of the range.
+        let mut blocks = Backfill::new(provider.clone(), 2..=5);
+        assert!(matches!(
+            blocks.next().await,
+            Some(Err(BackfillError::MissingBlock {
+                height: 3,
+                next_height: 4
+            }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_archival_fallback() {
+        // The regular node garbage collected every block but the latest one.
+        let chain = block_chain(&[1, 2, 4, 5]);
+        let regular = MockProvider::new();
+        serve_blocks(
+            &regular,
+            vec![clone_block_view(&chain[3])],
+            Arc::new(AtomicU64::new(5)),
+        );
+        let archival = MockProvider::new();
+        serve_blocks(&archival, chain, Arc::new(AtomicU64::new(5)));
+        let provider = Arc::new(ArchivalProvider::new(regular, archival));
+
+        let blocks: Vec<_> = Backfill::new(provider.clone(), 1..=5).collect().await;
+        assert_eq!(heights(&blocks), vec![1, 2, 4, 5]);
+        assert_eq!(provider.archival().call_count(ProviderMethod::Block), 4);
+    }
+}
@@ -0,0 +1,274 @@
+//! The `batch` module makes many provider calls at once, e.g. to fetch the balances of thousands of accounts.
+//!
+//! `Provider::batch` takes a list of `ProviderRequest`s and returns their `ProviderResponse`s in the same order. Each
+//! response holds the typed result of its own call, so one failed item does not fail the whole batch, and
+//! `Provider::query_batch` does the same for a list of queries.
+//!
+//! By default a batch is made of single calls to the other methods of the provider, at most
+//! `DEFAULT_BATCH_CONCURRENCY` at a time. The `JsonRpcProvider` sends the read requests of a batch as JSON RPC batch
+//! payloads of up to `DEFAULT_BATCH_SIZE` requests instead, saving an HTTP round trip per request. When the endpoint
+//! does not support batch payloads, the provider falls back to concurrent single requests and keeps doing so.
+//!
+//! The provider wrappers of this crate pass batches on to the `batch` of the provider they wrap, so a batch still
+//! takes a single payload through them. Each applies its concern to the batch as a whole or to its items: the
+//! `RateLimitProvider` takes one token per payload and one per request sent on its own, the `CachingProvider` only
+//! sends the requests it has no cached response for, and the `RetryProvider` sends the items that failed with a
+//! retryable error again as a new batch. `Provider::is_batched` tells which requests a provider sends in a payload.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use near_primitives::types::{AccountId, BlockReference, Finality};
+//! use near_primitives::views::QueryRequest;
+//! use near_providers::{JsonRpcProvider, Provider};
+//!
+//! # async fn run(account_ids: Vec<AccountId>) {
+//! let provider = JsonRpcProvider::new("https://rpc.mainnet.near.org");
+//! let queries = account_ids
+//!     .into_iter()
+//!     .map(|account_id| {
+//!         let request = QueryRequest::ViewAccount { account_id };
+//!         (request, BlockReference::Finality(Finality::Final))
+//!     })
+//!     .collect();
+//! for result in provider.query_batch(queries).await {
+//!     match result {
+//!         Ok(response) => println!("{:?}", response.kind),
+//!         Err(err) => println!("query failed: {}", err),
+//!     }
+//! }
+//! # }
+//! ```
+
+use crate::jsonrpc_client::errors::{
+    JsonRpcError, JsonRpcServerError, JsonRpcTransportHandlerResponseError,
+    JsonRpcTransportRecvError, JsonRpcTransportSendError, RpcTransportError,
+};
+use crate::jsonrpc_client::methods::{self, RpcMethod};
+use crate::jsonrpc_primitives::errors::RpcError;
+use crate::jsonrpc_primitives::message::Message;
+use crate::types::gas_price::RpcGasPriceRequest;
+use crate::types::query::RpcQueryRequest;
+use crate::types::receipts::ReceiptReference;
+use crate::utils::{clone_epoch_reference, clone_state_changes_request};
+use crate::{Provider, ProviderRequest, ProviderResponse};
+use futures::stream::{self, StreamExt};
+use serde_json::Value;
+
+/// The number of calls a batch has in flight at once, unless the provider is configured otherwise.
+pub const DEFAULT_BATCH_CONCURRENCY: usize = 8;
+
+/// The maximum number of requests the `JsonRpcProvider` sends in one JSON RPC batch payload, unless configured
+/// otherwise with `JsonRpcProviderBuilder::with_batch_size`.
+pub const DEFAULT_BATCH_SIZE: usize = 100;
+
+/// Makes the calls described by `requests` on `provider`, at most `concurrency` at a time, returning their responses
+/// in the same order.
+pub async fn dispatch_concurrently<P: Provider + Sync + ?Sized>(
+    provider: &P,
+    requests: Vec<ProviderRequest>,
+    concurrency: usize,
+) -> Vec<ProviderResponse> {
+    stream::iter(requests)
+        .map(|request| ProviderResponse::dispatch(provider, request))
+        .buffered(concurrency.max(1))
+        .collect()
+        .await
+}
+
+/// Puts the responses of a batch of `count` requests, given together with the index of their request, back in the
+/// order of the requests. Used by the wrappers that answer the items of a batch in several parts.
+pub(crate) fn in_request_order(
+    count: usize,
+    responses: impl IntoIterator<Item = (usize, ProviderResponse)>,
+) -> Vec<ProviderResponse> {
+    let mut ordered: Vec<Option<ProviderResponse>> = (0..count).map(|_| None).collect();
+    for (index, response) in responses {
+        ordered[index] = Some(response);
+    }
+    ordered
+        .into_iter()
+        .map(|response| response.expect("every request of the batch has a response"))
+        .collect()
+}
+
+/// The `ProviderResponse` variant holding the result of the RPC method `M`.
+type WrapResult<M> = fn(
+    Result<<M as RpcMethod>::Response, JsonRpcError<<M as RpcMethod>::Error>>,
+) -> ProviderResponse;
+
+/// A request sent in a JSON RPC batch payload, with the parser of its result.
+pub(crate) struct BatchCall {
+    message: Message,
+    parse: Box<dyn FnOnce(Result<Value, RpcError>) -> ProviderResponse + Send>,
+}
+
+impl BatchCall {
+    /// Returns the call for `request`, or `None` if its method is not sent in batches.
+    ///
+    /// Only read methods are batched. Transactions are sent on their own, as a node may take a long time to answer
+    /// them, and the node information methods are not worth batching. A request whose params fail to serialize
+    /// gets its error response right away.
+    pub(crate) fn new(request: &ProviderRequest) -> Option<Result<Self, ProviderResponse>> {
+        let call = match request {
+            ProviderRequest::TxStatus {
+                transaction_info,
+                wait_until,
+            } => Self::of(
+                methods::tx::RpcTransactionStatusRequest {
+                    transaction_info: transaction_info.clone(),
+                    wait_until: wait_until.clone(),
+                },
+                ProviderResponse::TxStatus,
+            ),
+            ProviderRequest::Chunk { chunk_reference } => Self::of(
+                methods::chunk::RpcChunkRequest {
+                    chunk_reference: chunk_reference.clone(),
+                },
+                ProviderResponse::Chunk,
+            ),
+            ProviderRequest::Block { block_reference } => Self::of(
+                methods::block::RpcBlockRequest {
+                    block_reference: block_reference.clone(),
+                },
+                ProviderResponse::Block,
+            ),
+            ProviderRequest::Validators { epoch_reference } => Self::of(
+                methods::validators::RpcValidatorRequest {
+                    epoch_reference: clone_epoch_reference(epoch_reference),
+                },
+                ProviderResponse::Validators,
+            ),
+            ProviderRequest::Query {
+                request,
+                block_reference,
+            } => Self::of(
+                RpcQueryRequest {
+                    block_reference: block_reference.clone(),
+                    request: request.clone(),
+                },
+                ProviderResponse::Query,
+            ),
+            ProviderRequest::ExperimentalProtocolConfig { block_reference } => Self::of(
+                methods::EXPERIMENTAL_protocol_config::RpcProtocolConfigRequest {
+                    block_reference: block_reference.clone(),
+                },
+                ProviderResponse::ExperimentalProtocolConfig,
+            ),
+            ProviderRequest::GasPrice { block_id } => Self::of(
+                RpcGasPriceRequest {
+                    block_id: block_id.clone(),
+                },
+                ProviderResponse::GasPrice,
+            ),
+            ProviderRequest::ChangesInBlock { block_reference } => Self::of(
+                methods::EXPERIMENTAL_changes_in_block::RpcStateChangesInBlockRequest {
+                    block_reference: block_reference.clone(),
+                },
+                ProviderResponse::ChangesInBlock,
+            ),
+            ProviderRequest::Changes {
+                request,
+                block_reference,
+            } => Self::of(
+                methods::EXPERIMENTAL_changes::RpcStateChangesInBlockByTypeRequest {
+                    block_reference: block_reference.clone(),
+                    state_changes_request: clone_state_changes_request(request),
+                },
+                ProviderResponse::Changes,
+            ),
+            ProviderRequest::Receipt { receipt_id } => Self::of(
+                methods::EXPERIMENTAL_receipt::RpcReceiptRequest {
+                    receipt_reference: ReceiptReference {
+                        receipt_id: *receipt_id,
+                    },
+                },
+                ProviderResponse::Receipt,
+            ),
+            _ => return None,
+        };
+        Some(call)
+    }
+
+    /// Builds the call of `method`, whose result is wrapped into a `ProviderResponse` by `wrap`.
+    #[allow(clippy::result_large_err)]
+    fn of<M>(method: M, wrap: WrapResult<M>) -> Result<Self, ProviderResponse>
+    where
+        M: RpcMethod + 'static,
+    {
+        let params = method.params().map_err(|err| {
+            wrap(Err(JsonRpcError::TransportError(
+                RpcTransportError::SendError(JsonRpcTransportSendError::PayloadSerializeError(err)),
+            )))
+        })?;
+        Ok(Self {
+            message: Message::request(method.method_name().to_string(), params),
+            parse: Box::new(move |result| wrap(parse_result::<M>(result))),
+        })
+    }
+
+    /// Returns the JSON RPC request of the call, with a unique id.
+    pub(crate) fn message(&self) -> &Message {
+        &self.message
+    }
+
+    /// Parses the result of the call, or the error the node answered it with.
+    pub(crate) fn parse(self, result: Result<Value, RpcError>) -> ProviderResponse {
+        (self.parse)(result)
+    }
+}
+
+/// Maps the result of a call in a batch the way `JsonRpcClient::call` maps the result of a single call.
+#[allow(clippy::result_large_err)]
+fn parse_result<M: RpcMethod>(
+    result: Result<Value, RpcError>,
+) -> Result<M::Response, JsonRpcError<M::Error>> {
+    M::parse_handler_response(result?)
+        .map_err(|err| {
+            JsonRpcError::TransportError(RpcTransportError::RecvError(
+                JsonRpcTransportRecvError::ResponseParseError(
+                    JsonRpcTransportHandlerResponseError::ResultParseError(err),
+                ),
+            ))
+        })?
+        .map_err(|err| JsonRpcError::ServerError(JsonRpcServerError::HandlerError(err)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{access_key_response, block_view, internal_error, MockProvider};
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::types::{BlockReference, Finality};
+    use near_primitives::views::QueryRequest;
+
+    #[tokio::test]
+    async fn test_default_batch() {
+        let provider = MockProvider::new();
+        provider.push_query(Ok(access_key_response(1)));
+        provider.push_query(Err(internal_error("node is down")));
+        provider.push_block(Ok(block_view(10, CryptoHash::default())));
+        let final_block = BlockReference::Finality(Finality::Final);
+        let query = |account_id: &str| ProviderRequest::Query {
+            request: QueryRequest::ViewAccount {
+                account_id: account_id.parse().unwrap(),
+            },
+            block_reference: final_block.clone(),
+        };
+
+        let responses = provider
+            .batch(vec![
+                query("alice.near"),
+                query("bob.near"),
+                ProviderRequest::Block {
+                    block_reference: final_block.clone(),
+                },
+            ])
+            .await;
+        assert_eq!(responses.len(), 3);
+        assert!(matches!(responses[0], ProviderResponse::Query(Ok(_))));
+        assert!(matches!(responses[1], ProviderResponse::Query(Err(_))));
+        assert!(matches!(responses[2], ProviderResponse::Block(Ok(_))));
+        assert_eq!(provider.calls().len(), 3);
+    }
+}
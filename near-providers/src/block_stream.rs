@@ -0,0 +1,606 @@
+//! The `block_stream` module turns any `Provider` into an async `Stream` of the final blocks of the chain, in height
+//! order, replacing hand-written loops polling `Provider::block` that miss or duplicate heights.
+//!
+//! The `BlockStream` fetches every height after the last block it yielded, once the height is final. A height the
+//! node has no block for was skipped by the chain and is passed over, which is checked against the `prev_hash` of the
+//! next block: a block that does not follow the last one yielded ends the stream with
+//! `BlockStreamError::Discontinuity`, e.g. when resuming from a checkpoint taken on another network. A next block
+//! following a height the node reported unknown sends the stream back once to fetch the heights in between; if the
+//! node still has no block there, e.g. because it garbage collected the heights after an old checkpoint, the stream
+//! ends with `BlockStreamError::MissingBlock`. Resume from such checkpoints through an `ArchivalProvider`.
+//!
+//! The stream is pulled: no request is made until the consumer polls the next block, so a slow consumer is never
+//! flooded. A failed request is yielded as an error without moving past the height, so polling again retries it;
+//! wrap the provider in a `RetryProvider` to retry transient failures before they reach the consumer. Each
+//! `StreamedBlock` has a `BlockCheckpoint` to persist once the block is processed, and to resume from after a
+//! restart with `StartAt::Checkpoint`.
+//!
+//! `BlockStream::with_finality` follows the chain at a weaker finality than `Finality::Final` to see blocks sooner. A
+//! block that is not final yet may be dropped by a fork, which ends the stream with a `Discontinuity`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use futures::StreamExt;
+//! use near_providers::block_stream::{BlockStream, StartAt};
+//! use near_providers::JsonRpcProvider;
+//! use std::sync::Arc;
+//!
+//! # async fn run() {
+//! let provider = Arc::new(JsonRpcProvider::new("https://rpc.testnet.near.org"));
+//! let mut blocks = BlockStream::new(provider, StartAt::LatestFinal).with_chunks(true);
+//! while let Some(block) = blocks.next().await {
+//!     match block {
+//!         Ok(block) => println!("block {} with {} new chunks", block.height(), block.chunks.len()),
+//!         Err(err) => eprintln!("{}", err),
+//!     }
+//! }
+//! # }
+//! ```
+
+use crate::jsonrpc_client::errors::JsonRpcError;
+use crate::types::{
+    blocks::RpcBlockError,
+    chunks::{ChunkReference, RpcChunkError},
+};
+use crate::Provider;
+use async_trait::async_trait;
+use futures::stream::{self, BoxStream, Stream};
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{BlockHeight, BlockId, BlockReference, Finality};
+use near_primitives::views::{BlockView, ChunkView};
+use serde::{Deserialize, Serialize};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// The time to wait before asking for the latest final block again when the stream caught up with the chain.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The first block of a `BlockStream`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StartAt {
+    /// The latest block at the finality of the stream, final by default, when the stream is first polled.
+    LatestFinal,
+    /// The block at the given height, or the first block after it if the height was skipped.
+    Height(BlockHeight),
+    /// The first block after a block processed before, identified by its checkpoint.
+    Checkpoint(BlockCheckpoint),
+}
+
+/// The height and hash of a block, persisted to resume a `BlockStream` after the block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockCheckpoint {
+    pub height: BlockHeight,
+    pub hash: CryptoHash,
+}
+
+/// A final block yielded by a `BlockStream`, along with its new chunks if the stream fetches them.
+#[derive(Debug)]
+pub struct StreamedBlock {
+    pub block: BlockView,
+    /// The chunks included for the first time in the block, i.e. the ones whose `height_included` is the height of the
+    /// block. A shard missing its chunk in this block is left out. Empty unless the stream fetches chunks.
+    pub chunks: Vec<ChunkView>,
+}
+
+impl StreamedBlock {
+    /// Returns the height of the block.
+    pub fn height(&self) -> BlockHeight {
+        self.block.header.height
+    }
+
+    /// Returns the hash of the block.
+    pub fn hash(&self) -> CryptoHash {
+        self.block.header.hash
+    }
+
+    /// Returns the checkpoint to resume the stream after this block.
+    pub fn checkpoint(&self) -> BlockCheckpoint {
+        BlockCheckpoint {
+            height: self.height(),
+            hash: self.hash(),
+        }
+    }
+}
+
+/// The reasons a `BlockStream`, or a `LakeStream` of the `lake` module, fails to yield a block. The errors of sources
+/// other than the RPC, e.g. the `LakeError`s of a `LakeStream`, are yielded as `Source`.
+#[derive(Debug, thiserror::Error)]
+pub enum BlockStreamError {
+    #[error("Failed to fetch a block: {0}")]
+    Block(Box<JsonRpcError<RpcBlockError>>),
+    #[error("Failed to fetch a chunk: {0}")]
+    Chunk(Box<JsonRpcError<RpcChunkError>>),
+    #[error("Failed to read a block: {0}")]
+    Source(Box<dyn std::error::Error + Send + Sync>),
+    #[error("Block {height} follows block {actual_prev_hash}, not the last block yielded {expected_prev_hash}")]
+    Discontinuity {
+        height: BlockHeight,
+        expected_prev_hash: CryptoHash,
+        actual_prev_hash: CryptoHash,
+    },
+    #[error(
+        "Block {height} exists, as block {next_height} follows it, but the node does not have it"
+    )]
+    MissingBlock {
+        height: BlockHeight,
+        next_height: BlockHeight,
+    },
+}
+
+impl From<JsonRpcError<RpcBlockError>> for BlockStreamError {
+    fn from(err: JsonRpcError<RpcBlockError>) -> Self {
+        BlockStreamError::Block(Box::new(err))
+    }
+}
+
+impl From<JsonRpcError<RpcChunkError>> for BlockStreamError {
+    fn from(err: JsonRpcError<RpcChunkError>) -> Self {
+        BlockStreamError::Chunk(Box::new(err))
+    }
+}
+
+/// How a block links to the last block a stream yielded, see `Continuity::link`.
+pub(crate) enum Link {
+    /// The block follows the last block yielded, or is the first block of the stream.
+    Follows,
+    /// The block follows a block at `prev_height`, a height the stream passed over as skipped.
+    Gap { prev_height: BlockHeight },
+    /// The block follows another block than the last one yielded.
+    Discontinuity {
+        expected_prev_hash: CryptoHash,
+        actual_prev_hash: CryptoHash,
+    },
+}
+
+/// The last block a stream yielded, which the next block must follow. Shared by the `BlockStream`, the `LakeStream`
+/// of the `lake` module and the `Backfill` of the `backfill` module.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Continuity {
+    last: Option<BlockCheckpoint>,
+    /// The height after the last block yielded, or the start of the stream if it is known. The heights from there to
+    /// the `prev_height` of the next block were passed over, so the next block must not follow any of them.
+    next_height: Option<BlockHeight>,
+    /// Set once the stream went back to `next_height`, which it only does once per gap.
+    rewound: bool,
+}
+
+impl Continuity {
+    pub(crate) fn new(start: &StartAt) -> Self {
+        let (next_height, last) = match start {
+            StartAt::LatestFinal => (None, None),
+            StartAt::Height(height) => (Some(*height), None),
+            StartAt::Checkpoint(checkpoint) => {
+                (checkpoint.height.checked_add(1), Some(*checkpoint))
+            }
+        };
+        Self {
+            last,
+            next_height,
+            rewound: false,
+        }
+    }
+
+    /// Returns the height the stream starts from after the last block yielded, or `None` if it is not known yet.
+    pub(crate) fn next_height(&self) -> Option<BlockHeight> {
+        self.next_height
+    }
+
+    /// Returns how `block` links to the last block yielded.
+    pub(crate) fn link(&self, block: &BlockView) -> Link {
+        if let Some(prev_height) = block.header.prev_height.filter(|&prev_height| {
+            self.next_height
+                .is_some_and(|next_height| prev_height >= next_height)
+        }) {
+            return Link::Gap { prev_height };
+        }
+        match self.last {
+            Some(last) if block.header.prev_hash != last.hash => Link::Discontinuity {
+                expected_prev_hash: last.hash,
+                actual_prev_hash: block.header.prev_hash,
+            },
+            _ => Link::Follows,
+        }
+    }
+
+    /// Checks that `block` follows the last block yielded. Returns the height to go back to if it follows a height
+    /// passed over, which was reported missing although it exists. A stream goes back once: a block following the
+    /// same gap again means the height is not there to read, and is reported as a `MissingBlock`.
+    pub(crate) fn check(
+        &mut self,
+        block: &BlockView,
+    ) -> Result<Option<BlockHeight>, BlockStreamError> {
+        match self.link(block) {
+            Link::Follows => Ok(None),
+            Link::Gap { prev_height } if self.rewound => Err(BlockStreamError::MissingBlock {
+                height: prev_height,
+                next_height: block.header.height,
+            }),
+            Link::Gap { .. } => {
+                self.rewound = true;
+                Ok(self.next_height)
+            }
+            Link::Discontinuity {
+                expected_prev_hash,
+                actual_prev_hash,
+            } => Err(BlockStreamError::Discontinuity {
+                height: block.header.height,
+                expected_prev_hash,
+                actual_prev_hash,
+            }),
+        }
+    }
+
+    /// Records `checkpoint` as the last block yielded.
+    pub(crate) fn advance(&mut self, checkpoint: BlockCheckpoint) {
+        self.last = Some(checkpoint);
+        self.next_height = checkpoint.height.checked_add(1);
+        self.rewound = false;
+    }
+}
+
+/// The source a stream pulls its blocks from, see `PulledBlocks`.
+#[async_trait]
+pub(crate) trait BlockSource: Send + 'static {
+    type Error: Send + 'static;
+
+    /// Returns the next block in height order, or `None` once the stream ended.
+    async fn next_block(&mut self) -> Option<Result<StreamedBlock, Self::Error>>;
+}
+
+/// The blocks of a stream, pulled from its source one at a time once the stream is first polled. Shared by the
+/// `BlockStream`, the `LakeStream` of the `lake` module and the `Backfill` of the `backfill` module.
+pub(crate) struct PulledBlocks<S, E> {
+    /// The source to start from, until the stream is first polled.
+    source: Option<S>,
+    blocks: Option<BoxStream<'static, Result<StreamedBlock, E>>>,
+}
+
+impl<S: BlockSource<Error = E>, E> PulledBlocks<S, E> {
+    pub(crate) fn new(source: S) -> Self {
+        Self {
+            source: Some(source),
+            blocks: None,
+        }
+    }
+
+    /// Returns the source to configure, until the stream is first polled.
+    pub(crate) fn source_mut(&mut self) -> Option<&mut S> {
+        self.source.as_mut()
+    }
+
+    pub(crate) fn poll_next(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<StreamedBlock, E>>> {
+        let source = &mut self.source;
+        let blocks = self.blocks.get_or_insert_with(|| {
+            let source = source
+                .take()
+                .expect("the source is taken on the first poll");
+            Box::pin(stream::unfold(source, |mut source| async move {
+                let block = source.next_block().await?;
+                Some((block, source))
+            }))
+        });
+        blocks.as_mut().poll_next(cx)
+    }
+}
+
+/// The position of a `BlockStream` in the chain.
+struct Cursor<P: ?Sized> {
+    provider: Arc<P>,
+    fetch_chunks: bool,
+    poll_interval: Duration,
+    /// The finality a block must reach before it is yielded.
+    finality: Finality,
+    /// The height to fetch next, or `None` to start at the latest final block.
+    next_height: Option<BlockHeight>,
+    /// The last block yielded, which the next block must follow.
+    continuity: Continuity,
+    /// The height of the latest block at `finality` seen so far.
+    final_height: BlockHeight,
+    /// Set once the stream yielded a `Discontinuity` or a `MissingBlock`, after which it ends.
+    finished: bool,
+}
+
+impl<P: Provider + Send + Sync + ?Sized> Cursor<P> {
+    /// Fetches the next block in height order, waiting until it is final.
+    async fn fetch_next(&mut self) -> Result<StreamedBlock, BlockStreamError> {
+        loop {
+            let Some(height) = self.next_height else {
+                let block = self.latest_final_block().await?;
+                match self.accept(block).await? {
+                    Some(block) => return Ok(block),
+                    None => continue,
+                }
+            };
+            if height > self.final_height {
+                self.latest_final_block().await?;
+                if height > self.final_height {
+                    tokio::time::sleep(self.poll_interval).await;
+                    continue;
+                }
+            }
+
+            let block_reference = BlockReference::BlockId(BlockId::Height(height));
+            match self.provider.block(block_reference).await {
+                Ok(block) => {
+                    if let Some(block) = self.accept(block).await? {
+                        return Ok(block);
+                    }
+                }
+                // The height is final, so a node without a block at it means the chain skipped it.
+                Err(err)
+                    if matches!(
+                        err.handler_error(),
+                        Some(RpcBlockError::UnknownBlock { .. })
+                    ) =>
+                {
+                    self.next_height = Some(height + 1);
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    async fn latest_final_block(&mut self) -> Result<BlockView, BlockStreamError> {
+        let block = self
+            .provider
+            .block(BlockReference::Finality(self.finality.clone()))
+            .await?;
+        self.final_height = self.final_height.max(block.header.height);
+        Ok(block)
+    }
+
+    /// Checks that `block` follows the last block yielded and fetches its chunks. Returns `None` if a block between
+    /// them was reported unknown although it exists, after which the stream goes back to fetch it, once.
+    async fn accept(
+        &mut self,
+        block: BlockView,
+    ) -> Result<Option<StreamedBlock>, BlockStreamError> {
+        match self.continuity.check(&block) {
+            Ok(None) => {}
+            Ok(Some(height)) => {
+                self.next_height = Some(height);
+                tokio::time::sleep(self.poll_interval).await;
+                return Ok(None);
+            }
+            Err(err) => {
+                self.finished = true;
+                return Err(err);
+            }
+        }
+
+        let mut chunks = Vec::new();
+        if self.fetch_chunks {
+            for chunk in &block.chunks {
+                if chunk.height_included != block.header.height {
+                    continue;
+                }
+                let chunk_reference = ChunkReference::ChunkHash {
+                    chunk_id: chunk.chunk_hash,
+                };
+                chunks.push(self.provider.chunk(chunk_reference).await?);
+            }
+        }
+
+        let block = StreamedBlock { block, chunks };
+        self.next_height = Some(block.height() + 1);
+        self.continuity.advance(block.checkpoint());
+        Ok(Some(block))
+    }
+}
+
+#[async_trait]
+impl<P: Provider + Send + Sync + ?Sized + 'static> BlockSource for Cursor<P> {
+    type Error = BlockStreamError;
+
+    async fn next_block(&mut self) -> Option<Result<StreamedBlock, BlockStreamError>> {
+        if self.finished {
+            return None;
+        }
+        Some(self.fetch_next().await)
+    }
+}
+
+/// An async `Stream` of the final blocks of the chain in height order, see the module documentation.
+pub struct BlockStream<P: ?Sized> {
+    blocks: PulledBlocks<Cursor<P>, BlockStreamError>,
+}
+
+impl<P: Provider + Send + Sync + ?Sized + 'static> BlockStream<P> {
+    /// Creates a stream of the final blocks fetched from `provider`, from `start` on, without their chunks.
+    pub fn new(provider: Arc<P>, start: StartAt) -> Self {
+        let continuity = Continuity::new(&start);
+        Self {
+            blocks: PulledBlocks::new(Cursor {
+                provider,
+                fetch_chunks: false,
+                poll_interval: DEFAULT_POLL_INTERVAL,
+                finality: Finality::Final,
+                next_height: continuity.next_height(),
+                continuity,
+                final_height: 0,
+                finished: false,
+            }),
+        }
+    }
+
+    /// Fetches the new chunks of every block, see `StreamedBlock::chunks`.
+    pub fn with_chunks(mut self, fetch_chunks: bool) -> Self {
+        if let Some(cursor) = self.blocks.source_mut() {
+            cursor.fetch_chunks = fetch_chunks;
+        }
+        self
+    }
+
+    /// Sets the time to wait for a new final block once the stream caught up with the chain, 1 second by default.
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        if let Some(cursor) = self.blocks.source_mut() {
+            cursor.poll_interval = poll_interval;
+        }
+        self
+    }
+
+    /// Yields the blocks once they reach `finality` instead of `Finality::Final`, see the module documentation.
+    pub fn with_finality(mut self, finality: Finality) -> Self {
+        if let Some(cursor) = self.blocks.source_mut() {
+            cursor.finality = finality;
+        }
+        self
+    }
+}
+
+impl<P: Provider + Send + Sync + ?Sized + 'static> Stream for BlockStream<P> {
+    type Item = Result<StreamedBlock, BlockStreamError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().blocks.poll_next(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::chain::{block_chain, serve_blocks};
+    use crate::testing::{chunk_header, internal_error, MockProvider};
+    use crate::ProviderMethod;
+    use futures::StreamExt;
+    use near_primitives::views::ChunkHeaderView;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    fn heights(blocks: &[Result<StreamedBlock, BlockStreamError>]) -> Vec<BlockHeight> {
+        blocks
+            .iter()
+            .map(|block| block.as_ref().unwrap().height())
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_skipped_heights_and_new_blocks() {
+        let provider = Arc::new(MockProvider::new());
+        let final_height = Arc::new(AtomicU64::new(13));
+        serve_blocks(
+            &provider,
+            block_chain(&[10, 11, 13, 14]),
+            final_height.clone(),
+        );
+        let mut blocks = BlockStream::new(provider.clone(), StartAt::Height(10));
+
+        let first: Vec<_> = blocks.by_ref().take(3).collect().await;
+        assert_eq!(heights(&first), vec![10, 11, 13]);
+
+        // The stream waits for block 14 to become final.
+        let next = tokio::spawn(async move { blocks.next().await.unwrap() });
+        tokio::time::sleep(DEFAULT_POLL_INTERVAL * 3).await;
+        assert!(!next.is_finished());
+        final_height.store(14, Ordering::SeqCst);
+        assert_eq!(next.await.unwrap().unwrap().height(), 14);
+    }
+
+    #[tokio::test]
+    async fn test_latest_final_and_retry() {
+        let provider = Arc::new(MockProvider::new());
+        serve_blocks(
+            &provider,
+            block_chain(&[20, 21]),
+            Arc::new(AtomicU64::new(21)),
+        );
+        let mut blocks = BlockStream::new(provider.clone(), StartAt::LatestFinal);
+        assert_eq!(blocks.next().await.unwrap().unwrap().height(), 21);
+
+        let provider = Arc::new(MockProvider::new());
+        serve_blocks(
+            &provider,
+            block_chain(&[20, 21]),
+            Arc::new(AtomicU64::new(21)),
+        );
+        provider.push_block(Err(internal_error("node is down")));
+        let mut blocks = BlockStream::new(provider.clone(), StartAt::Height(20));
+        assert!(matches!(
+            blocks.next().await,
+            Some(Err(BlockStreamError::Block(_)))
+        ));
+        assert_eq!(blocks.next().await.unwrap().unwrap().height(), 20);
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_and_chunks() {
+        let provider = Arc::new(MockProvider::new());
+        let mut blocks = block_chain(&[10, 11, 13]);
+        let checkpoint = BlockCheckpoint {
+            height: 11,
+            hash: blocks[1].header.hash,
+        };
+        let new_chunk = chunk_header(0, 13, CryptoHash::default());
+        let old_chunk = chunk_header(1, 12, CryptoHash::default());
+        blocks[2].chunks = vec![new_chunk.clone(), old_chunk];
+        serve_blocks(&provider, blocks, Arc::new(AtomicU64::new(13)));
+        provider.on_chunk(|chunk_reference| match chunk_reference {
+            ChunkReference::ChunkHash { chunk_id } => Some(Ok(chunk_view(*chunk_id))),
+            _ => None,
+        });
+
+        let mut stream =
+            BlockStream::new(provider.clone(), StartAt::Checkpoint(checkpoint)).with_chunks(true);
+        let block = stream.next().await.unwrap().unwrap();
+        assert_eq!(block.height(), 13);
+        assert_eq!(block.chunks.len(), 1);
+        assert_eq!(block.chunks[0].header.chunk_hash, new_chunk.chunk_hash);
+        assert_eq!(provider.call_count(ProviderMethod::Chunk), 1);
+
+        // A checkpoint of another chain ends the stream.
+        let checkpoint = BlockCheckpoint {
+            height: 11,
+            hash: CryptoHash::hash_bytes(b"other chain"),
+        };
+        let mut stream = BlockStream::new(provider.clone(), StartAt::Checkpoint(checkpoint));
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(BlockStreamError::Discontinuity { height: 13, .. }))
+        ));
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_garbage_collected_checkpoint() {
+        let provider = Arc::new(MockProvider::new());
+        let blocks = block_chain(&[10, 11, 12, 13, 14]);
+        let checkpoint = BlockCheckpoint {
+            height: 10,
+            hash: blocks[0].header.hash,
+        };
+        // The node garbage collected the blocks up to 12, and reports them as unknown like skipped heights.
+        serve_blocks(
+            &provider,
+            blocks.into_iter().skip(3).collect(),
+            Arc::new(AtomicU64::new(14)),
+        );
+
+        let mut stream = BlockStream::new(provider.clone(), StartAt::Checkpoint(checkpoint));
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(BlockStreamError::MissingBlock {
+                height: 12,
+                next_height: 13
+            }))
+        ));
+        assert!(stream.next().await.is_none());
+    }
+
+    fn chunk_view(chunk_hash: CryptoHash) -> ChunkView {
+        let header: ChunkHeaderView = ChunkHeaderView {
+            chunk_hash,
+            ..chunk_header(0, 13, CryptoHash::default())
+        };
+        ChunkView {
+            author: "test.near".parse().unwrap(),
+            header,
+            transactions: vec![],
+            receipts: vec![],
+        }
+    }
+}
@@ -0,0 +1,979 @@
+//! The `CachingProvider` module offers a `Provider` wrapper that memoizes responses which are immutable or change slowly.
+//!
+//! Cached data:
+//! - blocks and chunks requested by hash,
+//! - protocol configurations per epoch,
+//! - validators per epoch id, once the epoch is over,
+//! - the genesis configuration,
+//! - the latest final block, for a short TTL,
+//! - the epoch of the block a finality or sync checkpoint points to, for a longer TTL.
+//!
+//! The epoch of such a moving reference is what makes the protocol configuration of `Finality::Final` a cache hit,
+//! and tells whether the epoch of a validators request is over, without fetching the latest block on every call.
+//! For up to that TTL after a new epoch starts, the previous epoch is still taken as the current one.
+//!
+//! Every other call is passed through to the inner provider. Cache sizes and the TTL are configured with `CacheConfig`,
+//! and hit/miss statistics are available from `CachingProvider::stats`.
+//!
+//! `CacheLayer` applies the same caching as a `Middleware`, for a `ProviderBuilder` stack.
+
+use crate::batch::{self, DEFAULT_BATCH_CONCURRENCY};
+use crate::client_config::{RpcClientConfigError, RpcClientConfigResponse};
+use crate::jsonrpc_client::{
+    errors::JsonRpcError,
+    methods::{self, status::RpcStatusResponse},
+};
+use crate::middleware::{Middleware, Next};
+use crate::tx_status::RpcTransactionWithReceiptsResponse;
+use crate::types::{
+    blocks::RpcBlockError,
+    changes::{
+        RpcStateChangesError, RpcStateChangesInBlockByTypeResponse, RpcStateChangesInBlockResponse,
+    },
+    chunks::{ChunkReference, RpcChunkError},
+    config::RpcProtocolConfigError,
+    gas_price::RpcGasPriceError,
+    light_client::{
+        RpcLightClientExecutionProofResponse, RpcLightClientNextBlockError,
+        RpcLightClientProofError,
+    },
+    network_info::{RpcNetworkInfoError, RpcNetworkInfoResponse},
+    query::{RpcQueryError, RpcQueryResponse},
+    receipts::RpcReceiptError,
+    status::{RpcHealthResponse, RpcStatusError},
+    transactions::{RpcTransactionError, TransactionInfo},
+    validator::RpcValidatorError,
+};
+use crate::utils::{clone_block_view, clone_chunk_view};
+use crate::{Provider, ProviderRequest, ProviderResponse};
+use async_trait::async_trait;
+use lru::LruCache;
+use near_chain_configs::{GenesisConfig, ProtocolConfigView};
+use near_jsonrpc_client::methods::tx::RpcTransactionResponse;
+use near_primitives::{
+    hash::CryptoHash,
+    transaction::SignedTransaction,
+    types::{
+        BlockId, BlockReference, EpochId, EpochReference, Finality, ShardId, TransactionOrReceiptId,
+    },
+    views::{
+        BlockView, ChunkView, EpochValidatorInfo, FinalExecutionOutcomeView, GasPriceView,
+        LightClientBlockView, QueryRequest, ReceiptView, StateChangesRequestView,
+        TxExecutionStatus,
+    },
+};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Size limits and TTLs of a `CachingProvider`. A size of 0 disables the corresponding cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheConfig {
+    /// Maximum number of blocks cached by hash.
+    pub max_blocks: usize,
+    /// Maximum number of chunks cached by hash.
+    pub max_chunks: usize,
+    /// Maximum number of protocol configurations cached, one per epoch.
+    pub max_protocol_configs: usize,
+    /// Maximum number of validator sets cached, one per epoch.
+    pub max_validators: usize,
+    /// How long the latest final block is reused. `Duration::ZERO` disables it.
+    pub final_block_ttl: Duration,
+    /// How long the epoch of the block a finality or sync checkpoint points to is reused. `Duration::ZERO` disables
+    /// it.
+    pub epoch_ttl: Duration,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            max_blocks: 1024,
+            max_chunks: 1024,
+            max_protocol_configs: 16,
+            max_validators: 16,
+            final_block_ttl: Duration::from_secs(1),
+            epoch_ttl: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Hit and miss counts of a single cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheCounters {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Hit and miss counts of every cache of a `CachingProvider`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    pub blocks: CacheCounters,
+    pub chunks: CacheCounters,
+    pub protocol_configs: CacheCounters,
+    pub validators: CacheCounters,
+    pub final_block: CacheCounters,
+    pub genesis_config: CacheCounters,
+}
+
+/// Chunks can be requested by their own hash, or by block hash and shard id.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ChunkKey {
+    ChunkHash(CryptoHash),
+    BlockShard(CryptoHash, ShardId),
+}
+
+impl ChunkKey {
+    fn from_reference(chunk_reference: &ChunkReference) -> Option<Self> {
+        match chunk_reference {
+            ChunkReference::ChunkHash { chunk_id } => Some(ChunkKey::ChunkHash(*chunk_id)),
+            ChunkReference::BlockShardId {
+                block_id: BlockId::Hash(block_hash),
+                shard_id,
+            } => Some(ChunkKey::BlockShard(*block_hash, *shard_id)),
+            ChunkReference::BlockShardId { .. } => None,
+        }
+    }
+}
+
+/// The epochs of a block, as found in its header.
+#[derive(Debug, Clone, Copy)]
+struct BlockEpochs {
+    block_hash: CryptoHash,
+    epoch_id: CryptoHash,
+    next_epoch_id: CryptoHash,
+}
+
+impl BlockEpochs {
+    fn of(block: &BlockView) -> Self {
+        Self {
+            block_hash: block.header.hash,
+            epoch_id: block.header.epoch_id,
+            next_epoch_id: block.header.next_epoch_id,
+        }
+    }
+}
+
+/// Returns true if the block `block_reference` points to changes over time.
+fn is_moving(block_reference: &BlockReference) -> bool {
+    matches!(
+        block_reference,
+        BlockReference::Finality(_) | BlockReference::SyncCheckpoint(_)
+    )
+}
+
+struct CacheState {
+    blocks: Option<LruCache<CryptoHash, BlockView>>,
+    chunks: Option<LruCache<ChunkKey, ChunkView>>,
+    // `ProtocolConfigView` does not implement `Clone`, so it is cached in its JSON form.
+    protocol_configs: Option<LruCache<EpochId, serde_json::Value>>,
+    validators: Option<LruCache<EpochId, EpochValidatorInfo>>,
+    final_block: Option<(Instant, BlockView)>,
+    // `BlockReference` does not implement `Hash`, and there are only a few moving references.
+    epochs: Vec<(BlockReference, Instant, BlockEpochs)>,
+    genesis_config: Option<GenesisConfig>,
+    stats: CacheStats,
+}
+
+impl CacheState {
+    fn new(config: &CacheConfig) -> Arc<Mutex<Self>> {
+        let state = CacheState {
+            blocks: lru_cache(config.max_blocks),
+            chunks: lru_cache(config.max_chunks),
+            protocol_configs: lru_cache(config.max_protocol_configs),
+            validators: lru_cache(config.max_validators),
+            final_block: None,
+            epochs: Vec::new(),
+            genesis_config: None,
+            stats: CacheStats::default(),
+        };
+        Arc::new(Mutex::new(state))
+    }
+
+    fn clear(&mut self) {
+        self.blocks.iter_mut().for_each(LruCache::clear);
+        self.chunks.iter_mut().for_each(LruCache::clear);
+        self.protocol_configs.iter_mut().for_each(LruCache::clear);
+        self.validators.iter_mut().for_each(LruCache::clear);
+        self.final_block = None;
+        self.epochs.clear();
+        self.genesis_config = None;
+    }
+}
+
+fn lru_cache<K: std::hash::Hash + Eq, V>(capacity: usize) -> Option<LruCache<K, V>> {
+    NonZeroUsize::new(capacity).map(LruCache::new)
+}
+
+/// Represents a provider that caches immutable and slowly changing responses of an inner provider.
+pub struct CachingProvider<P> {
+    inner: P,
+    config: CacheConfig,
+    state: Arc<Mutex<CacheState>>,
+}
+
+impl<P: Provider> CachingProvider<P> {
+    /// Constructs a new `CachingProvider` around `inner`, with the default `CacheConfig`.
+    pub fn new(inner: P) -> Self {
+        Self::with_config(inner, CacheConfig::default())
+    }
+
+    /// Constructs a new `CachingProvider` around `inner`, with the given cache sizes and TTL.
+    pub fn with_config(inner: P, config: CacheConfig) -> Self {
+        Self {
+            inner,
+            state: CacheState::new(&config),
+            config,
+        }
+    }
+
+    /// Returns a reference to the wrapped provider.
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    /// Returns the hit and miss counts since the provider was created.
+    pub fn stats(&self) -> CacheStats {
+        self.state.lock().unwrap().stats
+    }
+
+    /// Removes every cached response. Statistics are kept.
+    pub fn clear(&self) {
+        self.state.lock().unwrap().clear();
+    }
+
+    fn cached_block(&self, block_hash: &CryptoHash) -> Option<BlockView> {
+        let mut state = self.state.lock().unwrap();
+        let block = state
+            .blocks
+            .as_mut()
+            .and_then(|blocks| blocks.get(block_hash).map(clone_block_view));
+        count(&mut state.stats.blocks, block.is_some());
+        block
+    }
+
+    fn cached_final_block(&self) -> Option<BlockView> {
+        let mut state = self.state.lock().unwrap();
+        let block = match &state.final_block {
+            Some((fetched_at, block)) if fetched_at.elapsed() < self.config.final_block_ttl => {
+                Some(clone_block_view(block))
+            }
+            _ => None,
+        };
+        count(&mut state.stats.final_block, block.is_some());
+        block
+    }
+
+    /// Stores `block`, fetched with `block_reference`, in the block caches, and remembers its epochs if the
+    /// reference is a moving one.
+    fn store_block(&self, block: &BlockView, block_reference: &BlockReference) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(blocks) = state.blocks.as_mut() {
+            blocks.put(block.header.hash, clone_block_view(block));
+        }
+        let is_latest_final = *block_reference == BlockReference::Finality(Finality::Final);
+        if is_latest_final && !self.config.final_block_ttl.is_zero() {
+            state.final_block = Some((Instant::now(), clone_block_view(block)));
+        }
+        if is_moving(block_reference) && !self.config.epoch_ttl.is_zero() {
+            state
+                .epochs
+                .retain(|(reference, _, _)| reference != block_reference);
+            state.epochs.push((
+                block_reference.clone(),
+                Instant::now(),
+                BlockEpochs::of(block),
+            ));
+        }
+    }
+
+    /// Returns the epochs of the block `block_reference` points to, from the epochs remembered for a moving reference
+    /// or from the block it points to.
+    async fn block_epochs(
+        &self,
+        block_reference: &BlockReference,
+    ) -> Result<BlockEpochs, JsonRpcError<RpcBlockError>> {
+        let remembered =
+            self.state
+                .lock()
+                .unwrap()
+                .epochs
+                .iter()
+                .find_map(|(reference, fetched_at, epochs)| {
+                    (reference == block_reference && fetched_at.elapsed() < self.config.epoch_ttl)
+                        .then_some(*epochs)
+                });
+        match remembered {
+            Some(epochs) => Ok(epochs),
+            None => Ok(BlockEpochs::of(
+                &self.fetch_block(block_reference.clone()).await?,
+            )),
+        }
+    }
+
+    /// Returns the block `block_reference` points to from the block caches, if it is requested by hash or is the
+    /// latest final block.
+    fn lookup_block(&self, block_reference: &BlockReference) -> Option<BlockView> {
+        match block_reference {
+            BlockReference::BlockId(BlockId::Hash(block_hash)) => self.cached_block(block_hash),
+            BlockReference::Finality(Finality::Final) => self.cached_final_block(),
+            _ => None,
+        }
+    }
+
+    fn cached_chunk(&self, key: &ChunkKey) -> Option<ChunkView> {
+        let mut state = self.state.lock().unwrap();
+        let chunk = state
+            .chunks
+            .as_mut()
+            .and_then(|chunks| chunks.get(key).map(clone_chunk_view));
+        count(&mut state.stats.chunks, chunk.is_some());
+        chunk
+    }
+
+    fn store_chunk(&self, key: ChunkKey, chunk: &ChunkView) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(chunks) = state.chunks.as_mut() {
+            chunks.put(
+                ChunkKey::ChunkHash(chunk.header.chunk_hash),
+                clone_chunk_view(chunk),
+            );
+            chunks.put(key, clone_chunk_view(chunk));
+        }
+    }
+
+    fn cached_genesis_config(&self) -> Option<GenesisConfig> {
+        let mut state = self.state.lock().unwrap();
+        let genesis_config = state.genesis_config.clone();
+        count(&mut state.stats.genesis_config, genesis_config.is_some());
+        genesis_config
+    }
+
+    /// Returns the response to `request` from the caches, if it is a block, chunk or genesis configuration request
+    /// found there.
+    fn cached_response(&self, request: &ProviderRequest) -> Option<ProviderResponse> {
+        match request {
+            ProviderRequest::Block { block_reference } => {
+                let block = self.lookup_block(block_reference)?;
+                Some(ProviderResponse::Block(Ok(block)))
+            }
+            ProviderRequest::Chunk { chunk_reference } => {
+                let key = ChunkKey::from_reference(chunk_reference)?;
+                let chunk = self.cached_chunk(&key)?;
+                Some(ProviderResponse::Chunk(Ok(chunk)))
+            }
+            ProviderRequest::ExperimentalGenesisConfig => {
+                let genesis_config = self.cached_genesis_config()?;
+                Some(ProviderResponse::ExperimentalGenesisConfig(Ok(
+                    genesis_config,
+                )))
+            }
+            _ => None,
+        }
+    }
+
+    /// Stores the response of a request sent to the inner provider in the caches it belongs to.
+    fn store_response(&self, request: &ProviderRequest, response: &ProviderResponse) {
+        match (request, response) {
+            (ProviderRequest::Block { block_reference }, ProviderResponse::Block(Ok(block))) => {
+                self.store_block(block, block_reference);
+            }
+            (ProviderRequest::Chunk { chunk_reference }, ProviderResponse::Chunk(Ok(chunk))) => {
+                if let Some(key) = ChunkKey::from_reference(chunk_reference) {
+                    self.store_chunk(key, chunk);
+                }
+            }
+            (
+                ProviderRequest::ExperimentalGenesisConfig,
+                ProviderResponse::ExperimentalGenesisConfig(Ok(genesis_config)),
+            ) => {
+                self.state.lock().unwrap().genesis_config = Some(genesis_config.clone());
+            }
+            _ => {}
+        }
+    }
+
+    /// Fetches a block, using and filling the block and final block caches.
+    async fn fetch_block(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<BlockView, JsonRpcError<RpcBlockError>> {
+        if let Some(block) = self.lookup_block(&block_reference) {
+            return Ok(block);
+        }
+
+        let block = self.inner.block(block_reference.clone()).await?;
+        self.store_block(&block, &block_reference);
+        Ok(block)
+    }
+}
+
+/// A `Middleware` answering the calls that reach it from a cache as a `CachingProvider` does, to stack caching with
+/// other middlewares in a `ProviderBuilder`. Calls answered from the cache do not reach the layers below it.
+pub struct CacheLayer {
+    config: CacheConfig,
+    state: Arc<Mutex<CacheState>>,
+}
+
+impl CacheLayer {
+    /// Constructs a new `CacheLayer` with the default `CacheConfig`.
+    pub fn new() -> Self {
+        Self::with_config(CacheConfig::default())
+    }
+
+    /// Constructs a new `CacheLayer` with the given cache sizes and TTL.
+    pub fn with_config(config: CacheConfig) -> Self {
+        Self {
+            state: CacheState::new(&config),
+            config,
+        }
+    }
+
+    /// Returns the hit and miss counts since the layer was created.
+    pub fn stats(&self) -> CacheStats {
+        self.state.lock().unwrap().stats
+    }
+
+    /// Removes every cached response. Statistics are kept.
+    pub fn clear(&self) {
+        self.state.lock().unwrap().clear();
+    }
+}
+
+impl Default for CacheLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Middleware for CacheLayer {
+    async fn handle(&self, request: ProviderRequest, next: Next<'_>) -> ProviderResponse {
+        let provider = CachingProvider {
+            inner: next,
+            config: self.config.clone(),
+            state: self.state.clone(),
+        };
+        ProviderResponse::dispatch(&provider, request).await
+    }
+}
+
+fn count(counters: &mut CacheCounters, hit: bool) {
+    if hit {
+        counters.hits += 1;
+    } else {
+        counters.misses += 1;
+    }
+}
+
+#[async_trait]
+impl<P: Provider + Send + Sync> Provider for CachingProvider<P> {
+    /// Retrieves the current status of the NEAR blockchain. Never cached.
+    async fn status(&self) -> Result<RpcStatusResponse, JsonRpcError<RpcStatusError>> {
+        self.inner.status().await
+    }
+
+    /// Executes a query. Never cached.
+    async fn query(
+        &self,
+        request: QueryRequest,
+        block_reference: BlockReference,
+    ) -> Result<RpcQueryResponse, JsonRpcError<RpcQueryError>> {
+        self.inner.query(request, block_reference).await
+    }
+
+    /// Sends a signed transaction and waits for its final execution outcome. Never cached.
+    async fn send_transaction(
+        &self,
+        signed_transaction: SignedTransaction,
+    ) -> Result<FinalExecutionOutcomeView, JsonRpcError<RpcTransactionError>> {
+        self.inner.send_transaction(signed_transaction).await
+    }
+
+    /// Sends a signed transaction asynchronously. Never cached.
+    async fn send_transaction_async(
+        &self,
+        signed_transaction: SignedTransaction,
+    ) -> Result<CryptoHash, JsonRpcError<methods::broadcast_tx_async::RpcBroadcastTxAsyncError>>
+    {
+        self.inner.send_transaction_async(signed_transaction).await
+    }
+
+    /// Sends a signed transaction with the given `wait_until`. Never cached.
+    async fn send_tx(
+        &self,
+        signed_transaction: SignedTransaction,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionResponse, JsonRpcError<RpcTransactionError>> {
+        self.inner.send_tx(signed_transaction, wait_until).await
+    }
+
+    /// Retrieves the status of a transaction. Never cached.
+    async fn tx_status(
+        &self,
+        transaction_info: TransactionInfo,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionResponse, JsonRpcError<RpcTransactionError>> {
+        self.inner.tx_status(transaction_info, wait_until).await
+    }
+
+    /// Fetches details of a specific chunk. Chunks requested by chunk hash, or by block hash and shard id, are cached.
+    async fn chunk(
+        &self,
+        chunk_reference: ChunkReference,
+    ) -> Result<ChunkView, JsonRpcError<RpcChunkError>> {
+        let Some(key) = ChunkKey::from_reference(&chunk_reference) else {
+            return self.inner.chunk(chunk_reference).await;
+        };
+        if let Some(chunk) = self.cached_chunk(&key) {
+            return Ok(chunk);
+        }
+
+        let chunk = self.inner.chunk(chunk_reference).await?;
+        self.store_chunk(key, &chunk);
+        Ok(chunk)
+    }
+
+    /// Retrieves a block. Blocks requested by hash are cached, and the latest final block is reused for the configured TTL.
+    async fn block(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<BlockView, JsonRpcError<RpcBlockError>> {
+        self.fetch_block(block_reference).await
+    }
+
+    /// Fetches the protocol configuration, cached per epoch.
+    ///
+    /// The epoch is determined from the block the reference points to, which goes through the block caches, or from
+    /// the epoch remembered for a moving reference.
+    async fn experimental_protocol_config(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<ProtocolConfigView, JsonRpcError<RpcProtocolConfigError>> {
+        if self.config.max_protocol_configs == 0 {
+            return self
+                .inner
+                .experimental_protocol_config(block_reference)
+                .await;
+        }
+        let Ok(epochs) = self.block_epochs(&block_reference).await else {
+            // Let the inner provider report the error for the original reference.
+            return self
+                .inner
+                .experimental_protocol_config(block_reference)
+                .await;
+        };
+        let epoch_id = EpochId(epochs.epoch_id);
+
+        {
+            let mut state = self.state.lock().unwrap();
+            let config = state
+                .protocol_configs
+                .as_mut()
+                .and_then(|configs| configs.get(&epoch_id))
+                .and_then(|config| serde_json::from_value(config.clone()).ok());
+            count(&mut state.stats.protocol_configs, config.is_some());
+            if let Some(config) = config {
+                return Ok(config);
+            }
+        }
+
+        let block_reference = BlockReference::BlockId(BlockId::Hash(epochs.block_hash));
+        let config = self
+            .inner
+            .experimental_protocol_config(block_reference)
+            .await?;
+        if let Ok(value) = serde_json::to_value(&config) {
+            let mut state = self.state.lock().unwrap();
+            if let Some(configs) = state.protocol_configs.as_mut() {
+                configs.put(epoch_id, value);
+            }
+        }
+        Ok(config)
+    }
+
+    /// Retrieves information about validators. Requests by epoch id are cached once that epoch is over,
+    /// since the block production statistics of the current epoch keep changing. The epochs of the latest final block
+    /// tell the current and next epochs apart from the ones that are over.
+    async fn validators(
+        &self,
+        epoch_reference: EpochReference,
+    ) -> Result<EpochValidatorInfo, JsonRpcError<RpcValidatorError>> {
+        let epoch_id = match &epoch_reference {
+            EpochReference::EpochId(epoch_id) if self.config.max_validators > 0 => epoch_id.clone(),
+            _ => return self.inner.validators(epoch_reference).await,
+        };
+
+        {
+            let mut state = self.state.lock().unwrap();
+            let validators = state
+                .validators
+                .as_mut()
+                .and_then(|validators| validators.get(&epoch_id).cloned());
+            count(&mut state.stats.validators, validators.is_some());
+            if let Some(validators) = validators {
+                return Ok(validators);
+            }
+        }
+
+        let validators = self.inner.validators(epoch_reference).await?;
+        let current = self
+            .block_epochs(&BlockReference::Finality(Finality::Final))
+            .await;
+        if matches!(current, Ok(current) if current.epoch_id != epoch_id.0 && current.next_epoch_id != epoch_id.0)
+        {
+            let mut state = self.state.lock().unwrap();
+            if let Some(cache) = state.validators.as_mut() {
+                cache.put(epoch_id, validators.clone());
+            }
+        }
+        Ok(validators)
+    }
+
+    /// Fetches the gas price. Never cached.
+    async fn gas_price(
+        &self,
+        block_id: Option<BlockId>,
+    ) -> Result<GasPriceView, JsonRpcError<RpcGasPriceError>> {
+        self.inner.gas_price(block_id).await
+    }
+
+    /// Checks the health of the node. Never cached.
+    async fn health(&self) -> Result<RpcHealthResponse, JsonRpcError<RpcStatusError>> {
+        self.inner.health().await
+    }
+
+    /// Fetches the network information of the node. Never cached.
+    async fn network_info(
+        &self,
+    ) -> Result<RpcNetworkInfoResponse, JsonRpcError<RpcNetworkInfoError>> {
+        self.inner.network_info().await
+    }
+
+    /// Fetches the genesis configuration, which is cached once fetched since it never changes.
+    async fn experimental_genesis_config(
+        &self,
+    ) -> Result<
+        GenesisConfig,
+        JsonRpcError<methods::EXPERIMENTAL_genesis_config::RpcGenesisConfigError>,
+    > {
+        if let Some(genesis_config) = self.cached_genesis_config() {
+            return Ok(genesis_config);
+        }
+
+        let genesis_config = self.inner.experimental_genesis_config().await?;
+        self.state.lock().unwrap().genesis_config = Some(genesis_config.clone());
+        Ok(genesis_config)
+    }
+
+    /// Fetches the client configuration of the node. Never cached.
+    async fn client_config(
+        &self,
+    ) -> Result<RpcClientConfigResponse, JsonRpcError<RpcClientConfigError>> {
+        self.inner.client_config().await
+    }
+
+    /// Lists the state changes made in a block. Never cached.
+    async fn changes_in_block(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<RpcStateChangesInBlockByTypeResponse, JsonRpcError<RpcStateChangesError>> {
+        self.inner.changes_in_block(block_reference).await
+    }
+
+    /// Retrieves state changes. Never cached.
+    async fn changes(
+        &self,
+        request: StateChangesRequestView,
+        block_reference: BlockReference,
+    ) -> Result<RpcStateChangesInBlockResponse, JsonRpcError<RpcStateChangesError>> {
+        self.inner.changes(request, block_reference).await
+    }
+
+    /// Retrieves the status of a transaction and its receipts. Never cached.
+    async fn tx_status_with_receipts(
+        &self,
+        transaction_info: TransactionInfo,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionWithReceiptsResponse, JsonRpcError<RpcTransactionError>> {
+        self.inner
+            .tx_status_with_receipts(transaction_info, wait_until)
+            .await
+    }
+
+    /// Retrieves a receipt. Never cached.
+    async fn receipt(
+        &self,
+        receipt_id: CryptoHash,
+    ) -> Result<ReceiptView, JsonRpcError<RpcReceiptError>> {
+        self.inner.receipt(receipt_id).await
+    }
+
+    /// Retrieves an execution proof. Never cached.
+    async fn light_client_proof(
+        &self,
+        id: TransactionOrReceiptId,
+        light_client_head: CryptoHash,
+    ) -> Result<RpcLightClientExecutionProofResponse, JsonRpcError<RpcLightClientProofError>> {
+        self.inner.light_client_proof(id, light_client_head).await
+    }
+
+    /// Retrieves the next light client block. Never cached.
+    async fn next_light_client_block(
+        &self,
+        last_block_hash: CryptoHash,
+    ) -> Result<Option<LightClientBlockView>, JsonRpcError<RpcLightClientNextBlockError>> {
+        self.inner.next_light_client_block(last_block_hash).await
+    }
+
+    /// Answers the requests found in the caches and sends the others to the inner provider as a single batch,
+    /// caching their responses. Protocol configurations and validators go through their own methods instead, as
+    /// finding their cache entry may take another call.
+    async fn batch(&self, requests: Vec<ProviderRequest>) -> Vec<ProviderResponse> {
+        let count = requests.len();
+        let mut cached = Vec::new();
+        let mut forwarded = Vec::new();
+        let mut singles = Vec::new();
+        for (index, request) in requests.into_iter().enumerate() {
+            match request {
+                ProviderRequest::ExperimentalProtocolConfig { .. }
+                | ProviderRequest::Validators { .. } => singles.push((index, request)),
+                request => match self.cached_response(&request) {
+                    Some(response) => cached.push((index, response)),
+                    None => forwarded.push((index, request)),
+                },
+            }
+        }
+
+        let batch = forwarded
+            .iter()
+            .map(|(_, request)| request.clone())
+            .collect();
+        let (single_indices, singles): (Vec<_>, Vec<_>) = singles.into_iter().unzip();
+        let (batch_responses, single_responses) = futures::join!(
+            self.inner.batch(batch),
+            batch::dispatch_concurrently(self, singles, DEFAULT_BATCH_CONCURRENCY),
+        );
+        let fetched =
+            forwarded
+                .into_iter()
+                .zip(batch_responses)
+                .map(|((index, request), response)| {
+                    self.store_response(&request, &response);
+                    (index, response)
+                });
+        let singles = single_indices.into_iter().zip(single_responses);
+        batch::in_request_order(count, cached.into_iter().chain(fetched).chain(singles))
+    }
+
+    fn is_batched(&self, request: &ProviderRequest) -> bool {
+        !matches!(
+            request,
+            ProviderRequest::ExperimentalProtocolConfig { .. } | ProviderRequest::Validators { .. }
+        ) && self.inner.is_batched(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{block_view, MockProvider};
+    use crate::ProviderMethod;
+    use near_rpc_test_server::fixtures;
+
+    /// Builds a provider answering block requests by hash, the final block, and genesis config requests.
+    fn block_provider() -> MockProvider {
+        let provider = MockProvider::new();
+        provider.on_block(|block_reference| {
+            Some(Ok(match block_reference {
+                BlockReference::BlockId(BlockId::Hash(hash)) => block_view(10, *hash),
+                _ => block_view(10, CryptoHash::hash_bytes(b"final")),
+            }))
+        });
+        provider.on_experimental_genesis_config(|| {
+            Some(Ok(GenesisConfig {
+                chain_id: "localnet".to_string(),
+                ..GenesisConfig::default()
+            }))
+        });
+        provider
+    }
+
+    #[tokio::test]
+    async fn test_caches_blocks_by_hash() {
+        let provider = CachingProvider::new(block_provider());
+        let hash = CryptoHash::hash_bytes(b"block");
+
+        for _ in 0..3 {
+            let block = provider
+                .block(BlockReference::BlockId(BlockId::Hash(hash)))
+                .await
+                .unwrap();
+            assert_eq!(block.header.hash, hash);
+        }
+
+        assert_eq!(provider.inner().call_count(ProviderMethod::Block), 1);
+        assert_eq!(
+            provider.stats().blocks,
+            CacheCounters { hits: 2, misses: 1 }
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reuses_final_block_until_ttl_expires() {
+        let provider = CachingProvider::with_config(
+            block_provider(),
+            CacheConfig {
+                final_block_ttl: Duration::from_secs(1),
+                ..CacheConfig::default()
+            },
+        );
+        let final_block = || provider.block(BlockReference::Finality(Finality::Final));
+
+        final_block().await.unwrap();
+        final_block().await.unwrap();
+        assert_eq!(provider.inner().call_count(ProviderMethod::Block), 1);
+
+        tokio::time::advance(Duration::from_secs(2)).await;
+        let block = final_block().await.unwrap();
+        assert_eq!(provider.inner().call_count(ProviderMethod::Block), 2);
+
+        // The final block is also cached by its hash.
+        provider
+            .block(BlockReference::BlockId(BlockId::Hash(block.header.hash)))
+            .await
+            .unwrap();
+        assert_eq!(provider.inner().call_count(ProviderMethod::Block), 2);
+        assert_eq!(
+            provider.stats().final_block,
+            CacheCounters { hits: 1, misses: 2 }
+        );
+    }
+
+    fn epoch_id(epoch: &str) -> CryptoHash {
+        CryptoHash::hash_bytes(epoch.as_bytes())
+    }
+
+    /// Builds a provider whose final block is in epoch `e2`, followed by epoch `e3`, answering protocol configuration
+    /// and validators requests.
+    fn epoch_provider() -> MockProvider {
+        let provider = MockProvider::new();
+        provider.on_block(|_| {
+            let mut block = block_view(10, CryptoHash::hash_bytes(b"final"));
+            block.header.epoch_id = epoch_id("e2");
+            block.header.next_epoch_id = epoch_id("e3");
+            Some(Ok(block))
+        });
+        provider.on_experimental_protocol_config(|_| {
+            Some(Ok(
+                serde_json::from_value(fixtures::protocol_config()).unwrap()
+            ))
+        });
+        provider
+            .on_validators(|_| Some(Ok(serde_json::from_value(fixtures::validators(2)).unwrap())));
+        provider
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_caches_protocol_config_per_epoch() {
+        let provider = CachingProvider::new(epoch_provider());
+        let protocol_config =
+            || provider.experimental_protocol_config(BlockReference::Finality(Finality::Final));
+
+        for _ in 0..3 {
+            protocol_config().await.unwrap();
+        }
+        assert_eq!(
+            provider
+                .inner()
+                .call_count(ProviderMethod::ExperimentalProtocolConfig),
+            1
+        );
+        assert_eq!(provider.inner().call_count(ProviderMethod::Block), 1);
+
+        // Once the final block expired, the epoch of the reference is still known.
+        tokio::time::advance(Duration::from_secs(2)).await;
+        protocol_config().await.unwrap();
+        assert_eq!(provider.inner().call_count(ProviderMethod::Block), 1);
+
+        // Once the epoch expired too, the final block is fetched again, and its epoch is still cached.
+        tokio::time::advance(Duration::from_secs(60)).await;
+        protocol_config().await.unwrap();
+        assert_eq!(provider.inner().call_count(ProviderMethod::Block), 2);
+        assert_eq!(
+            provider
+                .inner()
+                .call_count(ProviderMethod::ExperimentalProtocolConfig),
+            1
+        );
+        assert_eq!(
+            provider.stats().protocol_configs,
+            CacheCounters { hits: 4, misses: 1 }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_caches_validators_of_past_epochs() {
+        let provider = CachingProvider::new(epoch_provider());
+
+        for _ in 0..3 {
+            provider
+                .validators(EpochReference::EpochId(EpochId(epoch_id("e1"))))
+                .await
+                .unwrap();
+        }
+        assert_eq!(provider.inner().call_count(ProviderMethod::Validators), 1);
+        assert_eq!(provider.inner().call_count(ProviderMethod::Block), 1);
+        assert_eq!(
+            provider.stats().validators,
+            CacheCounters { hits: 2, misses: 1 }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_does_not_cache_validators_of_current_epoch() {
+        let provider = CachingProvider::new(epoch_provider());
+
+        for epoch in ["e2", "e2", "e3", "e3"] {
+            provider
+                .validators(EpochReference::EpochId(EpochId(epoch_id(epoch))))
+                .await
+                .unwrap();
+        }
+        assert_eq!(provider.inner().call_count(ProviderMethod::Validators), 4);
+        assert_eq!(
+            provider.stats().validators,
+            CacheCounters { hits: 0, misses: 4 }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_caches_genesis_config() {
+        let provider = CachingProvider::new(block_provider());
+
+        for _ in 0..3 {
+            let genesis_config = provider.experimental_genesis_config().await.unwrap();
+            assert_eq!(genesis_config.chain_id, "localnet");
+        }
+
+        assert_eq!(
+            provider
+                .inner()
+                .call_count(ProviderMethod::ExperimentalGenesisConfig),
+            1
+        );
+        assert_eq!(
+            provider.stats().genesis_config,
+            CacheCounters { hits: 2, misses: 1 }
+        );
+
+        provider.clear();
+        provider.experimental_genesis_config().await.unwrap();
+        assert_eq!(
+            provider
+                .inner()
+                .call_count(ProviderMethod::ExperimentalGenesisConfig),
+            2
+        );
+    }
+}
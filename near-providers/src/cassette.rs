@@ -0,0 +1,937 @@
+//! The `cassette` module records the interactions of a `Provider` with the chain and serves them back later.
+//!
+//! A `RecordingProvider` wraps any provider and stores every request together with its response, or its error, in a
+//! `Cassette`, which can be saved to disk as a JSON file. A `ReplayProvider` loads that file and answers the same
+//! requests with the recorded responses, without a network connection. This allows capturing a real interaction with
+//! an RPC node once and replaying it deterministically in CI, and keeps golden files of the requests a flow makes.
+//!
+//! Requests are matched by their method and arguments. When the same request was recorded several times, e.g. a
+//! `block` request for the final block, the recorded responses are replayed in the order they were recorded.
+
+use crate::client_config::{RpcClientConfigError, RpcClientConfigResponse};
+use crate::jsonrpc_client::{
+    errors::{JsonRpcError, JsonRpcServerError},
+    methods::{self, status::RpcStatusResponse},
+};
+use crate::provider::for_each_result;
+use crate::tx_status::RpcTransactionWithReceiptsResponse;
+use crate::types::{
+    blocks::RpcBlockError,
+    changes::{
+        RpcStateChangesError, RpcStateChangesInBlockByTypeResponse, RpcStateChangesInBlockResponse,
+    },
+    chunks::{ChunkReference, RpcChunkError},
+    config::RpcProtocolConfigError,
+    gas_price::RpcGasPriceError,
+    light_client::{
+        RpcLightClientExecutionProofResponse, RpcLightClientNextBlockError,
+        RpcLightClientProofError,
+    },
+    network_info::{RpcNetworkInfoError, RpcNetworkInfoResponse},
+    query::{RpcQueryError, RpcQueryResponse},
+    receipts::RpcReceiptError,
+    status::{RpcHealthResponse, RpcStatusError},
+    transactions::{RpcTransactionError, TransactionInfo},
+    validator::RpcValidatorError,
+};
+use crate::utils::{clone_epoch_reference, clone_state_changes_request};
+use crate::{Provider, ProviderRequest, ProviderResponse};
+use async_trait::async_trait;
+use near_chain_configs::{GenesisConfig, ProtocolConfigView};
+use near_jsonrpc_client::methods::tx::RpcTransactionResponse;
+use near_primitives::{
+    hash::CryptoHash,
+    transaction::SignedTransaction,
+    types::{BlockId, BlockReference, EpochReference, TransactionOrReceiptId},
+    views::{
+        BlockView, ChunkView, EpochValidatorInfo, FinalExecutionOutcomeView, GasPriceView,
+        LightClientBlockView, QueryRequest, ReceiptView, StateChangesRequestView,
+        TxExecutionStatus,
+    },
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// A recorded sequence of provider interactions, stored on disk as JSON.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+/// A single request made to a provider and the response it received.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+    /// The provider method and its arguments, as `{"method": ..., "params": {...}}`.
+    pub request: Value,
+    pub response: RecordedResponse,
+}
+
+/// The outcome of a recorded request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordedResponse {
+    Result(Value),
+    Error(RecordedError),
+}
+
+/// A recorded error. Errors that cannot be reconstructed, such as transport errors, are kept as their message and
+/// replayed as internal server errors.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordedError {
+    Handler(Value),
+    Internal { info: Option<String> },
+    Other { message: String },
+}
+
+impl Cassette {
+    /// Reads a cassette from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let contents = std::fs::read(path)?;
+        Ok(serde_json::from_slice(&contents)?)
+    }
+
+    /// Writes the cassette to a JSON file, replacing its contents.
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut contents = serde_json::to_vec_pretty(self)?;
+        contents.push(b'\n');
+        std::fs::write(path, contents)
+    }
+}
+
+/// Converts the handler error of an RPC method to JSON so it can be stored in a cassette.
+pub trait RecordableError {
+    fn to_json(&self) -> Value;
+}
+
+macro_rules! impl_recordable_error {
+    ($($error:ty),*) => {
+        $(impl RecordableError for $error {
+            fn to_json(&self) -> Value {
+                serde_json::to_value(self).unwrap_or_else(|err| Value::String(err.to_string()))
+            }
+        })*
+    };
+}
+
+impl_recordable_error!(
+    RpcStatusError,
+    RpcTransactionError,
+    RpcChunkError,
+    RpcBlockError,
+    RpcValidatorError,
+    RpcQueryError,
+    RpcProtocolConfigError,
+    RpcGasPriceError,
+    RpcNetworkInfoError,
+    RpcClientConfigError,
+    RpcStateChangesError,
+    RpcReceiptError,
+    RpcLightClientProofError,
+    RpcLightClientNextBlockError
+);
+
+impl RecordableError for methods::broadcast_tx_async::RpcBroadcastTxAsyncError {
+    fn to_json(&self) -> Value {
+        match *self {}
+    }
+}
+
+impl RecordableError for methods::EXPERIMENTAL_genesis_config::RpcGenesisConfigError {
+    fn to_json(&self) -> Value {
+        match *self {}
+    }
+}
+
+fn epoch_reference_json(epoch_reference: &EpochReference) -> Value {
+    match epoch_reference {
+        EpochReference::EpochId(epoch_id) => json!({ "epoch_id": epoch_id }),
+        EpochReference::BlockId(block_id) => json!({ "block_id": block_id }),
+        EpochReference::Latest => json!("latest"),
+    }
+}
+
+/// Converts a request to the JSON form used to store and match it in a cassette.
+fn request_json(request: &ProviderRequest) -> Value {
+    let params = match request {
+        ProviderRequest::Status => json!({}),
+        ProviderRequest::SendTransaction { signed_transaction }
+        | ProviderRequest::SendTransactionAsync { signed_transaction } => {
+            json!({ "signed_transaction": signed_transaction })
+        }
+        ProviderRequest::SendTx {
+            signed_transaction,
+            wait_until,
+        } => json!({ "signed_transaction": signed_transaction, "wait_until": wait_until }),
+        ProviderRequest::TxStatus {
+            transaction_info,
+            wait_until,
+        }
+        | ProviderRequest::TxStatusWithReceipts {
+            transaction_info,
+            wait_until,
+        } => json!({ "transaction_info": transaction_info, "wait_until": wait_until }),
+        ProviderRequest::Chunk { chunk_reference } => {
+            json!({ "chunk_reference": chunk_reference })
+        }
+        ProviderRequest::Block { block_reference }
+        | ProviderRequest::ExperimentalProtocolConfig { block_reference } => {
+            json!({ "block_reference": block_reference })
+        }
+        ProviderRequest::Validators { epoch_reference } => {
+            json!({ "epoch_reference": epoch_reference_json(epoch_reference) })
+        }
+        ProviderRequest::Query {
+            request,
+            block_reference,
+        } => json!({ "request": request, "block_reference": block_reference }),
+        ProviderRequest::GasPrice { block_id } => json!({ "block_id": block_id }),
+        ProviderRequest::Health
+        | ProviderRequest::NetworkInfo
+        | ProviderRequest::ExperimentalGenesisConfig
+        | ProviderRequest::ClientConfig => json!({}),
+        ProviderRequest::ChangesInBlock { block_reference } => {
+            json!({ "block_reference": block_reference })
+        }
+        ProviderRequest::Changes {
+            request,
+            block_reference,
+        } => json!({ "request": request, "block_reference": block_reference }),
+        ProviderRequest::Receipt { receipt_id } => json!({ "receipt_id": receipt_id }),
+        ProviderRequest::LightClientProof {
+            id,
+            light_client_head,
+        } => json!({ "id": id, "light_client_head": light_client_head }),
+        ProviderRequest::NextLightClientBlock { last_block_hash } => {
+            json!({ "last_block_hash": last_block_hash })
+        }
+    };
+    json!({ "method": request.method().rpc_method_name(), "params": params })
+}
+
+fn recorded_response<T, E>(result: &Result<T, JsonRpcError<E>>) -> RecordedResponse
+where
+    T: Serialize,
+    E: RecordableError + std::fmt::Debug + std::fmt::Display,
+{
+    match result {
+        Ok(response) => match serde_json::to_value(response) {
+            Ok(value) => RecordedResponse::Result(value),
+            Err(err) => RecordedResponse::Error(RecordedError::Other {
+                message: format!("failed to serialize the response: {}", err),
+            }),
+        },
+        Err(JsonRpcError::ServerError(JsonRpcServerError::HandlerError(err))) => {
+            RecordedResponse::Error(RecordedError::Handler(err.to_json()))
+        }
+        Err(JsonRpcError::ServerError(JsonRpcServerError::InternalError { info })) => {
+            RecordedResponse::Error(RecordedError::Internal { info: info.clone() })
+        }
+        Err(err) => RecordedResponse::Error(RecordedError::Other {
+            message: err.to_string(),
+        }),
+    }
+}
+
+/// A `Provider` wrapper that records every request made through it and the response of the inner provider.
+pub struct RecordingProvider<P> {
+    inner: P,
+    cassette: Mutex<Cassette>,
+}
+
+impl<P: Provider> RecordingProvider<P> {
+    /// Constructs a new `RecordingProvider` around `inner` with an empty cassette.
+    pub fn new(inner: P) -> Self {
+        Self {
+            inner,
+            cassette: Mutex::new(Cassette::default()),
+        }
+    }
+
+    /// Returns a reference to the wrapped provider.
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    /// Returns a copy of the interactions recorded so far.
+    pub fn cassette(&self) -> Cassette {
+        self.cassette.lock().unwrap().clone()
+    }
+
+    /// Writes the interactions recorded so far to a JSON file, to be replayed with `ReplayProvider::from_file`.
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        self.cassette.lock().unwrap().save(path)
+    }
+
+    fn record<T, E>(&self, request: &ProviderRequest, result: &Result<T, JsonRpcError<E>>)
+    where
+        T: Serialize,
+        E: RecordableError + std::fmt::Debug + std::fmt::Display,
+    {
+        let interaction = Interaction {
+            request: request_json(request),
+            response: recorded_response(result),
+        };
+        self.cassette.lock().unwrap().interactions.push(interaction);
+    }
+}
+
+#[async_trait]
+impl<P: Provider + Send + Sync> Provider for RecordingProvider<P> {
+    /// Fetches the status from the inner provider and records it.
+    async fn status(&self) -> Result<RpcStatusResponse, JsonRpcError<RpcStatusError>> {
+        let result = self.inner.status().await;
+        self.record(&ProviderRequest::Status, &result);
+        result
+    }
+
+    /// Sends the transaction through the inner provider and records the outcome.
+    async fn send_transaction(
+        &self,
+        signed_transaction: SignedTransaction,
+    ) -> Result<FinalExecutionOutcomeView, JsonRpcError<RpcTransactionError>> {
+        let request = ProviderRequest::SendTransaction {
+            signed_transaction: signed_transaction.clone(),
+        };
+        let result = self.inner.send_transaction(signed_transaction).await;
+        self.record(&request, &result);
+        result
+    }
+
+    /// Sends the transaction asynchronously through the inner provider and records the transaction hash.
+    async fn send_transaction_async(
+        &self,
+        signed_transaction: SignedTransaction,
+    ) -> Result<CryptoHash, JsonRpcError<methods::broadcast_tx_async::RpcBroadcastTxAsyncError>>
+    {
+        let request = ProviderRequest::SendTransactionAsync {
+            signed_transaction: signed_transaction.clone(),
+        };
+        let result = self.inner.send_transaction_async(signed_transaction).await;
+        self.record(&request, &result);
+        result
+    }
+
+    /// Sends the transaction through the inner provider and records the response.
+    async fn send_tx(
+        &self,
+        signed_transaction: SignedTransaction,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionResponse, JsonRpcError<RpcTransactionError>> {
+        let request = ProviderRequest::SendTx {
+            signed_transaction: signed_transaction.clone(),
+            wait_until: wait_until.clone(),
+        };
+        let result = self.inner.send_tx(signed_transaction, wait_until).await;
+        self.record(&request, &result);
+        result
+    }
+
+    /// Fetches the transaction status from the inner provider and records it.
+    async fn tx_status(
+        &self,
+        transaction_info: TransactionInfo,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionResponse, JsonRpcError<RpcTransactionError>> {
+        let request = ProviderRequest::TxStatus {
+            transaction_info: transaction_info.clone(),
+            wait_until: wait_until.clone(),
+        };
+        let result = self.inner.tx_status(transaction_info, wait_until).await;
+        self.record(&request, &result);
+        result
+    }
+
+    /// Fetches the chunk from the inner provider and records it.
+    async fn chunk(
+        &self,
+        chunk_reference: ChunkReference,
+    ) -> Result<ChunkView, JsonRpcError<RpcChunkError>> {
+        let request = ProviderRequest::Chunk {
+            chunk_reference: chunk_reference.clone(),
+        };
+        let result = self.inner.chunk(chunk_reference).await;
+        self.record(&request, &result);
+        result
+    }
+
+    /// Fetches the block from the inner provider and records it.
+    async fn block(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<BlockView, JsonRpcError<RpcBlockError>> {
+        let request = ProviderRequest::Block {
+            block_reference: block_reference.clone(),
+        };
+        let result = self.inner.block(block_reference).await;
+        self.record(&request, &result);
+        result
+    }
+
+    /// Fetches the validators from the inner provider and records them.
+    async fn validators(
+        &self,
+        epoch_reference: EpochReference,
+    ) -> Result<EpochValidatorInfo, JsonRpcError<RpcValidatorError>> {
+        let request = ProviderRequest::Validators {
+            epoch_reference: clone_epoch_reference(&epoch_reference),
+        };
+        let result = self.inner.validators(epoch_reference).await;
+        self.record(&request, &result);
+        result
+    }
+
+    /// Performs the query through the inner provider and records the response.
+    async fn query(
+        &self,
+        request: QueryRequest,
+        block_reference: BlockReference,
+    ) -> Result<RpcQueryResponse, JsonRpcError<RpcQueryError>> {
+        let provider_request = ProviderRequest::Query {
+            request: request.clone(),
+            block_reference: block_reference.clone(),
+        };
+        let result = self.inner.query(request, block_reference).await;
+        self.record(&provider_request, &result);
+        result
+    }
+
+    /// Fetches the protocol config from the inner provider and records it.
+    async fn experimental_protocol_config(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<ProtocolConfigView, JsonRpcError<RpcProtocolConfigError>> {
+        let request = ProviderRequest::ExperimentalProtocolConfig {
+            block_reference: block_reference.clone(),
+        };
+        let result = self
+            .inner
+            .experimental_protocol_config(block_reference)
+            .await;
+        self.record(&request, &result);
+        result
+    }
+
+    /// Fetches the gas price from the inner provider and records it.
+    async fn gas_price(
+        &self,
+        block_id: Option<BlockId>,
+    ) -> Result<GasPriceView, JsonRpcError<RpcGasPriceError>> {
+        let request = ProviderRequest::GasPrice {
+            block_id: block_id.clone(),
+        };
+        let result = self.inner.gas_price(block_id).await;
+        self.record(&request, &result);
+        result
+    }
+
+    /// Checks the health of the inner provider and records the outcome.
+    async fn health(&self) -> Result<RpcHealthResponse, JsonRpcError<RpcStatusError>> {
+        let result = self.inner.health().await;
+        self.record(&ProviderRequest::Health, &result);
+        result
+    }
+
+    /// Fetches the network information from the inner provider and records it.
+    async fn network_info(
+        &self,
+    ) -> Result<RpcNetworkInfoResponse, JsonRpcError<RpcNetworkInfoError>> {
+        let result = self.inner.network_info().await;
+        self.record(&ProviderRequest::NetworkInfo, &result);
+        result
+    }
+
+    /// Fetches the genesis configuration from the inner provider and records it.
+    async fn experimental_genesis_config(
+        &self,
+    ) -> Result<
+        GenesisConfig,
+        JsonRpcError<methods::EXPERIMENTAL_genesis_config::RpcGenesisConfigError>,
+    > {
+        let result = self.inner.experimental_genesis_config().await;
+        self.record(&ProviderRequest::ExperimentalGenesisConfig, &result);
+        result
+    }
+
+    /// Fetches the client configuration from the inner provider and records it.
+    async fn client_config(
+        &self,
+    ) -> Result<RpcClientConfigResponse, JsonRpcError<RpcClientConfigError>> {
+        let result = self.inner.client_config().await;
+        self.record(&ProviderRequest::ClientConfig, &result);
+        result
+    }
+
+    /// Lists the state changes made in a block using the inner provider and records them.
+    async fn changes_in_block(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<RpcStateChangesInBlockByTypeResponse, JsonRpcError<RpcStateChangesError>> {
+        let request = ProviderRequest::ChangesInBlock {
+            block_reference: block_reference.clone(),
+        };
+        let result = self.inner.changes_in_block(block_reference).await;
+        self.record(&request, &result);
+        result
+    }
+
+    /// Retrieves state changes from the inner provider and records them.
+    async fn changes(
+        &self,
+        request: StateChangesRequestView,
+        block_reference: BlockReference,
+    ) -> Result<RpcStateChangesInBlockResponse, JsonRpcError<RpcStateChangesError>> {
+        let recorded = ProviderRequest::Changes {
+            request: clone_state_changes_request(&request),
+            block_reference: block_reference.clone(),
+        };
+        let result = self.inner.changes(request, block_reference).await;
+        self.record(&recorded, &result);
+        result
+    }
+
+    /// Fetches the transaction status and receipts from the inner provider and records them.
+    async fn tx_status_with_receipts(
+        &self,
+        transaction_info: TransactionInfo,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionWithReceiptsResponse, JsonRpcError<RpcTransactionError>> {
+        let request = ProviderRequest::TxStatusWithReceipts {
+            transaction_info: transaction_info.clone(),
+            wait_until: wait_until.clone(),
+        };
+        let result = self
+            .inner
+            .tx_status_with_receipts(transaction_info, wait_until)
+            .await;
+        self.record(&request, &result);
+        result
+    }
+
+    /// Fetches the receipt from the inner provider and records it.
+    async fn receipt(
+        &self,
+        receipt_id: CryptoHash,
+    ) -> Result<ReceiptView, JsonRpcError<RpcReceiptError>> {
+        let result = self.inner.receipt(receipt_id).await;
+        self.record(&ProviderRequest::Receipt { receipt_id }, &result);
+        result
+    }
+
+    /// Fetches the execution proof from the inner provider and records it.
+    async fn light_client_proof(
+        &self,
+        id: TransactionOrReceiptId,
+        light_client_head: CryptoHash,
+    ) -> Result<RpcLightClientExecutionProofResponse, JsonRpcError<RpcLightClientProofError>> {
+        let request = ProviderRequest::LightClientProof {
+            id: id.clone(),
+            light_client_head,
+        };
+        let result = self.inner.light_client_proof(id, light_client_head).await;
+        self.record(&request, &result);
+        result
+    }
+
+    /// Fetches the next light client block from the inner provider and records it.
+    async fn next_light_client_block(
+        &self,
+        last_block_hash: CryptoHash,
+    ) -> Result<Option<LightClientBlockView>, JsonRpcError<RpcLightClientNextBlockError>> {
+        let result = self.inner.next_light_client_block(last_block_hash).await;
+        self.record(
+            &ProviderRequest::NextLightClientBlock { last_block_hash },
+            &result,
+        );
+        result
+    }
+
+    /// Sends the batch through the inner provider's `batch` and records each response, in request order.
+    async fn batch(&self, requests: Vec<ProviderRequest>) -> Vec<ProviderResponse> {
+        let responses = self.inner.batch(requests.clone()).await;
+        for (request, response) in requests.iter().zip(&responses) {
+            for_each_result!(response, result => self.record(request, result));
+        }
+        responses
+    }
+
+    fn is_batched(&self, request: &ProviderRequest) -> bool {
+        self.inner.is_batched(request)
+    }
+}
+
+/// A `Provider` answering requests with the responses recorded in a `Cassette`.
+///
+/// Each recorded interaction is replayed at most once. Requests without a matching interaction left fail with an
+/// internal server error describing the request.
+pub struct ReplayProvider {
+    interactions: Vec<Interaction>,
+    replayed: Mutex<Vec<bool>>,
+}
+
+impl ReplayProvider {
+    /// Constructs a new `ReplayProvider` serving the interactions of `cassette`.
+    pub fn new(cassette: Cassette) -> Self {
+        let replayed = vec![false; cassette.interactions.len()];
+        Self {
+            interactions: cassette.interactions,
+            replayed: Mutex::new(replayed),
+        }
+    }
+
+    /// Constructs a new `ReplayProvider` serving the interactions of a cassette file written by `RecordingProvider::save`.
+    pub fn from_file(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Ok(Self::new(Cassette::load(path)?))
+    }
+
+    /// Returns the recorded interactions that have not been replayed yet, e.g. to assert that a flow made every
+    /// request it made when it was recorded.
+    pub fn remaining(&self) -> Vec<Interaction> {
+        let replayed = self.replayed.lock().unwrap();
+        self.interactions
+            .iter()
+            .zip(replayed.iter())
+            .filter(|(_, replayed)| !**replayed)
+            .map(|(interaction, _)| interaction.clone())
+            .collect()
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn replay<T, E>(&self, request: &ProviderRequest) -> Result<T, JsonRpcError<E>>
+    where
+        T: DeserializeOwned,
+        E: DeserializeOwned,
+    {
+        let request = request_json(request);
+        let response = {
+            let mut replayed = self.replayed.lock().unwrap();
+            let index =
+                self.interactions.iter().zip(replayed.iter()).position(
+                    |(interaction, replayed)| !replayed && interaction.request == request,
+                );
+            match index {
+                Some(index) => {
+                    replayed[index] = true;
+                    &self.interactions[index].response
+                }
+                None => {
+                    return Err(internal_error(format!(
+                        "ReplayProvider: no recorded response for {}",
+                        request
+                    )))
+                }
+            }
+        };
+
+        match response {
+            RecordedResponse::Result(value) => T::deserialize(value).map_err(|err| {
+                internal_error(format!(
+                    "ReplayProvider: invalid recorded response for {}: {}",
+                    request, err
+                ))
+            }),
+            RecordedResponse::Error(RecordedError::Handler(value)) => match E::deserialize(value) {
+                Ok(err) => Err(JsonRpcError::ServerError(JsonRpcServerError::HandlerError(
+                    err,
+                ))),
+                Err(err) => Err(internal_error(format!(
+                    "ReplayProvider: invalid recorded error for {}: {}",
+                    request, err
+                ))),
+            },
+            RecordedResponse::Error(RecordedError::Internal { info }) => Err(
+                JsonRpcError::ServerError(JsonRpcServerError::InternalError { info: info.clone() }),
+            ),
+            RecordedResponse::Error(RecordedError::Other { message }) => {
+                Err(internal_error(message.clone()))
+            }
+        }
+    }
+}
+
+fn internal_error<E>(info: String) -> JsonRpcError<E> {
+    JsonRpcError::ServerError(JsonRpcServerError::InternalError { info: Some(info) })
+}
+
+#[async_trait]
+impl Provider for ReplayProvider {
+    /// Replays the recorded status.
+    async fn status(&self) -> Result<RpcStatusResponse, JsonRpcError<RpcStatusError>> {
+        self.replay(&ProviderRequest::Status)
+    }
+
+    /// Replays the recorded outcome of sending the transaction.
+    async fn send_transaction(
+        &self,
+        signed_transaction: SignedTransaction,
+    ) -> Result<FinalExecutionOutcomeView, JsonRpcError<RpcTransactionError>> {
+        self.replay(&ProviderRequest::SendTransaction { signed_transaction })
+    }
+
+    /// Replays the recorded transaction hash.
+    async fn send_transaction_async(
+        &self,
+        signed_transaction: SignedTransaction,
+    ) -> Result<CryptoHash, JsonRpcError<methods::broadcast_tx_async::RpcBroadcastTxAsyncError>>
+    {
+        self.replay(&ProviderRequest::SendTransactionAsync { signed_transaction })
+    }
+
+    /// Replays the recorded response to sending the transaction.
+    async fn send_tx(
+        &self,
+        signed_transaction: SignedTransaction,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionResponse, JsonRpcError<RpcTransactionError>> {
+        self.replay(&ProviderRequest::SendTx {
+            signed_transaction,
+            wait_until,
+        })
+    }
+
+    /// Replays the recorded transaction status.
+    async fn tx_status(
+        &self,
+        transaction_info: TransactionInfo,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionResponse, JsonRpcError<RpcTransactionError>> {
+        self.replay(&ProviderRequest::TxStatus {
+            transaction_info,
+            wait_until,
+        })
+    }
+
+    /// Replays the recorded chunk.
+    async fn chunk(
+        &self,
+        chunk_reference: ChunkReference,
+    ) -> Result<ChunkView, JsonRpcError<RpcChunkError>> {
+        self.replay(&ProviderRequest::Chunk { chunk_reference })
+    }
+
+    /// Replays the recorded block.
+    async fn block(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<BlockView, JsonRpcError<RpcBlockError>> {
+        self.replay(&ProviderRequest::Block { block_reference })
+    }
+
+    /// Replays the recorded validators.
+    async fn validators(
+        &self,
+        epoch_reference: EpochReference,
+    ) -> Result<EpochValidatorInfo, JsonRpcError<RpcValidatorError>> {
+        self.replay(&ProviderRequest::Validators { epoch_reference })
+    }
+
+    /// Replays the recorded query response.
+    async fn query(
+        &self,
+        request: QueryRequest,
+        block_reference: BlockReference,
+    ) -> Result<RpcQueryResponse, JsonRpcError<RpcQueryError>> {
+        self.replay(&ProviderRequest::Query {
+            request,
+            block_reference,
+        })
+    }
+
+    /// Replays the recorded protocol config.
+    async fn experimental_protocol_config(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<ProtocolConfigView, JsonRpcError<RpcProtocolConfigError>> {
+        self.replay(&ProviderRequest::ExperimentalProtocolConfig { block_reference })
+    }
+
+    /// Replays the recorded gas price.
+    async fn gas_price(
+        &self,
+        block_id: Option<BlockId>,
+    ) -> Result<GasPriceView, JsonRpcError<RpcGasPriceError>> {
+        self.replay(&ProviderRequest::GasPrice { block_id })
+    }
+
+    /// Replays the recorded health check.
+    async fn health(&self) -> Result<RpcHealthResponse, JsonRpcError<RpcStatusError>> {
+        self.replay(&ProviderRequest::Health)
+    }
+
+    /// Replays the recorded network information.
+    async fn network_info(
+        &self,
+    ) -> Result<RpcNetworkInfoResponse, JsonRpcError<RpcNetworkInfoError>> {
+        self.replay(&ProviderRequest::NetworkInfo)
+    }
+
+    /// Replays the recorded genesis configuration.
+    async fn experimental_genesis_config(
+        &self,
+    ) -> Result<
+        GenesisConfig,
+        JsonRpcError<methods::EXPERIMENTAL_genesis_config::RpcGenesisConfigError>,
+    > {
+        self.replay(&ProviderRequest::ExperimentalGenesisConfig)
+    }
+
+    /// Replays the recorded client configuration.
+    async fn client_config(
+        &self,
+    ) -> Result<RpcClientConfigResponse, JsonRpcError<RpcClientConfigError>> {
+        self.replay(&ProviderRequest::ClientConfig)
+    }
+
+    /// Replays the recorded state changes of a block.
+    async fn changes_in_block(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<RpcStateChangesInBlockByTypeResponse, JsonRpcError<RpcStateChangesError>> {
+        self.replay(&ProviderRequest::ChangesInBlock { block_reference })
+    }
+
+    /// Replays the recorded state changes.
+    async fn changes(
+        &self,
+        request: StateChangesRequestView,
+        block_reference: BlockReference,
+    ) -> Result<RpcStateChangesInBlockResponse, JsonRpcError<RpcStateChangesError>> {
+        self.replay(&ProviderRequest::Changes {
+            request,
+            block_reference,
+        })
+    }
+
+    /// Replays the recorded transaction status and receipts.
+    async fn tx_status_with_receipts(
+        &self,
+        transaction_info: TransactionInfo,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionWithReceiptsResponse, JsonRpcError<RpcTransactionError>> {
+        self.replay(&ProviderRequest::TxStatusWithReceipts {
+            transaction_info,
+            wait_until,
+        })
+    }
+
+    /// Replays the recorded receipt.
+    async fn receipt(
+        &self,
+        receipt_id: CryptoHash,
+    ) -> Result<ReceiptView, JsonRpcError<RpcReceiptError>> {
+        self.replay(&ProviderRequest::Receipt { receipt_id })
+    }
+
+    /// Replays the recorded execution proof.
+    async fn light_client_proof(
+        &self,
+        id: TransactionOrReceiptId,
+        light_client_head: CryptoHash,
+    ) -> Result<RpcLightClientExecutionProofResponse, JsonRpcError<RpcLightClientProofError>> {
+        self.replay(&ProviderRequest::LightClientProof {
+            id,
+            light_client_head,
+        })
+    }
+
+    /// Replays the recorded light client block.
+    async fn next_light_client_block(
+        &self,
+        last_block_hash: CryptoHash,
+    ) -> Result<Option<LightClientBlockView>, JsonRpcError<RpcLightClientNextBlockError>> {
+        self.replay(&ProviderRequest::NextLightClientBlock { last_block_hash })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{access_key_response, block_view, handler_error, MockProvider};
+    use crate::types::query::QueryResponseKind;
+    use near_primitives::types::{BlockId, Finality};
+    use near_primitives::views::AccessKeyView;
+
+    fn view_access_key() -> QueryRequest {
+        QueryRequest::ViewAccessKey {
+            account_id: "alice.testnet".parse().unwrap(),
+            public_key: "ed25519:6E8sCci9badyRkXb3JoRpBj5p8C6Tw41ELDZoiihKEtp"
+                .parse()
+                .unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay() {
+        let mock = MockProvider::new();
+        let hash = CryptoHash::hash_bytes(b"block");
+        mock.push_block(Ok(block_view(10, hash)));
+        mock.push_block(Ok(block_view(11, CryptoHash::default())));
+        mock.push_query(Ok(access_key_response(41)));
+        mock.push_query(Err(handler_error(RpcQueryError::NoSyncedBlocks)));
+        mock.push_validators(Err(handler_error(RpcValidatorError::UnknownEpoch)));
+
+        let recorder = RecordingProvider::new(mock);
+        let final_block = BlockReference::Finality(Finality::Final);
+        recorder.block(final_block.clone()).await.unwrap();
+        recorder.block(final_block.clone()).await.unwrap();
+        recorder
+            .query(view_access_key(), final_block.clone())
+            .await
+            .unwrap();
+        recorder
+            .query(view_access_key(), final_block.clone())
+            .await
+            .unwrap_err();
+        recorder
+            .validators(EpochReference::BlockId(BlockId::Height(10)))
+            .await
+            .unwrap_err();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cassette.json");
+        recorder.save(&path).unwrap();
+        let replay = ReplayProvider::from_file(&path).unwrap();
+        assert_eq!(replay.remaining().len(), 5);
+
+        // Identical requests are answered in the order they were recorded.
+        let first = replay.block(final_block.clone()).await.unwrap();
+        let second = replay.block(final_block.clone()).await.unwrap();
+        assert_eq!((first.header.hash, first.header.height), (hash, 10));
+        assert_eq!(second.header.height, 11);
+
+        let response = replay
+            .query(view_access_key(), final_block.clone())
+            .await
+            .unwrap();
+        assert!(matches!(
+            response.kind,
+            QueryResponseKind::AccessKey(AccessKeyView { nonce: 41, .. })
+        ));
+        assert!(matches!(
+            replay.query(view_access_key(), final_block.clone()).await,
+            Err(JsonRpcError::ServerError(JsonRpcServerError::HandlerError(
+                RpcQueryError::NoSyncedBlocks
+            )))
+        ));
+        assert!(matches!(
+            replay
+                .validators(EpochReference::BlockId(BlockId::Height(10)))
+                .await,
+            Err(JsonRpcError::ServerError(JsonRpcServerError::HandlerError(
+                RpcValidatorError::UnknownEpoch
+            )))
+        ));
+        assert!(replay.remaining().is_empty());
+
+        // Every recorded interaction has been used up.
+        assert!(matches!(
+            replay.block(final_block).await,
+            Err(JsonRpcError::ServerError(
+                JsonRpcServerError::InternalError { .. }
+            ))
+        ));
+    }
+}
@@ -0,0 +1,70 @@
+//! The `changes` module builds the requests of the `Provider::changes` method, which selects the kind of state
+//! changes to fetch from a block and the accounts to fetch them for.
+//!
+//! ```
+//! use near_providers::changes;
+//!
+//! let request = changes::data_changes(["alice.near".parse().unwrap()], b"STATE");
+//! ```
+
+use near_crypto::PublicKey;
+use near_primitives::types::{AccountId, AccountWithPublicKey, StoreKey};
+use near_primitives::views::StateChangesRequestView;
+
+/// Requests the changes of the given accounts, e.g. of their balance or storage usage, and their deletion.
+pub fn account_changes<I>(account_ids: I) -> StateChangesRequestView
+where
+    I: IntoIterator<Item = AccountId>,
+{
+    StateChangesRequestView::AccountChanges {
+        account_ids: account_ids.into_iter().collect(),
+    }
+}
+
+/// Requests the changes of single access keys, each identified by its account and public key.
+pub fn single_access_key_changes<I>(keys: I) -> StateChangesRequestView
+where
+    I: IntoIterator<Item = (AccountId, PublicKey)>,
+{
+    StateChangesRequestView::SingleAccessKeyChanges {
+        keys: keys
+            .into_iter()
+            .map(|(account_id, public_key)| AccountWithPublicKey {
+                account_id,
+                public_key,
+            })
+            .collect(),
+    }
+}
+
+/// Requests the changes of all the access keys of the given accounts.
+pub fn all_access_key_changes<I>(account_ids: I) -> StateChangesRequestView
+where
+    I: IntoIterator<Item = AccountId>,
+{
+    StateChangesRequestView::AllAccessKeyChanges {
+        account_ids: account_ids.into_iter().collect(),
+    }
+}
+
+/// Requests the deployments of contract code on the given accounts.
+pub fn contract_code_changes<I>(account_ids: I) -> StateChangesRequestView
+where
+    I: IntoIterator<Item = AccountId>,
+{
+    StateChangesRequestView::ContractCodeChanges {
+        account_ids: account_ids.into_iter().collect(),
+    }
+}
+
+/// Requests the changes of the contract storage of the given accounts, for the keys starting with `key_prefix`.
+/// An empty prefix selects every key.
+pub fn data_changes<I>(account_ids: I, key_prefix: impl AsRef<[u8]>) -> StateChangesRequestView
+where
+    I: IntoIterator<Item = AccountId>,
+{
+    StateChangesRequestView::DataChanges {
+        account_ids: account_ids.into_iter().collect(),
+        key_prefix: StoreKey::from(key_prefix.as_ref().to_vec()),
+    }
+}
@@ -0,0 +1,43 @@
+//! The `client_config` module defines the response and error types of the `client_config` RPC method.
+//!
+//! `near-jsonrpc-primitives` only implements `Serialize` for them, as the node's `ClientConfig` cannot be
+//! deserialized. The response defined here keeps the fields most often checked by tooling typed, and the
+//! remaining fields of the configuration as JSON.
+
+use crate::jsonrpc_client::methods::{RpcHandlerError, RpcHandlerResponse};
+use near_primitives::types::{AccountId, ShardId};
+use near_primitives::version::Version;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// The client configuration of a node, as returned by the `client_config` RPC method.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcClientConfigResponse {
+    /// Version of the node's binary.
+    pub version: Version,
+    /// Id of the chain the node is connected to.
+    pub chain_id: String,
+    /// Whether the node keeps the full history of the chain.
+    pub archive: bool,
+    /// Accounts whose shards the node tracks.
+    #[serde(default)]
+    pub tracked_accounts: Vec<AccountId>,
+    /// Shards the node tracks.
+    #[serde(default)]
+    pub tracked_shards: Vec<ShardId>,
+    /// The remaining fields of the configuration.
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+/// Errors returned by the `client_config` RPC method.
+#[derive(Debug, thiserror::Error, Serialize, Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcClientConfigError {
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+impl RpcHandlerResponse for RpcClientConfigResponse {}
+
+impl RpcHandlerError for RpcClientConfigError {}
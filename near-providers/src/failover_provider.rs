@@ -0,0 +1,627 @@
+//! The `FailoverProvider` module offers a `Provider` implementation that spreads requests over an ordered list of inner providers.
+//! Requests go to the first healthy provider; when it fails with a transport error, a 5xx response or a timeout, the provider is
+//! marked unhealthy for a cooldown period and the request moves on to the next one.
+//!
+//! Transactions are never re-broadcast blindly: before a signed transaction is sent to the next provider, `tx_status` is used to
+//! check whether the network already knows about it, and the known status is returned instead of sending it again.
+
+use crate::batch::{self, DEFAULT_BATCH_CONCURRENCY};
+use crate::client_config::{RpcClientConfigError, RpcClientConfigResponse};
+use crate::jsonrpc_client::{
+    errors::{JsonRpcError, JsonRpcServerError, JsonRpcServerResponseStatusError},
+    methods::{self, status::RpcStatusResponse},
+};
+use crate::provider::for_each_result;
+use crate::tx_status::RpcTransactionWithReceiptsResponse;
+use crate::types::{
+    blocks::RpcBlockError,
+    changes::{
+        RpcStateChangesError, RpcStateChangesInBlockByTypeResponse, RpcStateChangesInBlockResponse,
+    },
+    chunks::{ChunkReference, RpcChunkError},
+    config::RpcProtocolConfigError,
+    gas_price::RpcGasPriceError,
+    light_client::{
+        RpcLightClientExecutionProofResponse, RpcLightClientNextBlockError,
+        RpcLightClientProofError,
+    },
+    network_info::{RpcNetworkInfoError, RpcNetworkInfoResponse},
+    query::{RpcQueryError, RpcQueryResponse},
+    receipts::RpcReceiptError,
+    status::{RpcHealthResponse, RpcStatusError},
+    transactions::{RpcTransactionError, TransactionInfo},
+    validator::RpcValidatorError,
+};
+use crate::utils::{clone_epoch_reference, clone_state_changes_request};
+use crate::{Provider, ProviderRequest, ProviderResponse};
+use async_trait::async_trait;
+use near_chain_configs::{GenesisConfig, ProtocolConfigView};
+use near_jsonrpc_client::methods::tx::RpcTransactionResponse;
+use near_primitives::{
+    hash::CryptoHash,
+    transaction::SignedTransaction,
+    types::{BlockId, BlockReference, EpochReference, TransactionOrReceiptId},
+    views::{
+        BlockView, ChunkView, EpochValidatorInfo, FinalExecutionOutcomeView, GasPriceView,
+        LightClientBlockView, QueryRequest, ReceiptView, StateChangesRequestView,
+        TxExecutionStatus,
+    },
+};
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Cooldown applied to a provider after it fails, unless configured otherwise with `FailoverProvider::with_cooldown`.
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// An inner provider together with its health state.
+struct Endpoint {
+    provider: Box<dyn Provider + Send + Sync>,
+    unhealthy_until: Mutex<Option<Instant>>,
+}
+
+impl Endpoint {
+    fn is_healthy(&self, now: Instant) -> bool {
+        match *self.unhealthy_until.lock().unwrap() {
+            Some(until) => until <= now,
+            None => true,
+        }
+    }
+
+    fn mark_healthy(&self) {
+        *self.unhealthy_until.lock().unwrap() = None;
+    }
+
+    fn mark_unhealthy(&self, cooldown: Duration) {
+        *self.unhealthy_until.lock().unwrap() = Some(Instant::now() + cooldown);
+    }
+}
+
+/// Represents a provider that fails over between multiple inner providers, in the order they were given.
+pub struct FailoverProvider {
+    endpoints: Vec<Endpoint>,
+    cooldown: Duration,
+}
+
+impl FailoverProvider {
+    /// Constructs a new `FailoverProvider` from an ordered list of providers. The first provider is preferred.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `providers` is empty.
+    pub fn new(providers: Vec<Box<dyn Provider + Send + Sync>>) -> Self {
+        assert!(
+            !providers.is_empty(),
+            "FailoverProvider needs at least one provider"
+        );
+        Self {
+            endpoints: providers
+                .into_iter()
+                .map(|provider| Endpoint {
+                    provider,
+                    unhealthy_until: Mutex::new(None),
+                })
+                .collect(),
+            cooldown: DEFAULT_COOLDOWN,
+        }
+    }
+
+    /// Sets how long a provider is skipped after it fails.
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Returns the endpoints to try, in order. Unhealthy endpoints are skipped, unless all of them are unhealthy.
+    fn candidates(&self) -> Vec<&Endpoint> {
+        let now = Instant::now();
+        let healthy: Vec<&Endpoint> = self
+            .endpoints
+            .iter()
+            .filter(|endpoint| endpoint.is_healthy(now))
+            .collect();
+        if healthy.is_empty() {
+            self.endpoints.iter().collect()
+        } else {
+            healthy
+        }
+    }
+
+    /// Calls `request` on each candidate endpoint until one of them does not fail with an endpoint failure.
+    async fn call<'a, T, E, F, Fut>(&'a self, request: F) -> Result<T, JsonRpcError<E>>
+    where
+        F: Fn(&'a (dyn Provider + Send + Sync)) -> Fut,
+        Fut: Future<Output = Result<T, JsonRpcError<E>>>,
+    {
+        let mut last_error = None;
+        for endpoint in self.candidates() {
+            match request(endpoint.provider.as_ref()).await {
+                Err(err) if is_endpoint_failure(&err) => {
+                    endpoint.mark_unhealthy(self.cooldown);
+                    last_error = Some(err);
+                }
+                result => {
+                    endpoint.mark_healthy();
+                    return result;
+                }
+            }
+        }
+        Err(last_error.expect("FailoverProvider always has at least one endpoint"))
+    }
+
+    /// Broadcasts a signed transaction, failing over between endpoints.
+    ///
+    /// After an endpoint failed, the next endpoint is first asked for the transaction status with `wait_until`.
+    /// If the transaction is known, `known` turns the status response into the result; the transaction is only
+    /// sent again if the endpoint reports it as unknown. If the status cannot be determined, the last send error is returned.
+    async fn broadcast<'a, T, E, F, Fut, K>(
+        &'a self,
+        signed_transaction: &SignedTransaction,
+        wait_until: TxExecutionStatus,
+        send: F,
+        known: K,
+    ) -> Result<T, JsonRpcError<E>>
+    where
+        F: Fn(&'a (dyn Provider + Send + Sync)) -> Fut,
+        Fut: Future<Output = Result<T, JsonRpcError<E>>>,
+        K: Fn(RpcTransactionResponse) -> Option<T>,
+    {
+        let transaction_info = TransactionInfo::TransactionId {
+            tx_hash: signed_transaction.get_hash(),
+            sender_account_id: signed_transaction.transaction.signer_id.clone(),
+        };
+
+        let mut last_error = None;
+        for endpoint in self.candidates() {
+            if let Some(send_error) = last_error.take() {
+                match endpoint
+                    .provider
+                    .tx_status(transaction_info.clone(), wait_until.clone())
+                    .await
+                {
+                    Ok(response) => {
+                        endpoint.mark_healthy();
+                        if let Some(result) = known(response) {
+                            return Ok(result);
+                        }
+                    }
+                    Err(err) if is_unknown_transaction(&err) => endpoint.mark_healthy(),
+                    Err(err) if is_endpoint_failure(&err) => {
+                        endpoint.mark_unhealthy(self.cooldown);
+                        last_error = Some(send_error);
+                        continue;
+                    }
+                    Err(_) => return Err(send_error),
+                }
+            }
+
+            match send(endpoint.provider.as_ref()).await {
+                Err(err) if is_endpoint_failure(&err) => {
+                    endpoint.mark_unhealthy(self.cooldown);
+                    last_error = Some(err);
+                }
+                result => {
+                    endpoint.mark_healthy();
+                    return result;
+                }
+            }
+        }
+        Err(last_error.expect("FailoverProvider always has at least one endpoint"))
+    }
+
+    /// Sends `requests` to each candidate endpoint as one batch, passing the requests that failed with an endpoint
+    /// failure on to the next endpoint. An endpoint is marked unhealthy if any request of its batch failed that way.
+    async fn call_batch(
+        &self,
+        mut requests: Vec<(usize, ProviderRequest)>,
+    ) -> Vec<(usize, ProviderResponse)> {
+        let mut responses = Vec::with_capacity(requests.len());
+        let mut failures = Vec::new();
+        for endpoint in self.candidates() {
+            if requests.is_empty() {
+                break;
+            }
+            let batch = requests
+                .iter()
+                .map(|(_, request)| request.clone())
+                .collect();
+            let batch_responses = endpoint.provider.batch(batch).await;
+            let mut failed = Vec::new();
+            failures.clear();
+            for ((index, request), response) in requests.into_iter().zip(batch_responses) {
+                if is_endpoint_failure_response(&response) {
+                    failed.push((index, request));
+                    failures.push((index, response));
+                } else {
+                    responses.push((index, response));
+                }
+            }
+            if failures.is_empty() {
+                endpoint.mark_healthy();
+            } else {
+                endpoint.mark_unhealthy(self.cooldown);
+            }
+            requests = failed;
+        }
+        responses.extend(failures);
+        responses
+    }
+}
+
+/// Returns true if the error means the endpoint itself is unavailable (transport failure, timeout,
+/// 5xx status, rate limiting or an internal server error), rather than the request being invalid.
+pub fn is_endpoint_failure<E>(err: &JsonRpcError<E>) -> bool {
+    match err {
+        JsonRpcError::TransportError(_) => true,
+        JsonRpcError::ServerError(JsonRpcServerError::InternalError { .. }) => true,
+        JsonRpcError::ServerError(JsonRpcServerError::ResponseStatusError(status_error)) => {
+            match status_error {
+                JsonRpcServerResponseStatusError::Unauthorized
+                | JsonRpcServerResponseStatusError::TooManyRequests => true,
+                JsonRpcServerResponseStatusError::Unexpected { status } => status.is_server_error(),
+            }
+        }
+        JsonRpcError::ServerError(_) => false,
+    }
+}
+
+fn is_endpoint_failure_response(response: &ProviderResponse) -> bool {
+    for_each_result!(response, result => result.as_ref().err().is_some_and(is_endpoint_failure))
+}
+
+fn is_unknown_transaction(err: &JsonRpcError<RpcTransactionError>) -> bool {
+    matches!(
+        err.handler_error(),
+        Some(RpcTransactionError::UnknownTransaction { .. })
+    )
+}
+
+#[async_trait]
+impl Provider for FailoverProvider {
+    /// Retrieves the current status of the NEAR blockchain from the first healthy provider.
+    async fn status(&self) -> Result<RpcStatusResponse, JsonRpcError<RpcStatusError>> {
+        self.call(|provider| provider.status()).await
+    }
+
+    /// Executes a query on the first healthy provider.
+    async fn query(
+        &self,
+        request: QueryRequest,
+        block_reference: BlockReference,
+    ) -> Result<RpcQueryResponse, JsonRpcError<RpcQueryError>> {
+        self.call(|provider| provider.query(request.clone(), block_reference.clone()))
+            .await
+    }
+
+    /// Sends a signed transaction and waits for its final execution outcome, checking the transaction status before failing over.
+    async fn send_transaction(
+        &self,
+        signed_transaction: SignedTransaction,
+    ) -> Result<FinalExecutionOutcomeView, JsonRpcError<RpcTransactionError>> {
+        self.broadcast(
+            &signed_transaction,
+            TxExecutionStatus::default(),
+            |provider| provider.send_transaction(signed_transaction.clone()),
+            |response| {
+                response
+                    .final_execution_outcome
+                    .map(|outcome| outcome.into_outcome())
+            },
+        )
+        .await
+    }
+
+    /// Sends a signed transaction asynchronously, checking the transaction status before failing over.
+    async fn send_transaction_async(
+        &self,
+        signed_transaction: SignedTransaction,
+    ) -> Result<CryptoHash, JsonRpcError<methods::broadcast_tx_async::RpcBroadcastTxAsyncError>>
+    {
+        let tx_hash = signed_transaction.get_hash();
+        self.broadcast(
+            &signed_transaction,
+            TxExecutionStatus::None,
+            |provider| provider.send_transaction_async(signed_transaction.clone()),
+            |_| Some(tx_hash),
+        )
+        .await
+    }
+
+    /// Sends a signed transaction with the given `wait_until`, checking the transaction status before failing over.
+    async fn send_tx(
+        &self,
+        signed_transaction: SignedTransaction,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionResponse, JsonRpcError<RpcTransactionError>> {
+        self.broadcast(
+            &signed_transaction,
+            wait_until.clone(),
+            |provider| provider.send_tx(signed_transaction.clone(), wait_until.clone()),
+            Some,
+        )
+        .await
+    }
+
+    /// Retrieves the status of a transaction from the first healthy provider.
+    async fn tx_status(
+        &self,
+        transaction_info: TransactionInfo,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionResponse, JsonRpcError<RpcTransactionError>> {
+        self.call(|provider| provider.tx_status(transaction_info.clone(), wait_until.clone()))
+            .await
+    }
+
+    /// Fetches details of a specific chunk from the first healthy provider.
+    async fn chunk(
+        &self,
+        chunk_reference: ChunkReference,
+    ) -> Result<ChunkView, JsonRpcError<RpcChunkError>> {
+        self.call(|provider| provider.chunk(chunk_reference.clone()))
+            .await
+    }
+
+    /// Retrieves a block from the first healthy provider.
+    async fn block(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<BlockView, JsonRpcError<RpcBlockError>> {
+        self.call(|provider| provider.block(block_reference.clone()))
+            .await
+    }
+
+    /// Fetches the protocol configuration from the first healthy provider.
+    async fn experimental_protocol_config(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<ProtocolConfigView, JsonRpcError<RpcProtocolConfigError>> {
+        self.call(|provider| provider.experimental_protocol_config(block_reference.clone()))
+            .await
+    }
+
+    /// Retrieves information about validators from the first healthy provider.
+    async fn validators(
+        &self,
+        epoch_reference: EpochReference,
+    ) -> Result<EpochValidatorInfo, JsonRpcError<RpcValidatorError>> {
+        self.call(|provider| provider.validators(clone_epoch_reference(&epoch_reference)))
+            .await
+    }
+
+    /// Fetches the gas price from the first healthy provider.
+    async fn gas_price(
+        &self,
+        block_id: Option<BlockId>,
+    ) -> Result<GasPriceView, JsonRpcError<RpcGasPriceError>> {
+        self.call(|provider| provider.gas_price(block_id.clone()))
+            .await
+    }
+
+    /// Checks the health of the first healthy provider.
+    async fn health(&self) -> Result<RpcHealthResponse, JsonRpcError<RpcStatusError>> {
+        self.call(|provider| provider.health()).await
+    }
+
+    /// Fetches the network information of the first healthy provider.
+    async fn network_info(
+        &self,
+    ) -> Result<RpcNetworkInfoResponse, JsonRpcError<RpcNetworkInfoError>> {
+        self.call(|provider| provider.network_info()).await
+    }
+
+    /// Fetches the genesis configuration from the first healthy provider.
+    async fn experimental_genesis_config(
+        &self,
+    ) -> Result<
+        GenesisConfig,
+        JsonRpcError<methods::EXPERIMENTAL_genesis_config::RpcGenesisConfigError>,
+    > {
+        self.call(|provider| provider.experimental_genesis_config())
+            .await
+    }
+
+    /// Fetches the client configuration of the first healthy provider.
+    async fn client_config(
+        &self,
+    ) -> Result<RpcClientConfigResponse, JsonRpcError<RpcClientConfigError>> {
+        self.call(|provider| provider.client_config()).await
+    }
+
+    /// Lists the state changes made in a block, using the first healthy provider.
+    async fn changes_in_block(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<RpcStateChangesInBlockByTypeResponse, JsonRpcError<RpcStateChangesError>> {
+        self.call(|provider| provider.changes_in_block(block_reference.clone()))
+            .await
+    }
+
+    /// Retrieves state changes from the first healthy provider.
+    async fn changes(
+        &self,
+        request: StateChangesRequestView,
+        block_reference: BlockReference,
+    ) -> Result<RpcStateChangesInBlockResponse, JsonRpcError<RpcStateChangesError>> {
+        self.call(|provider| {
+            provider.changes(
+                clone_state_changes_request(&request),
+                block_reference.clone(),
+            )
+        })
+        .await
+    }
+
+    /// Retrieves the status of a transaction and its receipts from the first healthy provider.
+    async fn tx_status_with_receipts(
+        &self,
+        transaction_info: TransactionInfo,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionWithReceiptsResponse, JsonRpcError<RpcTransactionError>> {
+        self.call(|provider| {
+            provider.tx_status_with_receipts(transaction_info.clone(), wait_until.clone())
+        })
+        .await
+    }
+
+    /// Retrieves a receipt from the first healthy provider.
+    async fn receipt(
+        &self,
+        receipt_id: CryptoHash,
+    ) -> Result<ReceiptView, JsonRpcError<RpcReceiptError>> {
+        self.call(|provider| provider.receipt(receipt_id)).await
+    }
+
+    /// Retrieves an execution proof from the first healthy provider.
+    async fn light_client_proof(
+        &self,
+        id: TransactionOrReceiptId,
+        light_client_head: CryptoHash,
+    ) -> Result<RpcLightClientExecutionProofResponse, JsonRpcError<RpcLightClientProofError>> {
+        self.call(|provider| provider.light_client_proof(id.clone(), light_client_head))
+            .await
+    }
+
+    /// Retrieves the next light client block from the first healthy provider.
+    async fn next_light_client_block(
+        &self,
+        last_block_hash: CryptoHash,
+    ) -> Result<Option<LightClientBlockView>, JsonRpcError<RpcLightClientNextBlockError>> {
+        self.call(|provider| provider.next_light_client_block(last_block_hash))
+            .await
+    }
+
+    /// Sends the read requests of the batch to the first healthy provider as one batch, failing the requests that
+    /// hit an endpoint failure over to the next provider as another batch. Transactions are sent one by one, checking
+    /// the transaction status before failing over.
+    async fn batch(&self, requests: Vec<ProviderRequest>) -> Vec<ProviderResponse> {
+        let count = requests.len();
+        let (transactions, reads): (Vec<_>, Vec<_>) = requests
+            .into_iter()
+            .enumerate()
+            .partition(|(_, request)| request.signed_transaction().is_some());
+        let (transaction_indices, transactions): (Vec<_>, Vec<_>) =
+            transactions.into_iter().unzip();
+        let (transaction_responses, read_responses) = futures::join!(
+            batch::dispatch_concurrently(self, transactions, DEFAULT_BATCH_CONCURRENCY),
+            self.call_batch(reads),
+        );
+        let transactions = transaction_indices.into_iter().zip(transaction_responses);
+        batch::in_request_order(count, transactions.chain(read_responses))
+    }
+
+    fn is_batched(&self, request: &ProviderRequest) -> bool {
+        request.signed_transaction().is_none()
+            && self
+                .endpoints
+                .iter()
+                .all(|endpoint| endpoint.provider.is_batched(request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{access_key_response, handler_error, internal_error, MockProvider};
+    use crate::ProviderMethod;
+    use near_crypto::{InMemorySigner, KeyType};
+
+    /// Returns a provider failing every query and transaction with an internal error, and a handle to inspect it.
+    fn down() -> (Box<dyn Provider + Send + Sync>, MockProvider) {
+        let provider = MockProvider::new();
+        provider.on_query(|_, _| Some(Err(internal_error("node is down"))));
+        provider.on_send_transaction_async(|_| Some(Err(internal_error("node is down"))));
+        provider.on_tx_status(|_, _| Some(Err(internal_error("node is down"))));
+        (Box::new(provider.clone()), provider)
+    }
+
+    /// Returns a provider answering queries and transactions, and a handle to inspect it. It reports the
+    /// transactions it is asked about as executed if `knows_transaction` is set, and as unknown otherwise.
+    fn up(knows_transaction: bool) -> (Box<dyn Provider + Send + Sync>, MockProvider) {
+        let provider = MockProvider::new();
+        provider.on_query(|_, _| Some(Ok(access_key_response(7))));
+        provider.on_send_transaction_async(|signed_transaction| {
+            Some(Ok(signed_transaction.get_hash()))
+        });
+        provider.on_tx_status(move |transaction_info, wait_until| {
+            Some(if knows_transaction {
+                Ok(RpcTransactionResponse {
+                    final_execution_outcome: None,
+                    final_execution_status: wait_until.clone(),
+                })
+            } else {
+                Err(handler_error(RpcTransactionError::UnknownTransaction {
+                    requested_transaction_hash: transaction_info.to_tx_hash_and_account().0,
+                }))
+            })
+        });
+        (Box::new(provider.clone()), provider)
+    }
+
+    fn signed_transaction() -> SignedTransaction {
+        let signer =
+            InMemorySigner::from_seed("alice.near".parse().unwrap(), KeyType::ED25519, "alice");
+        SignedTransaction::send_money(
+            1,
+            "alice.near".parse().unwrap(),
+            "bob.near".parse().unwrap(),
+            &signer,
+            1,
+            CryptoHash::default(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_fails_over_and_skips_unhealthy_provider() {
+        let (down_provider, down) = down();
+        let (up_provider, up) = up(false);
+        let provider = FailoverProvider::new(vec![down_provider, up_provider]);
+
+        for _ in 0..2 {
+            let request = QueryRequest::ViewAccessKeyList {
+                account_id: "alice.near".parse().unwrap(),
+            };
+            let response = provider
+                .query(request, BlockReference::latest())
+                .await
+                .unwrap();
+            assert_eq!(response.block_height, 1);
+        }
+
+        // The failing provider is in its cooldown after the first call.
+        assert_eq!(down.call_count(ProviderMethod::Query), 1);
+        assert_eq!(up.call_count(ProviderMethod::Query), 2);
+    }
+
+    #[tokio::test]
+    async fn test_does_not_rebroadcast_known_transaction() {
+        let (down_provider, _) = down();
+        let (up_provider, up) = up(true);
+        let provider = FailoverProvider::new(vec![down_provider, up_provider]);
+
+        let signed_transaction = signed_transaction();
+        let tx_hash = provider
+            .send_transaction_async(signed_transaction.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(tx_hash, signed_transaction.get_hash());
+        assert_eq!(up.call_count(ProviderMethod::TxStatus), 1);
+        assert_eq!(up.call_count(ProviderMethod::SendTransactionAsync), 0);
+    }
+
+    #[tokio::test]
+    async fn test_broadcasts_unknown_transaction_to_next_provider() {
+        let (down_provider, down) = down();
+        let (up_provider, up) = up(false);
+        let provider = FailoverProvider::new(vec![down_provider, up_provider]);
+
+        provider
+            .send_transaction_async(signed_transaction())
+            .await
+            .unwrap();
+
+        assert_eq!(down.call_count(ProviderMethod::SendTransactionAsync), 1);
+        assert_eq!(up.signed_transactions().len(), 1);
+    }
+}
@@ -0,0 +1,374 @@
+//! The `instrumentation` module provides middlewares that make the calls of a provider observable in production.
+//!
+//! The `TracingLayer` wraps every call in a `tracing` span named `rpc`, carrying the RPC method, the endpoint, the
+//! block reference and the transaction hash of the request, and records the latency and error kind of the call on
+//! it. Spans opened by the caller, such as the ones of `Account` operations, become the parents of these spans.
+//!
+//! The `MetricsLayer` reports a request counter, an error counter labeled with the error kind and a latency histogram
+//! to a `MetricsSink`, which adapts them to the metrics library of the application. `InMemoryMetrics` is a sink that
+//! keeps them in memory, e.g. for tests or to expose them through a custom endpoint, with the histograms reduced to
+//! a count, a sum and fixed buckets so that their memory does not grow with the number of calls.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use near_providers::instrumentation::{InMemoryMetrics, MetricsLayer, TracingLayer};
+//! use near_providers::ProviderBuilder;
+//! use std::sync::Arc;
+//!
+//! let endpoint = "https://rpc.testnet.near.org";
+//! let metrics = Arc::new(InMemoryMetrics::new());
+//! let provider = ProviderBuilder::new()
+//!     .with_layer(TracingLayer::new().with_endpoint(endpoint))
+//!     .with_layer(MetricsLayer::new(metrics.clone()))
+//!     .connect(endpoint);
+//! ```
+
+use crate::middleware::{Middleware, Next};
+use crate::{ProviderRequest, ProviderResponse};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::field::{self, Empty};
+use tracing::Instrument;
+
+/// Counter of the calls made through a `MetricsLayer`, labeled with `method`.
+pub const REQUESTS_TOTAL: &str = "near_rpc_requests_total";
+/// Counter of the failed calls, labeled with `method` and `kind`, see `ProviderResponse::error_kind`.
+pub const ERRORS_TOTAL: &str = "near_rpc_errors_total";
+/// Histogram of the latency of the calls in seconds, labeled with `method` and `outcome`, either `ok` or `error`.
+pub const REQUEST_DURATION_SECONDS: &str = "near_rpc_request_duration_seconds";
+
+/// Wraps every call in an `rpc` span with the method, endpoint, block reference and transaction hash of the request.
+///
+/// The `latency_ms` and `error_kind` fields are recorded once the call completes, and a failed call emits a `warn`
+/// event with the error inside the span.
+#[derive(Debug, Clone, Default)]
+pub struct TracingLayer {
+    endpoint: Option<String>,
+}
+
+impl TracingLayer {
+    /// Creates a layer whose spans do not carry an endpoint.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records `endpoint` on every span, e.g. the URL of the provider the layer is stacked on.
+    pub fn with_endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = Some(endpoint.to_string());
+        self
+    }
+}
+
+#[async_trait]
+impl Middleware for TracingLayer {
+    async fn handle(&self, request: ProviderRequest, next: Next<'_>) -> ProviderResponse {
+        let span = tracing::info_span!(
+            "rpc",
+            method = request.method().rpc_method_name(),
+            endpoint = self.endpoint.as_deref(),
+            block_reference = Empty,
+            tx_hash = Empty,
+            latency_ms = Empty,
+            error_kind = Empty,
+        );
+        if let Some(block_reference) = request.block_reference() {
+            span.record("block_reference", field::debug(&block_reference));
+        }
+        if let Some(tx_hash) = request.transaction_hash() {
+            span.record("tx_hash", field::display(&tx_hash));
+        }
+
+        let start = Instant::now();
+        let response = next.run(request).instrument(span.clone()).await;
+        span.record("latency_ms", start.elapsed().as_millis() as u64);
+        if let Some(error_kind) = response.error_kind() {
+            span.record("error_kind", error_kind);
+            let error = response.error_message().unwrap_or_default();
+            tracing::warn!(parent: &span, %error, "RPC call failed");
+        }
+        response
+    }
+}
+
+/// Receives the metrics of a `MetricsLayer`, e.g. to forward them to Prometheus or StatsD.
+pub trait MetricsSink: Send + Sync {
+    /// Increments the counter `name` with the given labels by one.
+    fn increment_counter(&self, name: &'static str, labels: &[(&'static str, &str)]);
+
+    /// Records `value` in the histogram `name` with the given labels.
+    fn record_histogram(&self, name: &'static str, value: f64, labels: &[(&'static str, &str)]);
+}
+
+/// Reports `REQUESTS_TOTAL`, `ERRORS_TOTAL` and `REQUEST_DURATION_SECONDS` for every call to a `MetricsSink`.
+#[derive(Clone)]
+pub struct MetricsLayer {
+    sink: Arc<dyn MetricsSink>,
+}
+
+impl MetricsLayer {
+    /// Creates a layer reporting to `sink`.
+    pub fn new(sink: Arc<dyn MetricsSink>) -> Self {
+        Self { sink }
+    }
+}
+
+#[async_trait]
+impl Middleware for MetricsLayer {
+    async fn handle(&self, request: ProviderRequest, next: Next<'_>) -> ProviderResponse {
+        let method = request.method().rpc_method_name();
+        self.sink
+            .increment_counter(REQUESTS_TOTAL, &[("method", method)]);
+
+        let start = Instant::now();
+        let response = next.run(request).await;
+        let latency = start.elapsed().as_secs_f64();
+        let outcome = match response.error_kind() {
+            Some(kind) => {
+                self.sink
+                    .increment_counter(ERRORS_TOTAL, &[("method", method), ("kind", kind)]);
+                "error"
+            }
+            None => "ok",
+        };
+        self.sink.record_histogram(
+            REQUEST_DURATION_SECONDS,
+            latency,
+            &[("method", method), ("outcome", outcome)],
+        );
+        response
+    }
+}
+
+/// The name and sorted labels of a metric.
+type MetricKey = (&'static str, Vec<(&'static str, String)>);
+
+fn metric_key(name: &'static str, labels: &[(&'static str, &str)]) -> MetricKey {
+    let mut labels: Vec<(&'static str, String)> = labels
+        .iter()
+        .map(|(label, value)| (*label, value.to_string()))
+        .collect();
+    labels.sort();
+    (name, labels)
+}
+
+/// The upper bounds in seconds of the buckets of the histograms kept by `InMemoryMetrics`.
+pub const HISTOGRAM_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// A histogram kept by `InMemoryMetrics`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Histogram {
+    /// The number of recorded values.
+    pub count: u64,
+    /// The sum of the recorded values.
+    pub sum: f64,
+    /// The number of recorded values lower than or equal to each bound of `HISTOGRAM_BUCKETS`, so cumulative.
+    pub buckets: [u64; HISTOGRAM_BUCKETS.len()],
+}
+
+impl Histogram {
+    fn record(&mut self, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        for (bucket, bound) in self.buckets.iter_mut().zip(HISTOGRAM_BUCKETS) {
+            if value <= bound {
+                *bucket += 1;
+            }
+        }
+    }
+}
+
+/// A `MetricsSink` keeping the counters and histograms in memory.
+#[derive(Debug, Default)]
+pub struct InMemoryMetrics {
+    counters: Mutex<HashMap<MetricKey, u64>>,
+    histograms: Mutex<HashMap<MetricKey, Histogram>>,
+}
+
+impl InMemoryMetrics {
+    /// Creates a sink without any metrics.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the value of the counter `name` with exactly the given labels, in any order.
+    pub fn counter(&self, name: &'static str, labels: &[(&'static str, &str)]) -> u64 {
+        let counters = self.counters.lock().unwrap();
+        counters
+            .get(&metric_key(name, labels))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the histogram `name` with exactly the given labels, in any order.
+    pub fn histogram(&self, name: &'static str, labels: &[(&'static str, &str)]) -> Histogram {
+        let histograms = self.histograms.lock().unwrap();
+        histograms
+            .get(&metric_key(name, labels))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+impl MetricsSink for InMemoryMetrics {
+    fn increment_counter(&self, name: &'static str, labels: &[(&'static str, &str)]) {
+        let mut counters = self.counters.lock().unwrap();
+        *counters.entry(metric_key(name, labels)).or_default() += 1;
+    }
+
+    fn record_histogram(&self, name: &'static str, value: f64, labels: &[(&'static str, &str)]) {
+        let mut histograms = self.histograms.lock().unwrap();
+        histograms
+            .entry(metric_key(name, labels))
+            .or_default()
+            .record(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{block_view, internal_error, MockProvider};
+    use crate::{Provider, ProviderBuilder};
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::types::{BlockId, BlockReference, Finality};
+    use tracing::span;
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::registry::LookupSpan;
+
+    /// The fields of a closed span.
+    type SpanFields = HashMap<String, String>;
+
+    #[derive(Default)]
+    struct FieldVisitor(SpanFields);
+
+    impl field::Visit for FieldVisitor {
+        fn record_debug(&mut self, field: &field::Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+
+        fn record_str(&mut self, field: &field::Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+    }
+
+    /// Collects the fields of the spans it sees, in the order they are closed.
+    #[derive(Clone, Default)]
+    struct SpanCollector(Arc<Mutex<Vec<SpanFields>>>);
+
+    impl<S: tracing::Subscriber + for<'a> LookupSpan<'a>> tracing_subscriber::Layer<S>
+        for SpanCollector
+    {
+        fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+            let mut visitor = FieldVisitor::default();
+            attrs.record(&mut visitor);
+            ctx.span(id).unwrap().extensions_mut().insert(visitor);
+        }
+
+        fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+            let span = ctx.span(id).unwrap();
+            let mut extensions = span.extensions_mut();
+            values.record(extensions.get_mut::<FieldVisitor>().unwrap());
+        }
+
+        fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+            let span = ctx.span(&id).unwrap();
+            let fields = span.extensions().get::<FieldVisitor>().unwrap().0.clone();
+            self.0.lock().unwrap().push(fields);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tracing_layer() {
+        let collector = SpanCollector::default();
+        let _guard = tracing::subscriber::set_default(
+            tracing_subscriber::registry().with(collector.clone()),
+        );
+        let provider = ProviderBuilder::new()
+            .with_layer(TracingLayer::new().with_endpoint("http://127.0.0.1:3030"))
+            .build(MockProvider::new());
+        provider
+            .inner()
+            .push_block(Ok(block_view(10, CryptoHash::default())));
+        provider
+            .inner()
+            .push_block(Err(internal_error("node is down")));
+
+        provider
+            .block(BlockReference::BlockId(BlockId::Height(10)))
+            .await
+            .unwrap();
+        provider
+            .block(BlockReference::Finality(Finality::Final))
+            .await
+            .unwrap_err();
+
+        let spans = collector.0.lock().unwrap();
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0]["method"], "block");
+        assert_eq!(spans[0]["endpoint"], "http://127.0.0.1:3030");
+        assert_eq!(spans[0]["block_reference"], "BlockId(Height(10))");
+        assert!(spans[0].contains_key("latency_ms"));
+        assert!(!spans[0].contains_key("error_kind"));
+        assert_eq!(spans[1]["block_reference"], "Finality(Final)");
+        assert_eq!(spans[1]["error_kind"], "internal");
+    }
+
+    #[tokio::test]
+    async fn test_metrics_layer() {
+        let metrics = Arc::new(InMemoryMetrics::new());
+        let provider = ProviderBuilder::new()
+            .with_layer(MetricsLayer::new(metrics.clone()))
+            .build(MockProvider::new());
+        provider
+            .inner()
+            .push_block(Ok(block_view(10, CryptoHash::default())));
+        provider
+            .inner()
+            .push_block(Err(internal_error("node is down")));
+
+        for _ in 0..2 {
+            let _ = provider
+                .block(BlockReference::Finality(Finality::Final))
+                .await;
+        }
+
+        assert_eq!(metrics.counter(REQUESTS_TOTAL, &[("method", "block")]), 2);
+        assert_eq!(
+            metrics.counter(ERRORS_TOTAL, &[("kind", "internal"), ("method", "block")]),
+            1
+        );
+        let labels = [("method", "block"), ("outcome", "ok")];
+        assert_eq!(
+            metrics.histogram(REQUEST_DURATION_SECONDS, &labels).count,
+            1
+        );
+        let labels = [("method", "block"), ("outcome", "error")];
+        assert_eq!(
+            metrics.histogram(REQUEST_DURATION_SECONDS, &labels).count,
+            1
+        );
+    }
+
+    #[test]
+    fn test_in_memory_histogram() {
+        let metrics = InMemoryMetrics::new();
+        for value in [0.003, 0.2, 0.2, 30.0] {
+            metrics.record_histogram(REQUEST_DURATION_SECONDS, value, &[("method", "block")]);
+        }
+
+        let histogram = metrics.histogram(REQUEST_DURATION_SECONDS, &[("method", "block")]);
+        assert_eq!(histogram.count, 4);
+        assert!((histogram.sum - 30.403).abs() < 1e-9);
+        assert_eq!(histogram.buckets, [1, 1, 1, 1, 1, 3, 3, 3, 3, 3, 3]);
+        assert_eq!(
+            metrics.histogram(REQUEST_DURATION_SECONDS, &[("method", "query")]),
+            Histogram::default()
+        );
+    }
+}
@@ -1,46 +1,356 @@
 //! The `JsonRpcProvider` module offers a concrete implementation of the `Provider` trait, utilizing JSON RPC to communicate with the NEAR blockchain.
 //! This provider enables applications to query blockchain status, submit transactions, and fetch various blockchain data in an asynchronous manner.
 
+use crate::batch::{self, BatchCall, DEFAULT_BATCH_CONCURRENCY, DEFAULT_BATCH_SIZE};
+use crate::client_config::{RpcClientConfigError, RpcClientConfigResponse};
 use crate::jsonrpc_client::{
     errors::JsonRpcError,
     methods::{self, status::RpcStatusResponse},
     JsonRpcClient,
 };
+use crate::jsonrpc_primitives::errors::RpcError;
+use crate::jsonrpc_primitives::message::{self, Message};
+use crate::network::{Network, NetworkError};
+use crate::tx_status::{self, RpcTransactionWithReceiptsResponse};
 use crate::types::{
     blocks::RpcBlockError,
+    changes::{
+        RpcStateChangesError, RpcStateChangesInBlockByTypeResponse, RpcStateChangesInBlockResponse,
+    },
     chunks::{ChunkReference, RpcChunkError},
     config::RpcProtocolConfigError,
+    gas_price::{RpcGasPriceError, RpcGasPriceRequest},
+    light_client::{
+        RpcLightClientExecutionProofResponse, RpcLightClientNextBlockError,
+        RpcLightClientProofError,
+    },
+    network_info::{RpcNetworkInfoError, RpcNetworkInfoResponse},
     query::{RpcQueryError, RpcQueryRequest, RpcQueryResponse},
-    status::RpcStatusError,
+    receipts::{ReceiptReference, RpcReceiptError},
+    status::{RpcHealthResponse, RpcStatusError},
     transactions::{RpcTransactionError, TransactionInfo},
     validator::RpcValidatorError,
 };
-use crate::Provider;
+use crate::{Provider, ProviderRequest, ProviderResponse};
 use async_trait::async_trait;
-use near_chain_configs::ProtocolConfigView;
+use futures::stream::{self, StreamExt};
+use near_chain_configs::{GenesisConfig, ProtocolConfigView};
+use near_jsonrpc_client::header::{HeaderName, HeaderValue};
 use near_jsonrpc_client::methods::tx::RpcTransactionResponse;
 use near_primitives::{
     hash::CryptoHash,
     transaction::SignedTransaction,
-    types::{BlockReference, EpochReference, Finality},
+    types::{BlockId, BlockReference, EpochReference, TransactionOrReceiptId},
     views::{
-        BlockView, ChunkView, EpochValidatorInfo, FinalExecutionOutcomeView, QueryRequest,
+        BlockView, ChunkView, EpochValidatorInfo, FinalExecutionOutcomeView, GasPriceView,
+        LightClientBlockView, QueryRequest, ReceiptView, StateChangesRequestView,
         TxExecutionStatus,
     },
 };
+use reqwest::header::CONTENT_TYPE;
+use reqwest::StatusCode;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 /// Represents a provider that uses JSON RPC to interact with the NEAR blockchain.
 pub struct JsonRpcProvider {
     client: JsonRpcClient,
+    /// The HTTP client of `client`, to send JSON RPC batch payloads, which `JsonRpcClient` has no support for.
+    http_client: reqwest::Client,
+    batch_size: usize,
+    batch_concurrency: usize,
+    /// Set once the endpoint rejected a batch payload, after which batches are sent as single requests.
+    batches_unsupported: AtomicBool,
 }
 
 impl JsonRpcProvider {
     /// Constructs a new `JsonRpcProvider` with the specified RPC endpoint.
+    pub fn new(rpc_endpoint: &str) -> Self {
+        Self::builder(rpc_endpoint)
+            .build()
+            .expect("a provider without configuration always builds")
+    }
+
+    /// Constructs a new `JsonRpcProvider` connected to the default RPC endpoint of `network`.
+    pub fn for_network(network: &Network) -> Self {
+        Self::new(network.rpc_url())
+    }
+
+    /// Constructs a new `JsonRpcProvider` connected to the default RPC endpoint of `network`, after checking that
+    /// the node reports the chain id of the network.
+    pub async fn for_network_verified(network: &Network) -> Result<Self, NetworkError> {
+        let provider = Self::for_network(network);
+        network.verify(&provider).await?;
+        Ok(provider)
+    }
+
+    /// Returns a builder for a `JsonRpcProvider` connecting to the specified RPC endpoint, to configure the
+    /// headers, authentication, timeouts and HTTP client it uses.
+    pub fn builder(rpc_endpoint: &str) -> JsonRpcProviderBuilder {
+        JsonRpcProviderBuilder::new(rpc_endpoint)
+    }
+
+    /// Sends `calls` in one JSON RPC batch payload, returning the responses by index. If the endpoint does not
+    /// answer the batch, the requests are handed back to be sent on their own.
+    async fn send_batch(
+        &self,
+        calls: Vec<(usize, ProviderRequest, BatchCall)>,
+    ) -> Result<Vec<(usize, ProviderResponse)>, Vec<(usize, ProviderRequest)>> {
+        let messages: Vec<&Message> = calls.iter().map(|(_, _, call)| call.message()).collect();
+        let Some(mut results) = self.post_batch(&messages).await else {
+            return Err(calls
+                .into_iter()
+                .map(|(index, request, _)| (index, request))
+                .collect());
+        };
+        Ok(calls
+            .into_iter()
+            .map(|(index, request, call)| {
+                let result = call
+                    .message()
+                    .id()
+                    .as_str()
+                    .and_then(|id| results.remove(id));
+                let response = match result {
+                    Some(result) => call.parse(result),
+                    None => ProviderResponse::internal_error(
+                        request.method(),
+                        "The batch response has no response to this request",
+                    ),
+                };
+                (index, response)
+            })
+            .collect())
+    }
+
+    /// Returns true if the read requests of a batch are sent in batch payloads.
+    fn sends_batches(&self) -> bool {
+        self.batch_size >= 2 && !self.batches_unsupported.load(Ordering::Relaxed)
+    }
+
+    /// Posts a JSON RPC batch payload, returning the results in the response by request id, or `None` if the
+    /// endpoint did not answer with a batch.
+    ///
+    /// An endpoint answering with a single message, or with a client error other than an authentication failure or
+    /// a rate limit, does not support batches and is not sent any more of them. Other failures, such as a timeout,
+    /// are left to the single requests to report.
+    async fn post_batch(
+        &self,
+        messages: &[&Message],
+    ) -> Option<HashMap<String, Result<serde_json::Value, RpcError>>> {
+        let payload = serde_json::to_vec(messages).ok()?;
+        let response = self
+            .http_client
+            .post(self.client.server_addr())
+            .headers(self.client.headers().clone())
+            .body(payload)
+            .send()
+            .await
+            .ok()?;
+        match response.status() {
+            StatusCode::OK => {}
+            StatusCode::UNAUTHORIZED | StatusCode::TOO_MANY_REQUESTS => return None,
+            status if status.is_client_error() => {
+                self.batches_unsupported.store(true, Ordering::Relaxed);
+                return None;
+            }
+            _ => return None,
+        }
+        let payload = response.bytes().await.ok()?;
+        match message::decoded_to_parsed(serde_json::from_slice(&payload)) {
+            Ok(Message::Batch(messages)) => Some(
+                messages
+                    .into_iter()
+                    .filter_map(|message| match message {
+                        Message::Response(response) => {
+                            Some((response.id.as_str()?.to_string(), response.result))
+                        }
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+            _ => {
+                self.batches_unsupported.store(true, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+}
+
+/// The reasons a `JsonRpcProviderBuilder` fails to build a `JsonRpcProvider`.
+#[derive(Debug, thiserror::Error)]
+pub enum JsonRpcProviderBuildError {
+    #[error("Invalid header name {0:?}")]
+    InvalidHeaderName(String),
+    #[error("Invalid value for header {0:?}")]
+    InvalidHeaderValue(String),
+    #[error("Timeouts and proxies can't be set on a provided HTTP client, configure the client itself instead")]
+    ConflictingHttpClient,
+    #[error("Failed to build the HTTP client: {0}")]
+    HttpClient(#[from] reqwest::Error),
+}
+
+/// Builds a `JsonRpcProvider` with custom headers, authentication, timeouts, a proxy or a preconfigured HTTP
+/// client.
+///
+/// Headers are sent with every request. Timeouts and the proxy configure the HTTP client built by the builder,
+/// so they can't be combined with `with_http_client`: a client shared between providers for connection pooling
+/// is configured before it is handed over.
+///
+/// # Example
+///
+/// ```no_run
+/// use near_providers::JsonRpcProvider;
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), near_providers::JsonRpcProviderBuildError> {
+/// let provider = JsonRpcProvider::builder("https://rpc.mainnet.near.org")
+///     .with_api_key("my-api-key")
+///     .with_user_agent("my-app/1.0")
+///     .with_connect_timeout(Duration::from_secs(5))
+///     .with_timeout(Duration::from_secs(30))
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct JsonRpcProviderBuilder {
+    rpc_endpoint: String,
+    headers: Vec<(String, String, bool)>,
+    connect_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    proxy: Option<reqwest::Proxy>,
+    http_client: Option<reqwest::Client>,
+    batch_size: usize,
+    batch_concurrency: usize,
+}
+
+impl JsonRpcProviderBuilder {
+    /// Creates a builder for a provider connecting to the specified RPC endpoint, without any configuration.
     pub fn new(rpc_endpoint: &str) -> Self {
         Self {
-            client: JsonRpcClient::connect(rpc_endpoint),
+            rpc_endpoint: rpc_endpoint.to_string(),
+            headers: Vec::new(),
+            connect_timeout: None,
+            timeout: None,
+            proxy: None,
+            http_client: None,
+            batch_size: DEFAULT_BATCH_SIZE,
+            batch_concurrency: DEFAULT_BATCH_CONCURRENCY,
         }
     }
+
+    /// Sends the header `name` with the given value with every request. Replaces any previous value of the header.
+    pub fn with_header(self, name: &str, value: &str) -> Self {
+        self.header(name, value, false)
+    }
+
+    /// Authenticates with an API key, sent in the `x-api-key` header.
+    pub fn with_api_key(self, api_key: &str) -> Self {
+        self.header("x-api-key", api_key, true)
+    }
+
+    /// Authenticates with a bearer token, sent in the `authorization` header.
+    pub fn with_bearer_token(self, token: &str) -> Self {
+        self.header("authorization", &format!("Bearer {}", token), true)
+    }
+
+    /// Sends the given `user-agent` header with every request.
+    pub fn with_user_agent(self, user_agent: &str) -> Self {
+        self.header("user-agent", user_agent, false)
+    }
+
+    /// Sets the timeout for establishing a connection to the endpoint.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the timeout of a whole request, from connecting until the response body has been read.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sends every request through the given proxy.
+    pub fn with_proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Sends the requests with the given HTTP client instead of a new one, e.g. to share its connection pool
+    /// between providers.
+    pub fn with_http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.http_client = Some(http_client);
+        self
+    }
+
+    /// Sets the maximum number of requests `Provider::batch` sends in one JSON RPC batch payload, 100 by default.
+    /// A size of 1 sends every request of a batch on its own.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Sets how many batch payloads or single requests `Provider::batch` has in flight at once, 8 by default.
+    pub fn with_batch_concurrency(mut self, batch_concurrency: usize) -> Self {
+        self.batch_concurrency = batch_concurrency.max(1);
+        self
+    }
+
+    /// Builds the provider, validating the headers and building the HTTP client.
+    pub fn build(self) -> Result<JsonRpcProvider, JsonRpcProviderBuildError> {
+        let http_client = match self.http_client {
+            Some(_)
+                if self.connect_timeout.is_some()
+                    || self.timeout.is_some()
+                    || self.proxy.is_some() =>
+            {
+                return Err(JsonRpcProviderBuildError::ConflictingHttpClient);
+            }
+            Some(http_client) => http_client,
+            None => {
+                let mut builder = reqwest::Client::builder();
+                if let Some(timeout) = self.connect_timeout {
+                    builder = builder.connect_timeout(timeout);
+                }
+                if let Some(timeout) = self.timeout {
+                    builder = builder.timeout(timeout);
+                }
+                if let Some(proxy) = self.proxy {
+                    builder = builder.proxy(proxy);
+                }
+                builder.build()?
+            }
+        };
+
+        let mut client = JsonRpcClient::with(http_client.clone()).connect(&self.rpc_endpoint);
+        let headers = client.headers_mut();
+        // A client built by `JsonRpcClient::new_client` sends the content type by default, a provided one may not.
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        for (name, value, sensitive) in self.headers {
+            let header_name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| JsonRpcProviderBuildError::InvalidHeaderName(name.clone()))?;
+            let mut header_value = HeaderValue::from_str(&value)
+                .map_err(|_| JsonRpcProviderBuildError::InvalidHeaderValue(name))?;
+            header_value.set_sensitive(sensitive);
+            headers.insert(header_name, header_value);
+        }
+        Ok(JsonRpcProvider {
+            client,
+            http_client,
+            batch_size: self.batch_size,
+            batch_concurrency: self.batch_concurrency,
+            batches_unsupported: AtomicBool::new(false),
+        })
+    }
+
+    fn header(mut self, name: &str, value: &str, sensitive: bool) -> Self {
+        self.headers
+            .push((name.to_string(), value.to_string(), sensitive));
+        self
+    }
 }
 
 #[async_trait]
@@ -51,13 +361,14 @@ impl Provider for JsonRpcProvider {
         self.client.call(request).await
     }
 
-    /// Executes a query on the NEAR blockchain using a given `QueryRequest`.
+    /// Executes a query on the NEAR blockchain using a given `QueryRequest`, at the block specified by `BlockReference`.
     async fn query(
         &self,
         request: QueryRequest,
+        block_reference: BlockReference,
     ) -> Result<RpcQueryResponse, JsonRpcError<RpcQueryError>> {
         let query_request = RpcQueryRequest {
-            block_reference: BlockReference::Finality(Finality::Final),
+            block_reference,
             request,
         };
         self.client.call(query_request).await
@@ -150,8 +461,186 @@ impl Provider for JsonRpcProvider {
 
         self.client.call(request).await
     }
+
+    /// Fetches the gas price of the block identified by `BlockId`, or of the latest block if `None`.
+    async fn gas_price(
+        &self,
+        block_id: Option<BlockId>,
+    ) -> Result<GasPriceView, JsonRpcError<RpcGasPriceError>> {
+        let request = RpcGasPriceRequest { block_id };
+        self.client.call(request).await
+    }
+
+    /// Checks the health of the node.
+    async fn health(&self) -> Result<RpcHealthResponse, JsonRpcError<RpcStatusError>> {
+        let request = methods::health::RpcHealthRequest;
+        self.client.call(request).await
+    }
+
+    /// Fetches the network information of the node, including its active peers and known producers.
+    async fn network_info(
+        &self,
+    ) -> Result<RpcNetworkInfoResponse, JsonRpcError<RpcNetworkInfoError>> {
+        let request = methods::network_info::RpcNetworkInfoRequest;
+        self.client.call(request).await
+    }
+
+    /// Fetches the genesis configuration of the network.
+    async fn experimental_genesis_config(
+        &self,
+    ) -> Result<
+        GenesisConfig,
+        JsonRpcError<methods::EXPERIMENTAL_genesis_config::RpcGenesisConfigError>,
+    > {
+        let request = methods::EXPERIMENTAL_genesis_config::RpcGenesisConfigRequest;
+        self.client.call(request).await
+    }
+
+    /// Fetches the client configuration of the node.
+    async fn client_config(
+        &self,
+    ) -> Result<RpcClientConfigResponse, JsonRpcError<RpcClientConfigError>> {
+        // `near-jsonrpc-client` has no request type for `client_config`, so it is sent as a raw request.
+        let request = methods::any::<Result<RpcClientConfigResponse, RpcClientConfigError>>(
+            "client_config",
+            serde_json::Value::Null,
+        );
+        self.client.call(request).await
+    }
+
+    /// Lists the state changes made in the block specified by `BlockReference`, by kind and account.
+    async fn changes_in_block(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<RpcStateChangesInBlockByTypeResponse, JsonRpcError<RpcStateChangesError>> {
+        let request = methods::EXPERIMENTAL_changes_in_block::RpcStateChangesInBlockRequest {
+            block_reference,
+        };
+        self.client.call(request).await
+    }
+
+    /// Retrieves the state changes selected by `StateChangesRequestView` in the block specified by `BlockReference`.
+    async fn changes(
+        &self,
+        request: StateChangesRequestView,
+        block_reference: BlockReference,
+    ) -> Result<RpcStateChangesInBlockResponse, JsonRpcError<RpcStateChangesError>> {
+        let request = methods::EXPERIMENTAL_changes::RpcStateChangesInBlockByTypeRequest {
+            block_reference,
+            state_changes_request: request,
+        };
+        self.client.call(request).await
+    }
+
+    /// Retrieves the status of a transaction identified by `TransactionInfo`, including the receipts it created.
+    async fn tx_status_with_receipts(
+        &self,
+        transaction_info: TransactionInfo,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionWithReceiptsResponse, JsonRpcError<RpcTransactionError>> {
+        // `near-jsonrpc-client` drops the receipts of this response, see the `tx_status` module.
+        let request = tx_status::request(transaction_info, wait_until)?;
+        self.client.call(request).await
+    }
+
+    /// Retrieves the receipt with the given id.
+    async fn receipt(
+        &self,
+        receipt_id: CryptoHash,
+    ) -> Result<ReceiptView, JsonRpcError<RpcReceiptError>> {
+        let request = methods::EXPERIMENTAL_receipt::RpcReceiptRequest {
+            receipt_reference: ReceiptReference { receipt_id },
+        };
+        self.client.call(request).await
+    }
+
+    /// Retrieves the proof that a transaction or receipt was executed, relative to the light client head.
+    async fn light_client_proof(
+        &self,
+        id: TransactionOrReceiptId,
+        light_client_head: CryptoHash,
+    ) -> Result<RpcLightClientExecutionProofResponse, JsonRpcError<RpcLightClientProofError>> {
+        let request = methods::light_client_proof::RpcLightClientExecutionProofRequest {
+            id,
+            light_client_head,
+        };
+        self.client.call(request).await
+    }
+
+    /// Retrieves the next light client block after the block with the given hash.
+    async fn next_light_client_block(
+        &self,
+        last_block_hash: CryptoHash,
+    ) -> Result<Option<LightClientBlockView>, JsonRpcError<RpcLightClientNextBlockError>> {
+        let request =
+            methods::next_light_client_block::RpcLightClientNextBlockRequest { last_block_hash };
+        self.client.call(request).await
+    }
+
+    /// Sends the read requests among `requests` in JSON RPC batch payloads of up to `batch_size` requests, and the
+    /// other requests on their own. Once the endpoint rejected a batch payload, every request is sent on its own.
+    async fn batch(&self, requests: Vec<ProviderRequest>) -> Vec<ProviderResponse> {
+        if !self.sends_batches() {
+            return batch::dispatch_concurrently(self, requests, self.batch_concurrency).await;
+        }
+
+        let mut responses: Vec<Option<ProviderResponse>> = Vec::with_capacity(requests.len());
+        let mut calls = Vec::new();
+        let mut singles = Vec::new();
+        for (index, request) in requests.into_iter().enumerate() {
+            responses.push(None);
+            match BatchCall::new(&request) {
+                Some(Ok(call)) => calls.push((index, request, call)),
+                Some(Err(response)) => responses[index] = Some(response),
+                None => singles.push((index, request)),
+            }
+        }
+
+        let mut payloads = Vec::new();
+        while calls.len() > self.batch_size {
+            let rest = calls.split_off(self.batch_size);
+            payloads.push(std::mem::replace(&mut calls, rest));
+        }
+        if !calls.is_empty() {
+            payloads.push(calls);
+        }
+        let results: Vec<_> = stream::iter(payloads)
+            .map(|calls| self.send_batch(calls))
+            .buffered(self.batch_concurrency)
+            .collect()
+            .await;
+        for result in results {
+            match result {
+                Ok(batch_responses) => {
+                    for (index, response) in batch_responses {
+                        responses[index] = Some(response);
+                    }
+                }
+                Err(requests) => singles.extend(requests),
+            }
+        }
+
+        let (indices, requests): (Vec<_>, Vec<_>) = singles.into_iter().unzip();
+        let single_responses =
+            batch::dispatch_concurrently(self, requests, self.batch_concurrency).await;
+        for (index, response) in indices.into_iter().zip(single_responses) {
+            responses[index] = Some(response);
+        }
+        responses
+            .into_iter()
+            .map(|response| response.expect("every request of the batch has a response"))
+            .collect()
+    }
+
+    /// Returns true for the read requests, as long as the endpoint accepts batch payloads.
+    fn is_batched(&self, request: &ProviderRequest) -> bool {
+        self.sends_batches() && matches!(BatchCall::new(request), Some(Ok(_)))
+    }
 }
 
+#[cfg(test)]
+use near_primitives::types::Finality;
+
 #[cfg(test)]
 #[tokio::test]
 async fn test_status() {
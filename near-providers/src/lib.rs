@@ -4,17 +4,82 @@
 //! and specific implementations of this interface, such as the `JsonRpcProvider`.
 //!
 //! The `Provider` trait offers methods for querying blockchain status, sending transactions,
-//! and retrieving information about transactions, blocks, chunks, and validators.
+//! and retrieving information about transactions, blocks, chunks, and validators. The state changes
+//! made in a block are fetched with requests built by the `changes` module.
+//! The `light_client` module verifies light client blocks and execution proofs instead of trusting
+//! the node that serves them.
+//! The `state_proof` module verifies the contract state returned by `view_state` queries
+//! against the state root of the shard of the contract.
 //! The `JsonRpcProvider` is an implementation of the `Provider` trait that uses JSON RPC
-//! to communicate with NEAR blockchain nodes.
+//! to communicate with NEAR blockchain nodes. `JsonRpcProvider::builder` configures the headers,
+//! authentication, timeouts, proxy or HTTP client of the provider.
+//! `Provider::batch` makes many calls at once, which the `JsonRpcProvider` sends as JSON RPC batch
+//! payloads when the endpoint supports them, see the `batch` module.
+//! The `block_stream` module turns a provider into a `BlockStream` of every final block in height
+//! order, optionally with their chunks, which resumes from a persisted checkpoint. The
+//! `account_watcher` module scans these chunks for the transfers, function calls and access key
+//! changes of a set of accounts, and the `payment` module confirms that an account received an
+//! amount of NEAR from another one, following each transaction up to final execution. The
+//! `backfill` module fetches a range of historical blocks with bounded concurrency, in height order
+//! and with retries, resuming from a checkpoint as well. The `lake` module reads the same blocks from NEAR Lake
+//! files in a local directory or an S3-compatible bucket into a `LakeStream`, which yields the items of a
+//! `BlockStream`, so processors can switch between a node and a lake without changes.
+//! The `network` module names the mainnet, testnet and localnet networks with their chain ids and
+//! default RPC endpoints, and checks that a provider is connected to the expected network.
+//!
+//! Providers can be composed: the `FailoverProvider` wraps an ordered list of providers and moves
+//! to the next one when an endpoint is unavailable, and the `RetryProvider` retries transient
+//! failures of another provider with exponential backoff. The `RateLimitProvider` throttles the
+//! calls made through a provider that is shared between tasks, and the `CachingProvider` memoizes
+//! immutable chain data such as blocks by hash and protocol configurations per epoch. The
+//! `ArchivalProvider` sends requests for historical data to an archival node when a regular node has
+//! garbage collected it, or when the requested block is below a height threshold.
+//!
+//! Concerns that apply to every method, such as logging or metrics, are written once as a `Middleware`
+//! that sees each call as a `ProviderRequest` and its result as a `ProviderResponse`. The
+//! `ProviderBuilder` stacks middlewares on top of a `JsonRpcProvider`, or any other provider, into a
+//! `LayeredProvider`. The `instrumentation` module provides a `TracingLayer`, opening a `tracing` span
+//! per call, and a `MetricsLayer`, reporting request counts, error kinds and latencies to a pluggable
+//! `MetricsSink`.
+//!
+//! The `RecordingProvider` saves the requests made through a provider and their responses to a
+//! cassette file, which the `ReplayProvider` serves back later without a network connection.
+//!
+//! With the `testing` feature enabled, the `testing` module provides a `MockProvider` whose
+//! responses are scripted per method and which records every call, for unit tests that should not
+//! depend on a network connection.
 //!
 //! This crate is designed to be easily extendable with more providers and to offer a
 //! straightforward way to integrate NEAR blockchain functionalities into Rust applications.
 
+/// Re-export the AccountWatcher
+pub use crate::account_watcher::AccountWatcher;
+/// Re-export the ArchivalProvider
+pub use crate::archival_provider::ArchivalProvider;
+/// Re-export the BlockStream
+pub use crate::block_stream::BlockStream;
+/// Re-export the CachingProvider
+pub use crate::caching_provider::{CacheLayer, CachingProvider};
+/// Re-export the cassette providers
+pub use crate::cassette::{RecordingProvider, ReplayProvider};
+/// Re-export the FailoverProvider
+pub use crate::failover_provider::FailoverProvider;
 /// Re-export the JsonRpcProvider
-pub use crate::json_rpc_provider::JsonRpcProvider;
+pub use crate::json_rpc_provider::{
+    JsonRpcProvider, JsonRpcProviderBuildError, JsonRpcProviderBuilder,
+};
+/// Re-export the LakeStream
+pub use crate::lake::LakeStream;
+/// Re-export the middleware stack
+pub use crate::middleware::{LayeredProvider, Middleware, ProviderBuilder};
+/// Re-export the Network presets
+pub use crate::network::Network;
 /// Re-export the Provider trait
-pub use crate::provider::Provider;
+pub use crate::provider::{Provider, ProviderMethod, ProviderRequest, ProviderResponse};
+/// Re-export the RateLimitProvider
+pub use crate::rate_limit_provider::{RateLimitLayer, RateLimitProvider};
+/// Re-export the RetryProvider
+pub use crate::retry_provider::{RetryLayer, RetryProvider};
 
 /// Re-exporting jsonrpc_primitives types so users of near-providers don't need
 /// to keep track of multiple jsonrpc crates. For now we export them as types
@@ -25,5 +90,31 @@ pub use near_jsonrpc_primitives::types;
 pub use near_jsonrpc_client as jsonrpc_client;
 pub use near_jsonrpc_primitives as jsonrpc_primitives;
 
+/// The HTTP client used by the `JsonRpcProvider`, to build the clients and proxies passed to its builder
+pub use reqwest;
+
+pub mod account_watcher;
+pub mod archival_provider;
+pub mod backfill;
+pub mod batch;
+pub mod block_stream;
+pub mod caching_provider;
+pub mod cassette;
+pub mod changes;
+pub mod client_config;
+pub mod failover_provider;
+pub mod instrumentation;
 mod json_rpc_provider;
+pub mod lake;
+pub mod light_client;
+pub mod middleware;
+pub mod network;
+pub mod payment;
 mod provider;
+pub mod rate_limit_provider;
+pub mod retry_provider;
+pub mod state_proof;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod tx_status;
+mod utils;
@@ -0,0 +1,798 @@
+//! The `light_client` module verifies the data served by an RPC node instead of trusting it.
+//!
+//! A `LightClient` starts from a trusted block, e.g. a checkpoint obtained out of band, and follows the chain with the
+//! light client blocks returned by `Provider::next_light_client_block`. Each block is accepted only if it is approved by
+//! more than two thirds of the stake of the block producers of its epoch, which the client tracks from one epoch to the
+//! next. The head of the client is then the root of trust for the execution proofs returned by
+//! `Provider::light_client_proof`: an outcome is accepted only if its Merkle proof leads to a block included in the
+//! chain of the head.
+//!
+//! The checks follow the [light client specification](https://nomicon.io/ChainSpec/LightClient).
+//!
+//! ```no_run
+//! use near_providers::light_client::LightClient;
+//! use near_providers::JsonRpcProvider;
+//! use near_primitives::types::TransactionOrReceiptId;
+//!
+//! # async fn example(checkpoint: near_primitives::views::LightClientBlockView) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+//! let provider = JsonRpcProvider::new("https://archival-rpc.testnet.near.org");
+//! let mut light_client = LightClient::new(checkpoint)?;
+//! light_client.sync(&provider).await?;
+//!
+//! let outcome = light_client
+//!     .prove_outcome(
+//!         &provider,
+//!         TransactionOrReceiptId::Transaction {
+//!             transaction_hash: "47sXP4jKXCMpkUS6kcxsfNU7tqysYr5fxWFdEXQkZh6z".parse()?,
+//!             sender_id: "alice.testnet".parse()?,
+//!         },
+//!     )
+//!     .await?;
+//! println!("{:?}", outcome.outcome.status);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::jsonrpc_client::errors::JsonRpcError;
+use crate::types::light_client::{
+    RpcLightClientExecutionProofResponse, RpcLightClientNextBlockError, RpcLightClientProofError,
+};
+use crate::Provider;
+use near_primitives::{
+    block_header::{Approval, ApprovalInner},
+    hash::CryptoHash,
+    merkle::{combine_hash, compute_root_from_path},
+    types::{AccountId, Balance, BlockHeight, TransactionOrReceiptId},
+    views::{
+        validator_stake_view::ValidatorStakeView, ExecutionOutcomeWithIdView,
+        LightClientBlockLiteView, LightClientBlockView,
+    },
+};
+use std::collections::HashMap;
+
+/// The reasons a light client block or an execution proof is rejected.
+#[derive(Debug, thiserror::Error)]
+pub enum LightClientError {
+    #[error("Block at height {height} is not newer than the head at height {head_height}")]
+    NotNewer {
+        height: BlockHeight,
+        head_height: BlockHeight,
+    },
+    #[error("Block is from epoch {0}, which is neither the epoch of the head nor the next one")]
+    UnknownEpoch(CryptoHash),
+    #[error("The block producers of epoch {0} are not known")]
+    UnknownBlockProducers(CryptoHash),
+    #[error("Block is the first one of a new epoch but does not include the next block producers")]
+    MissingNextBlockProducers,
+    #[error("Block has {approvals} approvals for {block_producers} block producers")]
+    MissingApprovals {
+        approvals: usize,
+        block_producers: usize,
+    },
+    #[error("Invalid approval signature of block producer {0}")]
+    InvalidSignature(AccountId),
+    #[error("Approved stake {approved_stake} is not more than two thirds of the total stake {total_stake}")]
+    InsufficientStake {
+        approved_stake: Balance,
+        total_stake: Balance,
+    },
+    #[error("The next block producers do not match the hash committed to in the block")]
+    NextBlockProducersMismatch,
+    #[error("Proof is for {actual}, but {expected} was requested")]
+    UnexpectedOutcome {
+        expected: CryptoHash,
+        actual: CryptoHash,
+    },
+    #[error("The outcome proof does not lead to the outcome root of block {0}")]
+    OutcomeRootMismatch(CryptoHash),
+    #[error("Block {0} is not included in the chain of the head")]
+    BlockMerkleRootMismatch(CryptoHash),
+    #[error(transparent)]
+    NextBlock(Box<JsonRpcError<RpcLightClientNextBlockError>>),
+    #[error(transparent)]
+    Proof(Box<JsonRpcError<RpcLightClientProofError>>),
+}
+
+impl From<JsonRpcError<RpcLightClientNextBlockError>> for LightClientError {
+    fn from(err: JsonRpcError<RpcLightClientNextBlockError>) -> Self {
+        LightClientError::NextBlock(Box::new(err))
+    }
+}
+
+impl From<JsonRpcError<RpcLightClientProofError>> for LightClientError {
+    fn from(err: JsonRpcError<RpcLightClientProofError>) -> Self {
+        LightClientError::Proof(Box::new(err))
+    }
+}
+
+/// A client following the chain from a trusted block, see the module documentation.
+#[derive(Debug, Clone)]
+pub struct LightClient {
+    head: LightClientBlockLiteView,
+    /// The block producers of the epoch of the head and of the next epoch, by epoch id, as far as they are known.
+    block_producers: HashMap<CryptoHash, Vec<ValidatorStakeView>>,
+}
+
+// The errors of the provider make `LightClientError` large; validation is not on a hot path.
+impl LightClient {
+    /// Creates a light client whose head is the trusted block `checkpoint`.
+    ///
+    /// The checkpoint should be the first block of its epoch, the only one including the block producers of the next
+    /// epoch, which are needed to validate the blocks that follow it. The block producers of the epoch of the
+    /// checkpoint itself can be added with `with_block_producers`.
+    pub fn new(checkpoint: LightClientBlockView) -> Result<Self, LightClientError> {
+        let mut block_producers = HashMap::new();
+        if let Some(next_bps) = checkpoint.next_bps.clone() {
+            if block_producers_hash(&next_bps) != checkpoint.inner_lite.next_bp_hash {
+                return Err(LightClientError::NextBlockProducersMismatch);
+            }
+            block_producers.insert(checkpoint.inner_lite.next_epoch_id, next_bps);
+        }
+        Ok(Self {
+            head: lite_view(checkpoint),
+            block_producers,
+        })
+    }
+
+    /// Trusts `block_producers` as the block producers of the epoch `epoch_id`.
+    pub fn with_block_producers(
+        mut self,
+        epoch_id: CryptoHash,
+        block_producers: Vec<ValidatorStakeView>,
+    ) -> Self {
+        self.block_producers.insert(epoch_id, block_producers);
+        self
+    }
+
+    /// Returns the latest block accepted by the client.
+    pub fn head(&self) -> &LightClientBlockLiteView {
+        &self.head
+    }
+
+    /// Returns the hash of the latest block accepted by the client.
+    pub fn head_hash(&self) -> CryptoHash {
+        self.head.hash()
+    }
+
+    /// Returns the block producers of the epoch `epoch_id`, if it is the epoch of the head or the next one and its
+    /// block producers are known.
+    pub fn block_producers(&self, epoch_id: &CryptoHash) -> Option<&[ValidatorStakeView]> {
+        self.block_producers.get(epoch_id).map(Vec::as_slice)
+    }
+
+    /// Checks that `block` can become the new head: it is newer than the head, it belongs to the epoch of the head or
+    /// to the next one, it has an approval slot for every block producer of its epoch and is approved by more than
+    /// two thirds of their total stake, and
+    /// the next block producers it includes are the ones it commits to.
+    pub fn validate_block(&self, block: &LightClientBlockView) -> Result<(), LightClientError> {
+        let inner_lite = &block.inner_lite;
+        let head_lite = &self.head.inner_lite;
+        if inner_lite.height <= head_lite.height {
+            return Err(LightClientError::NotNewer {
+                height: inner_lite.height,
+                head_height: head_lite.height,
+            });
+        }
+        if inner_lite.epoch_id != head_lite.epoch_id
+            && inner_lite.epoch_id != head_lite.next_epoch_id
+        {
+            return Err(LightClientError::UnknownEpoch(inner_lite.epoch_id));
+        }
+        if inner_lite.epoch_id == head_lite.next_epoch_id && block.next_bps.is_none() {
+            return Err(LightClientError::MissingNextBlockProducers);
+        }
+        let block_producers = self
+            .block_producers(&inner_lite.epoch_id)
+            .ok_or(LightClientError::UnknownBlockProducers(inner_lite.epoch_id))?;
+
+        // The approvals are made on the block after the next one, endorsing the next block.
+        let current_hash = lite_view(block.clone()).hash();
+        let next_block_hash = combine_hash(&block.next_block_inner_hash, &current_hash);
+        let message = Approval::get_data_for_sig(
+            &ApprovalInner::Endorsement(next_block_hash),
+            inner_lite.height + 2,
+        );
+
+        // Every block producer of the epoch has a slot, so a truncated list cannot shrink the total stake.
+        if block.approvals_after_next.len() < block_producers.len() {
+            return Err(LightClientError::MissingApprovals {
+                approvals: block.approvals_after_next.len(),
+                block_producers: block_producers.len(),
+            });
+        }
+        let total_stake: Balance = block_producers
+            .iter()
+            .map(|ValidatorStakeView::V1(block_producer)| block_producer.stake)
+            .sum();
+        let mut approved_stake: Balance = 0;
+        for (approval, block_producer) in block.approvals_after_next.iter().zip(block_producers) {
+            let ValidatorStakeView::V1(block_producer) = block_producer;
+            let Some(signature) = approval else {
+                continue;
+            };
+            approved_stake += block_producer.stake;
+            if !signature.verify(&message, &block_producer.public_key) {
+                return Err(LightClientError::InvalidSignature(
+                    block_producer.account_id.clone(),
+                ));
+            }
+        }
+        if approved_stake <= total_stake * 2 / 3 {
+            return Err(LightClientError::InsufficientStake {
+                approved_stake,
+                total_stake,
+            });
+        }
+
+        if let Some(next_bps) = &block.next_bps {
+            if block_producers_hash(next_bps) != inner_lite.next_bp_hash {
+                return Err(LightClientError::NextBlockProducersMismatch);
+            }
+        }
+        Ok(())
+    }
+
+    /// Validates `block` and makes it the new head, keeping track of the block producers of its next epoch.
+    pub fn update_head(&mut self, block: LightClientBlockView) -> Result<(), LightClientError> {
+        self.validate_block(&block)?;
+        if let Some(next_bps) = block.next_bps.clone() {
+            self.block_producers
+                .insert(block.inner_lite.next_epoch_id, next_bps);
+        }
+        self.head = lite_view(block);
+        let head_lite = &self.head.inner_lite;
+        self.block_producers.retain(|epoch_id, _| {
+            *epoch_id == head_lite.epoch_id || *epoch_id == head_lite.next_epoch_id
+        });
+        Ok(())
+    }
+
+    /// Moves the head to the latest final block known to `provider`, one epoch at a time. Returns the number of blocks
+    /// accepted. A block failing validation stops the sync with an error and leaves the head at the last valid block.
+    pub async fn sync<P>(&mut self, provider: &P) -> Result<usize, LightClientError>
+    where
+        P: Provider + ?Sized,
+    {
+        let mut accepted = 0;
+        while let Some(block) = provider.next_light_client_block(self.head_hash()).await? {
+            // Once the head is in the current epoch, the node keeps returning the latest final block.
+            if block.inner_lite.height <= self.head.inner_lite.height {
+                break;
+            }
+            self.update_head(block)?;
+            accepted += 1;
+        }
+        Ok(accepted)
+    }
+
+    /// Checks that the execution proof `proof` leads to a block included in the chain of the head.
+    pub fn verify_execution_proof(
+        &self,
+        proof: &RpcLightClientExecutionProofResponse,
+    ) -> Result<(), LightClientError> {
+        verify_execution_proof(proof, &self.head)
+    }
+
+    /// Fetches the execution outcome of a transaction or receipt from `provider` and returns it once its proof is
+    /// verified against the head.
+    pub async fn prove_outcome<P>(
+        &self,
+        provider: &P,
+        id: TransactionOrReceiptId,
+    ) -> Result<ExecutionOutcomeWithIdView, LightClientError>
+    where
+        P: Provider + ?Sized,
+    {
+        let expected = match &id {
+            TransactionOrReceiptId::Transaction {
+                transaction_hash, ..
+            } => *transaction_hash,
+            TransactionOrReceiptId::Receipt { receipt_id, .. } => *receipt_id,
+        };
+        let proof = provider.light_client_proof(id, self.head_hash()).await?;
+        if proof.outcome_proof.id != expected {
+            return Err(LightClientError::UnexpectedOutcome {
+                expected,
+                actual: proof.outcome_proof.id,
+            });
+        }
+        self.verify_execution_proof(&proof)?;
+        Ok(proof.outcome_proof)
+    }
+}
+
+/// Checks that the execution proof `proof` leads to a block included in the chain of `head`: the outcome is part of the
+/// outcome root of the block in `block_header_lite`, and that block is part of the block Merkle root of the head.
+pub fn verify_execution_proof(
+    proof: &RpcLightClientExecutionProofResponse,
+    head: &LightClientBlockLiteView,
+) -> Result<(), LightClientError> {
+    let outcome_hash = CryptoHash::hash_borsh(proof.outcome_proof.to_hashes());
+    let shard_outcome_root = compute_root_from_path(&proof.outcome_proof.proof, outcome_hash);
+    let block_outcome_root = compute_root_from_path(
+        &proof.outcome_root_proof,
+        CryptoHash::hash_borsh(shard_outcome_root),
+    );
+    let block_hash = proof.block_header_lite.hash();
+    if block_outcome_root != proof.block_header_lite.inner_lite.outcome_root {
+        return Err(LightClientError::OutcomeRootMismatch(block_hash));
+    }
+    if compute_root_from_path(&proof.block_proof, block_hash) != head.inner_lite.block_merkle_root {
+        return Err(LightClientError::BlockMerkleRootMismatch(block_hash));
+    }
+    Ok(())
+}
+
+/// Returns the hash committed to in `next_bp_hash` for the block producers `block_producers`.
+fn block_producers_hash(block_producers: &[ValidatorStakeView]) -> CryptoHash {
+    CryptoHash::hash_borsh(
+        block_producers
+            .iter()
+            .cloned()
+            .map(ValidatorStakeView::into_validator_stake)
+            .collect::<Vec<_>>(),
+    )
+}
+
+fn lite_view(block: LightClientBlockView) -> LightClientBlockLiteView {
+    LightClientBlockLiteView {
+        prev_block_hash: block.prev_block_hash,
+        inner_rest_hash: block.inner_rest_hash,
+        inner_lite: block.inner_lite,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cassette::{RecordingProvider, ReplayProvider};
+    use crate::testing::{handler_error, MockProvider};
+    use near_crypto::{KeyType, SecretKey};
+    use near_primitives::merkle::{merklize, MerklePathItem};
+    use near_primitives::views::validator_stake_view::ValidatorStakeViewV1;
+    use near_primitives::views::{
+        BlockHeaderInnerLiteView, ExecutionMetadataView, ExecutionOutcomeView, ExecutionStatusView,
+    };
+
+    struct BlockProducer {
+        secret_key: SecretKey,
+        stake: ValidatorStakeView,
+    }
+
+    fn block_producers(epoch: &str, count: usize) -> Vec<BlockProducer> {
+        (0..count)
+            .map(|i| {
+                let account_id: AccountId = format!("bp{i}.{epoch}.near").parse().unwrap();
+                let secret_key = SecretKey::from_seed(KeyType::ED25519, account_id.as_str());
+                let stake = ValidatorStakeView::V1(ValidatorStakeViewV1 {
+                    account_id,
+                    public_key: secret_key.public_key(),
+                    stake: 100,
+                });
+                BlockProducer { secret_key, stake }
+            })
+            .collect()
+    }
+
+    fn stakes(block_producers: &[BlockProducer]) -> Vec<ValidatorStakeView> {
+        block_producers.iter().map(|bp| bp.stake.clone()).collect()
+    }
+
+    fn epoch_id(epoch: &str) -> CryptoHash {
+        CryptoHash::hash_bytes(epoch.as_bytes())
+    }
+
+    /// Builds a light client block of epoch `epoch`, approved by the first `approvals` of `signers`.
+    fn light_client_block(
+        height: BlockHeight,
+        epoch: &str,
+        next_epoch: &str,
+        next_bps: &[BlockProducer],
+        signers: &[BlockProducer],
+        approvals: usize,
+        block_merkle_root: CryptoHash,
+    ) -> LightClientBlockView {
+        let next_bps = stakes(next_bps);
+        let mut block = LightClientBlockView {
+            prev_block_hash: CryptoHash::hash_borsh(height - 1),
+            next_block_inner_hash: CryptoHash::hash_borsh(height + 1),
+            inner_lite: BlockHeaderInnerLiteView {
+                height,
+                epoch_id: epoch_id(epoch),
+                next_epoch_id: epoch_id(next_epoch),
+                prev_state_root: CryptoHash::default(),
+                outcome_root: CryptoHash::default(),
+                timestamp: height,
+                timestamp_nanosec: height,
+                next_bp_hash: block_producers_hash(&next_bps),
+                block_merkle_root,
+            },
+            inner_rest_hash: CryptoHash::hash_borsh(height),
+            next_bps: Some(next_bps),
+            approvals_after_next: vec![],
+        };
+        sign(&mut block, signers, approvals);
+        block
+    }
+
+    fn sign(block: &mut LightClientBlockView, signers: &[BlockProducer], approvals: usize) {
+        let current_hash = lite_view(block.clone()).hash();
+        let next_block_hash = combine_hash(&block.next_block_inner_hash, &current_hash);
+        let message = Approval::get_data_for_sig(
+            &ApprovalInner::Endorsement(next_block_hash),
+            block.inner_lite.height + 2,
+        );
+        block.approvals_after_next = signers
+            .iter()
+            .enumerate()
+            .map(|(i, bp)| (i < approvals).then(|| Box::new(bp.secret_key.sign(&message))))
+            .collect();
+    }
+
+    /// A chain of three epochs of four block producers each, starting from a checkpoint in epoch `e0`.
+    struct Chain {
+        checkpoint: LightClientBlockView,
+        bps1: Vec<BlockProducer>,
+        bps2: Vec<BlockProducer>,
+        bps3: Vec<BlockProducer>,
+    }
+
+    fn chain() -> Chain {
+        let bps0 = block_producers("e0", 4);
+        let bps1 = block_producers("e1", 4);
+        let checkpoint = light_client_block(10, "e0", "e1", &bps1, &bps0, 4, CryptoHash::default());
+        Chain {
+            checkpoint,
+            bps1,
+            bps2: block_producers("e2", 4),
+            bps3: block_producers("e3", 4),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sync_across_epochs() {
+        let chain = chain();
+        let block1 = light_client_block(
+            20,
+            "e1",
+            "e2",
+            &chain.bps2,
+            &chain.bps1,
+            3,
+            CryptoHash::default(),
+        );
+        let block2 = light_client_block(
+            30,
+            "e2",
+            "e3",
+            &chain.bps3,
+            &chain.bps2,
+            4,
+            CryptoHash::default(),
+        );
+        let mock = MockProvider::new();
+        mock.push_next_light_client_block(Ok(Some(block1.clone())));
+        mock.push_next_light_client_block(Ok(Some(block2.clone())));
+        // Once the head is the latest final block, the node returns it again.
+        mock.push_next_light_client_block(Ok(Some(block2.clone())));
+
+        let recording = RecordingProvider::new(mock);
+        let mut light_client = LightClient::new(chain.checkpoint.clone()).unwrap();
+        assert_eq!(light_client.sync(&recording).await.unwrap(), 2);
+
+        let replay = ReplayProvider::new(recording.cassette());
+        let mut light_client = LightClient::new(chain.checkpoint).unwrap();
+        assert_eq!(light_client.sync(&replay).await.unwrap(), 2);
+        assert!(replay.remaining().is_empty());
+
+        assert_eq!(light_client.head_hash(), lite_view(block2).hash());
+        assert_eq!(light_client.block_producers(&epoch_id("e1")), None);
+        assert_eq!(
+            light_client.block_producers(&epoch_id("e2")),
+            Some(stakes(&chain.bps2).as_slice())
+        );
+        assert_eq!(
+            light_client.block_producers(&epoch_id("e3")),
+            Some(stakes(&chain.bps3).as_slice())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sync_stops_at_invalid_block() {
+        let chain = chain();
+        let block1 = light_client_block(
+            20,
+            "e1",
+            "e2",
+            &chain.bps2,
+            &chain.bps1,
+            4,
+            CryptoHash::default(),
+        );
+        // Signed by the block producers of the wrong epoch.
+        let block2 = light_client_block(
+            30,
+            "e2",
+            "e3",
+            &chain.bps3,
+            &chain.bps3,
+            4,
+            CryptoHash::default(),
+        );
+        let mock = MockProvider::new();
+        mock.push_next_light_client_block(Ok(Some(block1.clone())));
+        mock.push_next_light_client_block(Ok(Some(block2)));
+
+        let mut light_client = LightClient::new(chain.checkpoint).unwrap();
+        let err = light_client.sync(&mock).await.unwrap_err();
+        assert!(
+            matches!(err, LightClientError::InvalidSignature(account_id) if account_id == "bp0.e2.near")
+        );
+        assert_eq!(light_client.head_hash(), lite_view(block1).hash());
+    }
+
+    #[test]
+    fn test_rejects_invalid_blocks() {
+        let chain = chain();
+        let light_client = LightClient::new(chain.checkpoint.clone()).unwrap();
+        let valid = light_client_block(
+            20,
+            "e1",
+            "e2",
+            &chain.bps2,
+            &chain.bps1,
+            3,
+            CryptoHash::default(),
+        );
+        light_client.validate_block(&valid).unwrap();
+
+        let not_enough_approvals = light_client_block(
+            20,
+            "e1",
+            "e2",
+            &chain.bps2,
+            &chain.bps1,
+            2,
+            CryptoHash::default(),
+        );
+        assert!(matches!(
+            light_client.validate_block(&not_enough_approvals),
+            Err(LightClientError::InsufficientStake {
+                approved_stake: 200,
+                total_stake: 400
+            })
+        ));
+
+        // A node cannot leave out the block producers that did not approve to shrink the total stake.
+        let mut truncated = valid.clone();
+        truncated.approvals_after_next.truncate(1);
+        assert!(matches!(
+            light_client.validate_block(&truncated),
+            Err(LightClientError::MissingApprovals {
+                approvals: 1,
+                block_producers: 4
+            })
+        ));
+
+        let mut tampered = valid.clone();
+        tampered.inner_lite.prev_state_root = CryptoHash::hash_bytes(b"forged");
+        assert!(matches!(
+            light_client.validate_block(&tampered),
+            Err(LightClientError::InvalidSignature(_))
+        ));
+
+        let mut wrong_next_bps = valid.clone();
+        wrong_next_bps.next_bps = Some(stakes(&chain.bps3));
+        assert!(matches!(
+            light_client.validate_block(&wrong_next_bps),
+            Err(LightClientError::NextBlockProducersMismatch)
+        ));
+
+        let mut without_next_bps = valid.clone();
+        without_next_bps.next_bps = None;
+        assert!(matches!(
+            light_client.validate_block(&without_next_bps),
+            Err(LightClientError::MissingNextBlockProducers)
+        ));
+
+        let stale = light_client_block(
+            10,
+            "e1",
+            "e2",
+            &chain.bps2,
+            &chain.bps1,
+            4,
+            CryptoHash::default(),
+        );
+        assert!(matches!(
+            light_client.validate_block(&stale),
+            Err(LightClientError::NotNewer {
+                height: 10,
+                head_height: 10
+            })
+        ));
+
+        let skipped_epoch = light_client_block(
+            30,
+            "e2",
+            "e3",
+            &chain.bps3,
+            &chain.bps2,
+            4,
+            CryptoHash::default(),
+        );
+        assert!(matches!(
+            light_client.validate_block(&skipped_epoch),
+            Err(LightClientError::UnknownEpoch(epoch)) if epoch == epoch_id("e2")
+        ));
+
+        // The block producers of the epoch of the checkpoint are only known when given.
+        let same_epoch = light_client_block(
+            15,
+            "e0",
+            "e1",
+            &chain.bps1,
+            &chain.bps1,
+            4,
+            CryptoHash::default(),
+        );
+        assert!(matches!(
+            light_client.validate_block(&same_epoch),
+            Err(LightClientError::UnknownBlockProducers(epoch)) if epoch == epoch_id("e0")
+        ));
+        let light_client = light_client.with_block_producers(epoch_id("e0"), stakes(&chain.bps1));
+        light_client.validate_block(&same_epoch).unwrap();
+    }
+
+    fn outcome(id: CryptoHash, gas_burnt: u64) -> ExecutionOutcomeWithIdView {
+        ExecutionOutcomeWithIdView {
+            proof: vec![],
+            block_hash: CryptoHash::hash_bytes(b"block"),
+            id,
+            outcome: ExecutionOutcomeView {
+                logs: vec![],
+                receipt_ids: vec![CryptoHash::hash_borsh(id)],
+                gas_burnt,
+                tokens_burnt: gas_burnt as u128,
+                executor_id: "alice.near".parse().unwrap(),
+                status: ExecutionStatusView::SuccessValue(vec![]),
+                metadata: ExecutionMetadataView::default(),
+            },
+        }
+    }
+
+    /// Builds the proof of the execution of transaction `tx_hash` next to other outcomes and shards, and the head of a
+    /// chain including the block of the proof.
+    fn execution_proof(
+        tx_hash: CryptoHash,
+    ) -> (RpcLightClientExecutionProofResponse, LightClientBlockView) {
+        let mut outcome_proof = outcome(tx_hash, 10);
+        let outcomes = [
+            outcome(CryptoHash::hash_bytes(b"other"), 20).to_hashes(),
+            outcome_proof.to_hashes(),
+            outcome(CryptoHash::hash_bytes(b"another"), 30).to_hashes(),
+        ];
+        let (shard_outcome_root, outcome_paths) = merklize(&outcomes);
+        outcome_proof.proof = outcome_paths[1].clone();
+        let (outcome_root, shard_paths) =
+            merklize(&[CryptoHash::hash_bytes(b"shard 0"), shard_outcome_root]);
+
+        let chain = chain();
+        let mut block = light_client_block(
+            12,
+            "e0",
+            "e1",
+            &chain.bps1,
+            &chain.bps1,
+            0,
+            CryptoHash::default(),
+        );
+        block.inner_lite.outcome_root = outcome_root;
+        let block_header_lite = lite_view(block);
+        let previous_block_hash = CryptoHash::hash_bytes(b"block 11");
+        let block_merkle_root = combine_hash(&previous_block_hash, &block_header_lite.hash());
+        let head = light_client_block(
+            20,
+            "e1",
+            "e2",
+            &chain.bps2,
+            &chain.bps1,
+            4,
+            block_merkle_root,
+        );
+        let proof = RpcLightClientExecutionProofResponse {
+            outcome_proof,
+            outcome_root_proof: shard_paths[1].clone(),
+            block_header_lite,
+            block_proof: vec![MerklePathItem {
+                hash: previous_block_hash,
+                direction: near_primitives::merkle::Direction::Left,
+            }],
+        };
+        (proof, head)
+    }
+
+    #[test]
+    fn test_verify_execution_proof() {
+        let (proof, head) = execution_proof(CryptoHash::hash_bytes(b"tx"));
+        let head = lite_view(head);
+        verify_execution_proof(&proof, &head).unwrap();
+
+        let mut forged_outcome = clone_proof(&proof);
+        forged_outcome.outcome_proof.outcome.gas_burnt = 1;
+        assert!(matches!(
+            verify_execution_proof(&forged_outcome, &head),
+            Err(LightClientError::OutcomeRootMismatch(_))
+        ));
+
+        let mut forked_block = clone_proof(&proof);
+        forked_block.block_header_lite.prev_block_hash = CryptoHash::hash_bytes(b"fork");
+        assert!(matches!(
+            verify_execution_proof(&forked_block, &head),
+            Err(LightClientError::BlockMerkleRootMismatch(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_prove_outcome() {
+        let tx_hash = CryptoHash::hash_bytes(b"tx");
+        let (proof, head) = execution_proof(tx_hash);
+        let chain = chain();
+        let mut light_client = LightClient::new(chain.checkpoint).unwrap();
+        light_client.update_head(head).unwrap();
+
+        let mock = MockProvider::new();
+        mock.push_light_client_proof(Ok(clone_proof(&proof)));
+        mock.push_light_client_proof(Ok(proof));
+        mock.push_light_client_proof(Err(handler_error(RpcLightClientProofError::NotConfirmed {
+            transaction_or_receipt_id: tx_hash,
+        })));
+        let recording = RecordingProvider::new(mock);
+        let id = TransactionOrReceiptId::Transaction {
+            transaction_hash: tx_hash,
+            sender_id: "alice.near".parse().unwrap(),
+        };
+        let receipt_id = TransactionOrReceiptId::Receipt {
+            receipt_id: CryptoHash::hash_bytes(b"receipt"),
+            receiver_id: "alice.near".parse().unwrap(),
+        };
+        light_client
+            .prove_outcome(&recording, id.clone())
+            .await
+            .unwrap();
+        light_client
+            .prove_outcome(&recording, receipt_id.clone())
+            .await
+            .unwrap_err();
+        light_client
+            .prove_outcome(&recording, id.clone())
+            .await
+            .unwrap_err();
+
+        let replay = ReplayProvider::new(recording.cassette());
+        let outcome = light_client
+            .prove_outcome(&replay, id.clone())
+            .await
+            .unwrap();
+        assert_eq!(outcome.id, tx_hash);
+        assert!(matches!(
+            light_client.prove_outcome(&replay, receipt_id).await,
+            Err(LightClientError::UnexpectedOutcome { actual, .. }) if actual == tx_hash
+        ));
+        assert!(matches!(
+            light_client.prove_outcome(&replay, id).await,
+            Err(LightClientError::Proof(_))
+        ));
+    }
+
+    fn clone_proof(
+        proof: &RpcLightClientExecutionProofResponse,
+    ) -> RpcLightClientExecutionProofResponse {
+        RpcLightClientExecutionProofResponse {
+            outcome_proof: proof.outcome_proof.clone(),
+            outcome_root_proof: proof.outcome_root_proof.clone(),
+            block_header_lite: proof.block_header_lite.clone(),
+            block_proof: proof.block_proof.clone(),
+        }
+    }
+}
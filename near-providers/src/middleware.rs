@@ -0,0 +1,751 @@
+//! The `middleware` module lets cross-cutting concerns, such as logging, metrics or request filtering, be written once
+//! for every `Provider` method instead of as a wrapper re-implementing the whole trait.
+//!
+//! A `Middleware` sees each call as a `ProviderRequest` and answers it with a `ProviderResponse`, usually by passing
+//! the request on to the next layer with `Next::run` and inspecting the response. The `ProviderBuilder` stacks
+//! middlewares on top of a provider, such as a `JsonRpcProvider`, into a `LayeredProvider`, which implements
+//! `Provider` itself and can be wrapped by or wrap the other providers of this crate.
+//!
+//! The retries, rate limits and caching of `RetryProvider`, `RateLimitProvider` and `CachingProvider` are available
+//! as middlewares too, as `RetryLayer`, `RateLimitLayer` and `CacheLayer`, so they can be stacked in any order with
+//! custom middlewares in a single `ProviderBuilder`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use async_trait::async_trait;
+//! use near_providers::middleware::{Middleware, Next, ProviderBuilder};
+//! use near_providers::{Provider, ProviderRequest, ProviderResponse};
+//!
+//! struct Logging;
+//!
+//! #[async_trait]
+//! impl Middleware for Logging {
+//!     async fn handle(&self, request: ProviderRequest, next: Next<'_>) -> ProviderResponse {
+//!         let method = request.method();
+//!         let response = next.run(request).await;
+//!         match response.error_message() {
+//!             Some(err) => eprintln!("{} failed: {}", method.rpc_method_name(), err),
+//!             None => eprintln!("{} succeeded", method.rpc_method_name()),
+//!         }
+//!         response
+//!     }
+//! }
+//!
+//! # async fn run() {
+//! let provider = ProviderBuilder::new()
+//!     .with_layer(Logging)
+//!     .connect("https://rpc.testnet.near.org");
+//! let status = provider.status().await;
+//! # }
+//! ```
+
+use crate::batch;
+use crate::client_config::{RpcClientConfigError, RpcClientConfigResponse};
+use crate::jsonrpc_client::{
+    errors::{JsonRpcError, JsonRpcServerError},
+    methods::{self, status::RpcStatusResponse},
+};
+use crate::tx_status::RpcTransactionWithReceiptsResponse;
+use crate::types::{
+    blocks::RpcBlockError,
+    changes::{
+        RpcStateChangesError, RpcStateChangesInBlockByTypeResponse, RpcStateChangesInBlockResponse,
+    },
+    chunks::{ChunkReference, RpcChunkError},
+    config::RpcProtocolConfigError,
+    gas_price::RpcGasPriceError,
+    light_client::{
+        RpcLightClientExecutionProofResponse, RpcLightClientNextBlockError,
+        RpcLightClientProofError,
+    },
+    network_info::{RpcNetworkInfoError, RpcNetworkInfoResponse},
+    query::{RpcQueryError, RpcQueryResponse},
+    receipts::RpcReceiptError,
+    status::{RpcHealthResponse, RpcStatusError},
+    transactions::{RpcTransactionError, TransactionInfo},
+    validator::RpcValidatorError,
+};
+use crate::{JsonRpcProvider, Provider, ProviderMethod, ProviderRequest, ProviderResponse};
+use async_trait::async_trait;
+use futures::future;
+use futures::stream::{FuturesUnordered, StreamExt};
+use near_chain_configs::{GenesisConfig, ProtocolConfigView};
+use near_jsonrpc_client::methods::tx::RpcTransactionResponse;
+use near_primitives::{
+    hash::CryptoHash,
+    transaction::SignedTransaction,
+    types::{BlockId, BlockReference, EpochReference, TransactionOrReceiptId},
+    views::{
+        BlockView, ChunkView, EpochValidatorInfo, FinalExecutionOutcomeView, GasPriceView,
+        LightClientBlockView, QueryRequest, ReceiptView, StateChangesRequestView,
+        TxExecutionStatus,
+    },
+};
+use std::sync::{Arc, Mutex};
+use std::task::Poll;
+use tokio::sync::oneshot;
+
+/// A layer of a `LayeredProvider`, handling every call made through it.
+#[async_trait]
+pub trait Middleware: Send + Sync {
+    /// Answers `request`, usually by passing it on with `next.run` and inspecting or replacing the response. The
+    /// response must be the one of the method of the request, as returned by `ProviderResponse::method`.
+    async fn handle(&self, request: ProviderRequest, next: Next<'_>) -> ProviderResponse;
+}
+
+/// The layers below a middleware, down to the provider they are stacked on.
+///
+/// `Next` is `Copy`, so a middleware may run a request more than once, e.g. to retry it.
+#[derive(Clone, Copy)]
+pub struct Next<'a> {
+    middlewares: &'a [Arc<dyn Middleware>],
+    provider: &'a (dyn Provider + Send + Sync),
+}
+
+impl Next<'_> {
+    /// Passes `request` on to the next layer and returns its response.
+    pub async fn run(self, request: ProviderRequest) -> ProviderResponse {
+        match self.middlewares.split_first() {
+            Some((middleware, middlewares)) => {
+                let next = Next {
+                    middlewares,
+                    provider: self.provider,
+                };
+                middleware.handle(request, next).await
+            }
+            None => ProviderResponse::dispatch(self.provider, request).await,
+        }
+    }
+
+    /// Passes `request` on to the next layer, for the `Provider` implementation of `Next`.
+    async fn call(&self, request: ProviderRequest) -> ProviderResponse {
+        self.run(request).await
+    }
+}
+
+/// Stacks middlewares on top of a provider.
+///
+/// The first layer added is the outermost one: it sees the requests first and the responses last.
+#[derive(Clone, Default)]
+pub struct ProviderBuilder {
+    middlewares: Vec<Arc<dyn Middleware>>,
+}
+
+impl ProviderBuilder {
+    /// Creates a builder without any layers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `middleware` below the layers added so far.
+    pub fn with_layer(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.middlewares.push(Arc::new(middleware));
+        self
+    }
+
+    /// Stacks the layers on top of `provider`.
+    pub fn build<P: Provider + Send + Sync>(self, provider: P) -> LayeredProvider<P> {
+        LayeredProvider {
+            inner: provider,
+            middlewares: self.middlewares,
+        }
+    }
+
+    /// Stacks the layers on top of a `JsonRpcProvider` connected to `rpc_endpoint`.
+    pub fn connect(self, rpc_endpoint: &str) -> LayeredProvider<JsonRpcProvider> {
+        self.build(JsonRpcProvider::new(rpc_endpoint))
+    }
+}
+
+/// Represents a provider whose calls go through a stack of middlewares before reaching the inner provider.
+pub struct LayeredProvider<P> {
+    inner: P,
+    middlewares: Vec<Arc<dyn Middleware>>,
+}
+
+impl<P: Provider + Send + Sync> LayeredProvider<P> {
+    /// Returns a reference to the provider the layers are stacked on.
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    /// Sends `request` through the layers.
+    pub async fn call(&self, request: ProviderRequest) -> ProviderResponse {
+        let next = Next {
+            middlewares: &self.middlewares,
+            provider: &self.inner,
+        };
+        next.run(request).await
+    }
+
+    /// Sends each request of a batch through the layers, collecting the requests that reach the inner provider in
+    /// the same round of polling into one batch of the inner provider. A middleware that waits, e.g. for a rate limit
+    /// or before a retry, sends its request with a later batch.
+    async fn run_batch(&self, requests: Vec<ProviderRequest>) -> Vec<ProviderResponse> {
+        let count = requests.len();
+        let collector = BatchCollector::default();
+        let next = Next {
+            middlewares: &self.middlewares,
+            provider: &collector,
+        };
+        let mut items: FuturesUnordered<_> = requests
+            .into_iter()
+            .enumerate()
+            .map(|(index, request)| async move { (index, next.run(request).await) })
+            .collect();
+        let mut batches = FuturesUnordered::new();
+        let mut responses = Vec::with_capacity(count);
+        future::poll_fn(|cx| loop {
+            while let Poll::Ready(Some(())) = batches.poll_next_unpin(cx) {}
+            while let Poll::Ready(Some(response)) = items.poll_next_unpin(cx) {
+                responses.push(response);
+            }
+            if items.is_empty() {
+                return Poll::Ready(());
+            }
+            let waiting = collector.take();
+            if waiting.is_empty() {
+                return Poll::Pending;
+            }
+            batches.push(self.send_collected(waiting));
+        })
+        .await;
+        batch::in_request_order(count, responses)
+    }
+
+    /// Sends the requests collected at the bottom of the layers to the inner provider as one batch.
+    async fn send_collected(&self, waiting: Vec<CollectedRequest>) {
+        let (requests, senders): (Vec<_>, Vec<_>) = waiting.into_iter().unzip();
+        let responses = self.inner.batch(requests).await;
+        for (sender, response) in senders.into_iter().zip(responses) {
+            // The item is only gone if the whole batch was dropped.
+            let _ = sender.send(response);
+        }
+    }
+}
+
+/// A request that reached the bottom of the layers during a batch, with the channel its response goes back through.
+type CollectedRequest = (ProviderRequest, oneshot::Sender<ProviderResponse>);
+
+/// The bottom of the layers during a batch, holding the requests that reach it until they are sent to the inner
+/// provider together.
+#[derive(Default)]
+struct BatchCollector {
+    waiting: Mutex<Vec<CollectedRequest>>,
+}
+
+impl BatchCollector {
+    fn take(&self) -> Vec<CollectedRequest> {
+        std::mem::take(&mut *self.waiting.lock().unwrap())
+    }
+
+    /// Holds `request` until it is sent with the next batch, for the `Provider` implementation of `BatchCollector`.
+    async fn call(&self, request: ProviderRequest) -> ProviderResponse {
+        let method = request.method();
+        let (sender, receiver) = oneshot::channel();
+        self.waiting.lock().unwrap().push((request, sender));
+        receiver.await.unwrap_or_else(|_| {
+            ProviderResponse::internal_error(method, "the batch was dropped before it was sent")
+        })
+    }
+}
+
+/// The error returned when a middleware answers a request with the response of another method.
+fn mismatched_response<E>(
+    expected: ProviderMethod,
+    response: &ProviderResponse,
+) -> JsonRpcError<E> {
+    JsonRpcError::ServerError(JsonRpcServerError::InternalError {
+        info: Some(format!(
+            "middleware answered a {} request with a {} response",
+            expected.rpc_method_name(),
+            response.method().rpc_method_name()
+        )),
+    })
+}
+
+/// Sends a request through the layers and unwraps the response of the expected method.
+macro_rules! layered_call {
+    ($self:ident, $variant:ident, $request:expr) => {
+        match $self.call($request).await {
+            ProviderResponse::$variant(result) => result,
+            response => Err(mismatched_response(ProviderMethod::$variant, &response)),
+        }
+    };
+}
+
+/// Implements `Provider` for a type answering every request with its `call` method, with the given `batch`
+/// implementation, if any.
+macro_rules! impl_layered_provider {
+    ([$($generics:tt)*] $provider:ty) => {
+        impl_layered_provider!([$($generics)*] $provider, {});
+    };
+    ([$($generics:tt)*] $provider:ty, { $($batch:tt)* }) => {
+        #[async_trait]
+        impl<$($generics)*> Provider for $provider {
+            /// Retrieves the current status of the NEAR blockchain through the layers.
+            async fn status(&self) -> Result<RpcStatusResponse, JsonRpcError<RpcStatusError>> {
+                layered_call!(self, Status, ProviderRequest::Status)
+            }
+
+            /// Executes a query through the layers.
+            async fn query(
+                &self,
+                request: QueryRequest,
+                block_reference: BlockReference,
+            ) -> Result<RpcQueryResponse, JsonRpcError<RpcQueryError>> {
+                layered_call!(
+                    self,
+                    Query,
+                    ProviderRequest::Query {
+                        request,
+                        block_reference,
+                    }
+                )
+            }
+
+            /// Sends a signed transaction through the layers and waits for its final execution outcome.
+            async fn send_transaction(
+                &self,
+                signed_transaction: SignedTransaction,
+            ) -> Result<FinalExecutionOutcomeView, JsonRpcError<RpcTransactionError>> {
+                layered_call!(
+                    self,
+                    SendTransaction,
+                    ProviderRequest::SendTransaction { signed_transaction }
+                )
+            }
+
+            /// Sends a signed transaction asynchronously through the layers.
+            async fn send_transaction_async(
+                &self,
+                signed_transaction: SignedTransaction,
+            ) -> Result<CryptoHash, JsonRpcError<methods::broadcast_tx_async::RpcBroadcastTxAsyncError>>
+            {
+                layered_call!(
+                    self,
+                    SendTransactionAsync,
+                    ProviderRequest::SendTransactionAsync { signed_transaction }
+                )
+            }
+
+            /// Sends a signed transaction through the layers with the given `wait_until`.
+            async fn send_tx(
+                &self,
+                signed_transaction: SignedTransaction,
+                wait_until: TxExecutionStatus,
+            ) -> Result<RpcTransactionResponse, JsonRpcError<RpcTransactionError>> {
+                layered_call!(
+                    self,
+                    SendTx,
+                    ProviderRequest::SendTx {
+                        signed_transaction,
+                        wait_until,
+                    }
+                )
+            }
+
+            /// Retrieves the status of a transaction through the layers.
+            async fn tx_status(
+                &self,
+                transaction_info: TransactionInfo,
+                wait_until: TxExecutionStatus,
+            ) -> Result<RpcTransactionResponse, JsonRpcError<RpcTransactionError>> {
+                layered_call!(
+                    self,
+                    TxStatus,
+                    ProviderRequest::TxStatus {
+                        transaction_info,
+                        wait_until,
+                    }
+                )
+            }
+
+            /// Fetches details of a specific chunk through the layers.
+            async fn chunk(
+                &self,
+                chunk_reference: ChunkReference,
+            ) -> Result<ChunkView, JsonRpcError<RpcChunkError>> {
+                layered_call!(self, Chunk, ProviderRequest::Chunk { chunk_reference })
+            }
+
+            /// Retrieves a block through the layers.
+            async fn block(
+                &self,
+                block_reference: BlockReference,
+            ) -> Result<BlockView, JsonRpcError<RpcBlockError>> {
+                layered_call!(self, Block, ProviderRequest::Block { block_reference })
+            }
+
+            /// Fetches the protocol configuration through the layers.
+            async fn experimental_protocol_config(
+                &self,
+                block_reference: BlockReference,
+            ) -> Result<ProtocolConfigView, JsonRpcError<RpcProtocolConfigError>> {
+                layered_call!(
+                    self,
+                    ExperimentalProtocolConfig,
+                    ProviderRequest::ExperimentalProtocolConfig { block_reference }
+                )
+            }
+
+            /// Retrieves information about validators through the layers.
+            async fn validators(
+                &self,
+                epoch_reference: EpochReference,
+            ) -> Result<EpochValidatorInfo, JsonRpcError<RpcValidatorError>> {
+                layered_call!(
+                    self,
+                    Validators,
+                    ProviderRequest::Validators { epoch_reference }
+                )
+            }
+
+            /// Fetches the gas price through the layers.
+            async fn gas_price(
+                &self,
+                block_id: Option<BlockId>,
+            ) -> Result<GasPriceView, JsonRpcError<RpcGasPriceError>> {
+                layered_call!(self, GasPrice, ProviderRequest::GasPrice { block_id })
+            }
+
+            /// Checks the health of the node through the layers.
+            async fn health(&self) -> Result<RpcHealthResponse, JsonRpcError<RpcStatusError>> {
+                layered_call!(self, Health, ProviderRequest::Health)
+            }
+
+            /// Fetches the network information of the node through the layers.
+            async fn network_info(
+                &self,
+            ) -> Result<RpcNetworkInfoResponse, JsonRpcError<RpcNetworkInfoError>> {
+                layered_call!(self, NetworkInfo, ProviderRequest::NetworkInfo)
+            }
+
+            /// Fetches the genesis configuration through the layers.
+            async fn experimental_genesis_config(
+                &self,
+            ) -> Result<
+                GenesisConfig,
+                JsonRpcError<methods::EXPERIMENTAL_genesis_config::RpcGenesisConfigError>,
+            > {
+                layered_call!(
+                    self,
+                    ExperimentalGenesisConfig,
+                    ProviderRequest::ExperimentalGenesisConfig
+                )
+            }
+
+            /// Fetches the client configuration of the node through the layers.
+            async fn client_config(
+                &self,
+            ) -> Result<RpcClientConfigResponse, JsonRpcError<RpcClientConfigError>> {
+                layered_call!(self, ClientConfig, ProviderRequest::ClientConfig)
+            }
+
+            /// Lists the state changes made in a block through the layers.
+            async fn changes_in_block(
+                &self,
+                block_reference: BlockReference,
+            ) -> Result<RpcStateChangesInBlockByTypeResponse, JsonRpcError<RpcStateChangesError>> {
+                layered_call!(
+                    self,
+                    ChangesInBlock,
+                    ProviderRequest::ChangesInBlock { block_reference }
+                )
+            }
+
+            /// Retrieves state changes through the layers.
+            async fn changes(
+                &self,
+                request: StateChangesRequestView,
+                block_reference: BlockReference,
+            ) -> Result<RpcStateChangesInBlockResponse, JsonRpcError<RpcStateChangesError>> {
+                layered_call!(
+                    self,
+                    Changes,
+                    ProviderRequest::Changes {
+                        request,
+                        block_reference,
+                    }
+                )
+            }
+
+            /// Retrieves the status of a transaction and its receipts through the layers.
+            async fn tx_status_with_receipts(
+                &self,
+                transaction_info: TransactionInfo,
+                wait_until: TxExecutionStatus,
+            ) -> Result<RpcTransactionWithReceiptsResponse, JsonRpcError<RpcTransactionError>> {
+                layered_call!(
+                    self,
+                    TxStatusWithReceipts,
+                    ProviderRequest::TxStatusWithReceipts {
+                        transaction_info,
+                        wait_until,
+                    }
+                )
+            }
+
+            /// Retrieves a receipt through the layers.
+            async fn receipt(
+                &self,
+                receipt_id: CryptoHash,
+            ) -> Result<ReceiptView, JsonRpcError<RpcReceiptError>> {
+                layered_call!(self, Receipt, ProviderRequest::Receipt { receipt_id })
+            }
+
+            /// Retrieves an execution proof through the layers.
+            async fn light_client_proof(
+                &self,
+                id: TransactionOrReceiptId,
+                light_client_head: CryptoHash,
+            ) -> Result<RpcLightClientExecutionProofResponse, JsonRpcError<RpcLightClientProofError>> {
+                layered_call!(
+                    self,
+                    LightClientProof,
+                    ProviderRequest::LightClientProof {
+                        id,
+                        light_client_head,
+                    }
+                )
+            }
+
+            /// Retrieves the next light client block through the layers.
+            async fn next_light_client_block(
+                &self,
+                last_block_hash: CryptoHash,
+            ) -> Result<Option<LightClientBlockView>, JsonRpcError<RpcLightClientNextBlockError>> {
+                layered_call!(
+                    self,
+                    NextLightClientBlock,
+                    ProviderRequest::NextLightClientBlock { last_block_hash }
+                )
+            }
+
+            $($batch)*
+        }
+    };
+}
+
+impl_layered_provider!([P: Provider + Send + Sync] LayeredProvider<P>, {
+    /// Sends each request of the batch through the layers, and the requests reaching the inner provider together
+    /// as a batch of the inner provider.
+    async fn batch(&self, requests: Vec<ProviderRequest>) -> Vec<ProviderResponse> {
+        if self.middlewares.is_empty() {
+            return self.inner.batch(requests).await;
+        }
+        self.run_batch(requests).await
+    }
+
+    /// Returns whether the inner provider batches `request` if there are no layers, as a layer may send the requests
+    /// reaching it on their own.
+    fn is_batched(&self, request: &ProviderRequest) -> bool {
+        self.middlewares.is_empty() && self.inner.is_batched(request)
+    }
+});
+
+// The layers below a middleware are a `Provider` themselves, so the provider wrappers of this crate can run as
+// middlewares over them, see `RetryLayer`, `RateLimitLayer` and `CacheLayer`.
+impl_layered_provider!(['a] Next<'a>);
+impl_layered_provider!([] BatchCollector);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::caching_provider::CacheLayer;
+    use crate::rate_limit_provider::{RateLimit, RateLimitLayer};
+    use crate::retry_provider::{RetryLayer, RetryPolicy};
+    use crate::testing::{block_view, internal_error, status_response, MockProvider};
+    use near_primitives::types::Finality;
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    /// Records the order in which the layers see requests and responses.
+    struct Trace {
+        name: &'static str,
+        events: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl Middleware for Trace {
+        async fn handle(&self, request: ProviderRequest, next: Next<'_>) -> ProviderResponse {
+            let method = request.method().rpc_method_name();
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("{} > {}", self.name, method));
+            let response = next.run(request).await;
+            self.events.lock().unwrap().push(format!(
+                "{} < {} ok={}",
+                self.name,
+                method,
+                response.is_ok()
+            ));
+            response
+        }
+    }
+
+    /// Runs a failed request once more.
+    struct RetryOnce;
+
+    #[async_trait]
+    impl Middleware for RetryOnce {
+        async fn handle(&self, request: ProviderRequest, next: Next<'_>) -> ProviderResponse {
+            let response = next.run(request.clone()).await;
+            if response.is_ok() {
+                return response;
+            }
+            next.run(request).await
+        }
+    }
+
+    /// Rejects sending transactions without reaching the provider.
+    struct ReadOnly;
+
+    #[async_trait]
+    impl Middleware for ReadOnly {
+        async fn handle(&self, request: ProviderRequest, next: Next<'_>) -> ProviderResponse {
+            if request.signed_transaction().is_some() {
+                return ProviderResponse::internal_error(request.method(), "read only provider");
+            }
+            next.run(request).await
+        }
+    }
+
+    /// Answers every request with a status response.
+    struct Misbehaving;
+
+    #[async_trait]
+    impl Middleware for Misbehaving {
+        async fn handle(&self, _request: ProviderRequest, _next: Next<'_>) -> ProviderResponse {
+            ProviderResponse::Status(Ok(status_response("testnet")))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_layers_run_in_order() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let provider = ProviderBuilder::new()
+            .with_layer(Trace {
+                name: "outer",
+                events: events.clone(),
+            })
+            .with_layer(RetryOnce)
+            .with_layer(Trace {
+                name: "inner",
+                events: events.clone(),
+            })
+            .build(MockProvider::new());
+        provider
+            .inner()
+            .push_block(Err(internal_error("node is down")));
+        provider
+            .inner()
+            .push_block(Ok(block_view(10, CryptoHash::default())));
+
+        let block = provider
+            .block(BlockReference::Finality(Finality::Final))
+            .await
+            .unwrap();
+        assert_eq!(block.header.height, 10);
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                "outer > block",
+                "inner > block",
+                "inner < block ok=false",
+                "inner > block",
+                "inner < block ok=true",
+                "outer < block ok=true",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_layer_short_circuits() {
+        let provider = ProviderBuilder::new()
+            .with_layer(ReadOnly)
+            .build(MockProvider::new());
+        provider.inner().push_status(Ok(status_response("testnet")));
+
+        let signer = near_crypto::InMemorySigner::from_seed(
+            "alice.near".parse().unwrap(),
+            near_crypto::KeyType::ED25519,
+            "alice.near",
+        );
+        let signed_transaction = SignedTransaction::send_money(
+            1,
+            "alice.near".parse().unwrap(),
+            "bob.near".parse().unwrap(),
+            &signer,
+            100,
+            CryptoHash::default(),
+        );
+        assert!(matches!(
+            provider.send_transaction(signed_transaction).await,
+            Err(JsonRpcError::ServerError(JsonRpcServerError::InternalError { info: Some(info) }))
+                if info == "read only provider"
+        ));
+        assert!(provider.inner().signed_transactions().is_empty());
+        assert_eq!(provider.status().await.unwrap().chain_id, "testnet");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_mixed_stack() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let no_jitter = RetryPolicy {
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        };
+        let provider = ProviderBuilder::new()
+            .with_layer(CacheLayer::new())
+            .with_layer(RetryLayer::new().with_policy(no_jitter))
+            .with_layer(RateLimitLayer::new(RateLimit::per_second(1.0)).unwrap())
+            .with_layer(Trace {
+                name: "node",
+                events: events.clone(),
+            })
+            .build(MockProvider::new());
+        let hash = CryptoHash::hash_bytes(b"block");
+        provider
+            .inner()
+            .push_block(Err(internal_error("node is down")));
+        provider.inner().push_block(Ok(block_view(10, hash)));
+
+        let started = Instant::now();
+        let by_hash = BlockReference::BlockId(BlockId::Hash(hash));
+        let block = provider.block(by_hash.clone()).await.unwrap();
+        assert_eq!(block.header.height, 10);
+        // The retry waits for the backoff, then for the rate limit to hand out the next token.
+        assert_eq!(started.elapsed(), Duration::from_secs(1));
+
+        let cached = provider.block(by_hash).await.unwrap();
+        assert_eq!(cached.header.hash, hash);
+        assert_eq!(started.elapsed(), Duration::from_secs(1));
+        assert_eq!(provider.inner().call_count(ProviderMethod::Block), 2);
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                "node > block",
+                "node < block ok=false",
+                "node > block",
+                "node < block ok=true",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mismatched_response() {
+        let provider = ProviderBuilder::new()
+            .with_layer(Misbehaving)
+            .build(MockProvider::new());
+
+        assert!(matches!(
+            provider.block(BlockReference::Finality(Finality::Final)).await,
+            Err(JsonRpcError::ServerError(JsonRpcServerError::InternalError { info: Some(info) }))
+                if info == "middleware answered a block request with a status response"
+        ));
+    }
+}
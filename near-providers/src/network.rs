@@ -0,0 +1,164 @@
+//! The `network` module names the NEAR networks a provider can connect to, with their chain ids and default RPC
+//! endpoints, and verifies that a provider is connected to the network it is expected to be.
+//!
+//! A key that controls funds on mainnet must never sign a transaction that is sent to testnet, or the other way
+//! around. `Network::verify` asks the node behind a provider for its chain id with `status` and compares it to the
+//! chain id of the network.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use near_providers::{JsonRpcProvider, Network};
+//!
+//! # async fn run() -> Result<(), near_providers::network::NetworkError> {
+//! let network = Network::Testnet;
+//! let provider = JsonRpcProvider::for_network_verified(&network).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::jsonrpc_client::errors::JsonRpcError;
+use crate::types::status::RpcStatusError;
+use crate::Provider;
+use std::fmt;
+use std::str::FromStr;
+
+/// A NEAR network, identified by its chain id.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    /// A node run locally, e.g. with `neard init` and `neard run`, listening on the default RPC port.
+    Localnet,
+    /// Any other network, such as a private shardnet or a local node listening on another port.
+    Custom {
+        chain_id: String,
+        rpc_url: String,
+        archival_rpc_url: Option<String>,
+    },
+}
+
+impl Network {
+    /// Creates a custom network with the given chain id and RPC endpoint, without an archival endpoint.
+    pub fn custom(chain_id: &str, rpc_url: &str) -> Self {
+        Network::Custom {
+            chain_id: chain_id.to_string(),
+            rpc_url: rpc_url.to_string(),
+            archival_rpc_url: None,
+        }
+    }
+
+    /// Returns the chain id reported by the nodes of the network.
+    pub fn chain_id(&self) -> &str {
+        match self {
+            Network::Mainnet => "mainnet",
+            Network::Testnet => "testnet",
+            Network::Localnet => "localnet",
+            Network::Custom { chain_id, .. } => chain_id,
+        }
+    }
+
+    /// Returns the default RPC endpoint of the network.
+    pub fn rpc_url(&self) -> &str {
+        match self {
+            Network::Mainnet => "https://rpc.mainnet.near.org",
+            Network::Testnet => "https://rpc.testnet.near.org",
+            Network::Localnet => "http://127.0.0.1:3030",
+            Network::Custom { rpc_url, .. } => rpc_url,
+        }
+    }
+
+    /// Returns the default archival RPC endpoint of the network, which serves blocks and state older than the
+    /// garbage collection window of regular nodes. A local node has none.
+    pub fn archival_rpc_url(&self) -> Option<&str> {
+        match self {
+            Network::Mainnet => Some("https://archival-rpc.mainnet.near.org"),
+            Network::Testnet => Some("https://archival-rpc.testnet.near.org"),
+            Network::Localnet => None,
+            Network::Custom {
+                archival_rpc_url, ..
+            } => archival_rpc_url.as_deref(),
+        }
+    }
+
+    /// Checks that the node behind `provider` reports the chain id of the network.
+    pub async fn verify<P: Provider + ?Sized>(&self, provider: &P) -> Result<(), NetworkError> {
+        let status = provider.status().await?;
+        if status.chain_id != self.chain_id() {
+            return Err(NetworkError::ChainIdMismatch {
+                expected: self.chain_id().to_string(),
+                actual: status.chain_id,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.chain_id())
+    }
+}
+
+/// Parses `mainnet`, `testnet` and `localnet`. Any other chain id is rejected, as its RPC endpoint is not known.
+impl FromStr for Network {
+    type Err = NetworkError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mainnet" => Ok(Network::Mainnet),
+            "testnet" => Ok(Network::Testnet),
+            "localnet" => Ok(Network::Localnet),
+            _ => Err(NetworkError::UnknownNetwork(s.to_string())),
+        }
+    }
+}
+
+/// The reasons a provider can't be used for a network.
+#[derive(Debug, thiserror::Error)]
+pub enum NetworkError {
+    #[error("Unknown network {0:?}, expected mainnet, testnet or localnet")]
+    UnknownNetwork(String),
+    #[error("The provider is connected to chain {actual:?}, but {expected:?} was expected")]
+    ChainIdMismatch { expected: String, actual: String },
+    #[error("Failed to fetch the chain id of the provider: {0}")]
+    Status(#[from] JsonRpcError<RpcStatusError>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{status_response, MockProvider};
+
+    #[test]
+    fn test_presets() {
+        assert_eq!("mainnet".parse::<Network>().unwrap(), Network::Mainnet);
+        assert_eq!(Network::Testnet.rpc_url(), "https://rpc.testnet.near.org");
+        assert_eq!(Network::Localnet.archival_rpc_url(), None);
+        assert_eq!(Network::Mainnet.to_string(), "mainnet");
+
+        let network = Network::custom("shardnet", "http://127.0.0.1:4040");
+        assert_eq!(network.chain_id(), "shardnet");
+        assert_eq!(network.rpc_url(), "http://127.0.0.1:4040");
+        assert!(matches!(
+            "shardnet".parse::<Network>(),
+            Err(NetworkError::UnknownNetwork(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_verify() {
+        let provider = MockProvider::new();
+        provider.push_status(Ok(status_response("testnet")));
+        provider.push_status(Ok(status_response("testnet")));
+
+        Network::Testnet.verify(&provider).await.unwrap();
+        match Network::Mainnet.verify(&provider).await {
+            Err(NetworkError::ChainIdMismatch { expected, actual }) => {
+                assert_eq!(expected, "mainnet");
+                assert_eq!(actual, "testnet");
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,630 @@
+//! The `payment` module confirms the receiving side of a payment: it waits until an account has received at least an
+//! amount of NEAR from another account, e.g. sent with `Account::send_money`, or times out.
+//!
+//! The `PaymentConfirmation` scans the blocks for the transactions signed by the sender to the receiver with an
+//! `AccountWatcher`, and follows each one with `Provider::tx_status` until it reaches the execution status waited
+//! for, `TxExecutionStatus::Final` by default. A transaction goes through these stages:
+//!
+//! - `Included`, once it is found in a chunk of a scanned block.
+//! - `ExecutedOptimistic`, once its receipts are executed, at which point a failed transfer receipt is known.
+//! - The status waited for, if it is stronger than `ExecutedOptimistic`, once its transferred amount is counted.
+//!
+//! Each stage is reported as a `PaymentProgress`. A transaction whose transaction or receipt outcome failed ends the
+//! wait with a `PaymentError` naming it, e.g. a transfer to an account that does not exist. Only the `Transfer`
+//! actions of transactions signed by the sender are counted: the deposits of function calls and transfers made by
+//! contracts are not.
+//!
+//! Failed requests end the wait as well, except for a node timing out while waiting for the status of a
+//! transaction. Wrap the provider in a `RetryProvider` to retry transient failures.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use near_providers::block_stream::StartAt;
+//! use near_providers::payment::PaymentConfirmation;
+//! use near_providers::JsonRpcProvider;
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! # async fn run() {
+//! let provider = Arc::new(JsonRpcProvider::new("https://rpc.testnet.near.org"));
+//! let confirmation = PaymentConfirmation::new(
+//!     provider,
+//!     "customer.testnet".parse().unwrap(),
+//!     "merchant.testnet".parse().unwrap(),
+//!     10u128.pow(24),
+//! )
+//! .with_timeout(Duration::from_secs(120));
+//! let result = confirmation
+//!     .wait_with_progress(|progress| {
+//!         println!("{} is {:?}", progress.tx_hash, progress.status);
+//!     })
+//!     .await;
+//! match result {
+//!     Ok(payment) => println!("received {} yoctoNEAR", payment.received),
+//!     Err(err) => eprintln!("{}", err),
+//! }
+//! # }
+//! ```
+
+use crate::account_watcher::{AccountEventKind, AccountWatcher, EventOrigin};
+use crate::block_stream::{BlockStreamError, StartAt, DEFAULT_POLL_INTERVAL};
+use crate::jsonrpc_client::errors::JsonRpcError;
+use crate::types::transactions::{RpcTransactionError, TransactionInfo};
+use crate::Provider;
+use futures::StreamExt;
+use near_primitives::errors::TxExecutionError;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{AccountId, Balance, Finality};
+use near_primitives::views::{
+    ActionView, ExecutionStatusView, FinalExecutionOutcomeView, FinalExecutionStatus,
+    TxExecutionStatus,
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The time to wait for a payment, unless configured otherwise with `PaymentConfirmation::with_timeout`.
+pub const DEFAULT_PAYMENT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// A stage reached by a transaction of a payment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentProgress {
+    pub tx_hash: CryptoHash,
+    /// The yoctoNEAR transferred by the transaction.
+    pub amount: Balance,
+    /// `Included`, `ExecutedOptimistic` or the status waited for.
+    pub status: TxExecutionStatus,
+    /// The yoctoNEAR received so far, including the amount of the transaction once it reached the status waited for.
+    pub received: Balance,
+}
+
+/// A confirmed payment.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfirmedPayment {
+    /// The yoctoNEAR received, which may exceed the amount waited for.
+    pub received: Balance,
+    /// The transactions the amount was received in, in block order.
+    pub transactions: Vec<CryptoHash>,
+}
+
+/// The reasons a payment is not confirmed.
+#[derive(Debug, thiserror::Error)]
+pub enum PaymentError {
+    #[error(transparent)]
+    Blocks(#[from] BlockStreamError),
+    #[error("Failed to fetch the status of transaction {tx_hash}: {source}")]
+    TxStatus {
+        tx_hash: CryptoHash,
+        source: Box<JsonRpcError<RpcTransactionError>>,
+    },
+    #[error("Transaction {tx_hash} failed: {error}")]
+    TransactionFailed {
+        tx_hash: CryptoHash,
+        error: Box<TxExecutionError>,
+    },
+    #[error("Receipt {receipt_id} of transaction {tx_hash} failed: {error}")]
+    ReceiptFailed {
+        tx_hash: CryptoHash,
+        receipt_id: CryptoHash,
+        error: Box<TxExecutionError>,
+    },
+    #[error("Timed out after receiving {received} of {expected} yoctoNEAR")]
+    Timeout {
+        received: Balance,
+        expected: Balance,
+    },
+}
+
+/// Waits for a payment, see the module documentation.
+pub struct PaymentConfirmation<P: ?Sized> {
+    provider: Arc<P>,
+    sender_id: AccountId,
+    receiver_id: AccountId,
+    amount: Balance,
+    start: StartAt,
+    scan_finality: Finality,
+    wait_until: TxExecutionStatus,
+    timeout: Duration,
+    poll_interval: Duration,
+}
+
+impl<P: Provider + Send + Sync + ?Sized + 'static> PaymentConfirmation<P> {
+    /// Creates a confirmation that `receiver_id` received at least `amount` yoctoNEAR from `sender_id`, in blocks
+    /// from the latest one on.
+    pub fn new(
+        provider: Arc<P>,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        amount: Balance,
+    ) -> Self {
+        Self {
+            provider,
+            sender_id,
+            receiver_id,
+            amount,
+            start: StartAt::LatestFinal,
+            scan_finality: Finality::DoomSlug,
+            wait_until: TxExecutionStatus::Final,
+            timeout: DEFAULT_PAYMENT_TIMEOUT,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+        }
+    }
+
+    /// Scans the blocks from `start` on, e.g. from the block before the payment was requested.
+    pub fn starting_at(mut self, start: StartAt) -> Self {
+        self.start = start;
+        self
+    }
+
+    /// Sets the finality of the scanned blocks, `Finality::DoomSlug` by default to see transactions soon after they
+    /// are included. A transaction is counted once it reaches the status waited for regardless.
+    pub fn with_scan_finality(mut self, finality: Finality) -> Self {
+        self.scan_finality = finality;
+        self
+    }
+
+    /// Counts a transaction once it reaches `wait_until`, `TxExecutionStatus::Final` by default. A status weaker
+    /// than `ExecutedOptimistic`, `IncludedFinal` included, is treated as `ExecutedOptimistic`, as a transfer may
+    /// fail until it is executed.
+    pub fn with_wait_until(mut self, wait_until: TxExecutionStatus) -> Self {
+        self.wait_until = wait_until;
+        self
+    }
+
+    /// Sets the time to wait for the payment, 5 minutes by default.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the time to wait between polls for new blocks and for the status of a transaction, 1 second by default.
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Waits until the payment is confirmed.
+    pub async fn wait(self) -> Result<ConfirmedPayment, PaymentError> {
+        self.wait_with_progress(|_| {}).await
+    }
+
+    /// Waits until the payment is confirmed, calling `on_progress` whenever one of its transactions reaches a stage.
+    pub async fn wait_with_progress<F>(
+        self,
+        mut on_progress: F,
+    ) -> Result<ConfirmedPayment, PaymentError>
+    where
+        F: FnMut(&PaymentProgress) + Send,
+    {
+        let mut payment = ConfirmedPayment::default();
+        match tokio::time::timeout(self.timeout, self.run(&mut payment, &mut on_progress)).await {
+            Ok(result) => result.map(|()| payment),
+            Err(_) => Err(PaymentError::Timeout {
+                received: payment.received,
+                expected: self.amount,
+            }),
+        }
+    }
+
+    /// Adds the transactions of the payment to `payment` until it reaches the amount.
+    async fn run<F>(
+        &self,
+        payment: &mut ConfirmedPayment,
+        on_progress: &mut F,
+    ) -> Result<(), PaymentError>
+    where
+        F: FnMut(&PaymentProgress) + Send,
+    {
+        let mut events = AccountWatcher::new(
+            self.provider.clone(),
+            [self.sender_id.clone()],
+            self.start.clone(),
+        )
+        .with_finality(self.scan_finality.clone())
+        .with_poll_interval(self.poll_interval);
+
+        while payment.received < self.amount {
+            let event = events
+                .next()
+                .await
+                .expect("an account watcher only ends after yielding an error")?;
+            let (
+                EventOrigin::Transaction(tx_hash),
+                AccountEventKind::OutgoingTransaction {
+                    receiver_id,
+                    actions,
+                },
+            ) = (event.origin, &event.kind)
+            else {
+                continue;
+            };
+            let amount = transferred_amount(actions);
+            if *receiver_id != self.receiver_id || amount == 0 {
+                continue;
+            }
+
+            let mut progress = PaymentProgress {
+                tx_hash,
+                amount,
+                status: TxExecutionStatus::Included,
+                received: payment.received,
+            };
+            on_progress(&progress);
+            let stages = self.stages();
+            for (index, status) in stages.iter().enumerate() {
+                self.wait_for(tx_hash, status.clone()).await?;
+                if index == stages.len() - 1 {
+                    payment.received += amount;
+                    payment.transactions.push(tx_hash);
+                }
+                progress.status = status.clone();
+                progress.received = payment.received;
+                on_progress(&progress);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the statuses a transaction is followed through once included, the last one being the status waited for.
+    fn stages(&self) -> Vec<TxExecutionStatus> {
+        match &self.wait_until {
+            TxExecutionStatus::None
+            | TxExecutionStatus::Included
+            | TxExecutionStatus::IncludedFinal
+            | TxExecutionStatus::ExecutedOptimistic => vec![TxExecutionStatus::ExecutedOptimistic],
+            wait_until => vec![TxExecutionStatus::ExecutedOptimistic, wait_until.clone()],
+        }
+    }
+
+    /// Waits until the transaction `tx_hash` reaches `status`, failing if one of its outcomes failed.
+    async fn wait_for(
+        &self,
+        tx_hash: CryptoHash,
+        status: TxExecutionStatus,
+    ) -> Result<(), PaymentError> {
+        loop {
+            let transaction_info = TransactionInfo::TransactionId {
+                tx_hash,
+                sender_account_id: self.sender_id.clone(),
+            };
+            match self
+                .provider
+                .tx_status(transaction_info, status.clone())
+                .await
+            {
+                Ok(response) => {
+                    if let Some(outcome) = response.final_execution_outcome {
+                        check_outcome(tx_hash, outcome.into_outcome())?;
+                        if reached(&response.final_execution_status, &status) {
+                            return Ok(());
+                        }
+                    }
+                }
+                // The node gave up waiting for the status, or did not see the transaction of a block it served yet.
+                Err(err)
+                    if matches!(
+                        err.handler_error(),
+                        Some(RpcTransactionError::TimeoutError)
+                            | Some(RpcTransactionError::UnknownTransaction { .. })
+                    ) => {}
+                Err(source) => {
+                    return Err(PaymentError::TxStatus {
+                        tx_hash,
+                        source: Box::new(source),
+                    })
+                }
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+}
+
+/// Returns the yoctoNEAR transferred by the `Transfer` actions of a transaction.
+fn transferred_amount(actions: &[ActionView]) -> Balance {
+    actions
+        .iter()
+        .map(|action| match action {
+            ActionView::Transfer { deposit } => *deposit,
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Fails with the first failed receipt of `outcome`, or with the failure of the transaction itself.
+fn check_outcome(
+    tx_hash: CryptoHash,
+    outcome: FinalExecutionOutcomeView,
+) -> Result<(), PaymentError> {
+    for receipt in outcome.receipts_outcome {
+        if let ExecutionStatusView::Failure(error) = receipt.outcome.status {
+            return Err(PaymentError::ReceiptFailed {
+                tx_hash,
+                receipt_id: receipt.id,
+                error: Box::new(error),
+            });
+        }
+    }
+    match outcome.status {
+        FinalExecutionStatus::Failure(error) => Err(PaymentError::TransactionFailed {
+            tx_hash,
+            error: Box::new(error),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Returns whether `status` is at least `expected`. `IncludedFinal` only says the block including the transaction is
+/// final, not that its receipts were executed, so it ranks with `Included`.
+fn reached(status: &TxExecutionStatus, expected: &TxExecutionStatus) -> bool {
+    let rank = |status: &TxExecutionStatus| match status {
+        TxExecutionStatus::None => 0,
+        TxExecutionStatus::Included | TxExecutionStatus::IncludedFinal => 1,
+        TxExecutionStatus::ExecutedOptimistic => 2,
+        TxExecutionStatus::Executed => 3,
+        TxExecutionStatus::Final => 4,
+    };
+    rank(status) >= rank(expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::chain::{serve_chain, transaction_view, tx_status_response};
+    use crate::testing::{handler_error, MockProvider};
+    use crate::{ProviderMethod, ProviderRequest};
+    use near_primitives::errors::ActionError;
+    use near_primitives::views::SignedTransactionView;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    fn transfer(hash: &[u8], receiver_id: &str, deposit: Balance) -> SignedTransactionView {
+        let actions = vec![ActionView::Transfer { deposit }];
+        transaction_view(
+            CryptoHash::hash_bytes(hash),
+            "bob.near",
+            receiver_id,
+            actions,
+        )
+    }
+
+    /// Answers `tx_status` calls for `transactions` at the status waited for, with their receipt statuses.
+    fn serve_statuses(
+        provider: &MockProvider,
+        transactions: Vec<(
+            SignedTransactionView,
+            Vec<(CryptoHash, ExecutionStatusView)>,
+        )>,
+    ) {
+        let transactions: HashMap<_, _> = transactions
+            .into_iter()
+            .map(|(transaction, receipts)| (transaction.hash, (transaction, receipts)))
+            .collect();
+        provider.on_tx_status(move |transaction_info, wait_until| {
+            let TransactionInfo::TransactionId { tx_hash, .. } = transaction_info else {
+                return None;
+            };
+            let (transaction, receipts) = transactions.get(tx_hash)?;
+            Some(Ok(tx_status_response(
+                transaction,
+                wait_until.clone(),
+                receipts.clone(),
+            )))
+        });
+    }
+
+    fn confirmation(
+        provider: &Arc<MockProvider>,
+        amount: Balance,
+    ) -> PaymentConfirmation<MockProvider> {
+        PaymentConfirmation::new(
+            provider.clone(),
+            "bob.near".parse().unwrap(),
+            "alice.near".parse().unwrap(),
+            amount,
+        )
+        .starting_at(StartAt::Height(10))
+    }
+
+    #[tokio::test]
+    async fn test_confirmed_payment() {
+        let provider = Arc::new(MockProvider::new());
+        let first = transfer(b"t1", "alice.near", 3);
+        let other = transfer(b"t2", "carol.near", 10);
+        let second = transfer(b"t3", "alice.near", 4);
+        let contents = HashMap::from([
+            (10, (vec![first.clone(), other.clone()], vec![])),
+            (11, (vec![second.clone()], vec![])),
+        ]);
+        serve_chain(&provider, &[10, 11], contents);
+        serve_statuses(
+            &provider,
+            vec![(first.clone(), vec![]), (second.clone(), vec![])],
+        );
+        // The node times out once while waiting for the first transaction to be final.
+        provider.push_tx_status(Ok(tx_status_response(
+            &first,
+            TxExecutionStatus::ExecutedOptimistic,
+            vec![],
+        )));
+        provider.push_tx_status(Err(handler_error(RpcTransactionError::TimeoutError)));
+
+        let progress = Mutex::new(Vec::new());
+        let payment = confirmation(&provider, 5)
+            .with_poll_interval(Duration::from_millis(1))
+            .wait_with_progress(|update| {
+                progress.lock().unwrap().push((
+                    update.tx_hash,
+                    update.status.clone(),
+                    update.received,
+                ))
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(payment.received, 7);
+        assert_eq!(payment.transactions, vec![first.hash, second.hash]);
+        assert_eq!(
+            progress.into_inner().unwrap(),
+            vec![
+                (first.hash, TxExecutionStatus::Included, 0),
+                (first.hash, TxExecutionStatus::ExecutedOptimistic, 0),
+                (first.hash, TxExecutionStatus::Final, 3),
+                (second.hash, TxExecutionStatus::Included, 3),
+                (second.hash, TxExecutionStatus::ExecutedOptimistic, 3),
+                (second.hash, TxExecutionStatus::Final, 7),
+            ]
+        );
+        let statuses: Vec<_> = provider
+            .calls()
+            .into_iter()
+            .filter_map(|call| match call {
+                ProviderRequest::TxStatus { wait_until, .. } => Some(wait_until),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                TxExecutionStatus::ExecutedOptimistic,
+                TxExecutionStatus::Final,
+                TxExecutionStatus::Final,
+                TxExecutionStatus::ExecutedOptimistic,
+                TxExecutionStatus::Final,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_included_final_is_not_executed() {
+        let provider = Arc::new(MockProvider::new());
+        let payment = transfer(b"t1", "alice.near", 5);
+        serve_chain(
+            &provider,
+            &[10],
+            HashMap::from([(10, (vec![payment.clone()], vec![]))]),
+        );
+        serve_statuses(&provider, vec![(payment.clone(), vec![])]);
+        // The node first answers with the block of the transaction final, but its receipt not executed yet.
+        let receipts = vec![(CryptoHash::hash_bytes(b"r1"), ExecutionStatusView::Unknown)];
+        provider.push_tx_status(Ok(tx_status_response(
+            &payment,
+            TxExecutionStatus::IncludedFinal,
+            receipts,
+        )));
+
+        let progress = Mutex::new(Vec::new());
+        let confirmed = confirmation(&provider, 5)
+            .with_poll_interval(Duration::from_millis(1))
+            .wait_with_progress(|update| progress.lock().unwrap().push(update.status.clone()))
+            .await
+            .unwrap();
+
+        assert_eq!(confirmed.received, 5);
+        assert_eq!(
+            progress.into_inner().unwrap(),
+            vec![
+                TxExecutionStatus::Included,
+                TxExecutionStatus::ExecutedOptimistic,
+                TxExecutionStatus::Final,
+            ]
+        );
+        let statuses: Vec<_> = provider
+            .calls()
+            .into_iter()
+            .filter_map(|call| match call {
+                ProviderRequest::TxStatus { wait_until, .. } => Some(wait_until),
+                _ => None,
+            })
+            .collect();
+        // The transaction is asked about again until it is executed.
+        assert_eq!(
+            statuses,
+            vec![
+                TxExecutionStatus::ExecutedOptimistic,
+                TxExecutionStatus::ExecutedOptimistic,
+                TxExecutionStatus::Final,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wait_until_included_final() {
+        let provider = Arc::new(MockProvider::new());
+        let payment = transfer(b"t1", "alice.near", 5);
+        serve_chain(
+            &provider,
+            &[10],
+            HashMap::from([(10, (vec![payment.clone()], vec![]))]),
+        );
+        serve_statuses(&provider, vec![(payment.clone(), vec![])]);
+
+        let progress = Mutex::new(Vec::new());
+        let confirmed = confirmation(&provider, 5)
+            .with_wait_until(TxExecutionStatus::IncludedFinal)
+            .wait_with_progress(|update| progress.lock().unwrap().push(update.status.clone()))
+            .await
+            .unwrap();
+
+        // The transfer is counted once executed, the weaker `IncludedFinal` is not reported as a later stage.
+        assert_eq!(confirmed.received, 5);
+        assert_eq!(
+            progress.into_inner().unwrap(),
+            vec![
+                TxExecutionStatus::Included,
+                TxExecutionStatus::ExecutedOptimistic,
+            ]
+        );
+        assert_eq!(provider.call_count(ProviderMethod::TxStatus), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failed_receipt() {
+        let provider = Arc::new(MockProvider::new());
+        let payment = transfer(b"t1", "alice.near", 5);
+        serve_chain(
+            &provider,
+            &[10],
+            HashMap::from([(10, (vec![payment.clone()], vec![]))]),
+        );
+        let receipt_id = CryptoHash::hash_bytes(b"r1");
+        let error = TxExecutionError::ActionError(ActionError {
+            index: Some(0),
+            kind: near_primitives::errors::ActionErrorKind::AccountDoesNotExist {
+                account_id: "alice.near".parse().unwrap(),
+            },
+        });
+        let receipts = vec![(receipt_id, ExecutionStatusView::Failure(error))];
+        serve_statuses(&provider, vec![(payment.clone(), receipts)]);
+
+        let result = confirmation(&provider, 5).wait().await;
+        assert!(matches!(
+            result,
+            Err(PaymentError::ReceiptFailed { tx_hash, receipt_id: id, .. })
+                if tx_hash == payment.hash && id == receipt_id
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timeout() {
+        let provider = Arc::new(MockProvider::new());
+        let partial = transfer(b"t1", "alice.near", 2);
+        serve_chain(
+            &provider,
+            &[10],
+            HashMap::from([(10, (vec![partial.clone()], vec![]))]),
+        );
+        serve_statuses(&provider, vec![(partial, vec![])]);
+
+        let result = confirmation(&provider, 5)
+            .with_timeout(Duration::from_secs(30))
+            .wait()
+            .await;
+        assert!(matches!(
+            result,
+            Err(PaymentError::Timeout {
+                received: 2,
+                expected: 5
+            })
+        ));
+    }
+}
@@ -4,27 +4,44 @@
 //! The `Provider` trait is designed to be implemented by specific providers, with a JSON RPC provider currently implemented,
 //! allowing users to easily connect to and interact with the NEAR chain.
 
+use crate::batch::{self, DEFAULT_BATCH_CONCURRENCY};
+use crate::client_config::{RpcClientConfigError, RpcClientConfigResponse};
 use crate::jsonrpc_client::{
-    errors::JsonRpcError,
+    errors::{JsonRpcError, JsonRpcServerError, JsonRpcServerResponseStatusError},
     methods::{self, status::RpcStatusResponse},
 };
+use crate::tx_status::RpcTransactionWithReceiptsResponse;
 use crate::types::{
     blocks::RpcBlockError,
+    changes::{
+        RpcStateChangesError, RpcStateChangesInBlockByTypeResponse, RpcStateChangesInBlockResponse,
+    },
     chunks::{ChunkReference, RpcChunkError},
     config::RpcProtocolConfigError,
+    gas_price::RpcGasPriceError,
+    light_client::{
+        RpcLightClientExecutionProofResponse, RpcLightClientNextBlockError,
+        RpcLightClientProofError,
+    },
+    network_info::{RpcNetworkInfoError, RpcNetworkInfoResponse},
     query::{RpcQueryError, RpcQueryResponse},
-    status::RpcStatusError,
-    transactions::{RpcTransactionError, TransactionInfo},
+    receipts::RpcReceiptError,
+    status::{RpcHealthResponse, RpcStatusError},
+    transactions::{
+        RpcTransactionError, SignedTransaction as SignedTransactionParam, TransactionInfo,
+    },
     validator::RpcValidatorError,
 };
+use crate::utils::{clone_epoch_reference, clone_state_changes_request};
 use async_trait::async_trait;
-use near_chain_configs::ProtocolConfigView;
+use near_chain_configs::{GenesisConfig, ProtocolConfigView};
 use near_primitives::{
     hash::CryptoHash,
     transaction::SignedTransaction,
-    types::{BlockReference, EpochReference},
+    types::{BlockId, BlockReference, EpochReference, TransactionOrReceiptId},
     views::{
-        BlockView, ChunkView, EpochValidatorInfo, FinalExecutionOutcomeView, QueryRequest,
+        BlockView, ChunkView, EpochValidatorInfo, FinalExecutionOutcomeView, GasPriceView,
+        LightClientBlockView, QueryRequest, ReceiptView, StateChangesRequestView,
         TxExecutionStatus,
     },
 };
@@ -77,6 +94,20 @@ pub trait Provider {
         block_reference: BlockReference,
     ) -> Result<BlockView, JsonRpcError<RpcBlockError>>;
 
+    /// Fetches the status of a specific transaction like `tx_status`, with the receipts created by the transaction
+    /// included next to their execution outcomes.
+    async fn tx_status_with_receipts(
+        &self,
+        transaction_info: TransactionInfo,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionWithReceiptsResponse, JsonRpcError<RpcTransactionError>>;
+
+    /// Retrieves a receipt by its id, e.g. one of the receipts listed in the outcome of a transaction.
+    async fn receipt(
+        &self,
+        receipt_id: CryptoHash,
+    ) -> Result<ReceiptView, JsonRpcError<RpcReceiptError>>;
+
     /// Fetches information about validators for a specified epoch, identified by its epoch reference (epoch ID).
     async fn validators(
         &self,
@@ -84,9 +115,11 @@ pub trait Provider {
     ) -> Result<EpochValidatorInfo, JsonRpcError<RpcValidatorError>>;
 
     /// Performs a query to the NEAR blockchain, allowing to retrieve data based on a specific request (e.g., account details, contract state).
+    /// The query is evaluated at the block identified by `block_reference`, which can be a finality level, a block height or a block hash.
     async fn query(
         &self,
         request: QueryRequest,
+        block_reference: BlockReference,
     ) -> Result<RpcQueryResponse, JsonRpcError<RpcQueryError>>;
 
     /// Retrieves the protocol configuration data for a specific block, identified by its block reference.
@@ -94,4 +127,700 @@ pub trait Provider {
         &self,
         block_reference: BlockReference,
     ) -> Result<ProtocolConfigView, JsonRpcError<RpcProtocolConfigError>>;
+
+    /// Fetches the gas price of a specific block, identified by its height or hash, or of the latest block if `None`.
+    async fn gas_price(
+        &self,
+        block_id: Option<BlockId>,
+    ) -> Result<GasPriceView, JsonRpcError<RpcGasPriceError>>;
+
+    /// Checks the health of the node, returning an error if it is not able to serve requests.
+    async fn health(&self) -> Result<RpcHealthResponse, JsonRpcError<RpcStatusError>>;
+
+    /// Fetches information about the network connections of the node, such as its active peers.
+    async fn network_info(
+        &self,
+    ) -> Result<RpcNetworkInfoResponse, JsonRpcError<RpcNetworkInfoError>>;
+
+    /// Retrieves the genesis configuration of the network.
+    async fn experimental_genesis_config(
+        &self,
+    ) -> Result<
+        GenesisConfig,
+        JsonRpcError<methods::EXPERIMENTAL_genesis_config::RpcGenesisConfigError>,
+    >;
+
+    /// Retrieves the client configuration of the node, such as whether it is an archival node.
+    async fn client_config(
+        &self,
+    ) -> Result<RpcClientConfigResponse, JsonRpcError<RpcClientConfigError>>;
+
+    /// Lists the accounts, access keys, contract code and storage whose state changed in the block identified by
+    /// its block reference, without the changes themselves.
+    async fn changes_in_block(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<RpcStateChangesInBlockByTypeResponse, JsonRpcError<RpcStateChangesError>>;
+
+    /// Retrieves the state changes of a given kind made in the block identified by its block reference, e.g. the
+    /// changes of some accounts or of their access keys. The requests are built with the `changes` module.
+    async fn changes(
+        &self,
+        request: StateChangesRequestView,
+        block_reference: BlockReference,
+    ) -> Result<RpcStateChangesInBlockResponse, JsonRpcError<RpcStateChangesError>>;
+
+    /// Fetches the proof that a transaction or receipt was executed, i.e. the Merkle paths from its execution outcome
+    /// to the block that includes it and from that block to the light client head `light_client_head`.
+    /// The proof is checked with the `light_client` module.
+    async fn light_client_proof(
+        &self,
+        id: TransactionOrReceiptId,
+        light_client_head: CryptoHash,
+    ) -> Result<RpcLightClientExecutionProofResponse, JsonRpcError<RpcLightClientProofError>>;
+
+    /// Fetches the next light client block after the block with hash `last_block_hash`, i.e. a final block of the
+    /// next epoch, or the latest final block if `last_block_hash` is in the current epoch. Returns `None` if there is
+    /// no newer block to move to.
+    async fn next_light_client_block(
+        &self,
+        last_block_hash: CryptoHash,
+    ) -> Result<Option<LightClientBlockView>, JsonRpcError<RpcLightClientNextBlockError>>;
+
+    /// Makes the calls described by `requests`, returning their responses in the same order. Each response holds the
+    /// result of its own call, so one failed call does not fail the others.
+    ///
+    /// The default implementation calls the other methods of the provider, at most `DEFAULT_BATCH_CONCURRENCY` at a
+    /// time. See the `batch` module.
+    async fn batch(&self, requests: Vec<ProviderRequest>) -> Vec<ProviderResponse> {
+        batch::dispatch_concurrently(self, requests, DEFAULT_BATCH_CONCURRENCY).await
+    }
+
+    /// Returns true if `batch` sends `request` to the node together with other requests, in a single call, rather
+    /// than on its own. The `RateLimitProvider` takes a single token for such requests.
+    ///
+    /// The default implementation returns false, as the default `batch` makes a call per request.
+    fn is_batched(&self, _request: &ProviderRequest) -> bool {
+        false
+    }
+
+    /// Runs each query of `queries` at its block reference as a `batch`, returning their results in the same order.
+    #[allow(clippy::result_large_err)]
+    async fn query_batch(
+        &self,
+        queries: Vec<(QueryRequest, BlockReference)>,
+    ) -> Vec<Result<RpcQueryResponse, JsonRpcError<RpcQueryError>>> {
+        let requests = queries
+            .into_iter()
+            .map(|(request, block_reference)| ProviderRequest::Query {
+                request,
+                block_reference,
+            })
+            .collect();
+        self.batch(requests)
+            .await
+            .into_iter()
+            .map(|response| match response {
+                ProviderResponse::Query(result) => result,
+                response => Err(JsonRpcError::ServerError(
+                    JsonRpcServerError::InternalError {
+                        info: Some(format!(
+                            "batch answered a query with a {} response",
+                            response.method().rpc_method_name()
+                        )),
+                    },
+                )),
+            })
+            .collect()
+    }
+}
+
+/// Identifies a method of the `Provider` trait, e.g. to configure a wrapping provider per method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProviderMethod {
+    Status,
+    SendTransaction,
+    SendTransactionAsync,
+    SendTx,
+    TxStatus,
+    Chunk,
+    Block,
+    Validators,
+    Query,
+    ExperimentalProtocolConfig,
+    GasPrice,
+    Health,
+    NetworkInfo,
+    ExperimentalGenesisConfig,
+    ClientConfig,
+    ChangesInBlock,
+    Changes,
+    TxStatusWithReceipts,
+    Receipt,
+    LightClientProof,
+    NextLightClientBlock,
+}
+
+impl ProviderMethod {
+    /// Returns the name of the JSON RPC method this provider method maps to.
+    pub fn rpc_method_name(&self) -> &'static str {
+        match self {
+            ProviderMethod::Status => "status",
+            ProviderMethod::SendTransaction => "broadcast_tx_commit",
+            ProviderMethod::SendTransactionAsync => "broadcast_tx_async",
+            ProviderMethod::SendTx => "send_tx",
+            ProviderMethod::TxStatus => "tx",
+            ProviderMethod::Chunk => "chunk",
+            ProviderMethod::Block => "block",
+            ProviderMethod::Validators => "validators",
+            ProviderMethod::Query => "query",
+            ProviderMethod::ExperimentalProtocolConfig => "EXPERIMENTAL_protocol_config",
+            ProviderMethod::GasPrice => "gas_price",
+            ProviderMethod::Health => "health",
+            ProviderMethod::NetworkInfo => "network_info",
+            ProviderMethod::ExperimentalGenesisConfig => "EXPERIMENTAL_genesis_config",
+            ProviderMethod::ClientConfig => "client_config",
+            ProviderMethod::ChangesInBlock => "EXPERIMENTAL_changes_in_block",
+            ProviderMethod::Changes => "EXPERIMENTAL_changes",
+            ProviderMethod::TxStatusWithReceipts => "EXPERIMENTAL_tx_status",
+            ProviderMethod::Receipt => "EXPERIMENTAL_receipt",
+            ProviderMethod::LightClientProof => "light_client_proof",
+            ProviderMethod::NextLightClientBlock => "next_light_client_block",
+        }
+    }
+}
+
+/// A call to a `Provider` method together with its arguments, e.g. as recorded by a mock provider.
+#[derive(Debug)]
+pub enum ProviderRequest {
+    Status,
+    SendTransaction {
+        signed_transaction: SignedTransaction,
+    },
+    SendTransactionAsync {
+        signed_transaction: SignedTransaction,
+    },
+    SendTx {
+        signed_transaction: SignedTransaction,
+        wait_until: TxExecutionStatus,
+    },
+    TxStatus {
+        transaction_info: TransactionInfo,
+        wait_until: TxExecutionStatus,
+    },
+    Chunk {
+        chunk_reference: ChunkReference,
+    },
+    Block {
+        block_reference: BlockReference,
+    },
+    Validators {
+        epoch_reference: EpochReference,
+    },
+    Query {
+        request: QueryRequest,
+        block_reference: BlockReference,
+    },
+    ExperimentalProtocolConfig {
+        block_reference: BlockReference,
+    },
+    GasPrice {
+        block_id: Option<BlockId>,
+    },
+    Health,
+    NetworkInfo,
+    ExperimentalGenesisConfig,
+    ClientConfig,
+    ChangesInBlock {
+        block_reference: BlockReference,
+    },
+    Changes {
+        request: StateChangesRequestView,
+        block_reference: BlockReference,
+    },
+    TxStatusWithReceipts {
+        transaction_info: TransactionInfo,
+        wait_until: TxExecutionStatus,
+    },
+    Receipt {
+        receipt_id: CryptoHash,
+    },
+    LightClientProof {
+        id: TransactionOrReceiptId,
+        light_client_head: CryptoHash,
+    },
+    NextLightClientBlock {
+        last_block_hash: CryptoHash,
+    },
+}
+
+impl ProviderRequest {
+    /// Returns the `Provider` method this request is made to.
+    pub fn method(&self) -> ProviderMethod {
+        match self {
+            ProviderRequest::Status => ProviderMethod::Status,
+            ProviderRequest::SendTransaction { .. } => ProviderMethod::SendTransaction,
+            ProviderRequest::SendTransactionAsync { .. } => ProviderMethod::SendTransactionAsync,
+            ProviderRequest::SendTx { .. } => ProviderMethod::SendTx,
+            ProviderRequest::TxStatus { .. } => ProviderMethod::TxStatus,
+            ProviderRequest::Chunk { .. } => ProviderMethod::Chunk,
+            ProviderRequest::Block { .. } => ProviderMethod::Block,
+            ProviderRequest::Validators { .. } => ProviderMethod::Validators,
+            ProviderRequest::Query { .. } => ProviderMethod::Query,
+            ProviderRequest::ExperimentalProtocolConfig { .. } => {
+                ProviderMethod::ExperimentalProtocolConfig
+            }
+            ProviderRequest::GasPrice { .. } => ProviderMethod::GasPrice,
+            ProviderRequest::Health => ProviderMethod::Health,
+            ProviderRequest::NetworkInfo => ProviderMethod::NetworkInfo,
+            ProviderRequest::ExperimentalGenesisConfig => ProviderMethod::ExperimentalGenesisConfig,
+            ProviderRequest::ClientConfig => ProviderMethod::ClientConfig,
+            ProviderRequest::ChangesInBlock { .. } => ProviderMethod::ChangesInBlock,
+            ProviderRequest::Changes { .. } => ProviderMethod::Changes,
+            ProviderRequest::TxStatusWithReceipts { .. } => ProviderMethod::TxStatusWithReceipts,
+            ProviderRequest::Receipt { .. } => ProviderMethod::Receipt,
+            ProviderRequest::LightClientProof { .. } => ProviderMethod::LightClientProof,
+            ProviderRequest::NextLightClientBlock { .. } => ProviderMethod::NextLightClientBlock,
+        }
+    }
+
+    /// Returns the signed transaction sent by this request, if it is one of the send methods.
+    pub fn signed_transaction(&self) -> Option<&SignedTransaction> {
+        match self {
+            ProviderRequest::SendTransaction { signed_transaction }
+            | ProviderRequest::SendTransactionAsync { signed_transaction }
+            | ProviderRequest::SendTx {
+                signed_transaction, ..
+            } => Some(signed_transaction),
+            _ => None,
+        }
+    }
+
+    /// Returns the hash of the transaction this request sends or looks up, if any.
+    pub fn transaction_hash(&self) -> Option<CryptoHash> {
+        if let Some(signed_transaction) = self.signed_transaction() {
+            return Some(signed_transaction.get_hash());
+        }
+        match self {
+            ProviderRequest::TxStatus {
+                transaction_info, ..
+            }
+            | ProviderRequest::TxStatusWithReceipts {
+                transaction_info, ..
+            } => match transaction_info {
+                TransactionInfo::Transaction(SignedTransactionParam::SignedTransaction(
+                    signed_transaction,
+                )) => Some(signed_transaction.get_hash()),
+                TransactionInfo::TransactionId { tx_hash, .. } => Some(*tx_hash),
+            },
+            ProviderRequest::LightClientProof {
+                id:
+                    TransactionOrReceiptId::Transaction {
+                        transaction_hash, ..
+                    },
+                ..
+            } => Some(*transaction_hash),
+            _ => None,
+        }
+    }
+
+    /// Returns the block this request reads from, if it is made at a specific block. Requests made by block height
+    /// or hash, such as `gas_price` or `chunk`, are given as a `BlockReference::BlockId`.
+    pub fn block_reference(&self) -> Option<BlockReference> {
+        match self {
+            ProviderRequest::Block { block_reference }
+            | ProviderRequest::Query {
+                block_reference, ..
+            }
+            | ProviderRequest::ExperimentalProtocolConfig { block_reference }
+            | ProviderRequest::ChangesInBlock { block_reference }
+            | ProviderRequest::Changes {
+                block_reference, ..
+            } => Some(block_reference.clone()),
+            ProviderRequest::Chunk {
+                chunk_reference: ChunkReference::BlockShardId { block_id, .. },
+            }
+            | ProviderRequest::GasPrice {
+                block_id: Some(block_id),
+            }
+            | ProviderRequest::Validators {
+                epoch_reference: EpochReference::BlockId(block_id),
+            } => Some(BlockReference::BlockId(block_id.clone())),
+            _ => None,
+        }
+    }
+}
+
+// `EpochReference` and `StateChangesRequestView` do not implement `Clone`, so neither can `ProviderRequest` derive it.
+impl Clone for ProviderRequest {
+    fn clone(&self) -> Self {
+        match self {
+            ProviderRequest::Validators { epoch_reference } => ProviderRequest::Validators {
+                epoch_reference: clone_epoch_reference(epoch_reference),
+            },
+            ProviderRequest::Status => ProviderRequest::Status,
+            ProviderRequest::SendTransaction { signed_transaction } => {
+                ProviderRequest::SendTransaction {
+                    signed_transaction: signed_transaction.clone(),
+                }
+            }
+            ProviderRequest::SendTransactionAsync { signed_transaction } => {
+                ProviderRequest::SendTransactionAsync {
+                    signed_transaction: signed_transaction.clone(),
+                }
+            }
+            ProviderRequest::SendTx {
+                signed_transaction,
+                wait_until,
+            } => ProviderRequest::SendTx {
+                signed_transaction: signed_transaction.clone(),
+                wait_until: wait_until.clone(),
+            },
+            ProviderRequest::TxStatus {
+                transaction_info,
+                wait_until,
+            } => ProviderRequest::TxStatus {
+                transaction_info: transaction_info.clone(),
+                wait_until: wait_until.clone(),
+            },
+            ProviderRequest::Chunk { chunk_reference } => ProviderRequest::Chunk {
+                chunk_reference: chunk_reference.clone(),
+            },
+            ProviderRequest::Block { block_reference } => ProviderRequest::Block {
+                block_reference: block_reference.clone(),
+            },
+            ProviderRequest::Query {
+                request,
+                block_reference,
+            } => ProviderRequest::Query {
+                request: request.clone(),
+                block_reference: block_reference.clone(),
+            },
+            ProviderRequest::ExperimentalProtocolConfig { block_reference } => {
+                ProviderRequest::ExperimentalProtocolConfig {
+                    block_reference: block_reference.clone(),
+                }
+            }
+            ProviderRequest::GasPrice { block_id } => ProviderRequest::GasPrice {
+                block_id: block_id.clone(),
+            },
+            ProviderRequest::Health => ProviderRequest::Health,
+            ProviderRequest::NetworkInfo => ProviderRequest::NetworkInfo,
+            ProviderRequest::ExperimentalGenesisConfig => {
+                ProviderRequest::ExperimentalGenesisConfig
+            }
+            ProviderRequest::ClientConfig => ProviderRequest::ClientConfig,
+            ProviderRequest::ChangesInBlock { block_reference } => {
+                ProviderRequest::ChangesInBlock {
+                    block_reference: block_reference.clone(),
+                }
+            }
+            ProviderRequest::Changes {
+                request,
+                block_reference,
+            } => ProviderRequest::Changes {
+                request: clone_state_changes_request(request),
+                block_reference: block_reference.clone(),
+            },
+            ProviderRequest::TxStatusWithReceipts {
+                transaction_info,
+                wait_until,
+            } => ProviderRequest::TxStatusWithReceipts {
+                transaction_info: transaction_info.clone(),
+                wait_until: wait_until.clone(),
+            },
+            ProviderRequest::Receipt { receipt_id } => ProviderRequest::Receipt {
+                receipt_id: *receipt_id,
+            },
+            ProviderRequest::LightClientProof {
+                id,
+                light_client_head,
+            } => ProviderRequest::LightClientProof {
+                id: id.clone(),
+                light_client_head: *light_client_head,
+            },
+            ProviderRequest::NextLightClientBlock { last_block_hash } => {
+                ProviderRequest::NextLightClientBlock {
+                    last_block_hash: *last_block_hash,
+                }
+            }
+        }
+    }
+}
+
+/// Evaluates `$body` with `$result` bound to the result held by a `ProviderResponse`, whatever its method.
+macro_rules! for_each_result {
+    ($response:expr, $result:ident => $body:expr) => {
+        match $response {
+            ProviderResponse::Status($result) => $body,
+            ProviderResponse::SendTransaction($result) => $body,
+            ProviderResponse::SendTransactionAsync($result) => $body,
+            ProviderResponse::SendTx($result) => $body,
+            ProviderResponse::TxStatus($result) => $body,
+            ProviderResponse::Chunk($result) => $body,
+            ProviderResponse::Block($result) => $body,
+            ProviderResponse::Validators($result) => $body,
+            ProviderResponse::Query($result) => $body,
+            ProviderResponse::ExperimentalProtocolConfig($result) => $body,
+            ProviderResponse::GasPrice($result) => $body,
+            ProviderResponse::Health($result) => $body,
+            ProviderResponse::NetworkInfo($result) => $body,
+            ProviderResponse::ExperimentalGenesisConfig($result) => $body,
+            ProviderResponse::ClientConfig($result) => $body,
+            ProviderResponse::ChangesInBlock($result) => $body,
+            ProviderResponse::Changes($result) => $body,
+            ProviderResponse::TxStatusWithReceipts($result) => $body,
+            ProviderResponse::Receipt($result) => $body,
+            ProviderResponse::LightClientProof($result) => $body,
+            ProviderResponse::NextLightClientBlock($result) => $body,
+        }
+    };
+}
+
+pub(crate) use for_each_result;
+
+/// The result of a call to a `Provider` method, with the response and error types of the method, e.g. as seen by a
+/// `Middleware`.
+// A response only lives for the duration of a call, so its variants are not boxed to keep them easy to match on.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+pub enum ProviderResponse {
+    Status(Result<RpcStatusResponse, JsonRpcError<RpcStatusError>>),
+    SendTransaction(Result<FinalExecutionOutcomeView, JsonRpcError<RpcTransactionError>>),
+    SendTransactionAsync(
+        Result<CryptoHash, JsonRpcError<methods::broadcast_tx_async::RpcBroadcastTxAsyncError>>,
+    ),
+    SendTx(Result<RpcTransactionResponse, JsonRpcError<RpcTransactionError>>),
+    TxStatus(Result<RpcTransactionResponse, JsonRpcError<RpcTransactionError>>),
+    Chunk(Result<ChunkView, JsonRpcError<RpcChunkError>>),
+    Block(Result<BlockView, JsonRpcError<RpcBlockError>>),
+    Validators(Result<EpochValidatorInfo, JsonRpcError<RpcValidatorError>>),
+    Query(Result<RpcQueryResponse, JsonRpcError<RpcQueryError>>),
+    ExperimentalProtocolConfig(Result<ProtocolConfigView, JsonRpcError<RpcProtocolConfigError>>),
+    GasPrice(Result<GasPriceView, JsonRpcError<RpcGasPriceError>>),
+    Health(Result<RpcHealthResponse, JsonRpcError<RpcStatusError>>),
+    NetworkInfo(Result<RpcNetworkInfoResponse, JsonRpcError<RpcNetworkInfoError>>),
+    ExperimentalGenesisConfig(
+        Result<
+            GenesisConfig,
+            JsonRpcError<methods::EXPERIMENTAL_genesis_config::RpcGenesisConfigError>,
+        >,
+    ),
+    ClientConfig(Result<RpcClientConfigResponse, JsonRpcError<RpcClientConfigError>>),
+    ChangesInBlock(
+        Result<RpcStateChangesInBlockByTypeResponse, JsonRpcError<RpcStateChangesError>>,
+    ),
+    Changes(Result<RpcStateChangesInBlockResponse, JsonRpcError<RpcStateChangesError>>),
+    TxStatusWithReceipts(
+        Result<RpcTransactionWithReceiptsResponse, JsonRpcError<RpcTransactionError>>,
+    ),
+    Receipt(Result<ReceiptView, JsonRpcError<RpcReceiptError>>),
+    LightClientProof(
+        Result<RpcLightClientExecutionProofResponse, JsonRpcError<RpcLightClientProofError>>,
+    ),
+    NextLightClientBlock(
+        Result<Option<LightClientBlockView>, JsonRpcError<RpcLightClientNextBlockError>>,
+    ),
+}
+
+impl ProviderResponse {
+    /// Makes the call described by `request` on `provider`, wrapping its result.
+    pub async fn dispatch<P: Provider + ?Sized>(provider: &P, request: ProviderRequest) -> Self {
+        match request {
+            ProviderRequest::Status => ProviderResponse::Status(provider.status().await),
+            ProviderRequest::SendTransaction { signed_transaction } => {
+                ProviderResponse::SendTransaction(
+                    provider.send_transaction(signed_transaction).await,
+                )
+            }
+            ProviderRequest::SendTransactionAsync { signed_transaction } => {
+                ProviderResponse::SendTransactionAsync(
+                    provider.send_transaction_async(signed_transaction).await,
+                )
+            }
+            ProviderRequest::SendTx {
+                signed_transaction,
+                wait_until,
+            } => ProviderResponse::SendTx(provider.send_tx(signed_transaction, wait_until).await),
+            ProviderRequest::TxStatus {
+                transaction_info,
+                wait_until,
+            } => ProviderResponse::TxStatus(provider.tx_status(transaction_info, wait_until).await),
+            ProviderRequest::Chunk { chunk_reference } => {
+                ProviderResponse::Chunk(provider.chunk(chunk_reference).await)
+            }
+            ProviderRequest::Block { block_reference } => {
+                ProviderResponse::Block(provider.block(block_reference).await)
+            }
+            ProviderRequest::Validators { epoch_reference } => {
+                ProviderResponse::Validators(provider.validators(epoch_reference).await)
+            }
+            ProviderRequest::Query {
+                request,
+                block_reference,
+            } => ProviderResponse::Query(provider.query(request, block_reference).await),
+            ProviderRequest::ExperimentalProtocolConfig { block_reference } => {
+                ProviderResponse::ExperimentalProtocolConfig(
+                    provider.experimental_protocol_config(block_reference).await,
+                )
+            }
+            ProviderRequest::GasPrice { block_id } => {
+                ProviderResponse::GasPrice(provider.gas_price(block_id).await)
+            }
+            ProviderRequest::Health => ProviderResponse::Health(provider.health().await),
+            ProviderRequest::NetworkInfo => {
+                ProviderResponse::NetworkInfo(provider.network_info().await)
+            }
+            ProviderRequest::ExperimentalGenesisConfig => {
+                ProviderResponse::ExperimentalGenesisConfig(
+                    provider.experimental_genesis_config().await,
+                )
+            }
+            ProviderRequest::ClientConfig => {
+                ProviderResponse::ClientConfig(provider.client_config().await)
+            }
+            ProviderRequest::ChangesInBlock { block_reference } => {
+                ProviderResponse::ChangesInBlock(provider.changes_in_block(block_reference).await)
+            }
+            ProviderRequest::Changes {
+                request,
+                block_reference,
+            } => ProviderResponse::Changes(provider.changes(request, block_reference).await),
+            ProviderRequest::TxStatusWithReceipts {
+                transaction_info,
+                wait_until,
+            } => ProviderResponse::TxStatusWithReceipts(
+                provider
+                    .tx_status_with_receipts(transaction_info, wait_until)
+                    .await,
+            ),
+            ProviderRequest::Receipt { receipt_id } => {
+                ProviderResponse::Receipt(provider.receipt(receipt_id).await)
+            }
+            ProviderRequest::LightClientProof {
+                id,
+                light_client_head,
+            } => ProviderResponse::LightClientProof(
+                provider.light_client_proof(id, light_client_head).await,
+            ),
+            ProviderRequest::NextLightClientBlock { last_block_hash } => {
+                ProviderResponse::NextLightClientBlock(
+                    provider.next_light_client_block(last_block_hash).await,
+                )
+            }
+        }
+    }
+
+    /// Builds a response to `method` failing with an internal server error, e.g. to short-circuit a call.
+    ///
+    /// The RPC methods without any internal error, `broadcast_tx_async` and `EXPERIMENTAL_genesis_config`, get the
+    /// error as a top-level server error as well.
+    pub fn internal_error(method: ProviderMethod, info: impl Into<String>) -> Self {
+        fn error<E>(info: String) -> JsonRpcError<E> {
+            JsonRpcError::ServerError(JsonRpcServerError::InternalError { info: Some(info) })
+        }
+        let info = info.into();
+        match method {
+            ProviderMethod::Status => ProviderResponse::Status(Err(error(info))),
+            ProviderMethod::SendTransaction => ProviderResponse::SendTransaction(Err(error(info))),
+            ProviderMethod::SendTransactionAsync => {
+                ProviderResponse::SendTransactionAsync(Err(error(info)))
+            }
+            ProviderMethod::SendTx => ProviderResponse::SendTx(Err(error(info))),
+            ProviderMethod::TxStatus => ProviderResponse::TxStatus(Err(error(info))),
+            ProviderMethod::Chunk => ProviderResponse::Chunk(Err(error(info))),
+            ProviderMethod::Block => ProviderResponse::Block(Err(error(info))),
+            ProviderMethod::Validators => ProviderResponse::Validators(Err(error(info))),
+            ProviderMethod::Query => ProviderResponse::Query(Err(error(info))),
+            ProviderMethod::ExperimentalProtocolConfig => {
+                ProviderResponse::ExperimentalProtocolConfig(Err(error(info)))
+            }
+            ProviderMethod::GasPrice => ProviderResponse::GasPrice(Err(error(info))),
+            ProviderMethod::Health => ProviderResponse::Health(Err(error(info))),
+            ProviderMethod::NetworkInfo => ProviderResponse::NetworkInfo(Err(error(info))),
+            ProviderMethod::ExperimentalGenesisConfig => {
+                ProviderResponse::ExperimentalGenesisConfig(Err(error(info)))
+            }
+            ProviderMethod::ClientConfig => ProviderResponse::ClientConfig(Err(error(info))),
+            ProviderMethod::ChangesInBlock => ProviderResponse::ChangesInBlock(Err(error(info))),
+            ProviderMethod::Changes => ProviderResponse::Changes(Err(error(info))),
+            ProviderMethod::TxStatusWithReceipts => {
+                ProviderResponse::TxStatusWithReceipts(Err(error(info)))
+            }
+            ProviderMethod::Receipt => ProviderResponse::Receipt(Err(error(info))),
+            ProviderMethod::LightClientProof => {
+                ProviderResponse::LightClientProof(Err(error(info)))
+            }
+            ProviderMethod::NextLightClientBlock => {
+                ProviderResponse::NextLightClientBlock(Err(error(info)))
+            }
+        }
+    }
+
+    /// Returns the `Provider` method this is a response to.
+    pub fn method(&self) -> ProviderMethod {
+        match self {
+            ProviderResponse::Status(_) => ProviderMethod::Status,
+            ProviderResponse::SendTransaction(_) => ProviderMethod::SendTransaction,
+            ProviderResponse::SendTransactionAsync(_) => ProviderMethod::SendTransactionAsync,
+            ProviderResponse::SendTx(_) => ProviderMethod::SendTx,
+            ProviderResponse::TxStatus(_) => ProviderMethod::TxStatus,
+            ProviderResponse::Chunk(_) => ProviderMethod::Chunk,
+            ProviderResponse::Block(_) => ProviderMethod::Block,
+            ProviderResponse::Validators(_) => ProviderMethod::Validators,
+            ProviderResponse::Query(_) => ProviderMethod::Query,
+            ProviderResponse::ExperimentalProtocolConfig(_) => {
+                ProviderMethod::ExperimentalProtocolConfig
+            }
+            ProviderResponse::GasPrice(_) => ProviderMethod::GasPrice,
+            ProviderResponse::Health(_) => ProviderMethod::Health,
+            ProviderResponse::NetworkInfo(_) => ProviderMethod::NetworkInfo,
+            ProviderResponse::ExperimentalGenesisConfig(_) => {
+                ProviderMethod::ExperimentalGenesisConfig
+            }
+            ProviderResponse::ClientConfig(_) => ProviderMethod::ClientConfig,
+            ProviderResponse::ChangesInBlock(_) => ProviderMethod::ChangesInBlock,
+            ProviderResponse::Changes(_) => ProviderMethod::Changes,
+            ProviderResponse::TxStatusWithReceipts(_) => ProviderMethod::TxStatusWithReceipts,
+            ProviderResponse::Receipt(_) => ProviderMethod::Receipt,
+            ProviderResponse::LightClientProof(_) => ProviderMethod::LightClientProof,
+            ProviderResponse::NextLightClientBlock(_) => ProviderMethod::NextLightClientBlock,
+        }
+    }
+
+    /// Returns the error of the call formatted as a string, or `None` if it succeeded.
+    pub fn error_message(&self) -> Option<String> {
+        for_each_result!(self, result => result.as_ref().err().map(|err| err.to_string()))
+    }
+
+    /// Returns true if the call succeeded.
+    pub fn is_ok(&self) -> bool {
+        for_each_result!(self, result => result.is_ok())
+    }
+
+    /// Returns the kind of error the call failed with, or `None` if it succeeded, e.g. to label error metrics.
+    ///
+    /// The kind is one of `transport`, `handler`, `internal`, `request_validation`, `non_contextual`, `unauthorized`,
+    /// `rate_limited` or `http_status`.
+    pub fn error_kind(&self) -> Option<&'static str> {
+        for_each_result!(self, result => result.as_ref().err().map(error_kind))
+    }
+}
+
+/// Classifies a failed call, see `ProviderResponse::error_kind`.
+fn error_kind<E>(err: &JsonRpcError<E>) -> &'static str {
+    match err {
+        JsonRpcError::TransportError(_) => "transport",
+        JsonRpcError::ServerError(server_error) => match server_error {
+            JsonRpcServerError::HandlerError(_) => "handler",
+            JsonRpcServerError::InternalError { .. } => "internal",
+            JsonRpcServerError::RequestValidationError(_) => "request_validation",
+            JsonRpcServerError::NonContextualError(_) => "non_contextual",
+            JsonRpcServerError::ResponseStatusError(status_error) => match status_error {
+                JsonRpcServerResponseStatusError::Unauthorized => "unauthorized",
+                JsonRpcServerResponseStatusError::TooManyRequests => "rate_limited",
+                JsonRpcServerResponseStatusError::Unexpected { .. } => "http_status",
+            },
+        },
+    }
 }
@@ -0,0 +1,685 @@
+//! The `RateLimitProvider` module offers a `Provider` wrapper that throttles the calls made to an inner provider.
+//!
+//! Calls are limited by a token bucket, in requests per second, and by a maximum number of requests in flight.
+//! Limits can be set for all methods together and additionally per method, so a single provider instance can be
+//! shared across tasks (e.g. as an `Arc`) without each caller implementing its own throttling.
+//!
+//! A limit that could never let a call through, such as 0 requests per second or 0 requests in flight, is rejected
+//! with a `RateLimitError` when the provider is built.
+//!
+//! `RateLimitLayer` applies the same limits as a `Middleware`, for a `ProviderBuilder` stack.
+
+use crate::batch::{self, DEFAULT_BATCH_CONCURRENCY, DEFAULT_BATCH_SIZE};
+use crate::client_config::{RpcClientConfigError, RpcClientConfigResponse};
+use crate::jsonrpc_client::{
+    errors::JsonRpcError,
+    methods::{self, status::RpcStatusResponse},
+};
+use crate::middleware::{Middleware, Next};
+use crate::tx_status::RpcTransactionWithReceiptsResponse;
+use crate::types::{
+    blocks::RpcBlockError,
+    changes::{
+        RpcStateChangesError, RpcStateChangesInBlockByTypeResponse, RpcStateChangesInBlockResponse,
+    },
+    chunks::{ChunkReference, RpcChunkError},
+    config::RpcProtocolConfigError,
+    gas_price::RpcGasPriceError,
+    light_client::{
+        RpcLightClientExecutionProofResponse, RpcLightClientNextBlockError,
+        RpcLightClientProofError,
+    },
+    network_info::{RpcNetworkInfoError, RpcNetworkInfoResponse},
+    query::{RpcQueryError, RpcQueryResponse},
+    receipts::RpcReceiptError,
+    status::{RpcHealthResponse, RpcStatusError},
+    transactions::{RpcTransactionError, TransactionInfo},
+    validator::RpcValidatorError,
+};
+use crate::{Provider, ProviderMethod, ProviderRequest, ProviderResponse};
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
+use near_chain_configs::{GenesisConfig, ProtocolConfigView};
+use near_jsonrpc_client::methods::tx::RpcTransactionResponse;
+use near_primitives::{
+    hash::CryptoHash,
+    transaction::SignedTransaction,
+    types::{BlockId, BlockReference, EpochReference, TransactionOrReceiptId},
+    views::{
+        BlockView, ChunkView, EpochValidatorInfo, FinalExecutionOutcomeView, GasPriceView,
+        LightClientBlockView, QueryRequest, ReceiptView, StateChangesRequestView,
+        TxExecutionStatus,
+    },
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio::time::Instant;
+
+/// Describes the limits applied to a set of calls.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RateLimit {
+    /// Sustained number of requests per second. `None` disables the token bucket.
+    pub requests_per_second: Option<f64>,
+    /// Number of requests that may be sent at once before `requests_per_second` applies. At least 1.
+    pub burst: u32,
+    /// Maximum number of requests waiting for a response at the same time. `None` disables the limit.
+    pub max_in_flight: Option<usize>,
+}
+
+impl RateLimit {
+    /// A limit of `requests_per_second`, with a burst of a single request and no in-flight limit.
+    pub fn per_second(requests_per_second: f64) -> Self {
+        Self {
+            requests_per_second: Some(requests_per_second),
+            burst: 1,
+            max_in_flight: None,
+        }
+    }
+
+    /// Sets the number of requests that may be sent at once.
+    pub fn with_burst(mut self, burst: u32) -> Self {
+        self.burst = burst;
+        self
+    }
+
+    /// Sets the maximum number of requests in flight.
+    pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = Some(max_in_flight);
+        self
+    }
+
+    /// Checks that the limit lets calls through: `requests_per_second` must be a positive finite number and
+    /// `max_in_flight` at least 1.
+    pub fn validate(&self) -> Result<(), RateLimitError> {
+        if let Some(requests_per_second) = self.requests_per_second {
+            if !requests_per_second.is_finite() || requests_per_second <= 0.0 {
+                return Err(RateLimitError::InvalidRequestsPerSecond(
+                    requests_per_second,
+                ));
+            }
+        }
+        if self.max_in_flight == Some(0) {
+            return Err(RateLimitError::ZeroMaxInFlight);
+        }
+        Ok(())
+    }
+}
+
+/// The reasons a `RateLimit` is rejected.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum RateLimitError {
+    #[error("requests_per_second must be a positive finite number, got {0}")]
+    InvalidRequestsPerSecond(f64),
+    #[error("max_in_flight must be at least 1")]
+    ZeroMaxInFlight,
+}
+
+/// A token bucket that lets callers reserve tokens ahead of time, so waiting callers are served in order.
+#[derive(Debug)]
+struct TokenBucket {
+    requests_per_second: f64,
+    capacity: f64,
+    state: Mutex<TokenBucketState>,
+}
+
+#[derive(Debug)]
+struct TokenBucketState {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(requests_per_second: f64, burst: u32) -> Self {
+        let capacity = burst.max(1) as f64;
+        Self {
+            requests_per_second,
+            capacity,
+            state: Mutex::new(TokenBucketState {
+                tokens: capacity,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes a token, waiting until one is available.
+    async fn acquire(&self) {
+        let wait = {
+            let mut state = self.state.lock().unwrap();
+            let now = Instant::now();
+            let elapsed = now.duration_since(state.refilled_at).as_secs_f64();
+            state.tokens = (state.tokens + elapsed * self.requests_per_second).min(self.capacity);
+            state.refilled_at = now;
+            state.tokens -= 1.0;
+            if state.tokens < 0.0 {
+                Duration::from_secs_f64(-state.tokens / self.requests_per_second)
+            } else {
+                Duration::ZERO
+            }
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// The state enforcing a `RateLimit`.
+#[derive(Debug)]
+struct Limiter {
+    bucket: Option<TokenBucket>,
+    in_flight: Option<Semaphore>,
+}
+
+impl Limiter {
+    fn new(limit: &RateLimit) -> Self {
+        Self {
+            bucket: limit
+                .requests_per_second
+                .map(|requests_per_second| TokenBucket::new(requests_per_second, limit.burst)),
+            in_flight: limit.max_in_flight.map(Semaphore::new),
+        }
+    }
+
+    /// Waits for a free in-flight slot and a token. The returned permit must be held until the call completes.
+    async fn acquire(&self) -> Option<SemaphorePermit<'_>> {
+        let permit = match &self.in_flight {
+            Some(semaphore) => Some(
+                semaphore
+                    .acquire()
+                    .await
+                    .expect("the in-flight semaphore is never closed"),
+            ),
+            None => None,
+        };
+        if let Some(bucket) = &self.bucket {
+            bucket.acquire().await;
+        }
+        permit
+    }
+}
+
+/// The limiters of a `RateLimitProvider` or `RateLimitLayer`, for all methods together and per method.
+#[derive(Debug)]
+struct Limiters {
+    limiter: Limiter,
+    method_limiters: HashMap<ProviderMethod, Limiter>,
+}
+
+impl Limiters {
+    fn new(limit: RateLimit) -> Result<Self, RateLimitError> {
+        limit.validate()?;
+        Ok(Self {
+            limiter: Limiter::new(&limit),
+            method_limiters: HashMap::new(),
+        })
+    }
+
+    fn insert(&mut self, method: ProviderMethod, limit: RateLimit) -> Result<(), RateLimitError> {
+        limit.validate()?;
+        self.method_limiters.insert(method, Limiter::new(&limit));
+        Ok(())
+    }
+
+    /// Waits until a call to `method` is allowed. The returned permits must be held until the call completes.
+    async fn acquire(
+        &self,
+        method: ProviderMethod,
+    ) -> (Option<SemaphorePermit<'_>>, Option<SemaphorePermit<'_>>) {
+        let method_permit = match self.method_limiters.get(&method) {
+            Some(limiter) => limiter.acquire().await,
+            None => None,
+        };
+        let permit = self.limiter.acquire().await;
+        (method_permit, permit)
+    }
+
+    /// Waits until a payload of `requests` is allowed, taking a single token from the limit shared by all methods and
+    /// from the limit of each method in the payload. The limiters are acquired in a fixed order, so that concurrent
+    /// payloads never hold in-flight slots the other one waits for.
+    async fn acquire_payload(&self, requests: &[ProviderRequest]) -> Vec<SemaphorePermit<'_>> {
+        let mut methods: Vec<ProviderMethod> =
+            requests.iter().map(ProviderRequest::method).collect();
+        methods.sort_by_key(|method| method.rpc_method_name());
+        methods.dedup();
+        let mut permits = Vec::new();
+        for method in methods {
+            if let Some(limiter) = self.method_limiters.get(&method) {
+                permits.extend(limiter.acquire().await);
+            }
+        }
+        permits.extend(self.limiter.acquire().await);
+        permits
+    }
+}
+
+/// Represents a provider that applies client-side rate and concurrency limits to an inner provider.
+pub struct RateLimitProvider<P> {
+    inner: P,
+    limiters: Arc<Limiters>,
+    batch_size: usize,
+}
+
+impl<P: Provider> RateLimitProvider<P> {
+    /// Constructs a new `RateLimitProvider` around `inner`, applying `limit` to all calls together.
+    ///
+    /// Fails if `limit` does not let any call through, see `RateLimit::validate`.
+    pub fn new(inner: P, limit: RateLimit) -> Result<Self, RateLimitError> {
+        Ok(Self {
+            inner,
+            limiters: Arc::new(Limiters::new(limit)?),
+            batch_size: DEFAULT_BATCH_SIZE,
+        })
+    }
+
+    /// Applies `limit` to the calls of a single method, in addition to the limit shared by all methods.
+    ///
+    /// Fails if `limit` does not let any call through, see `RateLimit::validate`.
+    pub fn with_method_limit(
+        mut self,
+        method: ProviderMethod,
+        limit: RateLimit,
+    ) -> Result<Self, RateLimitError> {
+        Arc::get_mut(&mut self.limiters)
+            .expect("the limiters are not shared before the first call")
+            .insert(method, limit)?;
+        Ok(self)
+    }
+
+    /// Sets the number of requests of a batch that take a single token, `DEFAULT_BATCH_SIZE` by default. Set it to
+    /// the batch size of the inner provider, so that each JSON RPC batch payload it sends counts as one call.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Returns a reference to the wrapped provider.
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    /// Waits until a call to `method` is allowed. The returned permits must be held until the call completes.
+    async fn acquire(
+        &self,
+        method: ProviderMethod,
+    ) -> (Option<SemaphorePermit<'_>>, Option<SemaphorePermit<'_>>) {
+        self.limiters.acquire(method).await
+    }
+}
+
+/// A `Middleware` throttling the calls that reach it as a `RateLimitProvider` does, to stack rate limits with other
+/// middlewares in a `ProviderBuilder`. Below a `RetryLayer`, every retry waits for the limits as well.
+///
+/// A middleware sees the requests of a batch one by one, so each of them waits for the limits on its own, and the
+/// requests that have to wait reach the provider with a later batch. A `RateLimitProvider` takes a single token per
+/// batch payload instead.
+pub struct RateLimitLayer {
+    limiters: Arc<Limiters>,
+}
+
+impl RateLimitLayer {
+    /// Constructs a new `RateLimitLayer`, applying `limit` to all calls together.
+    ///
+    /// Fails if `limit` does not let any call through, see `RateLimit::validate`.
+    pub fn new(limit: RateLimit) -> Result<Self, RateLimitError> {
+        Ok(Self {
+            limiters: Arc::new(Limiters::new(limit)?),
+        })
+    }
+
+    /// Applies `limit` to the calls of a single method, in addition to the limit shared by all methods.
+    ///
+    /// Fails if `limit` does not let any call through, see `RateLimit::validate`.
+    pub fn with_method_limit(
+        mut self,
+        method: ProviderMethod,
+        limit: RateLimit,
+    ) -> Result<Self, RateLimitError> {
+        Arc::get_mut(&mut self.limiters)
+            .expect("the limiters are not shared before the first call")
+            .insert(method, limit)?;
+        Ok(self)
+    }
+}
+
+#[async_trait]
+impl Middleware for RateLimitLayer {
+    async fn handle(&self, request: ProviderRequest, next: Next<'_>) -> ProviderResponse {
+        let provider = RateLimitProvider {
+            inner: next,
+            limiters: self.limiters.clone(),
+            batch_size: DEFAULT_BATCH_SIZE,
+        };
+        ProviderResponse::dispatch(&provider, request).await
+    }
+}
+
+#[async_trait]
+impl<P: Provider + Send + Sync> Provider for RateLimitProvider<P> {
+    /// Retrieves the current status of the NEAR blockchain, once the rate limit allows it.
+    async fn status(&self) -> Result<RpcStatusResponse, JsonRpcError<RpcStatusError>> {
+        let _permits = self.acquire(ProviderMethod::Status).await;
+        self.inner.status().await
+    }
+
+    /// Executes a query, once the rate limit allows it.
+    async fn query(
+        &self,
+        request: QueryRequest,
+        block_reference: BlockReference,
+    ) -> Result<RpcQueryResponse, JsonRpcError<RpcQueryError>> {
+        let _permits = self.acquire(ProviderMethod::Query).await;
+        self.inner.query(request, block_reference).await
+    }
+
+    /// Sends a signed transaction and waits for its final execution outcome, once the rate limit allows it.
+    async fn send_transaction(
+        &self,
+        signed_transaction: SignedTransaction,
+    ) -> Result<FinalExecutionOutcomeView, JsonRpcError<RpcTransactionError>> {
+        let _permits = self.acquire(ProviderMethod::SendTransaction).await;
+        self.inner.send_transaction(signed_transaction).await
+    }
+
+    /// Sends a signed transaction asynchronously, once the rate limit allows it.
+    async fn send_transaction_async(
+        &self,
+        signed_transaction: SignedTransaction,
+    ) -> Result<CryptoHash, JsonRpcError<methods::broadcast_tx_async::RpcBroadcastTxAsyncError>>
+    {
+        let _permits = self.acquire(ProviderMethod::SendTransactionAsync).await;
+        self.inner.send_transaction_async(signed_transaction).await
+    }
+
+    /// Sends a signed transaction with the given `wait_until`, once the rate limit allows it.
+    async fn send_tx(
+        &self,
+        signed_transaction: SignedTransaction,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionResponse, JsonRpcError<RpcTransactionError>> {
+        let _permits = self.acquire(ProviderMethod::SendTx).await;
+        self.inner.send_tx(signed_transaction, wait_until).await
+    }
+
+    /// Retrieves the status of a transaction, once the rate limit allows it.
+    async fn tx_status(
+        &self,
+        transaction_info: TransactionInfo,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionResponse, JsonRpcError<RpcTransactionError>> {
+        let _permits = self.acquire(ProviderMethod::TxStatus).await;
+        self.inner.tx_status(transaction_info, wait_until).await
+    }
+
+    /// Fetches details of a specific chunk, once the rate limit allows it.
+    async fn chunk(
+        &self,
+        chunk_reference: ChunkReference,
+    ) -> Result<ChunkView, JsonRpcError<RpcChunkError>> {
+        let _permits = self.acquire(ProviderMethod::Chunk).await;
+        self.inner.chunk(chunk_reference).await
+    }
+
+    /// Retrieves a block, once the rate limit allows it.
+    async fn block(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<BlockView, JsonRpcError<RpcBlockError>> {
+        let _permits = self.acquire(ProviderMethod::Block).await;
+        self.inner.block(block_reference).await
+    }
+
+    /// Fetches the protocol configuration, once the rate limit allows it.
+    async fn experimental_protocol_config(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<ProtocolConfigView, JsonRpcError<RpcProtocolConfigError>> {
+        let _permits = self
+            .acquire(ProviderMethod::ExperimentalProtocolConfig)
+            .await;
+        self.inner
+            .experimental_protocol_config(block_reference)
+            .await
+    }
+
+    /// Retrieves information about validators, once the rate limit allows it.
+    async fn validators(
+        &self,
+        epoch_reference: EpochReference,
+    ) -> Result<EpochValidatorInfo, JsonRpcError<RpcValidatorError>> {
+        let _permits = self.acquire(ProviderMethod::Validators).await;
+        self.inner.validators(epoch_reference).await
+    }
+
+    /// Fetches the gas price, once the rate limit allows it.
+    async fn gas_price(
+        &self,
+        block_id: Option<BlockId>,
+    ) -> Result<GasPriceView, JsonRpcError<RpcGasPriceError>> {
+        let _permits = self.acquire(ProviderMethod::GasPrice).await;
+        self.inner.gas_price(block_id).await
+    }
+
+    /// Checks the health of the node, once the rate limit allows it.
+    async fn health(&self) -> Result<RpcHealthResponse, JsonRpcError<RpcStatusError>> {
+        let _permits = self.acquire(ProviderMethod::Health).await;
+        self.inner.health().await
+    }
+
+    /// Fetches the network information of the node, once the rate limit allows it.
+    async fn network_info(
+        &self,
+    ) -> Result<RpcNetworkInfoResponse, JsonRpcError<RpcNetworkInfoError>> {
+        let _permits = self.acquire(ProviderMethod::NetworkInfo).await;
+        self.inner.network_info().await
+    }
+
+    /// Fetches the genesis configuration, once the rate limit allows it.
+    async fn experimental_genesis_config(
+        &self,
+    ) -> Result<
+        GenesisConfig,
+        JsonRpcError<methods::EXPERIMENTAL_genesis_config::RpcGenesisConfigError>,
+    > {
+        let _permits = self
+            .acquire(ProviderMethod::ExperimentalGenesisConfig)
+            .await;
+        self.inner.experimental_genesis_config().await
+    }
+
+    /// Fetches the client configuration of the node, once the rate limit allows it.
+    async fn client_config(
+        &self,
+    ) -> Result<RpcClientConfigResponse, JsonRpcError<RpcClientConfigError>> {
+        let _permits = self.acquire(ProviderMethod::ClientConfig).await;
+        self.inner.client_config().await
+    }
+
+    /// Lists the state changes made in a block, once the rate limit allows it.
+    async fn changes_in_block(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<RpcStateChangesInBlockByTypeResponse, JsonRpcError<RpcStateChangesError>> {
+        let _permits = self.acquire(ProviderMethod::ChangesInBlock).await;
+        self.inner.changes_in_block(block_reference).await
+    }
+
+    /// Retrieves state changes, once the rate limit allows it.
+    async fn changes(
+        &self,
+        request: StateChangesRequestView,
+        block_reference: BlockReference,
+    ) -> Result<RpcStateChangesInBlockResponse, JsonRpcError<RpcStateChangesError>> {
+        let _permits = self.acquire(ProviderMethod::Changes).await;
+        self.inner.changes(request, block_reference).await
+    }
+
+    /// Retrieves the status of a transaction and its receipts, once the rate limit allows it.
+    async fn tx_status_with_receipts(
+        &self,
+        transaction_info: TransactionInfo,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionWithReceiptsResponse, JsonRpcError<RpcTransactionError>> {
+        let _permits = self.acquire(ProviderMethod::TxStatusWithReceipts).await;
+        self.inner
+            .tx_status_with_receipts(transaction_info, wait_until)
+            .await
+    }
+
+    /// Retrieves a receipt, once the rate limit allows it.
+    async fn receipt(
+        &self,
+        receipt_id: CryptoHash,
+    ) -> Result<ReceiptView, JsonRpcError<RpcReceiptError>> {
+        let _permits = self.acquire(ProviderMethod::Receipt).await;
+        self.inner.receipt(receipt_id).await
+    }
+
+    /// Retrieves an execution proof, once the rate limit allows it.
+    async fn light_client_proof(
+        &self,
+        id: TransactionOrReceiptId,
+        light_client_head: CryptoHash,
+    ) -> Result<RpcLightClientExecutionProofResponse, JsonRpcError<RpcLightClientProofError>> {
+        let _permits = self.acquire(ProviderMethod::LightClientProof).await;
+        self.inner.light_client_proof(id, light_client_head).await
+    }
+
+    /// Retrieves the next light client block, once the rate limit allows it.
+    async fn next_light_client_block(
+        &self,
+        last_block_hash: CryptoHash,
+    ) -> Result<Option<LightClientBlockView>, JsonRpcError<RpcLightClientNextBlockError>> {
+        let _permits = self.acquire(ProviderMethod::NextLightClientBlock).await;
+        self.inner.next_light_client_block(last_block_hash).await
+    }
+
+    /// Sends the requests the inner provider batches as batches of up to the batch size, each batch waiting for a
+    /// single token once the rate limit allows it. The other requests are sent on their own, each waiting for its own
+    /// token, see `Provider::is_batched`. A payload the endpoint turns out not to support is sent again request by
+    /// request under its single token, after which the inner provider no longer batches.
+    async fn batch(&self, requests: Vec<ProviderRequest>) -> Vec<ProviderResponse> {
+        let count = requests.len();
+        let (mut batched, singles): (Vec<_>, Vec<_>) = requests
+            .into_iter()
+            .enumerate()
+            .partition(|(_, request)| self.inner.is_batched(request));
+        let mut payloads = Vec::new();
+        while batched.len() > self.batch_size {
+            let rest = batched.split_off(self.batch_size);
+            payloads.push(std::mem::replace(&mut batched, rest));
+        }
+        if !batched.is_empty() {
+            payloads.push(batched);
+        }
+        let batch_responses = stream::iter(payloads)
+            .map(|payload| async move {
+                let (indices, payload): (Vec<_>, Vec<_>) = payload.into_iter().unzip();
+                let _permits = self.limiters.acquire_payload(&payload).await;
+                let responses = self.inner.batch(payload).await;
+                indices.into_iter().zip(responses).collect::<Vec<_>>()
+            })
+            .buffered(DEFAULT_BATCH_CONCURRENCY)
+            .collect::<Vec<_>>();
+        let (single_indices, singles): (Vec<_>, Vec<_>) = singles.into_iter().unzip();
+        let (batch_responses, single_responses) = futures::join!(
+            batch_responses,
+            batch::dispatch_concurrently(self, singles, DEFAULT_BATCH_CONCURRENCY),
+        );
+        let singles = single_indices.into_iter().zip(single_responses);
+        batch::in_request_order(count, batch_responses.into_iter().flatten().chain(singles))
+    }
+
+    fn is_batched(&self, request: &ProviderRequest) -> bool {
+        self.inner.is_batched(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{access_key_response, MockProvider};
+    use std::sync::Arc;
+
+    /// Builds a provider whose queries take 100ms.
+    fn slow_provider() -> MockProvider {
+        let provider = MockProvider::new().with_latency(Duration::from_millis(100));
+        provider.on_query(|_, _| Some(Ok(access_key_response(7))));
+        provider
+    }
+
+    async fn run_queries(provider: Arc<RateLimitProvider<MockProvider>>, count: usize) {
+        let handles: Vec<_> = (0..count)
+            .map(|_| {
+                let provider = provider.clone();
+                tokio::spawn(async move {
+                    let request = QueryRequest::ViewAccount {
+                        account_id: "alice.near".parse().unwrap(),
+                    };
+                    provider.query(request, BlockReference::latest()).await
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap().unwrap();
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_limits_requests_per_second() {
+        let provider =
+            Arc::new(RateLimitProvider::new(slow_provider(), RateLimit::per_second(4.0)).unwrap());
+
+        let started = Instant::now();
+        run_queries(provider, 5).await;
+
+        // The first request uses the burst, the other four wait 250ms each.
+        assert!(started.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_limits_requests_in_flight_per_method() {
+        let provider = Arc::new(
+            RateLimitProvider::new(slow_provider(), RateLimit::default())
+                .unwrap()
+                .with_method_limit(
+                    ProviderMethod::Query,
+                    RateLimit::default().with_max_in_flight(2),
+                )
+                .unwrap(),
+        );
+
+        let started = Instant::now();
+        run_queries(provider.clone(), 6).await;
+
+        // Two queries at a time, 100ms each.
+        assert_eq!(started.elapsed(), Duration::from_millis(300));
+        assert_eq!(provider.inner().call_count(ProviderMethod::Query), 6);
+    }
+
+    #[test]
+    fn test_rejects_limits_without_throughput() {
+        for requests_per_second in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                RateLimitProvider::new(
+                    MockProvider::new(),
+                    RateLimit::per_second(requests_per_second)
+                ),
+                Err(RateLimitError::InvalidRequestsPerSecond(_))
+            ));
+        }
+
+        let provider = RateLimitProvider::new(MockProvider::new(), RateLimit::per_second(1.0));
+        assert!(matches!(
+            provider.unwrap().with_method_limit(
+                ProviderMethod::Query,
+                RateLimit::default().with_max_in_flight(0)
+            ),
+            Err(RateLimitError::ZeroMaxInFlight)
+        ));
+        assert_eq!(
+            RateLimit::default().with_max_in_flight(0).validate(),
+            Err(RateLimitError::ZeroMaxInFlight)
+        );
+    }
+}
@@ -0,0 +1,879 @@
+//! The `RetryProvider` module offers a `Provider` wrapper that retries failed calls of an inner provider with exponential backoff.
+//!
+//! Errors are classified before retrying: transport errors, rate limiting, 5xx responses and transient handler errors
+//! (e.g. a syncing node) are retried, while deterministic failures such as `RpcQueryError::UnknownAccount` or
+//! `RpcTransactionError::InvalidTransaction` are returned immediately.
+//!
+//! The methods broadcasting a transaction, `send_transaction`, `send_transaction_async` and `send_tx`, are not retried
+//! by default: a timeout or a dropped connection does not tell whether the node accepted the transaction, and sending
+//! it again fails with `InvalidNonce` instead of returning its outcome. Look up the outcome with `tx_status`, or opt in
+//! with `with_method_policy` for a node known to drop transactions before accepting them.
+//!
+//! `RetryLayer` applies the same retries as a `Middleware`, for a `ProviderBuilder` stack.
+
+use crate::client_config::{RpcClientConfigError, RpcClientConfigResponse};
+use crate::jsonrpc_client::{
+    errors::{JsonRpcError, JsonRpcServerError, JsonRpcServerResponseStatusError},
+    methods::{self, status::RpcStatusResponse},
+};
+use crate::middleware::{Middleware, Next};
+use crate::provider::for_each_result;
+use crate::tx_status::RpcTransactionWithReceiptsResponse;
+use crate::types::{
+    blocks::RpcBlockError,
+    changes::{
+        RpcStateChangesError, RpcStateChangesInBlockByTypeResponse, RpcStateChangesInBlockResponse,
+    },
+    chunks::{ChunkReference, RpcChunkError},
+    config::RpcProtocolConfigError,
+    gas_price::RpcGasPriceError,
+    light_client::{
+        RpcLightClientExecutionProofResponse, RpcLightClientNextBlockError,
+        RpcLightClientProofError,
+    },
+    network_info::{RpcNetworkInfoError, RpcNetworkInfoResponse},
+    query::{RpcQueryError, RpcQueryResponse},
+    receipts::RpcReceiptError,
+    status::{RpcHealthResponse, RpcStatusError},
+    transactions::{RpcTransactionError, TransactionInfo},
+    validator::RpcValidatorError,
+};
+use crate::utils::{clone_epoch_reference, clone_state_changes_request};
+use crate::{Provider, ProviderMethod, ProviderRequest, ProviderResponse};
+use async_trait::async_trait;
+use near_chain_configs::{GenesisConfig, ProtocolConfigView};
+use near_jsonrpc_client::methods::tx::RpcTransactionResponse;
+use near_primitives::{
+    hash::CryptoHash,
+    transaction::SignedTransaction,
+    types::{BlockId, BlockReference, EpochReference, TransactionOrReceiptId},
+    views::{
+        BlockView, ChunkView, EpochValidatorInfo, FinalExecutionOutcomeView, GasPriceView,
+        LightClientBlockView, QueryRequest, ReceiptView, StateChangesRequestView,
+        TxExecutionStatus,
+    },
+};
+use rand::Rng;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Classifies the handler errors of an RPC method as retryable or fatal.
+pub trait RetryableError {
+    /// Returns true if the same request may succeed when sent again.
+    fn is_retryable(&self) -> bool;
+}
+
+impl RetryableError for RpcStatusError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            RpcStatusError::NodeIsSyncing
+            | RpcStatusError::NoNewBlocks { .. }
+            | RpcStatusError::InternalError { .. } => true,
+            RpcStatusError::EpochOutOfBounds { .. } => false,
+        }
+    }
+}
+
+impl RetryableError for RpcQueryError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            RpcQueryError::NoSyncedBlocks | RpcQueryError::InternalError { .. } => true,
+            RpcQueryError::UnavailableShard { .. }
+            | RpcQueryError::GarbageCollectedBlock { .. }
+            | RpcQueryError::UnknownBlock { .. }
+            | RpcQueryError::InvalidAccount { .. }
+            | RpcQueryError::UnknownAccount { .. }
+            | RpcQueryError::NoContractCode { .. }
+            | RpcQueryError::TooLargeContractState { .. }
+            | RpcQueryError::UnknownAccessKey { .. }
+            | RpcQueryError::ContractExecutionError { .. } => false,
+        }
+    }
+}
+
+impl RetryableError for RpcTransactionError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            RpcTransactionError::InternalError { .. } | RpcTransactionError::TimeoutError => true,
+            RpcTransactionError::InvalidTransaction { .. }
+            | RpcTransactionError::DoesNotTrackShard
+            | RpcTransactionError::RequestRouted { .. }
+            | RpcTransactionError::UnknownTransaction { .. } => false,
+        }
+    }
+}
+
+impl RetryableError for RpcReceiptError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            RpcReceiptError::InternalError { .. } => true,
+            RpcReceiptError::UnknownReceipt { .. } => false,
+        }
+    }
+}
+
+impl RetryableError for RpcLightClientProofError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            RpcLightClientProofError::InternalError { .. } => true,
+            RpcLightClientProofError::UnknownBlock { .. }
+            | RpcLightClientProofError::InconsistentState { .. }
+            | RpcLightClientProofError::NotConfirmed { .. }
+            | RpcLightClientProofError::UnknownTransactionOrReceipt { .. }
+            | RpcLightClientProofError::UnavailableShard { .. } => false,
+        }
+    }
+}
+
+impl RetryableError for RpcLightClientNextBlockError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            RpcLightClientNextBlockError::InternalError { .. } => true,
+            RpcLightClientNextBlockError::UnknownBlock { .. }
+            | RpcLightClientNextBlockError::EpochOutOfBounds { .. } => false,
+        }
+    }
+}
+
+impl RetryableError for methods::broadcast_tx_async::RpcBroadcastTxAsyncError {
+    fn is_retryable(&self) -> bool {
+        match *self {}
+    }
+}
+
+impl RetryableError for RpcChunkError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            RpcChunkError::InternalError { .. } => true,
+            RpcChunkError::UnknownBlock { .. }
+            | RpcChunkError::InvalidShardId { .. }
+            | RpcChunkError::UnknownChunk { .. } => false,
+        }
+    }
+}
+
+impl RetryableError for RpcBlockError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            RpcBlockError::NotSyncedYet | RpcBlockError::InternalError { .. } => true,
+            RpcBlockError::UnknownBlock { .. } => false,
+        }
+    }
+}
+
+impl RetryableError for RpcValidatorError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            RpcValidatorError::ValidatorInfoUnavailable
+            | RpcValidatorError::InternalError { .. } => true,
+            RpcValidatorError::UnknownEpoch => false,
+        }
+    }
+}
+
+impl RetryableError for RpcProtocolConfigError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            RpcProtocolConfigError::InternalError { .. } => true,
+            RpcProtocolConfigError::UnknownBlock { .. } => false,
+        }
+    }
+}
+
+impl RetryableError for RpcGasPriceError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            RpcGasPriceError::InternalError { .. } => true,
+            RpcGasPriceError::UnknownBlock { .. } => false,
+        }
+    }
+}
+
+impl RetryableError for RpcStateChangesError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            RpcStateChangesError::NotSyncedYet | RpcStateChangesError::InternalError { .. } => true,
+            RpcStateChangesError::UnknownBlock { .. } => false,
+        }
+    }
+}
+
+impl RetryableError for RpcNetworkInfoError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            RpcNetworkInfoError::InternalError { .. } => true,
+        }
+    }
+}
+
+impl RetryableError for methods::EXPERIMENTAL_genesis_config::RpcGenesisConfigError {
+    fn is_retryable(&self) -> bool {
+        match *self {}
+    }
+}
+
+impl RetryableError for RpcClientConfigError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            RpcClientConfigError::InternalError { .. } => true,
+        }
+    }
+}
+
+/// Returns true if a failed call may succeed when sent again.
+///
+/// Transport errors, internal server errors, rate limiting and 5xx responses are retryable. Request validation errors,
+/// unauthorized requests and non-contextual errors are not. Handler errors are classified by their `RetryableError` impl.
+pub fn is_retryable<E: RetryableError>(err: &JsonRpcError<E>) -> bool {
+    match err {
+        JsonRpcError::TransportError(_) => true,
+        JsonRpcError::ServerError(server_error) => match server_error {
+            JsonRpcServerError::HandlerError(handler_error) => handler_error.is_retryable(),
+            JsonRpcServerError::InternalError { .. } => true,
+            JsonRpcServerError::ResponseStatusError(status_error) => match status_error {
+                JsonRpcServerResponseStatusError::TooManyRequests => true,
+                JsonRpcServerResponseStatusError::Unauthorized => false,
+                JsonRpcServerResponseStatusError::Unexpected { status } => {
+                    status.is_server_error() || status.as_u16() == 408
+                }
+            },
+            JsonRpcServerError::RequestValidationError(_)
+            | JsonRpcServerError::NonContextualError(_) => false,
+        },
+    }
+}
+
+/// Controls how often and how quickly a failed call is retried.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one. `1` disables retries.
+    pub max_attempts: u32,
+    /// Delay before the first retry.
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between two attempts.
+    pub max_backoff: Duration,
+    /// Factor the delay is multiplied by after every retry.
+    pub multiplier: f64,
+    /// Fraction of the delay, between `0.0` and `1.0`, that is randomly subtracted from it to spread out retries.
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(10),
+            multiplier: 2.0,
+            jitter: 0.2,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that makes a single attempt and never retries.
+    pub fn no_retry() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Returns the delay before the given retry, where `retry` starts at 1 for the first retry.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let exponent = retry.saturating_sub(1) as i32;
+        let backoff = self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent);
+        let backoff = backoff.min(self.max_backoff.as_secs_f64());
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let factor = if jitter > 0.0 {
+            1.0 - rand::thread_rng().gen_range(0.0..=jitter)
+        } else {
+            1.0
+        };
+        Duration::from_secs_f64(backoff * factor)
+    }
+}
+
+/// The methods broadcasting a transaction, which are not retried unless configured otherwise.
+const BROADCAST_METHODS: [ProviderMethod; 3] = [
+    ProviderMethod::SendTransaction,
+    ProviderMethod::SendTransactionAsync,
+    ProviderMethod::SendTx,
+];
+
+/// The policies of a `RetryProvider` or `RetryLayer`, for every method and per method.
+#[derive(Debug, Clone)]
+struct RetryPolicies {
+    policy: RetryPolicy,
+    method_policies: HashMap<ProviderMethod, RetryPolicy>,
+}
+
+impl Default for RetryPolicies {
+    fn default() -> Self {
+        Self {
+            policy: RetryPolicy::default(),
+            method_policies: BROADCAST_METHODS
+                .into_iter()
+                .map(|method| (method, RetryPolicy::no_retry()))
+                .collect(),
+        }
+    }
+}
+
+impl RetryPolicies {
+    fn get(&self, method: ProviderMethod) -> &RetryPolicy {
+        self.method_policies.get(&method).unwrap_or(&self.policy)
+    }
+}
+
+/// Represents a provider that retries the calls of an inner provider according to a `RetryPolicy`.
+pub struct RetryProvider<P> {
+    inner: P,
+    policies: Arc<RetryPolicies>,
+}
+
+impl<P: Provider> RetryProvider<P> {
+    /// Constructs a new `RetryProvider` around `inner`, using the default `RetryPolicy` for every method but the ones
+    /// broadcasting a transaction, which are not retried.
+    pub fn new(inner: P) -> Self {
+        Self {
+            inner,
+            policies: Arc::default(),
+        }
+    }
+
+    /// Sets the policy used by every method without a method specific policy. The methods broadcasting a transaction
+    /// keep `RetryPolicy::no_retry()` unless set with `with_method_policy`.
+    pub fn with_policy(mut self, policy: RetryPolicy) -> Self {
+        Arc::make_mut(&mut self.policies).policy = policy;
+        self
+    }
+
+    /// Sets the policy used by a single method, e.g. `RetryPolicy::no_retry()` for `ProviderMethod::Query`.
+    pub fn with_method_policy(mut self, method: ProviderMethod, policy: RetryPolicy) -> Self {
+        Arc::make_mut(&mut self.policies)
+            .method_policies
+            .insert(method, policy);
+        self
+    }
+
+    /// Returns the policy used for `method`.
+    pub fn policy(&self, method: ProviderMethod) -> &RetryPolicy {
+        self.policies.get(method)
+    }
+
+    /// Returns a reference to the wrapped provider.
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    /// Calls `request` until it succeeds, fails with a non retryable error or runs out of attempts.
+    async fn call<'a, T, E, F, Fut>(
+        &'a self,
+        method: ProviderMethod,
+        request: F,
+    ) -> Result<T, JsonRpcError<E>>
+    where
+        E: RetryableError,
+        F: Fn(&'a P) -> Fut,
+        Fut: Future<Output = Result<T, JsonRpcError<E>>>,
+    {
+        retry(self.policy(method), || request(&self.inner)).await
+    }
+}
+
+/// A `Middleware` retrying the calls that reach it as a `RetryProvider` does, to stack retries with other middlewares
+/// in a `ProviderBuilder`. Each retry runs the layers below it again.
+#[derive(Debug, Clone, Default)]
+pub struct RetryLayer {
+    policies: Arc<RetryPolicies>,
+}
+
+impl RetryLayer {
+    /// Constructs a new `RetryLayer`, using the default `RetryPolicy` for every method but the ones broadcasting a
+    /// transaction, which are not retried.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the policy used by every method without a method specific policy. The methods broadcasting a transaction
+    /// keep `RetryPolicy::no_retry()` unless set with `with_method_policy`.
+    pub fn with_policy(mut self, policy: RetryPolicy) -> Self {
+        Arc::make_mut(&mut self.policies).policy = policy;
+        self
+    }
+
+    /// Sets the policy used by a single method, e.g. `RetryPolicy::no_retry()` for `ProviderMethod::Query`.
+    pub fn with_method_policy(mut self, method: ProviderMethod, policy: RetryPolicy) -> Self {
+        Arc::make_mut(&mut self.policies)
+            .method_policies
+            .insert(method, policy);
+        self
+    }
+
+    /// Returns the policy used for `method`.
+    pub fn policy(&self, method: ProviderMethod) -> &RetryPolicy {
+        self.policies.get(method)
+    }
+}
+
+#[async_trait]
+impl Middleware for RetryLayer {
+    async fn handle(&self, request: ProviderRequest, next: Next<'_>) -> ProviderResponse {
+        let provider = RetryProvider {
+            inner: next,
+            policies: self.policies.clone(),
+        };
+        ProviderResponse::dispatch(&provider, request).await
+    }
+}
+
+/// Calls `request` until it succeeds, fails with a non retryable error or runs out of the attempts of `policy`.
+pub(crate) async fn retry<T, E, F, Fut>(
+    policy: &RetryPolicy,
+    mut request: F,
+) -> Result<T, JsonRpcError<E>>
+where
+    E: RetryableError,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, JsonRpcError<E>>>,
+{
+    let mut attempt = 1;
+    loop {
+        match request().await {
+            Err(err) if attempt < policy.max_attempts && is_retryable(&err) => {
+                tokio::time::sleep(policy.backoff(attempt)).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[async_trait]
+impl<P: Provider + Send + Sync> Provider for RetryProvider<P> {
+    /// Retrieves the current status of the NEAR blockchain, retrying transient failures.
+    async fn status(&self) -> Result<RpcStatusResponse, JsonRpcError<RpcStatusError>> {
+        self.call(ProviderMethod::Status, |provider| provider.status())
+            .await
+    }
+
+    /// Executes a query, retrying transient failures.
+    async fn query(
+        &self,
+        request: QueryRequest,
+        block_reference: BlockReference,
+    ) -> Result<RpcQueryResponse, JsonRpcError<RpcQueryError>> {
+        self.call(ProviderMethod::Query, |provider| {
+            provider.query(request.clone(), block_reference.clone())
+        })
+        .await
+    }
+
+    /// Sends a signed transaction and waits for its final execution outcome, without retrying by default.
+    async fn send_transaction(
+        &self,
+        signed_transaction: SignedTransaction,
+    ) -> Result<FinalExecutionOutcomeView, JsonRpcError<RpcTransactionError>> {
+        self.call(ProviderMethod::SendTransaction, |provider| {
+            provider.send_transaction(signed_transaction.clone())
+        })
+        .await
+    }
+
+    /// Sends a signed transaction asynchronously, without retrying by default.
+    async fn send_transaction_async(
+        &self,
+        signed_transaction: SignedTransaction,
+    ) -> Result<CryptoHash, JsonRpcError<methods::broadcast_tx_async::RpcBroadcastTxAsyncError>>
+    {
+        self.call(ProviderMethod::SendTransactionAsync, |provider| {
+            provider.send_transaction_async(signed_transaction.clone())
+        })
+        .await
+    }
+
+    /// Sends a signed transaction with the given `wait_until`, without retrying by default.
+    async fn send_tx(
+        &self,
+        signed_transaction: SignedTransaction,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionResponse, JsonRpcError<RpcTransactionError>> {
+        self.call(ProviderMethod::SendTx, |provider| {
+            provider.send_tx(signed_transaction.clone(), wait_until.clone())
+        })
+        .await
+    }
+
+    /// Retrieves the status of a transaction, retrying transient failures.
+    async fn tx_status(
+        &self,
+        transaction_info: TransactionInfo,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionResponse, JsonRpcError<RpcTransactionError>> {
+        self.call(ProviderMethod::TxStatus, |provider| {
+            provider.tx_status(transaction_info.clone(), wait_until.clone())
+        })
+        .await
+    }
+
+    /// Fetches details of a specific chunk, retrying transient failures.
+    async fn chunk(
+        &self,
+        chunk_reference: ChunkReference,
+    ) -> Result<ChunkView, JsonRpcError<RpcChunkError>> {
+        self.call(ProviderMethod::Chunk, |provider| {
+            provider.chunk(chunk_reference.clone())
+        })
+        .await
+    }
+
+    /// Retrieves a block, retrying transient failures.
+    async fn block(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<BlockView, JsonRpcError<RpcBlockError>> {
+        self.call(ProviderMethod::Block, |provider| {
+            provider.block(block_reference.clone())
+        })
+        .await
+    }
+
+    /// Fetches the protocol configuration, retrying transient failures.
+    async fn experimental_protocol_config(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<ProtocolConfigView, JsonRpcError<RpcProtocolConfigError>> {
+        self.call(ProviderMethod::ExperimentalProtocolConfig, |provider| {
+            provider.experimental_protocol_config(block_reference.clone())
+        })
+        .await
+    }
+
+    /// Retrieves information about validators, retrying transient failures.
+    async fn validators(
+        &self,
+        epoch_reference: EpochReference,
+    ) -> Result<EpochValidatorInfo, JsonRpcError<RpcValidatorError>> {
+        self.call(ProviderMethod::Validators, |provider| {
+            provider.validators(clone_epoch_reference(&epoch_reference))
+        })
+        .await
+    }
+
+    /// Fetches the gas price, retrying transient failures.
+    async fn gas_price(
+        &self,
+        block_id: Option<BlockId>,
+    ) -> Result<GasPriceView, JsonRpcError<RpcGasPriceError>> {
+        self.call(ProviderMethod::GasPrice, |provider| {
+            provider.gas_price(block_id.clone())
+        })
+        .await
+    }
+
+    /// Checks the health of the node, retrying transient failures.
+    async fn health(&self) -> Result<RpcHealthResponse, JsonRpcError<RpcStatusError>> {
+        self.call(ProviderMethod::Health, |provider| provider.health())
+            .await
+    }
+
+    /// Fetches the network information of the node, retrying transient failures.
+    async fn network_info(
+        &self,
+    ) -> Result<RpcNetworkInfoResponse, JsonRpcError<RpcNetworkInfoError>> {
+        self.call(ProviderMethod::NetworkInfo, |provider| {
+            provider.network_info()
+        })
+        .await
+    }
+
+    /// Fetches the genesis configuration, retrying transient failures.
+    async fn experimental_genesis_config(
+        &self,
+    ) -> Result<
+        GenesisConfig,
+        JsonRpcError<methods::EXPERIMENTAL_genesis_config::RpcGenesisConfigError>,
+    > {
+        self.call(ProviderMethod::ExperimentalGenesisConfig, |provider| {
+            provider.experimental_genesis_config()
+        })
+        .await
+    }
+
+    /// Fetches the client configuration of the node, retrying transient failures.
+    async fn client_config(
+        &self,
+    ) -> Result<RpcClientConfigResponse, JsonRpcError<RpcClientConfigError>> {
+        self.call(ProviderMethod::ClientConfig, |provider| {
+            provider.client_config()
+        })
+        .await
+    }
+
+    /// Lists the state changes made in a block, retrying transient failures.
+    async fn changes_in_block(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<RpcStateChangesInBlockByTypeResponse, JsonRpcError<RpcStateChangesError>> {
+        self.call(ProviderMethod::ChangesInBlock, |provider| {
+            provider.changes_in_block(block_reference.clone())
+        })
+        .await
+    }
+
+    /// Retrieves state changes, retrying transient failures.
+    async fn changes(
+        &self,
+        request: StateChangesRequestView,
+        block_reference: BlockReference,
+    ) -> Result<RpcStateChangesInBlockResponse, JsonRpcError<RpcStateChangesError>> {
+        self.call(ProviderMethod::Changes, |provider| {
+            provider.changes(
+                clone_state_changes_request(&request),
+                block_reference.clone(),
+            )
+        })
+        .await
+    }
+
+    /// Retrieves the status of a transaction and its receipts, retrying transient failures.
+    async fn tx_status_with_receipts(
+        &self,
+        transaction_info: TransactionInfo,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionWithReceiptsResponse, JsonRpcError<RpcTransactionError>> {
+        self.call(ProviderMethod::TxStatusWithReceipts, |provider| {
+            provider.tx_status_with_receipts(transaction_info.clone(), wait_until.clone())
+        })
+        .await
+    }
+
+    /// Retrieves a receipt, retrying transient failures.
+    async fn receipt(
+        &self,
+        receipt_id: CryptoHash,
+    ) -> Result<ReceiptView, JsonRpcError<RpcReceiptError>> {
+        self.call(ProviderMethod::Receipt, |provider| {
+            provider.receipt(receipt_id)
+        })
+        .await
+    }
+
+    /// Retrieves an execution proof, retrying transient failures.
+    async fn light_client_proof(
+        &self,
+        id: TransactionOrReceiptId,
+        light_client_head: CryptoHash,
+    ) -> Result<RpcLightClientExecutionProofResponse, JsonRpcError<RpcLightClientProofError>> {
+        self.call(ProviderMethod::LightClientProof, |provider| {
+            provider.light_client_proof(id.clone(), light_client_head)
+        })
+        .await
+    }
+
+    /// Retrieves the next light client block, retrying transient failures.
+    async fn next_light_client_block(
+        &self,
+        last_block_hash: CryptoHash,
+    ) -> Result<Option<LightClientBlockView>, JsonRpcError<RpcLightClientNextBlockError>> {
+        self.call(ProviderMethod::NextLightClientBlock, |provider| {
+            provider.next_light_client_block(last_block_hash)
+        })
+        .await
+    }
+
+    /// Sends `requests` as a batch of the inner provider. The requests that failed with a retryable error are sent
+    /// again, as a batch of their own, until they succeed or run out of the attempts of the policy of their method.
+    async fn batch(&self, requests: Vec<ProviderRequest>) -> Vec<ProviderResponse> {
+        let mut responses: Vec<Option<ProviderResponse>> = requests.iter().map(|_| None).collect();
+        let mut pending: Vec<(usize, ProviderRequest)> = requests.into_iter().enumerate().collect();
+        let mut attempt = 1;
+        while !pending.is_empty() {
+            let batch = pending.iter().map(|(_, request)| request.clone()).collect();
+            let batch_responses = self.inner.batch(batch).await;
+            let mut backoff = Duration::ZERO;
+            let mut retries = Vec::new();
+            for ((index, request), response) in pending.into_iter().zip(batch_responses) {
+                let policy = self.policy(request.method());
+                if attempt < policy.max_attempts && is_retryable_response(&response) {
+                    backoff = backoff.max(policy.backoff(attempt));
+                    retries.push((index, request));
+                }
+                responses[index] = Some(response);
+            }
+            if !retries.is_empty() {
+                tokio::time::sleep(backoff).await;
+            }
+            pending = retries;
+            attempt += 1;
+        }
+        responses
+            .into_iter()
+            .map(|response| response.expect("every request of the batch has a response"))
+            .collect()
+    }
+
+    fn is_batched(&self, request: &ProviderRequest) -> bool {
+        self.inner.is_batched(request)
+    }
+}
+
+/// Returns true if the call of `response` failed with a retryable error, see `is_retryable`.
+fn is_retryable_response(response: &ProviderResponse) -> bool {
+    for_each_result!(response, result => result.as_ref().err().is_some_and(is_retryable))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{access_key_response, handler_error, MockProvider};
+    use near_crypto::{InMemorySigner, KeyType};
+
+    /// Builds a provider whose queries fail with `failures`, in order, before succeeding.
+    fn failing_with(failures: Vec<RpcQueryError>) -> MockProvider {
+        let provider = MockProvider::new();
+        for failure in failures {
+            provider.push_query(Err(handler_error(failure)));
+        }
+        provider.on_query(|_, _| Some(Ok(access_key_response(7))));
+        provider
+    }
+
+    fn signed_transaction() -> SignedTransaction {
+        let signer =
+            InMemorySigner::from_seed("alice.near".parse().unwrap(), KeyType::ED25519, "alice");
+        SignedTransaction::send_money(
+            1,
+            "alice.near".parse().unwrap(),
+            "bob.near".parse().unwrap(),
+            &signer,
+            1,
+            CryptoHash::default(),
+        )
+    }
+
+    fn view_account_request() -> QueryRequest {
+        QueryRequest::ViewAccount {
+            account_id: "alice.near".parse().unwrap(),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retries_transient_errors() {
+        let provider = RetryProvider::new(failing_with(vec![
+            RpcQueryError::NoSyncedBlocks,
+            RpcQueryError::InternalError {
+                error_message: "busy".to_string(),
+            },
+        ]));
+
+        let response = provider
+            .query(view_account_request(), BlockReference::latest())
+            .await;
+
+        assert!(response.is_ok());
+        assert_eq!(provider.inner().call_count(ProviderMethod::Query), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_does_not_retry_deterministic_errors() {
+        let provider = RetryProvider::new(failing_with(vec![RpcQueryError::UnknownAccount {
+            requested_account_id: "alice.near".parse().unwrap(),
+            block_height: 1,
+            block_hash: CryptoHash::default(),
+        }]));
+
+        let response = provider
+            .query(view_account_request(), BlockReference::latest())
+            .await;
+
+        assert!(matches!(
+            response.unwrap_err().handler_error(),
+            Some(RpcQueryError::UnknownAccount { .. })
+        ));
+        assert_eq!(provider.inner().call_count(ProviderMethod::Query), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stops_after_max_attempts() {
+        let failures = (0..10).map(|_| RpcQueryError::NoSyncedBlocks).collect();
+        let provider = RetryProvider::new(failing_with(failures)).with_method_policy(
+            ProviderMethod::Query,
+            RetryPolicy {
+                max_attempts: 3,
+                ..RetryPolicy::default()
+            },
+        );
+
+        let response = provider
+            .query(view_account_request(), BlockReference::latest())
+            .await;
+
+        assert!(response.is_err());
+        assert_eq!(provider.inner().call_count(ProviderMethod::Query), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_does_not_resend_timed_out_transactions() {
+        let inner = MockProvider::new();
+        inner.push_send_tx(Err(handler_error(RpcTransactionError::TimeoutError)));
+        inner.push_send_tx(Err(handler_error(RpcTransactionError::TimeoutError)));
+        let provider = RetryProvider::new(inner).with_policy(RetryPolicy {
+            max_attempts: 10,
+            ..RetryPolicy::default()
+        });
+        let transaction = signed_transaction();
+
+        // The node may have accepted the transaction before timing out, so it is not sent again.
+        let response = provider
+            .send_tx(transaction.clone(), TxExecutionStatus::Final)
+            .await;
+        assert!(matches!(
+            response.unwrap_err().handler_error(),
+            Some(RpcTransactionError::TimeoutError)
+        ));
+        assert_eq!(provider.inner().call_count(ProviderMethod::SendTx), 1);
+        let responses = provider
+            .batch(vec![ProviderRequest::SendTx {
+                signed_transaction: transaction,
+                wait_until: TxExecutionStatus::Final,
+            }])
+            .await;
+        assert!(matches!(&responses[0], ProviderResponse::SendTx(Err(_))));
+        assert_eq!(provider.inner().call_count(ProviderMethod::SendTx), 2);
+
+        // Retrying a broadcast is opted in per method.
+        let inner = MockProvider::new();
+        inner.push_send_tx(Err(handler_error(RpcTransactionError::TimeoutError)));
+        inner.push_send_tx(Err(handler_error(RpcTransactionError::TimeoutError)));
+        let provider = RetryProvider::new(inner).with_method_policy(
+            ProviderMethod::SendTx,
+            RetryPolicy {
+                max_attempts: 2,
+                ..RetryPolicy::default()
+            },
+        );
+        provider
+            .send_tx(signed_transaction(), TxExecutionStatus::Final)
+            .await
+            .unwrap_err();
+        assert_eq!(provider.inner().call_count(ProviderMethod::SendTx), 2);
+    }
+
+    #[test]
+    fn test_backoff_is_exponential_and_capped() {
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(300),
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        };
+
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(300));
+    }
+}
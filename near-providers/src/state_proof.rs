@@ -0,0 +1,478 @@
+//! The `state_proof` module verifies the contract state returned by `view_state` queries made with `include_proof`.
+//!
+//! The proof of a `view_state` result is the list of trie nodes the node visited to read the state. Starting from the
+//! state root of the shard of the contract, the verifier follows these nodes down to the keys starting with the
+//! requested prefix and checks that the returned values are exactly the values committed to in the trie, so a node
+//! can neither alter, add nor omit a key.
+//!
+//! The state root is taken from the chunk headers of a block: the chunk of a shard included in a block records the
+//! state root of the shard after the parent block, see `parent_state_root`. The block itself is trusted as returned by
+//! `Provider::block`; it can be authenticated with the `light_client` module.
+
+use near_primitives::{
+    hash::CryptoHash,
+    serialize::to_base64,
+    trie_key::TrieKey,
+    types::{AccountId, ShardId, StateRoot},
+    views::{BlockView, StateItem, ViewStateResult},
+};
+use std::collections::{BTreeMap, HashMap};
+
+/// The reasons the state returned by a `view_state` query is rejected.
+#[derive(Debug, thiserror::Error)]
+pub enum StateProofError {
+    #[error("Trie node {0} is missing from the proof")]
+    MissingNode(CryptoHash),
+    #[error("Trie node {0} is malformed")]
+    MalformedNode(CryptoHash),
+    #[error("The value of key {} does not match the proof", to_base64(.0))]
+    ValueMismatch(Vec<u8>),
+    #[error("Key {} is not part of the proven state", to_base64(.0))]
+    UnprovenKey(Vec<u8>),
+    #[error("Key {} of the proven state is missing from the result", to_base64(.0))]
+    MissingKey(Vec<u8>),
+    #[error("Block {block_hash} does not include a new chunk of shard {shard_id}")]
+    ChunkMissing {
+        block_hash: CryptoHash,
+        shard_id: ShardId,
+    },
+}
+
+/// The contract state returned by a `view_state` query, once verified against a state root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedViewStateResult {
+    /// The state root the values were verified against.
+    pub state_root: StateRoot,
+    /// The key-value pairs of the contract storage starting with the requested prefix, ordered by key.
+    pub values: Vec<StateItem>,
+}
+
+/// Returns the state root of shard `shard_id` after the parent of `block`, i.e. the state a query at the block
+/// `block.header.prev_hash` is evaluated on.
+///
+/// It is the previous state root recorded in the chunk of the shard included in `block`, and is only available if that
+/// chunk is new in `block`: a block without a new chunk repeats the header of the last chunk of the shard.
+pub fn parent_state_root(
+    block: &BlockView,
+    shard_id: ShardId,
+) -> Result<StateRoot, StateProofError> {
+    block
+        .chunks
+        .iter()
+        .find(|chunk| chunk.shard_id == shard_id && chunk.height_included == block.header.height)
+        .map(|chunk| chunk.prev_state_root)
+        .ok_or(StateProofError::ChunkMissing {
+            block_hash: block.header.hash,
+            shard_id,
+        })
+}
+
+/// Verifies the result of a `view_state` query for the keys of the contract `account_id` starting with `prefix`
+/// against the state root `state_root`. The result must have been requested with `include_proof`.
+pub fn verify_view_state(
+    account_id: &AccountId,
+    prefix: &[u8],
+    result: ViewStateResult,
+    state_root: StateRoot,
+) -> Result<VerifiedViewStateResult, StateProofError> {
+    let account_prefix_len = TrieKey::ContractData {
+        account_id: account_id.clone(),
+        key: vec![],
+    }
+    .to_vec()
+    .len();
+    let trie_prefix = TrieKey::ContractData {
+        account_id: account_id.clone(),
+        key: prefix.to_vec(),
+    }
+    .to_vec();
+
+    let trie = ProvenTrie::new(&result.proof);
+    let mut proven = BTreeMap::new();
+    for (path, value) in trie.values_with_prefix(state_root, &to_nibbles(&trie_prefix))? {
+        let trie_key = from_nibbles(&path).ok_or(StateProofError::MalformedNode(state_root))?;
+        proven.insert(trie_key[account_prefix_len..].to_vec(), value);
+    }
+
+    let mut values = result.values;
+    values.sort_by(|a, b| a.key.cmp(&b.key));
+    for item in &values {
+        let value = proven
+            .remove(item.key.as_slice())
+            .ok_or_else(|| StateProofError::UnprovenKey(item.key.to_vec()))?;
+        if value.length as usize != item.value.len()
+            || value.hash != CryptoHash::hash_bytes(&item.value)
+        {
+            return Err(StateProofError::ValueMismatch(item.key.to_vec()));
+        }
+    }
+    if let Some((key, _)) = proven.into_iter().next() {
+        return Err(StateProofError::MissingKey(key));
+    }
+    Ok(VerifiedViewStateResult { state_root, values })
+}
+
+/// A reference to a value stored in the trie.
+struct ValueRef {
+    length: u32,
+    hash: CryptoHash,
+}
+
+/// A decoded trie node. Keys are stored as nibbles.
+enum TrieNode {
+    Leaf(Vec<u8>, ValueRef),
+    Branch(Option<ValueRef>, Box<[Option<CryptoHash>; 16]>),
+    Extension(Vec<u8>, CryptoHash),
+}
+
+/// The trie nodes of a proof, by hash.
+struct ProvenTrie<'a> {
+    nodes: HashMap<CryptoHash, &'a [u8]>,
+}
+
+impl<'a> ProvenTrie<'a> {
+    fn new(proof: &'a [std::sync::Arc<[u8]>]) -> Self {
+        let nodes = proof
+            .iter()
+            .map(|node| (CryptoHash::hash_bytes(node), node.as_ref()))
+            .collect();
+        Self { nodes }
+    }
+
+    fn node(&self, hash: CryptoHash) -> Result<TrieNode, StateProofError> {
+        let bytes = self
+            .nodes
+            .get(&hash)
+            .ok_or(StateProofError::MissingNode(hash))?;
+        decode_node(bytes).ok_or(StateProofError::MalformedNode(hash))
+    }
+
+    /// Returns the paths and values of all the keys starting with `prefix`, in key order.
+    fn values_with_prefix(
+        &self,
+        root: StateRoot,
+        prefix: &[u8],
+    ) -> Result<Vec<(Vec<u8>, ValueRef)>, StateProofError> {
+        let mut values = vec![];
+        if root == StateRoot::default() {
+            // The state of the shard is empty.
+            return Ok(values);
+        }
+        let mut hash = root;
+        let mut path = vec![];
+        let mut remaining = prefix;
+        loop {
+            match self.node(hash)? {
+                TrieNode::Leaf(key, value) => {
+                    if key.starts_with(remaining) {
+                        path.extend(key);
+                        values.push((path, value));
+                    }
+                    return Ok(values);
+                }
+                TrieNode::Extension(key, child) => {
+                    if key.starts_with(remaining) {
+                        path.extend(key);
+                        self.collect(self.node(child)?, path, &mut values)?;
+                        return Ok(values);
+                    }
+                    if !remaining.starts_with(&key) {
+                        return Ok(values);
+                    }
+                    path.extend_from_slice(&key);
+                    remaining = &remaining[key.len()..];
+                    hash = child;
+                }
+                node @ TrieNode::Branch(..) if remaining.is_empty() => {
+                    self.collect(node, path, &mut values)?;
+                    return Ok(values);
+                }
+                TrieNode::Branch(_, children) => {
+                    let Some(child) = children[remaining[0] as usize] else {
+                        return Ok(values);
+                    };
+                    path.push(remaining[0]);
+                    remaining = &remaining[1..];
+                    hash = child;
+                }
+            }
+        }
+    }
+
+    /// Collects the paths and values of all the keys under `node`, whose path is `path`.
+    fn collect(
+        &self,
+        node: TrieNode,
+        mut path: Vec<u8>,
+        values: &mut Vec<(Vec<u8>, ValueRef)>,
+    ) -> Result<(), StateProofError> {
+        match node {
+            TrieNode::Leaf(key, value) => {
+                path.extend(key);
+                values.push((path, value));
+            }
+            TrieNode::Extension(key, child) => {
+                path.extend(key);
+                self.collect(self.node(child)?, path, values)?;
+            }
+            TrieNode::Branch(value, children) => {
+                if let Some(value) = value {
+                    values.push((path.clone(), value));
+                }
+                for (nibble, child) in children.into_iter().enumerate() {
+                    if let Some(child) = child {
+                        let mut child_path = path.clone();
+                        child_path.push(nibble as u8);
+                        self.collect(self.node(child)?, child_path, values)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Decodes a borsh serialized `RawTrieNodeWithSize` of nearcore.
+fn decode_node(bytes: &[u8]) -> Option<TrieNode> {
+    let mut reader = Reader(bytes);
+    let node = match reader.u8()? {
+        0 => {
+            let key = decode_nibbles(reader.bytes()?)?;
+            TrieNode::Leaf(key, reader.value_ref()?)
+        }
+        1 => TrieNode::Branch(None, reader.children()?),
+        2 => {
+            let value = reader.value_ref()?;
+            TrieNode::Branch(Some(value), reader.children()?)
+        }
+        3 => {
+            let key = decode_nibbles(reader.bytes()?)?;
+            TrieNode::Extension(key, reader.hash()?)
+        }
+        _ => return None,
+    };
+    // The memory usage of the node.
+    reader.take(8)?;
+    reader.0.is_empty().then_some(node)
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn hash(&mut self) -> Option<CryptoHash> {
+        Some(CryptoHash(self.take(32)?.try_into().ok()?))
+    }
+
+    fn bytes(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    fn value_ref(&mut self) -> Option<ValueRef> {
+        Some(ValueRef {
+            length: self.u32()?,
+            hash: self.hash()?,
+        })
+    }
+
+    /// Reads the children of a branch: a bitmap of the present children, then their hashes in order.
+    fn children(&mut self) -> Option<Box<[Option<CryptoHash>; 16]>> {
+        let bitmap = u16::from_le_bytes(self.take(2)?.try_into().ok()?);
+        let mut children = Box::new([None; 16]);
+        for (nibble, child) in children.iter_mut().enumerate() {
+            if bitmap & (1 << nibble) != 0 {
+                *child = Some(self.hash()?);
+            }
+        }
+        Some(children)
+    }
+}
+
+/// Decodes the key of a leaf or extension node. The first byte holds flags, and the first nibble if the key has an odd
+/// number of nibbles.
+fn decode_nibbles(encoded: &[u8]) -> Option<Vec<u8>> {
+    let (first, rest) = encoded.split_first()?;
+    let mut nibbles = Vec::with_capacity(rest.len() * 2 + 1);
+    if first & 0x10 != 0 {
+        nibbles.push(first & 0x0f);
+    }
+    nibbles.extend(to_nibbles(rest));
+    Some(nibbles)
+}
+
+fn to_nibbles(bytes: &[u8]) -> Vec<u8> {
+    bytes
+        .iter()
+        .flat_map(|byte| [byte >> 4, byte & 0x0f])
+        .collect()
+}
+
+fn from_nibbles(nibbles: &[u8]) -> Option<Vec<u8>> {
+    if !nibbles.len().is_multiple_of(2) {
+        return None;
+    }
+    Some(
+        nibbles
+            .chunks(2)
+            .map(|pair| (pair[0] << 4) | pair[1])
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{block_view, chunk_header, trie_proof};
+    use near_primitives::types::StoreKey;
+
+    fn contract_data(account_id: &str, key: &[u8], value: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let key = TrieKey::ContractData {
+            account_id: account_id.parse().unwrap(),
+            key: key.to_vec(),
+        };
+        (key.to_vec(), value.to_vec())
+    }
+
+    fn state_item(key: &[u8], value: &[u8]) -> StateItem {
+        StateItem {
+            key: StoreKey::from(key.to_vec()),
+            value: value.to_vec().into(),
+        }
+    }
+
+    /// The state of two contracts, with keys that share prefixes, end inside other keys and have odd nibble counts.
+    fn state() -> Vec<(Vec<u8>, Vec<u8>)> {
+        vec![
+            contract_data("alice.near", b"STATE", b"state"),
+            contract_data("alice.near", b"a", b"1"),
+            contract_data("alice.near", b"ab", b"2"),
+            contract_data("alice.near", b"abc", b"3"),
+            contract_data("alice.near", b"b", b"4"),
+            contract_data("alice.nea", b"a", b"5"),
+            contract_data("bob.near", b"a", b"6"),
+        ]
+    }
+
+    fn view_state(values: Vec<StateItem>, proof: &[std::sync::Arc<[u8]>]) -> ViewStateResult {
+        ViewStateResult {
+            values,
+            proof: proof.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_verify_view_state() {
+        let (state_root, proof) = trie_proof(&state());
+        let alice: AccountId = "alice.near".parse().unwrap();
+
+        let values = vec![
+            state_item(b"ab", b"2"),
+            state_item(b"a", b"1"),
+            state_item(b"abc", b"3"),
+        ];
+        let verified =
+            verify_view_state(&alice, b"a", view_state(values, &proof), state_root).unwrap();
+        assert_eq!(verified.state_root, state_root);
+        assert_eq!(
+            verified.values,
+            vec![
+                state_item(b"a", b"1"),
+                state_item(b"ab", b"2"),
+                state_item(b"abc", b"3"),
+            ]
+        );
+
+        let values = vec![
+            state_item(b"STATE", b"state"),
+            state_item(b"a", b"1"),
+            state_item(b"ab", b"2"),
+            state_item(b"abc", b"3"),
+            state_item(b"b", b"4"),
+        ];
+        let verified =
+            verify_view_state(&alice, b"", view_state(values, &proof), state_root).unwrap();
+        assert_eq!(verified.values.len(), 5);
+
+        verify_view_state(&alice, b"c", view_state(vec![], &proof), state_root).unwrap();
+        let carol: AccountId = "carol.near".parse().unwrap();
+        verify_view_state(&carol, b"", view_state(vec![], &proof), state_root).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_unproven_state() {
+        let (state_root, proof) = trie_proof(&state());
+        let alice: AccountId = "alice.near".parse().unwrap();
+
+        let altered = vec![state_item(b"ab", b"7"), state_item(b"abc", b"3")];
+        assert!(matches!(
+            verify_view_state(&alice, b"ab", view_state(altered, &proof), state_root),
+            Err(StateProofError::ValueMismatch(key)) if key == b"ab"
+        ));
+
+        let omitted = vec![state_item(b"ab", b"2")];
+        assert!(matches!(
+            verify_view_state(&alice, b"ab", view_state(omitted, &proof), state_root),
+            Err(StateProofError::MissingKey(key)) if key == b"abc"
+        ));
+
+        let added = vec![
+            state_item(b"ab", b"2"),
+            state_item(b"abc", b"3"),
+            state_item(b"abd", b"8"),
+        ];
+        assert!(matches!(
+            verify_view_state(&alice, b"ab", view_state(added, &proof), state_root),
+            Err(StateProofError::UnprovenKey(key)) if key == b"abd"
+        ));
+
+        // The nodes are listed children first, so the last one is the root.
+        let values = vec![state_item(b"b", b"4")];
+        assert!(matches!(
+            verify_view_state(
+                &alice,
+                b"b",
+                view_state(values.clone(), &proof[..proof.len() - 1]),
+                state_root
+            ),
+            Err(StateProofError::MissingNode(_))
+        ));
+        let (other_root, _) = trie_proof(&state()[1..]);
+        assert!(matches!(
+            verify_view_state(&alice, b"b", view_state(values, &proof), other_root),
+            Err(StateProofError::MissingNode(hash)) if hash == other_root
+        ));
+    }
+
+    #[test]
+    fn test_parent_state_root() {
+        let state_root = CryptoHash::hash_bytes(b"state");
+        let mut block = block_view(10, CryptoHash::hash_bytes(b"block"));
+        block.chunks = vec![
+            chunk_header(0, 10, CryptoHash::default()),
+            chunk_header(1, 10, state_root),
+            chunk_header(2, 9, state_root),
+        ];
+        assert_eq!(parent_state_root(&block, 1).unwrap(), state_root);
+        assert!(matches!(
+            parent_state_root(&block, 2),
+            Err(StateProofError::ChunkMissing { shard_id: 2, .. })
+        ));
+    }
+}
@@ -0,0 +1,1508 @@
+//! This module provides the `MockProvider`, an in-memory implementation of the `Provider` trait for
+//! unit testing code built on `near-providers` without a network connection.
+//!
+//! Responses are scripted per method, either as one-shot results consumed in order or as a handler
+//! that answers each request based on its arguments, e.g. a `QueryRequest::ViewAccessKey` for a
+//! given key returning a given nonce. Errors are injected the same way. Every call made to the mock
+//! is recorded together with its arguments, so tests can assert on what was requested and on the
+//! signed transactions that were sent.
+//!
+//! The module is compiled when the `testing` feature of `near-providers` is enabled.
+
+use crate::client_config::{RpcClientConfigError, RpcClientConfigResponse};
+use crate::jsonrpc_client::{
+    errors::{JsonRpcError, JsonRpcServerError},
+    methods::{self, status::RpcStatusResponse},
+};
+use crate::provider::{Provider, ProviderMethod, ProviderRequest};
+use crate::tx_status::RpcTransactionWithReceiptsResponse;
+use crate::types::{
+    blocks::RpcBlockError,
+    changes::{
+        RpcStateChangesError, RpcStateChangesInBlockByTypeResponse, RpcStateChangesInBlockResponse,
+    },
+    chunks::{ChunkReference, RpcChunkError},
+    config::RpcProtocolConfigError,
+    gas_price::RpcGasPriceError,
+    light_client::{
+        RpcLightClientExecutionProofResponse, RpcLightClientNextBlockError,
+        RpcLightClientProofError,
+    },
+    network_info::{RpcNetworkInfoError, RpcNetworkInfoResponse},
+    query::{QueryResponseKind, RpcQueryError, RpcQueryResponse},
+    receipts::RpcReceiptError,
+    status::{RpcHealthResponse, RpcStatusError},
+    transactions::{RpcTransactionError, TransactionInfo},
+    validator::RpcValidatorError,
+};
+use crate::utils::{clone_epoch_reference, clone_state_changes_request};
+use async_trait::async_trait;
+use near_chain_configs::{GenesisConfig, ProtocolConfigView};
+use near_crypto::{KeyType, Signature};
+use near_jsonrpc_client::methods::tx::RpcTransactionResponse;
+use near_primitives::{
+    hash::CryptoHash,
+    transaction::SignedTransaction,
+    types::{
+        BlockHeight, BlockId, BlockReference, EpochReference, ShardId, StateRoot,
+        TransactionOrReceiptId,
+    },
+    views::{
+        AccessKeyPermissionView, AccessKeyView, BlockHeaderView, BlockView, ChunkHeaderView,
+        ChunkView, EpochValidatorInfo, FinalExecutionOutcomeView, GasPriceView,
+        LightClientBlockView, QueryRequest, ReceiptView, StateChangesRequestView,
+        TxExecutionStatus,
+    },
+};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+type Handler<A, T, E> = Box<dyn Fn(&A) -> Option<Result<T, JsonRpcError<E>>> + Send + Sync>;
+
+/// The scripted responses of a single `Provider` method.
+struct Script<A, T, E> {
+    queue: VecDeque<Result<T, JsonRpcError<E>>>,
+    handler: Option<Handler<A, T, E>>,
+}
+
+impl<A, T, E> Default for Script<A, T, E> {
+    fn default() -> Self {
+        Self {
+            queue: VecDeque::new(),
+            handler: None,
+        }
+    }
+}
+
+#[allow(clippy::result_large_err)]
+impl<A, T, E> Script<A, T, E> {
+    /// Answers a call with the next queued result, or with the handler if the queue is empty.
+    fn respond(&mut self, method: ProviderMethod, args: &A) -> Result<T, JsonRpcError<E>> {
+        if let Some(result) = self.queue.pop_front() {
+            return result;
+        }
+        self.handler
+            .as_ref()
+            .and_then(|handler| handler(args))
+            .unwrap_or_else(|| {
+                Err(internal_error(format!(
+                    "MockProvider: no response scripted for {}",
+                    method.rpc_method_name()
+                )))
+            })
+    }
+}
+
+#[derive(Default)]
+struct Scripts {
+    status: Script<(), RpcStatusResponse, RpcStatusError>,
+    send_transaction: Script<SignedTransaction, FinalExecutionOutcomeView, RpcTransactionError>,
+    send_transaction_async: Script<
+        SignedTransaction,
+        CryptoHash,
+        methods::broadcast_tx_async::RpcBroadcastTxAsyncError,
+    >,
+    send_tx:
+        Script<(SignedTransaction, TxExecutionStatus), RpcTransactionResponse, RpcTransactionError>,
+    tx_status:
+        Script<(TransactionInfo, TxExecutionStatus), RpcTransactionResponse, RpcTransactionError>,
+    chunk: Script<ChunkReference, ChunkView, RpcChunkError>,
+    block: Script<BlockReference, BlockView, RpcBlockError>,
+    validators: Script<EpochReference, EpochValidatorInfo, RpcValidatorError>,
+    query: Script<(QueryRequest, BlockReference), RpcQueryResponse, RpcQueryError>,
+    experimental_protocol_config:
+        Script<BlockReference, ProtocolConfigView, RpcProtocolConfigError>,
+    gas_price: Script<Option<BlockId>, GasPriceView, RpcGasPriceError>,
+    health: Script<(), RpcHealthResponse, RpcStatusError>,
+    network_info: Script<(), RpcNetworkInfoResponse, RpcNetworkInfoError>,
+    experimental_genesis_config:
+        Script<(), GenesisConfig, methods::EXPERIMENTAL_genesis_config::RpcGenesisConfigError>,
+    client_config: Script<(), RpcClientConfigResponse, RpcClientConfigError>,
+    changes_in_block:
+        Script<BlockReference, RpcStateChangesInBlockByTypeResponse, RpcStateChangesError>,
+    changes: Script<
+        (StateChangesRequestView, BlockReference),
+        RpcStateChangesInBlockResponse,
+        RpcStateChangesError,
+    >,
+    tx_status_with_receipts: Script<
+        (TransactionInfo, TxExecutionStatus),
+        RpcTransactionWithReceiptsResponse,
+        RpcTransactionError,
+    >,
+    receipt: Script<CryptoHash, ReceiptView, RpcReceiptError>,
+    light_client_proof: Script<
+        (TransactionOrReceiptId, CryptoHash),
+        RpcLightClientExecutionProofResponse,
+        RpcLightClientProofError,
+    >,
+    next_light_client_block:
+        Script<CryptoHash, Option<LightClientBlockView>, RpcLightClientNextBlockError>,
+}
+
+/// An in-memory `Provider` answering calls with scripted responses and recording every call.
+///
+/// For each method, results pushed with the `push_*` methods are returned first, one per call, in
+/// the order they were pushed. Once they are used up, the handler registered with the matching
+/// `on_*` method is asked for a response; a handler returning `None` declines the request. Calls
+/// that nothing answers fail with an internal server error naming the method.
+///
+/// Clones share their scripted responses and recorded calls, so a clone can be moved into a
+/// provider that takes ownership of its inner providers, such as the `FailoverProvider`, while the
+/// test keeps another one to script and inspect.
+///
+/// ```
+/// use near_primitives::types::{BlockReference, Finality};
+/// use near_primitives::views::QueryRequest;
+/// use near_providers::testing::{access_key_response, MockProvider};
+/// use near_providers::{Provider, ProviderMethod};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let provider = MockProvider::new();
+/// provider.on_query(|request, _| match request {
+///     QueryRequest::ViewAccessKey { .. } => Some(Ok(access_key_response(41))),
+///     _ => None,
+/// });
+///
+/// let request = QueryRequest::ViewAccessKey {
+///     account_id: "alice.testnet".parse().unwrap(),
+///     public_key: "ed25519:6E8sCci9badyRkXb3JoRpBj5p8C6Tw41ELDZoiihKEtp".parse().unwrap(),
+/// };
+/// let response = provider
+///     .query(request, BlockReference::Finality(Finality::Final))
+///     .await
+///     .unwrap();
+/// assert_eq!(response.block_height, 1);
+/// assert_eq!(provider.call_count(ProviderMethod::Query), 1);
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct MockProvider {
+    scripts: Arc<Mutex<Scripts>>,
+    calls: Arc<Mutex<Vec<ProviderRequest>>>,
+    latency: Duration,
+}
+
+impl MockProvider {
+    /// Creates a `MockProvider` with no scripted responses.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Delays every response of this provider by `latency`, e.g. to observe how many calls are in flight at once.
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Returns every call made to the provider so far, in order.
+    pub fn calls(&self) -> Vec<ProviderRequest> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Returns the number of calls made to the given method so far.
+    pub fn call_count(&self, method: ProviderMethod) -> usize {
+        self.calls
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|call| call.method() == method)
+            .count()
+    }
+
+    /// Returns the signed transactions received by any of the send methods so far, in order.
+    pub fn signed_transactions(&self) -> Vec<SignedTransaction> {
+        self.calls
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|call| call.signed_transaction().cloned())
+            .collect()
+    }
+
+    /// Forgets the calls recorded so far, keeping the scripted responses.
+    pub fn clear_calls(&self) {
+        self.calls.lock().unwrap().clear();
+    }
+
+    async fn record(&self, request: ProviderRequest) {
+        self.calls.lock().unwrap().push(request);
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+    }
+
+    /// Queues the result of the next `status` call.
+    pub fn push_status(&self, result: Result<RpcStatusResponse, JsonRpcError<RpcStatusError>>) {
+        self.scripts.lock().unwrap().status.queue.push_back(result);
+    }
+
+    /// Answers `status` calls that have no queued result.
+    pub fn on_status<F>(&self, handler: F)
+    where
+        F: Fn() -> Option<Result<RpcStatusResponse, JsonRpcError<RpcStatusError>>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.scripts.lock().unwrap().status.handler = Some(Box::new(move |_| handler()));
+    }
+
+    /// Queues the result of the next `send_transaction` call.
+    pub fn push_send_transaction(
+        &self,
+        result: Result<FinalExecutionOutcomeView, JsonRpcError<RpcTransactionError>>,
+    ) {
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts.send_transaction.queue.push_back(result);
+    }
+
+    /// Answers `send_transaction` calls that have no queued result.
+    pub fn on_send_transaction<F>(&self, handler: F)
+    where
+        F: Fn(
+                &SignedTransaction,
+            )
+                -> Option<Result<FinalExecutionOutcomeView, JsonRpcError<RpcTransactionError>>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.scripts.lock().unwrap().send_transaction.handler = Some(Box::new(handler));
+    }
+
+    /// Queues the result of the next `send_transaction_async` call.
+    pub fn push_send_transaction_async(
+        &self,
+        result: Result<
+            CryptoHash,
+            JsonRpcError<methods::broadcast_tx_async::RpcBroadcastTxAsyncError>,
+        >,
+    ) {
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts.send_transaction_async.queue.push_back(result);
+    }
+
+    /// Answers `send_transaction_async` calls that have no queued result.
+    pub fn on_send_transaction_async<F>(&self, handler: F)
+    where
+        F: Fn(
+                &SignedTransaction,
+            ) -> Option<
+                Result<
+                    CryptoHash,
+                    JsonRpcError<methods::broadcast_tx_async::RpcBroadcastTxAsyncError>,
+                >,
+            > + Send
+            + Sync
+            + 'static,
+    {
+        self.scripts.lock().unwrap().send_transaction_async.handler = Some(Box::new(handler));
+    }
+
+    /// Queues the result of the next `send_tx` call.
+    pub fn push_send_tx(
+        &self,
+        result: Result<RpcTransactionResponse, JsonRpcError<RpcTransactionError>>,
+    ) {
+        self.scripts.lock().unwrap().send_tx.queue.push_back(result);
+    }
+
+    /// Answers `send_tx` calls that have no queued result.
+    pub fn on_send_tx<F>(&self, handler: F)
+    where
+        F: Fn(
+                &SignedTransaction,
+                &TxExecutionStatus,
+            )
+                -> Option<Result<RpcTransactionResponse, JsonRpcError<RpcTransactionError>>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.scripts.lock().unwrap().send_tx.handler =
+            Some(Box::new(move |(signed_transaction, wait_until)| {
+                handler(signed_transaction, wait_until)
+            }));
+    }
+
+    /// Queues the result of the next `tx_status` call.
+    pub fn push_tx_status(
+        &self,
+        result: Result<RpcTransactionResponse, JsonRpcError<RpcTransactionError>>,
+    ) {
+        self.scripts
+            .lock()
+            .unwrap()
+            .tx_status
+            .queue
+            .push_back(result);
+    }
+
+    /// Answers `tx_status` calls that have no queued result.
+    pub fn on_tx_status<F>(&self, handler: F)
+    where
+        F: Fn(
+                &TransactionInfo,
+                &TxExecutionStatus,
+            )
+                -> Option<Result<RpcTransactionResponse, JsonRpcError<RpcTransactionError>>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.scripts.lock().unwrap().tx_status.handler =
+            Some(Box::new(move |(transaction_info, wait_until)| {
+                handler(transaction_info, wait_until)
+            }));
+    }
+
+    /// Queues the result of the next `chunk` call.
+    pub fn push_chunk(&self, result: Result<ChunkView, JsonRpcError<RpcChunkError>>) {
+        self.scripts.lock().unwrap().chunk.queue.push_back(result);
+    }
+
+    /// Answers `chunk` calls that have no queued result.
+    pub fn on_chunk<F>(&self, handler: F)
+    where
+        F: Fn(&ChunkReference) -> Option<Result<ChunkView, JsonRpcError<RpcChunkError>>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.scripts.lock().unwrap().chunk.handler = Some(Box::new(handler));
+    }
+
+    /// Queues the result of the next `block` call.
+    pub fn push_block(&self, result: Result<BlockView, JsonRpcError<RpcBlockError>>) {
+        self.scripts.lock().unwrap().block.queue.push_back(result);
+    }
+
+    /// Answers `block` calls that have no queued result.
+    pub fn on_block<F>(&self, handler: F)
+    where
+        F: Fn(&BlockReference) -> Option<Result<BlockView, JsonRpcError<RpcBlockError>>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.scripts.lock().unwrap().block.handler = Some(Box::new(handler));
+    }
+
+    /// Queues the result of the next `validators` call.
+    pub fn push_validators(
+        &self,
+        result: Result<EpochValidatorInfo, JsonRpcError<RpcValidatorError>>,
+    ) {
+        self.scripts
+            .lock()
+            .unwrap()
+            .validators
+            .queue
+            .push_back(result);
+    }
+
+    /// Answers `validators` calls that have no queued result.
+    pub fn on_validators<F>(&self, handler: F)
+    where
+        F: Fn(
+                &EpochReference,
+            ) -> Option<Result<EpochValidatorInfo, JsonRpcError<RpcValidatorError>>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.scripts.lock().unwrap().validators.handler = Some(Box::new(handler));
+    }
+
+    /// Queues the result of the next `query` call.
+    pub fn push_query(&self, result: Result<RpcQueryResponse, JsonRpcError<RpcQueryError>>) {
+        self.scripts.lock().unwrap().query.queue.push_back(result);
+    }
+
+    /// Answers `query` calls that have no queued result.
+    pub fn on_query<F>(&self, handler: F)
+    where
+        F: Fn(
+                &QueryRequest,
+                &BlockReference,
+            ) -> Option<Result<RpcQueryResponse, JsonRpcError<RpcQueryError>>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.scripts.lock().unwrap().query.handler =
+            Some(Box::new(move |(request, block_reference)| {
+                handler(request, block_reference)
+            }));
+    }
+
+    /// Queues the result of the next `experimental_protocol_config` call.
+    pub fn push_experimental_protocol_config(
+        &self,
+        result: Result<ProtocolConfigView, JsonRpcError<RpcProtocolConfigError>>,
+    ) {
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts.experimental_protocol_config.queue.push_back(result);
+    }
+
+    /// Answers `experimental_protocol_config` calls that have no queued result.
+    pub fn on_experimental_protocol_config<F>(&self, handler: F)
+    where
+        F: Fn(
+                &BlockReference,
+            )
+                -> Option<Result<ProtocolConfigView, JsonRpcError<RpcProtocolConfigError>>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.scripts
+            .lock()
+            .unwrap()
+            .experimental_protocol_config
+            .handler = Some(Box::new(handler));
+    }
+
+    /// Queues the result of the next `gas_price` call.
+    pub fn push_gas_price(&self, result: Result<GasPriceView, JsonRpcError<RpcGasPriceError>>) {
+        self.scripts
+            .lock()
+            .unwrap()
+            .gas_price
+            .queue
+            .push_back(result);
+    }
+
+    /// Answers `gas_price` calls that have no queued result.
+    pub fn on_gas_price<F>(&self, handler: F)
+    where
+        F: Fn(&Option<BlockId>) -> Option<Result<GasPriceView, JsonRpcError<RpcGasPriceError>>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.scripts.lock().unwrap().gas_price.handler = Some(Box::new(handler));
+    }
+
+    /// Queues the result of the next `health` call.
+    pub fn push_health(&self, result: Result<RpcHealthResponse, JsonRpcError<RpcStatusError>>) {
+        self.scripts.lock().unwrap().health.queue.push_back(result);
+    }
+
+    /// Answers `health` calls that have no queued result.
+    pub fn on_health<F>(&self, handler: F)
+    where
+        F: Fn() -> Option<Result<RpcHealthResponse, JsonRpcError<RpcStatusError>>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.scripts.lock().unwrap().health.handler = Some(Box::new(move |_| handler()));
+    }
+
+    /// Queues the result of the next `network_info` call.
+    pub fn push_network_info(
+        &self,
+        result: Result<RpcNetworkInfoResponse, JsonRpcError<RpcNetworkInfoError>>,
+    ) {
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts.network_info.queue.push_back(result);
+    }
+
+    /// Answers `network_info` calls that have no queued result.
+    pub fn on_network_info<F>(&self, handler: F)
+    where
+        F: Fn() -> Option<Result<RpcNetworkInfoResponse, JsonRpcError<RpcNetworkInfoError>>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.scripts.lock().unwrap().network_info.handler = Some(Box::new(move |_| handler()));
+    }
+
+    /// Queues the result of the next `experimental_genesis_config` call.
+    pub fn push_experimental_genesis_config(
+        &self,
+        result: Result<
+            GenesisConfig,
+            JsonRpcError<methods::EXPERIMENTAL_genesis_config::RpcGenesisConfigError>,
+        >,
+    ) {
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts.experimental_genesis_config.queue.push_back(result);
+    }
+
+    /// Answers `experimental_genesis_config` calls that have no queued result.
+    pub fn on_experimental_genesis_config<F>(&self, handler: F)
+    where
+        F: Fn() -> Option<
+                Result<
+                    GenesisConfig,
+                    JsonRpcError<methods::EXPERIMENTAL_genesis_config::RpcGenesisConfigError>,
+                >,
+            > + Send
+            + Sync
+            + 'static,
+    {
+        self.scripts
+            .lock()
+            .unwrap()
+            .experimental_genesis_config
+            .handler = Some(Box::new(move |_| handler()));
+    }
+
+    /// Queues the result of the next `client_config` call.
+    pub fn push_client_config(
+        &self,
+        result: Result<RpcClientConfigResponse, JsonRpcError<RpcClientConfigError>>,
+    ) {
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts.client_config.queue.push_back(result);
+    }
+
+    /// Answers `client_config` calls that have no queued result.
+    pub fn on_client_config<F>(&self, handler: F)
+    where
+        F: Fn() -> Option<Result<RpcClientConfigResponse, JsonRpcError<RpcClientConfigError>>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.scripts.lock().unwrap().client_config.handler = Some(Box::new(move |_| handler()));
+    }
+
+    /// Queues the result of the next `changes_in_block` call.
+    pub fn push_changes_in_block(
+        &self,
+        result: Result<RpcStateChangesInBlockByTypeResponse, JsonRpcError<RpcStateChangesError>>,
+    ) {
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts.changes_in_block.queue.push_back(result);
+    }
+
+    /// Answers `changes_in_block` calls that have no queued result.
+    pub fn on_changes_in_block<F>(&self, handler: F)
+    where
+        F: Fn(
+                &BlockReference,
+            ) -> Option<
+                Result<RpcStateChangesInBlockByTypeResponse, JsonRpcError<RpcStateChangesError>>,
+            > + Send
+            + Sync
+            + 'static,
+    {
+        self.scripts.lock().unwrap().changes_in_block.handler = Some(Box::new(handler));
+    }
+
+    /// Queues the result of the next `changes` call.
+    pub fn push_changes(
+        &self,
+        result: Result<RpcStateChangesInBlockResponse, JsonRpcError<RpcStateChangesError>>,
+    ) {
+        self.scripts.lock().unwrap().changes.queue.push_back(result);
+    }
+
+    /// Answers `changes` calls that have no queued result.
+    pub fn on_changes<F>(&self, handler: F)
+    where
+        F: Fn(
+                &StateChangesRequestView,
+                &BlockReference,
+            )
+                -> Option<Result<RpcStateChangesInBlockResponse, JsonRpcError<RpcStateChangesError>>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.scripts.lock().unwrap().changes.handler =
+            Some(Box::new(move |(request, block_reference)| {
+                handler(request, block_reference)
+            }));
+    }
+
+    /// Queues the result of the next `tx_status_with_receipts` call.
+    pub fn push_tx_status_with_receipts(
+        &self,
+        result: Result<RpcTransactionWithReceiptsResponse, JsonRpcError<RpcTransactionError>>,
+    ) {
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts.tx_status_with_receipts.queue.push_back(result);
+    }
+
+    /// Answers `tx_status_with_receipts` calls that have no queued result.
+    pub fn on_tx_status_with_receipts<F>(&self, handler: F)
+    where
+        F: Fn(
+                &TransactionInfo,
+                &TxExecutionStatus,
+            ) -> Option<
+                Result<RpcTransactionWithReceiptsResponse, JsonRpcError<RpcTransactionError>>,
+            > + Send
+            + Sync
+            + 'static,
+    {
+        self.scripts.lock().unwrap().tx_status_with_receipts.handler =
+            Some(Box::new(move |(transaction_info, wait_until)| {
+                handler(transaction_info, wait_until)
+            }));
+    }
+
+    /// Queues the result of the next `receipt` call.
+    pub fn push_receipt(&self, result: Result<ReceiptView, JsonRpcError<RpcReceiptError>>) {
+        self.scripts.lock().unwrap().receipt.queue.push_back(result);
+    }
+
+    /// Answers `receipt` calls that have no queued result.
+    pub fn on_receipt<F>(&self, handler: F)
+    where
+        F: Fn(&CryptoHash) -> Option<Result<ReceiptView, JsonRpcError<RpcReceiptError>>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.scripts.lock().unwrap().receipt.handler = Some(Box::new(handler));
+    }
+
+    /// Queues the result of the next `light_client_proof` call.
+    pub fn push_light_client_proof(
+        &self,
+        result: Result<
+            RpcLightClientExecutionProofResponse,
+            JsonRpcError<RpcLightClientProofError>,
+        >,
+    ) {
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts.light_client_proof.queue.push_back(result);
+    }
+
+    /// Answers `light_client_proof` calls that have no queued result.
+    pub fn on_light_client_proof<F>(&self, handler: F)
+    where
+        F: Fn(
+                &TransactionOrReceiptId,
+                &CryptoHash,
+            ) -> Option<
+                Result<
+                    RpcLightClientExecutionProofResponse,
+                    JsonRpcError<RpcLightClientProofError>,
+                >,
+            > + Send
+            + Sync
+            + 'static,
+    {
+        self.scripts.lock().unwrap().light_client_proof.handler =
+            Some(Box::new(move |(id, light_client_head)| {
+                handler(id, light_client_head)
+            }));
+    }
+
+    /// Queues the result of the next `next_light_client_block` call.
+    pub fn push_next_light_client_block(
+        &self,
+        result: Result<Option<LightClientBlockView>, JsonRpcError<RpcLightClientNextBlockError>>,
+    ) {
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts.next_light_client_block.queue.push_back(result);
+    }
+
+    /// Answers `next_light_client_block` calls that have no queued result.
+    pub fn on_next_light_client_block<F>(&self, handler: F)
+    where
+        F: Fn(
+                &CryptoHash,
+            ) -> Option<
+                Result<Option<LightClientBlockView>, JsonRpcError<RpcLightClientNextBlockError>>,
+            > + Send
+            + Sync
+            + 'static,
+    {
+        self.scripts.lock().unwrap().next_light_client_block.handler = Some(Box::new(handler));
+    }
+}
+
+#[async_trait]
+impl Provider for MockProvider {
+    async fn status(&self) -> Result<RpcStatusResponse, JsonRpcError<RpcStatusError>> {
+        self.record(ProviderRequest::Status).await;
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts.status.respond(ProviderMethod::Status, &())
+    }
+
+    async fn send_transaction(
+        &self,
+        signed_transaction: SignedTransaction,
+    ) -> Result<FinalExecutionOutcomeView, JsonRpcError<RpcTransactionError>> {
+        self.record(ProviderRequest::SendTransaction {
+            signed_transaction: signed_transaction.clone(),
+        })
+        .await;
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts
+            .send_transaction
+            .respond(ProviderMethod::SendTransaction, &signed_transaction)
+    }
+
+    async fn send_transaction_async(
+        &self,
+        signed_transaction: SignedTransaction,
+    ) -> Result<CryptoHash, JsonRpcError<methods::broadcast_tx_async::RpcBroadcastTxAsyncError>>
+    {
+        self.record(ProviderRequest::SendTransactionAsync {
+            signed_transaction: signed_transaction.clone(),
+        })
+        .await;
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts
+            .send_transaction_async
+            .respond(ProviderMethod::SendTransactionAsync, &signed_transaction)
+    }
+
+    async fn send_tx(
+        &self,
+        signed_transaction: SignedTransaction,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionResponse, JsonRpcError<RpcTransactionError>> {
+        self.record(ProviderRequest::SendTx {
+            signed_transaction: signed_transaction.clone(),
+            wait_until: wait_until.clone(),
+        })
+        .await;
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts
+            .send_tx
+            .respond(ProviderMethod::SendTx, &(signed_transaction, wait_until))
+    }
+
+    async fn tx_status(
+        &self,
+        transaction_info: TransactionInfo,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionResponse, JsonRpcError<RpcTransactionError>> {
+        self.record(ProviderRequest::TxStatus {
+            transaction_info: transaction_info.clone(),
+            wait_until: wait_until.clone(),
+        })
+        .await;
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts
+            .tx_status
+            .respond(ProviderMethod::TxStatus, &(transaction_info, wait_until))
+    }
+
+    async fn chunk(
+        &self,
+        chunk_reference: ChunkReference,
+    ) -> Result<ChunkView, JsonRpcError<RpcChunkError>> {
+        self.record(ProviderRequest::Chunk {
+            chunk_reference: chunk_reference.clone(),
+        })
+        .await;
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts
+            .chunk
+            .respond(ProviderMethod::Chunk, &chunk_reference)
+    }
+
+    async fn block(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<BlockView, JsonRpcError<RpcBlockError>> {
+        self.record(ProviderRequest::Block {
+            block_reference: block_reference.clone(),
+        })
+        .await;
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts
+            .block
+            .respond(ProviderMethod::Block, &block_reference)
+    }
+
+    async fn validators(
+        &self,
+        epoch_reference: EpochReference,
+    ) -> Result<EpochValidatorInfo, JsonRpcError<RpcValidatorError>> {
+        self.record(ProviderRequest::Validators {
+            epoch_reference: clone_epoch_reference(&epoch_reference),
+        })
+        .await;
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts
+            .validators
+            .respond(ProviderMethod::Validators, &epoch_reference)
+    }
+
+    async fn query(
+        &self,
+        request: QueryRequest,
+        block_reference: BlockReference,
+    ) -> Result<RpcQueryResponse, JsonRpcError<RpcQueryError>> {
+        self.record(ProviderRequest::Query {
+            request: request.clone(),
+            block_reference: block_reference.clone(),
+        })
+        .await;
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts
+            .query
+            .respond(ProviderMethod::Query, &(request, block_reference))
+    }
+
+    async fn experimental_protocol_config(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<ProtocolConfigView, JsonRpcError<RpcProtocolConfigError>> {
+        self.record(ProviderRequest::ExperimentalProtocolConfig {
+            block_reference: block_reference.clone(),
+        })
+        .await;
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts
+            .experimental_protocol_config
+            .respond(ProviderMethod::ExperimentalProtocolConfig, &block_reference)
+    }
+
+    async fn gas_price(
+        &self,
+        block_id: Option<BlockId>,
+    ) -> Result<GasPriceView, JsonRpcError<RpcGasPriceError>> {
+        self.record(ProviderRequest::GasPrice {
+            block_id: block_id.clone(),
+        })
+        .await;
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts
+            .gas_price
+            .respond(ProviderMethod::GasPrice, &block_id)
+    }
+
+    async fn health(&self) -> Result<RpcHealthResponse, JsonRpcError<RpcStatusError>> {
+        self.record(ProviderRequest::Health).await;
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts.health.respond(ProviderMethod::Health, &())
+    }
+
+    async fn network_info(
+        &self,
+    ) -> Result<RpcNetworkInfoResponse, JsonRpcError<RpcNetworkInfoError>> {
+        self.record(ProviderRequest::NetworkInfo).await;
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts
+            .network_info
+            .respond(ProviderMethod::NetworkInfo, &())
+    }
+
+    async fn experimental_genesis_config(
+        &self,
+    ) -> Result<
+        GenesisConfig,
+        JsonRpcError<methods::EXPERIMENTAL_genesis_config::RpcGenesisConfigError>,
+    > {
+        self.record(ProviderRequest::ExperimentalGenesisConfig)
+            .await;
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts
+            .experimental_genesis_config
+            .respond(ProviderMethod::ExperimentalGenesisConfig, &())
+    }
+
+    async fn client_config(
+        &self,
+    ) -> Result<RpcClientConfigResponse, JsonRpcError<RpcClientConfigError>> {
+        self.record(ProviderRequest::ClientConfig).await;
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts
+            .client_config
+            .respond(ProviderMethod::ClientConfig, &())
+    }
+
+    async fn changes_in_block(
+        &self,
+        block_reference: BlockReference,
+    ) -> Result<RpcStateChangesInBlockByTypeResponse, JsonRpcError<RpcStateChangesError>> {
+        self.record(ProviderRequest::ChangesInBlock {
+            block_reference: block_reference.clone(),
+        })
+        .await;
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts
+            .changes_in_block
+            .respond(ProviderMethod::ChangesInBlock, &block_reference)
+    }
+
+    async fn changes(
+        &self,
+        request: StateChangesRequestView,
+        block_reference: BlockReference,
+    ) -> Result<RpcStateChangesInBlockResponse, JsonRpcError<RpcStateChangesError>> {
+        self.record(ProviderRequest::Changes {
+            request: clone_state_changes_request(&request),
+            block_reference: block_reference.clone(),
+        })
+        .await;
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts
+            .changes
+            .respond(ProviderMethod::Changes, &(request, block_reference))
+    }
+
+    async fn tx_status_with_receipts(
+        &self,
+        transaction_info: TransactionInfo,
+        wait_until: TxExecutionStatus,
+    ) -> Result<RpcTransactionWithReceiptsResponse, JsonRpcError<RpcTransactionError>> {
+        self.record(ProviderRequest::TxStatusWithReceipts {
+            transaction_info: transaction_info.clone(),
+            wait_until: wait_until.clone(),
+        })
+        .await;
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts.tx_status_with_receipts.respond(
+            ProviderMethod::TxStatusWithReceipts,
+            &(transaction_info, wait_until),
+        )
+    }
+
+    async fn receipt(
+        &self,
+        receipt_id: CryptoHash,
+    ) -> Result<ReceiptView, JsonRpcError<RpcReceiptError>> {
+        self.record(ProviderRequest::Receipt { receipt_id }).await;
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts
+            .receipt
+            .respond(ProviderMethod::Receipt, &receipt_id)
+    }
+
+    async fn light_client_proof(
+        &self,
+        id: TransactionOrReceiptId,
+        light_client_head: CryptoHash,
+    ) -> Result<RpcLightClientExecutionProofResponse, JsonRpcError<RpcLightClientProofError>> {
+        self.record(ProviderRequest::LightClientProof {
+            id: id.clone(),
+            light_client_head,
+        })
+        .await;
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts
+            .light_client_proof
+            .respond(ProviderMethod::LightClientProof, &(id, light_client_head))
+    }
+
+    async fn next_light_client_block(
+        &self,
+        last_block_hash: CryptoHash,
+    ) -> Result<Option<LightClientBlockView>, JsonRpcError<RpcLightClientNextBlockError>> {
+        self.record(ProviderRequest::NextLightClientBlock { last_block_hash })
+            .await;
+        let mut scripts = self.scripts.lock().unwrap();
+        scripts
+            .next_light_client_block
+            .respond(ProviderMethod::NextLightClientBlock, &last_block_hash)
+    }
+}
+
+/// Builds the error returned by the RPC server when it fails to process a request.
+pub fn internal_error<E>(info: impl Into<String>) -> JsonRpcError<E> {
+    JsonRpcError::ServerError(JsonRpcServerError::InternalError {
+        info: Some(info.into()),
+    })
+}
+
+/// Builds the error returned by the RPC server when the request itself is rejected by the handler.
+pub fn handler_error<E>(error: E) -> JsonRpcError<E> {
+    JsonRpcError::ServerError(JsonRpcServerError::HandlerError(error))
+}
+
+/// Builds a query response of the given kind, at block height 1 with the default block hash.
+pub fn query_response(kind: QueryResponseKind) -> RpcQueryResponse {
+    RpcQueryResponse {
+        kind,
+        block_height: 1,
+        block_hash: CryptoHash::default(),
+    }
+}
+
+/// Builds the response to a `QueryRequest::ViewAccessKey` for a full access key with the given nonce.
+pub fn access_key_response(nonce: u64) -> RpcQueryResponse {
+    query_response(QueryResponseKind::AccessKey(AccessKeyView {
+        nonce,
+        permission: AccessKeyPermissionView::FullAccess,
+    }))
+}
+
+/// Builds the status of a synced node of the chain with the given id, at block height 100.
+pub fn status_response(chain_id: &str) -> RpcStatusResponse {
+    serde_json::from_value(serde_json::json!({
+        "version": { "version": "1.38.0", "build": "test" },
+        "chain_id": chain_id,
+        "protocol_version": 66,
+        "latest_protocol_version": 66,
+        "rpc_addr": "0.0.0.0:3030",
+        "validators": [],
+        "sync_info": {
+            "latest_block_hash": "11111111111111111111111111111111",
+            "latest_block_height": 100,
+            "latest_state_root": "11111111111111111111111111111111",
+            "latest_block_time": "2024-01-01T00:00:00.000000000Z",
+            "syncing": false,
+            "earliest_block_hash": null,
+            "earliest_block_height": null,
+            "earliest_block_time": null,
+            "epoch_id": "11111111111111111111111111111111",
+            "epoch_start_height": 1
+        },
+        "validator_account_id": null,
+        "validator_public_key": null,
+        "node_public_key": "ed25519:6DSjZ8mvsRZDvFqFxo8tCKePG96omXW7eVYVSySmDk8e",
+        "node_key": null,
+        "uptime_sec": 1,
+        "genesis_hash": "11111111111111111111111111111111",
+        "detailed_debug_status": null
+    }))
+    .unwrap()
+}
+
+/// Builds a block with no chunks at the given height, with default values for the other fields.
+pub fn block_view(height: u64, hash: CryptoHash) -> BlockView {
+    BlockView {
+        author: "test.near".parse().unwrap(),
+        header: BlockHeaderView {
+            height,
+            prev_height: height.checked_sub(1),
+            epoch_id: CryptoHash::default(),
+            next_epoch_id: CryptoHash::default(),
+            hash,
+            prev_hash: CryptoHash::default(),
+            prev_state_root: CryptoHash::default(),
+            block_body_hash: None,
+            chunk_receipts_root: CryptoHash::default(),
+            chunk_headers_root: CryptoHash::default(),
+            chunk_tx_root: CryptoHash::default(),
+            outcome_root: CryptoHash::default(),
+            chunks_included: 0,
+            challenges_root: CryptoHash::default(),
+            timestamp: 0,
+            timestamp_nanosec: 0,
+            random_value: CryptoHash::default(),
+            validator_proposals: vec![],
+            chunk_mask: vec![],
+            gas_price: 0,
+            block_ordinal: None,
+            rent_paid: 0,
+            validator_reward: 0,
+            total_supply: 0,
+            challenges_result: vec![],
+            last_final_block: CryptoHash::default(),
+            last_ds_final_block: CryptoHash::default(),
+            next_bp_hash: CryptoHash::default(),
+            block_merkle_root: CryptoHash::default(),
+            epoch_sync_data_hash: None,
+            approvals: vec![],
+            signature: Signature::empty(KeyType::ED25519),
+            latest_protocol_version: 0,
+        },
+        chunks: vec![],
+    }
+}
+
+/// Builds the header of an empty chunk of shard `shard_id` included at `height_included`, recording `prev_state_root`
+/// as the state root of the shard before the chunk.
+pub fn chunk_header(
+    shard_id: ShardId,
+    height_included: BlockHeight,
+    prev_state_root: StateRoot,
+) -> ChunkHeaderView {
+    ChunkHeaderView {
+        chunk_hash: CryptoHash::hash_borsh((shard_id, height_included)),
+        prev_block_hash: CryptoHash::default(),
+        outcome_root: CryptoHash::default(),
+        prev_state_root,
+        encoded_merkle_root: CryptoHash::default(),
+        encoded_length: 0,
+        height_created: height_included,
+        height_included,
+        shard_id,
+        gas_used: 0,
+        gas_limit: 0,
+        rent_paid: 0,
+        validator_reward: 0,
+        balance_burnt: 0,
+        outgoing_receipts_root: CryptoHash::default(),
+        tx_root: CryptoHash::default(),
+        validator_proposals: vec![],
+        signature: Signature::empty(KeyType::ED25519),
+    }
+}
+
+/// Builds a trie holding `entries`, given as trie keys, e.g. `TrieKey::ContractData` keys, and values. Returns its
+/// state root and all its nodes, which prove any `view_state` result read from it.
+#[doc(hidden)]
+pub fn trie_proof(entries: &[(Vec<u8>, Vec<u8>)]) -> (StateRoot, Vec<Arc<[u8]>>) {
+    let mut entries: Vec<(Vec<u8>, &[u8])> = entries
+        .iter()
+        .map(|(key, value)| {
+            let nibbles = key.iter().flat_map(|byte| [byte >> 4, byte & 0x0f]);
+            (nibbles.collect(), value.as_slice())
+        })
+        .collect();
+    entries.sort();
+    let mut nodes = vec![];
+    if entries.is_empty() {
+        return (StateRoot::default(), nodes);
+    }
+    let root = trie_node(&entries, 0, &mut nodes);
+    (root, nodes)
+}
+
+/// Encodes the node holding `entries`, whose keys share their first `depth` nibbles, as a `RawTrieNodeWithSize` of
+/// nearcore, and returns its hash.
+fn trie_node(entries: &[(Vec<u8>, &[u8])], depth: usize, nodes: &mut Vec<Arc<[u8]>>) -> CryptoHash {
+    fn encode_nibbles(node: &mut Vec<u8>, nibbles: &[u8], is_leaf: bool) {
+        let mut encoded = vec![if is_leaf { 0x20 } else { 0 }];
+        if nibbles.len() % 2 == 1 {
+            encoded[0] |= 0x10 | nibbles[0];
+        }
+        for pair in nibbles[nibbles.len() % 2..].chunks(2) {
+            encoded.push((pair[0] << 4) | pair[1]);
+        }
+        node.extend((encoded.len() as u32).to_le_bytes());
+        node.extend(encoded);
+    }
+    fn value_ref(node: &mut Vec<u8>, value: &[u8]) {
+        node.extend((value.len() as u32).to_le_bytes());
+        node.extend(CryptoHash::hash_bytes(value).0);
+    }
+
+    let mut node = vec![];
+    let (first, last) = (
+        &entries[0].0[depth..],
+        &entries[entries.len() - 1].0[depth..],
+    );
+    let common = first.iter().zip(last).take_while(|(a, b)| a == b).count();
+    if entries.len() == 1 {
+        node.push(0);
+        encode_nibbles(&mut node, first, true);
+        value_ref(&mut node, entries[0].1);
+    } else if common > 0 {
+        let child = trie_node(entries, depth + common, nodes);
+        node.push(3);
+        encode_nibbles(&mut node, &first[..common], false);
+        node.extend(child.0);
+    } else {
+        // Entries are sorted, so a value ending at this node comes first.
+        let (value, children) = if entries[0].0.len() == depth {
+            (Some(entries[0].1), &entries[1..])
+        } else {
+            (None, entries)
+        };
+        let mut bitmap = 0u16;
+        let mut hashes = vec![];
+        for nibble in 0..16u8 {
+            let group: Vec<_> = children
+                .iter()
+                .filter(|(key, _)| key[depth] == nibble)
+                .cloned()
+                .collect();
+            if !group.is_empty() {
+                bitmap |= 1 << nibble;
+                hashes.push(trie_node(&group, depth + 1, nodes));
+            }
+        }
+        match value {
+            Some(value) => {
+                node.push(2);
+                value_ref(&mut node, value);
+            }
+            None => node.push(1),
+        }
+        node.extend(bitmap.to_le_bytes());
+        for hash in hashes {
+            node.extend(hash.0);
+        }
+    }
+    // The memory usage of the node, which is not verified.
+    node.extend(0u64.to_le_bytes());
+    let hash = CryptoHash::hash_bytes(&node);
+    nodes.push(node.into());
+    hash
+}
+
+/// Builders of chains of blocks and of their contents for the tests of the streams of this crate.
+#[cfg(test)]
+pub(crate) mod chain {
+    use super::{block_view, chunk_header, handler_error, MockProvider};
+    use crate::types::{blocks::RpcBlockError, chunks::ChunkReference};
+    use crate::utils::{clone_block_view, clone_chunk_view};
+    use near_crypto::{KeyType, PublicKey, Signature};
+    use near_jsonrpc_client::methods::tx::RpcTransactionResponse;
+    use near_primitives::{
+        hash::CryptoHash,
+        types::{AccountId, BlockHeight, BlockId, BlockReference},
+        views::{
+            ActionView, BlockView, ChunkView, ExecutionMetadataView, ExecutionOutcomeView,
+            ExecutionOutcomeWithIdView, ExecutionStatusView, FinalExecutionOutcomeView,
+            FinalExecutionOutcomeViewEnum, FinalExecutionStatus, ReceiptEnumView, ReceiptView,
+            SignedTransactionView, TxExecutionStatus,
+        },
+    };
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    /// Builds a chain of blocks at `heights`, each following the previous one, with the hash of its height as hash.
+    pub(crate) fn block_chain(heights: &[BlockHeight]) -> Vec<BlockView> {
+        let mut prev: Option<(BlockHeight, CryptoHash)> = None;
+        heights
+            .iter()
+            .map(|&height| {
+                let mut block = block_view(height, CryptoHash::hash_borsh(height));
+                if let Some((prev_height, prev_hash)) = prev {
+                    block.header.prev_height = Some(prev_height);
+                    block.header.prev_hash = prev_hash;
+                }
+                prev = Some((height, block.header.hash));
+                block
+            })
+            .collect()
+    }
+
+    /// Answers the `block` calls of `provider` with `blocks` by height, with the block at `head_height` as the latest
+    /// block at any finality. Heights without a block are unknown to the provider.
+    pub(crate) fn serve_blocks(
+        provider: &MockProvider,
+        blocks: Vec<BlockView>,
+        head_height: Arc<AtomicU64>,
+    ) {
+        let blocks: HashMap<_, _> = blocks
+            .into_iter()
+            .map(|block| (block.header.height, block))
+            .collect();
+        provider.on_block(move |block_reference| {
+            let height = match block_reference {
+                BlockReference::Finality(_) => head_height.load(Ordering::SeqCst),
+                BlockReference::BlockId(BlockId::Height(height)) => *height,
+                _ => return None,
+            };
+            Some(match blocks.get(&height) {
+                Some(block) => Ok(clone_block_view(block)),
+                None => Err(handler_error(RpcBlockError::UnknownBlock {
+                    error_message: format!("no block at {}", height),
+                })),
+            })
+        });
+    }
+
+    /// The transactions and receipts of a chunk served by `serve_chain`.
+    pub(crate) type ChunkContents = (Vec<SignedTransactionView>, Vec<ReceiptView>);
+
+    /// Answers the `block` and `chunk` calls of `provider` with a chain of blocks at `heights`, the last one being the
+    /// latest block, where every block has a single new chunk holding the contents given for its height.
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn serve_chain(
+        provider: &MockProvider,
+        heights: &[BlockHeight],
+        mut contents: HashMap<BlockHeight, ChunkContents>,
+    ) {
+        let mut blocks = block_chain(heights);
+        let mut chunks = HashMap::new();
+        for block in &mut blocks {
+            let height = block.header.height;
+            let header = chunk_header(0, height, CryptoHash::default());
+            let (transactions, receipts) = contents.remove(&height).unwrap_or_default();
+            block.chunks = vec![header.clone()];
+            chunks.insert(
+                header.chunk_hash,
+                ChunkView {
+                    author: "test.near".parse().unwrap(),
+                    header,
+                    transactions,
+                    receipts,
+                },
+            );
+        }
+        let head_height = *heights.last().unwrap();
+        serve_blocks(provider, blocks, Arc::new(AtomicU64::new(head_height)));
+        provider.on_chunk(move |chunk_reference| match chunk_reference {
+            ChunkReference::ChunkHash { chunk_id } => chunks
+                .get(chunk_id)
+                .map(|chunk| Ok(clone_chunk_view(chunk))),
+            _ => None,
+        });
+    }
+
+    /// Builds the view of a transaction with the given hash from `signer_id` to `receiver_id`, signed with an empty key.
+    pub(crate) fn transaction_view(
+        hash: CryptoHash,
+        signer_id: &str,
+        receiver_id: &str,
+        actions: Vec<ActionView>,
+    ) -> SignedTransactionView {
+        SignedTransactionView {
+            signer_id: signer_id.parse().unwrap(),
+            public_key: PublicKey::empty(KeyType::ED25519),
+            nonce: 1,
+            receiver_id: receiver_id.parse().unwrap(),
+            actions,
+            signature: Signature::empty(KeyType::ED25519),
+            hash,
+        }
+    }
+
+    /// Builds the view of an action receipt with the given id from `predecessor_id` to `receiver_id`, signed by the
+    /// predecessor.
+    pub(crate) fn action_receipt_view(
+        receipt_id: CryptoHash,
+        predecessor_id: &str,
+        receiver_id: &str,
+        actions: Vec<ActionView>,
+    ) -> ReceiptView {
+        ReceiptView {
+            predecessor_id: predecessor_id.parse().unwrap(),
+            receiver_id: receiver_id.parse().unwrap(),
+            receipt_id,
+            receipt: ReceiptEnumView::Action {
+                signer_id: predecessor_id.parse().unwrap(),
+                signer_public_key: PublicKey::empty(KeyType::ED25519),
+                gas_price: 0,
+                output_data_receivers: vec![],
+                input_data_ids: vec![],
+                actions,
+            },
+        }
+    }
+
+    /// Builds the response of `tx_status` for `transaction` at `status`, whose outcome lists the receipts with the given
+    /// ids and execution statuses. The transaction fails with the error of the first failed receipt, if any.
+    pub(crate) fn tx_status_response(
+        transaction: &SignedTransactionView,
+        status: TxExecutionStatus,
+        receipts: Vec<(CryptoHash, ExecutionStatusView)>,
+    ) -> RpcTransactionResponse {
+        let outcome = |id: CryptoHash, executor_id: &AccountId, status: ExecutionStatusView| {
+            ExecutionOutcomeWithIdView {
+                proof: vec![],
+                block_hash: CryptoHash::default(),
+                id,
+                outcome: ExecutionOutcomeView {
+                    logs: vec![],
+                    receipt_ids: vec![],
+                    gas_burnt: 0,
+                    tokens_burnt: 0,
+                    executor_id: executor_id.clone(),
+                    status,
+                    metadata: ExecutionMetadataView::default(),
+                },
+            }
+        };
+        let final_status = receipts
+            .iter()
+            .find_map(|(_, status)| match status {
+                ExecutionStatusView::Failure(error) => {
+                    Some(FinalExecutionStatus::Failure(error.clone()))
+                }
+                _ => None,
+            })
+            .unwrap_or(FinalExecutionStatus::SuccessValue(vec![]));
+        let mut transaction_outcome = outcome(
+            transaction.hash,
+            &transaction.signer_id,
+            ExecutionStatusView::SuccessValue(vec![]),
+        );
+        transaction_outcome.outcome.receipt_ids = receipts.iter().map(|(id, _)| *id).collect();
+        let receipts_outcome = receipts
+            .into_iter()
+            .map(|(id, status)| outcome(id, &transaction.receiver_id, status))
+            .collect();
+        RpcTransactionResponse {
+            final_execution_outcome: Some(FinalExecutionOutcomeViewEnum::FinalExecutionOutcome(
+                FinalExecutionOutcomeView {
+                    status: final_status,
+                    transaction: transaction.clone(),
+                    transaction_outcome,
+                    receipts_outcome,
+                },
+            )),
+            final_execution_status: status,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_primitives::types::Finality;
+
+    fn view_access_key(account_id: &str) -> QueryRequest {
+        QueryRequest::ViewAccessKey {
+            account_id: account_id.parse().unwrap(),
+            public_key: "ed25519:6E8sCci9badyRkXb3JoRpBj5p8C6Tw41ELDZoiihKEtp"
+                .parse()
+                .unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_queued_results_are_returned_before_the_handler() {
+        let provider = MockProvider::new();
+        provider.push_query(Err(internal_error("node is down")));
+        provider.on_query(|request, _| match request {
+            QueryRequest::ViewAccessKey { account_id, .. } if account_id == "alice.testnet" => {
+                Some(Ok(access_key_response(41)))
+            }
+            _ => None,
+        });
+
+        let final_block = BlockReference::Finality(Finality::Final);
+        let first = provider
+            .query(view_access_key("alice.testnet"), final_block.clone())
+            .await;
+        assert!(matches!(
+            first,
+            Err(JsonRpcError::ServerError(
+                JsonRpcServerError::InternalError { .. }
+            ))
+        ));
+
+        let second = provider
+            .query(view_access_key("alice.testnet"), final_block.clone())
+            .await
+            .unwrap();
+        assert!(matches!(
+            second.kind,
+            QueryResponseKind::AccessKey(AccessKeyView { nonce: 41, .. })
+        ));
+
+        // The handler declines requests for other accounts.
+        let declined = provider
+            .query(view_access_key("bob.testnet"), final_block)
+            .await;
+        assert!(declined.is_err());
+        assert_eq!(provider.call_count(ProviderMethod::Query), 3);
+    }
+
+    #[tokio::test]
+    async fn test_calls_are_recorded() {
+        let provider = MockProvider::new();
+        let hash = CryptoHash::hash_bytes(b"block");
+        provider.push_block(Ok(block_view(5, hash)));
+
+        let block = provider
+            .block(BlockReference::BlockId(
+                near_primitives::types::BlockId::Height(5),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(block.header.hash, hash);
+        assert!(provider.status().await.is_err());
+
+        let calls = provider.calls();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].method(), ProviderMethod::Block);
+        assert_eq!(calls[1].method(), ProviderMethod::Status);
+        assert!(provider.signed_transactions().is_empty());
+    }
+}
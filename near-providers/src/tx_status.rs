@@ -0,0 +1,73 @@
+//! The `tx_status` module defines the response of the `EXPERIMENTAL_tx_status` RPC method and builds its request.
+//!
+//! `near-jsonrpc-client` deserializes the response into `RpcTransactionResponse`, whose untagged outcome enum matches
+//! the outcome without receipts first, so the receipts returned by the node are dropped. The response defined here
+//! keeps them.
+
+use crate::jsonrpc_client::{
+    errors::{JsonRpcError, JsonRpcTransportSendError, RpcTransportError},
+    methods::{self, RpcAnyRequest, RpcHandlerResponse},
+};
+use crate::types::transactions::{RpcTransactionError, SignedTransaction, TransactionInfo};
+use near_primitives::serialize::to_base64;
+use near_primitives::views::{FinalExecutionOutcomeWithReceiptView, TxExecutionStatus};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// The status of a transaction together with the receipts it created, as returned by `EXPERIMENTAL_tx_status`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcTransactionWithReceiptsResponse {
+    /// The outcomes of the transaction and its receipts, and the receipts themselves. `None` until the transaction
+    /// is executed.
+    #[serde(flatten)]
+    pub final_execution_outcome: Option<FinalExecutionOutcomeWithReceiptView>,
+    /// The execution status reached by the transaction.
+    pub final_execution_status: TxExecutionStatus,
+}
+
+impl RpcHandlerResponse for RpcTransactionWithReceiptsResponse {}
+
+/// Builds the `EXPERIMENTAL_tx_status` request for the transaction identified by `transaction_info`.
+#[allow(clippy::result_large_err)]
+pub(crate) fn request(
+    transaction_info: TransactionInfo,
+    wait_until: TxExecutionStatus,
+) -> Result<
+    RpcAnyRequest<RpcTransactionWithReceiptsResponse, RpcTransactionError>,
+    JsonRpcError<RpcTransactionError>,
+> {
+    let params = match transaction_info {
+        TransactionInfo::Transaction(SignedTransaction::SignedTransaction(tx)) => {
+            let bytes = near_primitives::borsh::to_vec(&tx).map_err(|err| {
+                JsonRpcError::TransportError(RpcTransportError::SendError(
+                    JsonRpcTransportSendError::PayloadSerializeError(err),
+                ))
+            })?;
+            json!({ "signed_tx_base64": to_base64(&bytes), "wait_until": wait_until })
+        }
+        TransactionInfo::TransactionId {
+            tx_hash,
+            sender_account_id,
+        } => json!({
+            "tx_hash": tx_hash,
+            "sender_account_id": sender_account_id,
+            "wait_until": wait_until,
+        }),
+    };
+    Ok(methods::any::<
+        Result<RpcTransactionWithReceiptsResponse, RpcTransactionError>,
+    >("EXPERIMENTAL_tx_status", params))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_without_outcome() {
+        let response: RpcTransactionWithReceiptsResponse =
+            serde_json::from_value(json!({ "final_execution_status": "INCLUDED" })).unwrap();
+        assert!(response.final_execution_outcome.is_none());
+        assert_eq!(response.final_execution_status, TxExecutionStatus::Included);
+    }
+}
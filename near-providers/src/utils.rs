@@ -0,0 +1,74 @@
+//! Helpers shared by the provider implementations in this crate.
+
+use near_primitives::types::{AccountWithPublicKey, EpochReference};
+use near_primitives::views::{BlockView, ChunkView, StateChangesRequestView};
+
+/// `EpochReference` does not implement `Clone`, so wrapping providers that need to send the
+/// same request more than once rebuild it with this helper.
+pub(crate) fn clone_epoch_reference(epoch_reference: &EpochReference) -> EpochReference {
+    match epoch_reference {
+        EpochReference::EpochId(epoch_id) => EpochReference::EpochId(epoch_id.clone()),
+        EpochReference::BlockId(block_id) => EpochReference::BlockId(block_id.clone()),
+        EpochReference::Latest => EpochReference::Latest,
+    }
+}
+
+/// `BlockView` does not implement `Clone`, although all of its fields do.
+pub(crate) fn clone_block_view(block: &BlockView) -> BlockView {
+    BlockView {
+        author: block.author.clone(),
+        header: block.header.clone(),
+        chunks: block.chunks.clone(),
+    }
+}
+
+/// `ChunkView` does not implement `Clone`, although all of its fields do.
+pub(crate) fn clone_chunk_view(chunk: &ChunkView) -> ChunkView {
+    ChunkView {
+        author: chunk.author.clone(),
+        header: chunk.header.clone(),
+        transactions: chunk.transactions.clone(),
+        receipts: chunk.receipts.clone(),
+    }
+}
+
+/// `StateChangesRequestView` does not implement `Clone`, nor does the `AccountWithPublicKey` it may contain.
+pub(crate) fn clone_state_changes_request(
+    request: &StateChangesRequestView,
+) -> StateChangesRequestView {
+    match request {
+        StateChangesRequestView::AccountChanges { account_ids } => {
+            StateChangesRequestView::AccountChanges {
+                account_ids: account_ids.clone(),
+            }
+        }
+        StateChangesRequestView::SingleAccessKeyChanges { keys } => {
+            StateChangesRequestView::SingleAccessKeyChanges {
+                keys: keys
+                    .iter()
+                    .map(|key| AccountWithPublicKey {
+                        account_id: key.account_id.clone(),
+                        public_key: key.public_key.clone(),
+                    })
+                    .collect(),
+            }
+        }
+        StateChangesRequestView::AllAccessKeyChanges { account_ids } => {
+            StateChangesRequestView::AllAccessKeyChanges {
+                account_ids: account_ids.clone(),
+            }
+        }
+        StateChangesRequestView::ContractCodeChanges { account_ids } => {
+            StateChangesRequestView::ContractCodeChanges {
+                account_ids: account_ids.clone(),
+            }
+        }
+        StateChangesRequestView::DataChanges {
+            account_ids,
+            key_prefix,
+        } => StateChangesRequestView::DataChanges {
+            account_ids: account_ids.clone(),
+            key_prefix: key_prefix.clone(),
+        },
+    }
+}
@@ -0,0 +1,699 @@
+//! End to end tests of `JsonRpcProvider` against a local JSON RPC server, covering the serialization
+//! of requests and the mapping of the errors returned by the server.
+
+use near_crypto::{InMemorySigner, KeyType};
+use near_primitives::hash::CryptoHash;
+use near_primitives::transaction::SignedTransaction;
+use near_primitives::types::{BlockId, BlockReference, EpochReference, Finality};
+use near_primitives::views::{
+    FinalExecutionStatus, QueryRequest, StateChangeKindView, StateChangeValueView,
+    StateChangeWithCauseView, TxExecutionStatus,
+};
+use near_providers::changes;
+use near_providers::client_config::RpcClientConfigError;
+use near_providers::jsonrpc_client::errors::{
+    JsonRpcError, JsonRpcServerError, JsonRpcServerResponseStatusError, JsonRpcTransportSendError,
+    RpcTransportError,
+};
+use near_providers::middleware::ProviderBuilder;
+use near_providers::rate_limit_provider::RateLimit;
+use near_providers::retry_provider::RetryPolicy;
+use near_providers::types::chunks::ChunkReference;
+use near_providers::types::query::{QueryResponseKind, RpcQueryError};
+use near_providers::types::receipts::RpcReceiptError;
+use near_providers::types::transactions::TransactionInfo;
+use near_providers::{
+    reqwest, ArchivalProvider, CacheLayer, CachingProvider, FailoverProvider, JsonRpcProvider,
+    JsonRpcProviderBuildError, Provider, ProviderRequest, ProviderResponse, RateLimitProvider,
+    RecordingProvider, RetryLayer, RetryProvider,
+};
+use near_rpc_test_server::{fixtures, RpcResponse, TestRpcServer};
+use serde_json::json;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+fn signer() -> InMemorySigner {
+    InMemorySigner::from_seed(
+        "alice.test.near".parse().unwrap(),
+        KeyType::ED25519,
+        "alice.test.near",
+    )
+}
+
+fn signed_transaction() -> SignedTransaction {
+    let signer = signer();
+    SignedTransaction::send_money(
+        1,
+        "alice.test.near".parse().unwrap(),
+        "bob.test.near".parse().unwrap(),
+        &signer,
+        100,
+        CryptoHash::hash_bytes(b"block"),
+    )
+}
+
+fn view_access_key() -> QueryRequest {
+    let signer = signer();
+    QueryRequest::ViewAccessKey {
+        account_id: signer.account_id,
+        public_key: signer.public_key,
+    }
+}
+
+#[tokio::test]
+async fn test_fixtures_deserialize() {
+    let server = TestRpcServer::start().with_fixtures();
+    let provider = JsonRpcProvider::new(&server.url());
+
+    let status = provider.status().await.unwrap();
+    assert_eq!(status.chain_id, fixtures::CHAIN_ID);
+    assert_eq!(status.sync_info.latest_block_height, 100);
+
+    let block = provider
+        .block(BlockReference::BlockId(BlockId::Height(100)))
+        .await
+        .unwrap();
+    assert_eq!(block.header.height, 100);
+
+    let chunk = provider
+        .chunk(ChunkReference::BlockShardId {
+            block_id: BlockId::Height(100),
+            shard_id: 0,
+        })
+        .await
+        .unwrap();
+    assert_eq!(chunk.header.height_included, 100);
+
+    let validators = provider.validators(EpochReference::Latest).await.unwrap();
+    assert_eq!(validators.epoch_height, 1);
+
+    let protocol_config = provider
+        .experimental_protocol_config(BlockReference::Finality(Finality::Final))
+        .await
+        .unwrap();
+    assert_eq!(protocol_config.chain_id, fixtures::CHAIN_ID);
+
+    let methods: Vec<_> = server
+        .requests()
+        .into_iter()
+        .map(|request| request.method)
+        .collect();
+    assert_eq!(
+        methods,
+        [
+            "status",
+            "block",
+            "chunk",
+            "validators",
+            "EXPERIMENTAL_protocol_config"
+        ]
+    );
+    assert_eq!(server.requests_for("block"), [json!({ "block_id": 100 })]);
+}
+
+#[tokio::test]
+async fn test_node_and_network_methods() {
+    let server = TestRpcServer::start().with_fixtures();
+    let provider = JsonRpcProvider::new(&server.url());
+
+    let gas_price = provider
+        .gas_price(Some(BlockId::Height(100)))
+        .await
+        .unwrap();
+    assert_eq!(gas_price.gas_price, 100_000_000);
+    assert_eq!(server.requests_for("gas_price"), [json!([100])]);
+
+    provider.health().await.unwrap();
+
+    let network_info = provider.network_info().await.unwrap();
+    assert_eq!(network_info.num_active_peers, 1);
+
+    let genesis_config = provider.experimental_genesis_config().await.unwrap();
+    assert_eq!(genesis_config.chain_id, fixtures::CHAIN_ID);
+
+    let client_config = provider.client_config().await.unwrap();
+    assert_eq!(client_config.chain_id, fixtures::CHAIN_ID);
+    assert!(!client_config.archive);
+    assert_eq!(client_config.tracked_shards, [0]);
+    assert_eq!(client_config.other["epoch_length"], 500);
+
+    server.respond_with(
+        "client_config",
+        RpcResponse::handler_error("INTERNAL_ERROR", json!({ "error_message": "busy" })),
+    );
+    assert!(matches!(
+        provider.client_config().await,
+        Err(JsonRpcError::ServerError(JsonRpcServerError::HandlerError(
+            RpcClientConfigError::InternalError { .. }
+        )))
+    ));
+}
+
+#[tokio::test]
+async fn test_state_changes() {
+    let server = TestRpcServer::start().with_fixtures();
+    let provider = JsonRpcProvider::new(&server.url());
+    let block_hash = CryptoHash::hash_bytes(b"block");
+    let block = BlockReference::BlockId(BlockId::Hash(block_hash));
+
+    let response = provider.changes_in_block(block.clone()).await.unwrap();
+    assert_eq!(response.block_hash, block_hash);
+    assert!(matches!(
+        &response.changes[..],
+        [StateChangeKindView::AccountTouched { account_id }] if account_id == "test.near"
+    ));
+    assert_eq!(
+        server.requests_for("EXPERIMENTAL_changes_in_block"),
+        [json!({ "block_id": block_hash })]
+    );
+
+    let response = provider
+        .changes(
+            changes::account_changes(["alice.test.near".parse().unwrap()]),
+            block.clone(),
+        )
+        .await
+        .unwrap();
+    assert!(matches!(
+        &response.changes[..],
+        [StateChangeWithCauseView {
+            value: StateChangeValueView::AccountUpdate { account_id, .. },
+            ..
+        }] if account_id == "alice.test.near"
+    ));
+
+    let signer = signer();
+    provider
+        .changes(
+            changes::single_access_key_changes([(signer.account_id, signer.public_key.clone())]),
+            block.clone(),
+        )
+        .await
+        .unwrap();
+    provider
+        .changes(
+            changes::data_changes(["alice.test.near".parse().unwrap()], b"STATE"),
+            block,
+        )
+        .await
+        .unwrap();
+    let requests = server.requests_for("EXPERIMENTAL_changes");
+    assert_eq!(
+        requests[0],
+        json!({
+            "block_id": block_hash,
+            "changes_type": "account_changes",
+            "account_ids": ["alice.test.near"],
+        })
+    );
+    assert_eq!(
+        requests[1]["keys"],
+        json!([{ "account_id": "alice.test.near", "public_key": signer.public_key }])
+    );
+    assert_eq!(requests[2]["changes_type"], "data_changes");
+    assert_eq!(requests[2]["key_prefix_base64"], "U1RBVEU=");
+}
+
+#[tokio::test]
+async fn test_query_request_and_response() {
+    let server = TestRpcServer::start().with_fixtures();
+    let provider = JsonRpcProvider::new(&server.url());
+
+    let response = provider
+        .query(view_access_key(), BlockReference::Finality(Finality::Final))
+        .await
+        .unwrap();
+    assert!(matches!(response.kind, QueryResponseKind::AccessKey(_)));
+    assert_eq!(response.block_height, 100);
+
+    let params = &server.requests_for("query")[0];
+    assert_eq!(params["request_type"], "view_access_key");
+    assert_eq!(params["account_id"], "alice.test.near");
+    assert_eq!(params["finality"], "final");
+}
+
+#[tokio::test]
+async fn test_error_mapping() {
+    let server = TestRpcServer::start();
+    let provider = JsonRpcProvider::new(&server.url());
+    let final_block = BlockReference::Finality(Finality::Final);
+
+    server.respond_with(
+        "query",
+        RpcResponse::handler_error(
+            "UNKNOWN_ACCOUNT",
+            json!({
+                "requested_account_id": "alice.test.near",
+                "block_height": 100,
+                "block_hash": CryptoHash::default(),
+            }),
+        ),
+    );
+    assert!(matches!(
+        provider.query(view_access_key(), final_block.clone()).await,
+        Err(JsonRpcError::ServerError(JsonRpcServerError::HandlerError(
+            RpcQueryError::UnknownAccount {
+                block_height: 100,
+                ..
+            }
+        )))
+    ));
+
+    server.respond_with("block", RpcResponse::internal_error("node is down"));
+    assert!(matches!(
+        provider.block(final_block.clone()).await,
+        Err(JsonRpcError::ServerError(
+            JsonRpcServerError::InternalError { .. }
+        ))
+    ));
+
+    server.respond_with("status", RpcResponse::Status(429));
+    assert!(matches!(
+        provider.status().await,
+        Err(JsonRpcError::ServerError(
+            JsonRpcServerError::ResponseStatusError(
+                JsonRpcServerResponseStatusError::TooManyRequests
+            )
+        ))
+    ));
+
+    server.respond_with("status", RpcResponse::Status(503));
+    assert!(matches!(
+        provider.status().await,
+        Err(JsonRpcError::ServerError(
+            JsonRpcServerError::ResponseStatusError(
+                JsonRpcServerResponseStatusError::Unexpected { status }
+            )
+        )) if status.as_u16() == 503
+    ));
+
+    // Methods without a handler are rejected like an unknown method on a real node.
+    assert!(matches!(
+        provider.validators(EpochReference::Latest).await,
+        Err(JsonRpcError::ServerError(
+            JsonRpcServerError::RequestValidationError(_)
+        ))
+    ));
+}
+
+#[tokio::test]
+async fn test_send_transactions() {
+    let server = TestRpcServer::start().with_fixtures();
+    let provider = JsonRpcProvider::new(&server.url());
+    let signed_transaction = signed_transaction();
+
+    let response = provider
+        .send_tx(signed_transaction.clone(), TxExecutionStatus::Final)
+        .await
+        .unwrap();
+    assert_eq!(response.final_execution_status, TxExecutionStatus::Final);
+    let params = &server.requests_for("send_tx")[0];
+    assert_eq!(params["wait_until"], "FINAL");
+    assert_eq!(
+        fixtures::signed_transaction(params),
+        Some(signed_transaction.clone())
+    );
+
+    let outcome = provider
+        .send_transaction(signed_transaction.clone())
+        .await
+        .unwrap();
+    assert_eq!(outcome.status, FinalExecutionStatus::SuccessValue(vec![]));
+    assert_eq!(
+        outcome.transaction_outcome.id,
+        signed_transaction.get_hash()
+    );
+
+    let hash = provider
+        .send_transaction_async(signed_transaction.clone())
+        .await
+        .unwrap();
+    assert_eq!(hash, signed_transaction.get_hash());
+    assert_eq!(
+        fixtures::signed_transaction(&server.requests_for("broadcast_tx_async")[0]),
+        Some(signed_transaction)
+    );
+}
+
+#[tokio::test]
+async fn test_tx_status_with_receipts() {
+    let server = TestRpcServer::start().with_fixtures();
+    let provider = JsonRpcProvider::new(&server.url());
+    let signed_transaction = signed_transaction();
+
+    provider
+        .send_transaction_async(signed_transaction.clone())
+        .await
+        .unwrap();
+    let transaction_info = TransactionInfo::TransactionId {
+        tx_hash: signed_transaction.get_hash(),
+        sender_account_id: signed_transaction.transaction.signer_id.clone(),
+    };
+    let response = provider
+        .tx_status_with_receipts(transaction_info, TxExecutionStatus::Final)
+        .await
+        .unwrap();
+    let outcome = response.final_execution_outcome.unwrap();
+    let receipts = outcome.receipts;
+    assert_eq!(receipts.len(), 1);
+    assert_eq!(
+        outcome
+            .final_outcome
+            .transaction_outcome
+            .outcome
+            .receipt_ids,
+        [receipts[0].receipt_id]
+    );
+    let params = &server.requests_for("EXPERIMENTAL_tx_status")[0];
+    assert_eq!(params["tx_hash"], json!(signed_transaction.get_hash()));
+    assert_eq!(params["wait_until"], "FINAL");
+
+    let receipt = provider.receipt(receipts[0].receipt_id).await.unwrap();
+    assert_eq!(receipt, receipts[0]);
+    assert_eq!(receipt.receiver_id, "bob.test.near");
+
+    assert!(matches!(
+        provider.receipt(CryptoHash::default()).await,
+        Err(JsonRpcError::ServerError(JsonRpcServerError::HandlerError(
+            RpcReceiptError::UnknownReceipt { .. }
+        )))
+    ));
+}
+
+#[tokio::test]
+async fn test_builder_headers() {
+    let server = TestRpcServer::start().with_fixtures();
+    let provider = JsonRpcProvider::builder(&server.url())
+        .with_api_key("secret-key")
+        .with_bearer_token("secret-token")
+        .with_user_agent("near-providers-test/1.0")
+        .with_header("x-request-source", "tests")
+        .build()
+        .unwrap();
+
+    let status = provider.status().await.unwrap();
+    assert_eq!(status.chain_id, fixtures::CHAIN_ID);
+    let headers = &server.requests()[0].headers;
+    assert_eq!(headers["x-api-key"], "secret-key");
+    assert_eq!(headers["authorization"], "Bearer secret-token");
+    assert_eq!(headers["user-agent"], "near-providers-test/1.0");
+    assert_eq!(headers["x-request-source"], "tests");
+    assert_eq!(headers["content-type"], "application/json");
+
+    // A provided HTTP client gets the headers of the builder as well.
+    let http_client = reqwest::Client::builder().build().unwrap();
+    let provider = JsonRpcProvider::builder(&server.url())
+        .with_http_client(http_client)
+        .with_api_key("other-key")
+        .build()
+        .unwrap();
+    provider.status().await.unwrap();
+    let headers = &server.requests()[1].headers;
+    assert_eq!(headers["x-api-key"], "other-key");
+    assert_eq!(headers["content-type"], "application/json");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_builder_timeout() {
+    let server = TestRpcServer::start().with_fixtures();
+    server.on("status", |_| {
+        std::thread::sleep(Duration::from_millis(500));
+        RpcResponse::Result(json!({}))
+    });
+    let provider = JsonRpcProvider::builder(&server.url())
+        .with_timeout(Duration::from_millis(100))
+        .build()
+        .unwrap();
+
+    assert!(matches!(
+        provider.status().await,
+        Err(JsonRpcError::TransportError(RpcTransportError::SendError(
+            JsonRpcTransportSendError::PayloadSendError(err)
+        ))) if err.is_timeout()
+    ));
+}
+
+#[test]
+fn test_builder_errors() {
+    assert!(matches!(
+        JsonRpcProvider::builder("http://127.0.0.1:3030")
+            .with_header("invalid header", "value")
+            .build(),
+        Err(JsonRpcProviderBuildError::InvalidHeaderName(name)) if name == "invalid header"
+    ));
+    assert!(matches!(
+        JsonRpcProvider::builder("http://127.0.0.1:3030")
+            .with_api_key("invalid\nkey")
+            .build(),
+        Err(JsonRpcProviderBuildError::InvalidHeaderValue(name)) if name == "x-api-key"
+    ));
+    assert!(matches!(
+        JsonRpcProvider::builder("http://127.0.0.1:3030")
+            .with_http_client(reqwest::Client::new())
+            .with_timeout(Duration::from_secs(1))
+            .build(),
+        Err(JsonRpcProviderBuildError::ConflictingHttpClient)
+    ));
+}
+
+/// Answers `view_account` queries with a balance of 1 NEAR, except for `missing.test.near` which does not exist.
+fn on_view_account(server: &TestRpcServer) {
+    server.on("query", |params| {
+        if params["account_id"] == "missing.test.near" {
+            return RpcResponse::handler_error(
+                "UNKNOWN_ACCOUNT",
+                json!({
+                    "requested_account_id": "missing.test.near",
+                    "block_height": 100,
+                    "block_hash": CryptoHash::default(),
+                }),
+            );
+        }
+        RpcResponse::Result(fixtures::account(
+            10_u128.pow(24),
+            100,
+            CryptoHash::default(),
+        ))
+    });
+}
+
+fn view_accounts(account_ids: &[&str]) -> Vec<(QueryRequest, BlockReference)> {
+    account_ids
+        .iter()
+        .map(|account_id| {
+            let request = QueryRequest::ViewAccount {
+                account_id: account_id.parse().unwrap(),
+            };
+            (request, BlockReference::Finality(Finality::Final))
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_batch() {
+    let server = TestRpcServer::start().with_fixtures();
+    on_view_account(&server);
+    let provider = JsonRpcProvider::builder(&server.url())
+        .with_batch_size(3)
+        .build()
+        .unwrap();
+
+    let final_block = BlockReference::Finality(Finality::Final);
+    let mut requests: Vec<ProviderRequest> =
+        view_accounts(&["alice.test.near", "missing.test.near"])
+            .into_iter()
+            .map(|(request, block_reference)| ProviderRequest::Query {
+                request,
+                block_reference,
+            })
+            .collect();
+    requests.push(ProviderRequest::Status);
+    requests.push(ProviderRequest::Block {
+        block_reference: final_block.clone(),
+    });
+    let responses = provider.batch(requests).await;
+
+    // The query and block requests are sent as one batch payload, the status request on its own.
+    assert_eq!(server.http_request_count(), 2);
+    assert_eq!(server.requests().len(), 4);
+    assert!(matches!(
+        &responses[0],
+        ProviderResponse::Query(Ok(response))
+            if matches!(&response.kind, QueryResponseKind::ViewAccount(account) if account.amount == 10_u128.pow(24))
+    ));
+    assert!(matches!(
+        &responses[1],
+        ProviderResponse::Query(Err(JsonRpcError::ServerError(
+            JsonRpcServerError::HandlerError(RpcQueryError::UnknownAccount { .. })
+        )))
+    ));
+    assert!(matches!(&responses[2], ProviderResponse::Status(Ok(_))));
+    assert!(
+        matches!(&responses[3], ProviderResponse::Block(Ok(block)) if block.header.height == 100)
+    );
+
+    // Larger batches are split into payloads of at most `batch_size` requests.
+    let results = provider
+        .query_batch(view_accounts(&[
+            "alice.test.near",
+            "bob.test.near",
+            "carol.test.near",
+            "missing.test.near",
+        ]))
+        .await;
+    assert_eq!(server.http_request_count(), 4);
+    assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 3);
+    assert!(results[3].is_err());
+}
+
+#[tokio::test]
+async fn test_batch_fallback() {
+    let server = TestRpcServer::start();
+    on_view_account(&server);
+    server.reject_batches();
+    let provider = JsonRpcProvider::new(&server.url());
+
+    let accounts = ["alice.test.near", "missing.test.near", "bob.test.near"];
+    let results = provider.query_batch(view_accounts(&accounts)).await;
+    // The rejected batch payload is followed by a request per query.
+    assert_eq!(server.http_request_count(), 4);
+    assert!(results[0].is_ok());
+    assert!(matches!(
+        &results[1],
+        Err(JsonRpcError::ServerError(JsonRpcServerError::HandlerError(
+            RpcQueryError::UnknownAccount { .. }
+        )))
+    ));
+    assert!(results[2].is_ok());
+
+    // The provider remembers that the endpoint has no batch support.
+    let results = provider.query_batch(view_accounts(&accounts)).await;
+    assert_eq!(server.http_request_count(), 7);
+    assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 2);
+}
+
+#[tokio::test]
+async fn test_batch_rate_limit_without_batch_support() {
+    let server = TestRpcServer::start();
+    on_view_account(&server);
+    server.reject_batches();
+    let provider = RateLimitProvider::new(
+        JsonRpcProvider::new(&server.url()),
+        RateLimit::per_second(10.0),
+    )
+    .unwrap();
+    let accounts = ["alice.test.near", "bob.test.near", "carol.test.near"];
+
+    // The first payload is rejected, after which the provider sends every request on its own.
+    provider.query_batch(view_accounts(&accounts)).await;
+    assert_eq!(server.http_request_count(), 4);
+
+    // Each of those requests waits for a token of its own, rather than one token letting them all through.
+    let started = Instant::now();
+    let results = provider.query_batch(view_accounts(&accounts)).await;
+    assert!(results.iter().all(Result::is_ok));
+    assert_eq!(server.http_request_count(), 7);
+    assert!(started.elapsed() >= Duration::from_millis(250));
+}
+
+/// Sends a batch of `view_account` queries through `provider` and checks that it reaches `server` as a single HTTP
+/// request.
+async fn assert_one_http_request<P: Provider + Sync>(server: &TestRpcServer, provider: &P) {
+    let before = server.http_request_count();
+    let accounts = ["alice.test.near", "missing.test.near", "bob.test.near"];
+    let results = provider.query_batch(view_accounts(&accounts)).await;
+    assert_eq!(server.http_request_count(), before + 1);
+    assert!(results[0].is_ok());
+    assert!(results[1].is_err());
+    assert!(results[2].is_ok());
+}
+
+#[tokio::test]
+async fn test_batch_through_wrappers() {
+    let server = TestRpcServer::start().with_fixtures();
+    on_view_account(&server);
+    let provider = || JsonRpcProvider::new(&server.url());
+
+    assert_one_http_request(&server, &RetryProvider::new(provider())).await;
+    let limit = RateLimit::per_second(100.0).with_max_in_flight(1);
+    assert_one_http_request(&server, &RateLimitProvider::new(provider(), limit).unwrap()).await;
+    assert_one_http_request(&server, &CachingProvider::new(provider())).await;
+    assert_one_http_request(&server, &FailoverProvider::new(vec![Box::new(provider())])).await;
+    assert_one_http_request(&server, &ArchivalProvider::new(provider(), provider())).await;
+    assert_one_http_request(&server, &RecordingProvider::new(provider())).await;
+    let layered = ProviderBuilder::new()
+        .with_layer(RetryLayer::new())
+        .with_layer(CacheLayer::new())
+        .build(provider());
+    assert_one_http_request(&server, &layered).await;
+}
+
+#[tokio::test]
+async fn test_batch_wrapper_concerns() {
+    let server = TestRpcServer::start().with_fixtures();
+    let flaky = Arc::new(AtomicBool::new(true));
+    let failing = flaky.clone();
+    server.on("query", move |params| {
+        if params["account_id"] == "flaky.test.near" && failing.swap(false, Ordering::SeqCst) {
+            return RpcResponse::internal_error("flaky");
+        }
+        RpcResponse::Result(fixtures::account(1, 100, CryptoHash::default()))
+    });
+
+    // Only the item that failed with a retryable error is sent again, as a batch of its own.
+    let policy = RetryPolicy {
+        initial_backoff: Duration::from_millis(1),
+        ..RetryPolicy::default()
+    };
+    let retry = RetryProvider::new(JsonRpcProvider::new(&server.url())).with_policy(policy);
+    let results = retry
+        .query_batch(view_accounts(&["alice.test.near", "flaky.test.near"]))
+        .await;
+    assert!(results.iter().all(|result| result.is_ok()));
+    assert_eq!(server.http_request_count(), 2);
+    assert_eq!(server.requests_for("query").len(), 3);
+
+    // Cached blocks are answered without reaching the server, the other requests are sent as one batch.
+    let caching = CachingProvider::new(JsonRpcProvider::new(&server.url()));
+    let requests = || {
+        vec![
+            ProviderRequest::Block {
+                block_reference: BlockReference::Finality(Finality::Final),
+            },
+            ProviderRequest::Query {
+                request: QueryRequest::ViewAccount {
+                    account_id: "alice.test.near".parse().unwrap(),
+                },
+                block_reference: BlockReference::Finality(Finality::Final),
+            },
+        ]
+    };
+    caching.batch(requests()).await;
+    let responses = caching.batch(requests()).await;
+    assert!(matches!(&responses[0], ProviderResponse::Block(Ok(_))));
+    assert!(matches!(&responses[1], ProviderResponse::Query(Ok(_))));
+    assert_eq!(server.http_request_count(), 4);
+    assert_eq!(server.requests_for("block").len(), 1);
+
+    // Requests below the height threshold go to the archival node, the others to the regular node, one batch each.
+    let archival_server = TestRpcServer::start().with_fixtures();
+    on_view_account(&archival_server);
+    let archival = ArchivalProvider::new(
+        JsonRpcProvider::new(&server.url()),
+        JsonRpcProvider::new(&archival_server.url()),
+    )
+    .with_height_threshold(50);
+    let old_block = BlockReference::BlockId(BlockId::Height(10));
+    let mut queries = view_accounts(&["alice.test.near"]);
+    queries.extend(
+        view_accounts(&["bob.test.near", "carol.test.near"])
+            .into_iter()
+            .map(|(request, _)| (request, old_block.clone())),
+    );
+    let results = archival.query_batch(queries).await;
+    assert!(results.iter().all(|result| result.is_ok()));
+    assert_eq!(server.http_request_count(), 5);
+    assert_eq!(archival_server.http_request_count(), 1);
+    assert_eq!(archival_server.requests_for("query").len(), 2);
+}
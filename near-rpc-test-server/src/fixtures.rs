@@ -0,0 +1,371 @@
+//! Canned JSON results for the NEAR RPC methods, shaped like the responses of a real node.
+//!
+//! The fixtures are built from the `near-primitives` views, so they deserialize into the types
+//! expected by `near-jsonrpc-client`. They are used by `TestRpcServer::with_fixtures` and can be
+//! returned from custom handlers as well.
+
+use near_chain_configs::{GenesisConfig, ProtocolConfig, ProtocolConfigView};
+use near_crypto::{KeyType, PublicKey, Signature};
+use near_parameters::RuntimeConfig;
+use near_primitives::{
+    borsh::BorshDeserialize,
+    hash::CryptoHash,
+    serialize::from_base64,
+    transaction::SignedTransaction,
+    types::{AccountId, Balance, BlockHeight, EpochHeight},
+    views::{
+        AccessKeyPermissionView, AccessKeyView, AccountView, ActionView, BlockHeaderView,
+        BlockView, ChunkHeaderView, ChunkView, EpochValidatorInfo, ExecutionMetadataView,
+        ExecutionOutcomeView, ExecutionOutcomeWithIdView, ExecutionStatusView,
+        FinalExecutionOutcomeView, FinalExecutionStatus, GasPriceView, ReceiptEnumView,
+        ReceiptView, SignedTransactionView, StateChangeCauseView, StateChangeKindView,
+        StateChangeValueView, StateChangeWithCauseView, TxExecutionStatus,
+    },
+};
+use serde_json::{json, Value};
+
+/// The chain id reported by the fixtures.
+pub const CHAIN_ID: &str = "localnet";
+
+/// The result of `status`, for a node whose latest block is at `latest_block_height` with `latest_block_hash`.
+pub fn status(latest_block_height: BlockHeight, latest_block_hash: CryptoHash) -> Value {
+    json!({
+        "version": { "version": "1.38.0", "build": "test", "rustc_version": "1.77.0" },
+        "chain_id": CHAIN_ID,
+        "protocol_version": near_primitives::version::PROTOCOL_VERSION,
+        "latest_protocol_version": near_primitives::version::PROTOCOL_VERSION,
+        "rpc_addr": "127.0.0.1:3030",
+        "validators": [{ "account_id": "test.near", "is_slashed": false }],
+        "sync_info": {
+            "latest_block_hash": latest_block_hash,
+            "latest_block_height": latest_block_height,
+            "latest_state_root": CryptoHash::default(),
+            "latest_block_time": "2024-04-01T00:00:00.000000000Z",
+            "syncing": false,
+            "earliest_block_hash": CryptoHash::default(),
+            "earliest_block_height": 0,
+            "earliest_block_time": "2024-03-01T00:00:00.000000000Z",
+            "epoch_id": CryptoHash::default(),
+            "epoch_start_height": 0
+        },
+        "validator_account_id": null,
+        "validator_public_key": null,
+        "node_public_key": PublicKey::empty(KeyType::ED25519),
+        "node_key": null,
+        "uptime_sec": 1
+    })
+}
+
+/// The result of `block`, for a block without chunks at `height` with `hash`.
+pub fn block(height: BlockHeight, hash: CryptoHash) -> Value {
+    let block = BlockView {
+        author: "test.near".parse().unwrap(),
+        header: BlockHeaderView {
+            height,
+            prev_height: height.checked_sub(1),
+            epoch_id: CryptoHash::default(),
+            next_epoch_id: CryptoHash::default(),
+            hash,
+            prev_hash: CryptoHash::default(),
+            prev_state_root: CryptoHash::default(),
+            block_body_hash: None,
+            chunk_receipts_root: CryptoHash::default(),
+            chunk_headers_root: CryptoHash::default(),
+            chunk_tx_root: CryptoHash::default(),
+            outcome_root: CryptoHash::default(),
+            chunks_included: 0,
+            challenges_root: CryptoHash::default(),
+            timestamp: 0,
+            timestamp_nanosec: 0,
+            random_value: CryptoHash::default(),
+            validator_proposals: vec![],
+            chunk_mask: vec![],
+            gas_price: 100_000_000,
+            block_ordinal: Some(height),
+            rent_paid: 0,
+            validator_reward: 0,
+            total_supply: 0,
+            challenges_result: vec![],
+            last_final_block: CryptoHash::default(),
+            last_ds_final_block: CryptoHash::default(),
+            next_bp_hash: CryptoHash::default(),
+            block_merkle_root: CryptoHash::default(),
+            epoch_sync_data_hash: None,
+            approvals: vec![],
+            signature: Signature::empty(KeyType::ED25519),
+            latest_protocol_version: near_primitives::version::PROTOCOL_VERSION,
+        },
+        chunks: vec![],
+    };
+    serde_json::to_value(block).unwrap()
+}
+
+/// The result of `chunk`, for an empty chunk of shard 0 with `chunk_hash`, included at `height`.
+pub fn chunk(chunk_hash: CryptoHash, height: BlockHeight) -> Value {
+    let chunk = ChunkView {
+        author: "test.near".parse().unwrap(),
+        header: ChunkHeaderView {
+            chunk_hash,
+            prev_block_hash: CryptoHash::default(),
+            outcome_root: CryptoHash::default(),
+            prev_state_root: CryptoHash::default(),
+            encoded_merkle_root: CryptoHash::default(),
+            encoded_length: 0,
+            height_created: height,
+            height_included: height,
+            shard_id: 0,
+            gas_used: 0,
+            gas_limit: 1_000_000_000_000_000,
+            rent_paid: 0,
+            validator_reward: 0,
+            balance_burnt: 0,
+            outgoing_receipts_root: CryptoHash::default(),
+            tx_root: CryptoHash::default(),
+            validator_proposals: vec![],
+            signature: Signature::empty(KeyType::ED25519),
+        },
+        transactions: vec![],
+        receipts: vec![],
+    };
+    serde_json::to_value(chunk).unwrap()
+}
+
+/// The result of `validators`, for an epoch without validators.
+pub fn validators(epoch_height: EpochHeight) -> Value {
+    let validators = EpochValidatorInfo {
+        current_validators: vec![],
+        next_validators: vec![],
+        current_fishermen: vec![],
+        next_fishermen: vec![],
+        current_proposals: vec![],
+        prev_epoch_kickout: vec![],
+        epoch_start_height: 0,
+        epoch_height,
+    };
+    serde_json::to_value(validators).unwrap()
+}
+
+/// Adds the block fields of a `query` result to a view.
+fn query_result(view: Value, block_height: BlockHeight, block_hash: CryptoHash) -> Value {
+    let mut result = view;
+    result["block_height"] = json!(block_height);
+    result["block_hash"] = json!(block_hash);
+    result
+}
+
+/// The result of a `view_access_key` query, for a full access key with `nonce`.
+pub fn access_key(nonce: u64, block_height: BlockHeight, block_hash: CryptoHash) -> Value {
+    let access_key = AccessKeyView {
+        nonce,
+        permission: AccessKeyPermissionView::FullAccess,
+    };
+    query_result(
+        serde_json::to_value(access_key).unwrap(),
+        block_height,
+        block_hash,
+    )
+}
+
+/// The result of a `view_account` query, for an account without a contract holding `amount`.
+pub fn account(amount: Balance, block_height: BlockHeight, block_hash: CryptoHash) -> Value {
+    let account = AccountView {
+        amount,
+        locked: 0,
+        code_hash: CryptoHash::default(),
+        storage_usage: 182,
+        storage_paid_at: 0,
+    };
+    query_result(
+        serde_json::to_value(account).unwrap(),
+        block_height,
+        block_hash,
+    )
+}
+
+/// The result of `EXPERIMENTAL_changes_in_block`, for a block with hash `block_hash` that touched `account_ids`.
+pub fn changes_in_block(block_hash: CryptoHash, account_ids: &[AccountId]) -> Value {
+    let changes: Vec<_> = account_ids
+        .iter()
+        .map(|account_id| StateChangeKindView::AccountTouched {
+            account_id: account_id.clone(),
+        })
+        .collect();
+    json!({ "block_hash": block_hash, "changes": changes })
+}
+
+/// The result of `EXPERIMENTAL_changes` for account changes, where a transaction with hash `tx_hash` left each of
+/// `account_ids` holding `amount` in the block with hash `block_hash`.
+pub fn account_changes(
+    block_hash: CryptoHash,
+    tx_hash: CryptoHash,
+    account_ids: &[AccountId],
+    amount: Balance,
+) -> Value {
+    let changes: Vec<_> = account_ids
+        .iter()
+        .map(|account_id| StateChangeWithCauseView {
+            cause: StateChangeCauseView::TransactionProcessing { tx_hash },
+            value: StateChangeValueView::AccountUpdate {
+                account_id: account_id.clone(),
+                account: AccountView {
+                    amount,
+                    locked: 0,
+                    code_hash: CryptoHash::default(),
+                    storage_usage: 182,
+                    storage_paid_at: 0,
+                },
+            },
+        })
+        .collect();
+    json!({ "block_hash": block_hash, "changes": changes })
+}
+
+/// The result of `EXPERIMENTAL_protocol_config`, using the default genesis and the test runtime config.
+pub fn protocol_config() -> Value {
+    let protocol_config = ProtocolConfigView::from(ProtocolConfig {
+        genesis_config: GenesisConfig {
+            chain_id: CHAIN_ID.to_string(),
+            ..Default::default()
+        },
+        runtime_config: RuntimeConfig::test(),
+    });
+    serde_json::to_value(protocol_config).unwrap()
+}
+
+/// The result of `EXPERIMENTAL_genesis_config`, the default genesis with the fixtures' chain id.
+pub fn genesis_config() -> Value {
+    let genesis_config = GenesisConfig {
+        chain_id: CHAIN_ID.to_string(),
+        ..Default::default()
+    };
+    serde_json::to_value(genesis_config).unwrap()
+}
+
+/// The result of `gas_price`, for a block whose gas price is `gas_price`.
+pub fn gas_price(gas_price: Balance) -> Value {
+    serde_json::to_value(GasPriceView { gas_price }).unwrap()
+}
+
+/// The result of `network_info`, for a node connected to `num_active_peers` anonymous peers.
+pub fn network_info(num_active_peers: usize) -> Value {
+    let active_peers: Vec<_> = (0..num_active_peers)
+        .map(|i| {
+            json!({
+                "id": PublicKey::empty(KeyType::ED25519),
+                "addr": format!("127.0.0.1:{}", 24567 + i),
+                "account_id": null
+            })
+        })
+        .collect();
+    json!({
+        "active_peers": active_peers,
+        "num_active_peers": num_active_peers,
+        "peer_max_count": 40,
+        "sent_bytes_per_sec": 0,
+        "received_bytes_per_sec": 0,
+        "known_producers": []
+    })
+}
+
+/// The result of `client_config`, for a node tracking shard 0 that keeps the full history if `archive` is set.
+pub fn client_config(archive: bool) -> Value {
+    json!({
+        "version": { "version": "1.38.0", "build": "test", "rustc_version": "1.77.0" },
+        "chain_id": CHAIN_ID,
+        "archive": archive,
+        "tracked_accounts": [],
+        "tracked_shards": [0],
+        "min_num_peers": 1,
+        "epoch_length": 500,
+        "block_fetch_horizon": 50
+    })
+}
+
+/// The result of `broadcast_tx_commit`, for a successful transaction without receipts.
+pub fn final_execution_outcome(signed_transaction: &SignedTransaction) -> Value {
+    let outcome = FinalExecutionOutcomeView {
+        status: FinalExecutionStatus::SuccessValue(vec![]),
+        transaction: SignedTransactionView::from(signed_transaction.clone()),
+        transaction_outcome: ExecutionOutcomeWithIdView {
+            proof: vec![],
+            block_hash: CryptoHash::default(),
+            id: signed_transaction.get_hash(),
+            outcome: ExecutionOutcomeView {
+                logs: vec![],
+                receipt_ids: vec![],
+                gas_burnt: 0,
+                tokens_burnt: 0,
+                executor_id: signed_transaction.transaction.signer_id.clone(),
+                status: ExecutionStatusView::SuccessValue(vec![]),
+                metadata: ExecutionMetadataView::default(),
+            },
+        },
+        receipts_outcome: vec![],
+    };
+    serde_json::to_value(outcome).unwrap()
+}
+
+/// The result of `send_tx` and `tx`, for a successful transaction that reached `wait_until`.
+pub fn transaction_outcome(
+    signed_transaction: &SignedTransaction,
+    wait_until: TxExecutionStatus,
+) -> Value {
+    let mut result = final_execution_outcome(signed_transaction);
+    result["final_execution_status"] = json!(wait_until);
+    result
+}
+
+/// The id of the action receipt created by `signed_transaction` in the fixtures.
+pub fn receipt_id(signed_transaction: &SignedTransaction) -> CryptoHash {
+    CryptoHash::hash_bytes(signed_transaction.get_hash().as_ref())
+}
+
+/// The result of `EXPERIMENTAL_receipt`, for the action receipt created by `signed_transaction`.
+pub fn receipt(signed_transaction: &SignedTransaction) -> Value {
+    serde_json::to_value(receipt_view(signed_transaction)).unwrap()
+}
+
+fn receipt_view(signed_transaction: &SignedTransaction) -> ReceiptView {
+    let transaction = &signed_transaction.transaction;
+    ReceiptView {
+        predecessor_id: transaction.signer_id.clone(),
+        receiver_id: transaction.receiver_id.clone(),
+        receipt_id: receipt_id(signed_transaction),
+        receipt: ReceiptEnumView::Action {
+            signer_id: transaction.signer_id.clone(),
+            signer_public_key: transaction.public_key.clone(),
+            gas_price: 100_000_000,
+            output_data_receivers: vec![],
+            input_data_ids: vec![],
+            actions: transaction
+                .actions
+                .iter()
+                .cloned()
+                .map(ActionView::from)
+                .collect(),
+        },
+    }
+}
+
+/// The result of `EXPERIMENTAL_tx_status`, for a successful transaction that reached `wait_until`, whose outcome
+/// lists the receipt returned by `receipt`.
+pub fn transaction_outcome_with_receipts(
+    signed_transaction: &SignedTransaction,
+    wait_until: TxExecutionStatus,
+) -> Value {
+    let mut result = transaction_outcome(signed_transaction, wait_until);
+    result["transaction_outcome"]["outcome"]["receipt_ids"] =
+        json!([receipt_id(signed_transaction)]);
+    result["receipts"] = json!([receipt_view(signed_transaction)]);
+    result
+}
+
+/// Decodes the signed transaction sent in the params of `send_tx`, `tx`, `broadcast_tx_commit` or `broadcast_tx_async`.
+pub fn signed_transaction(params: &Value) -> Option<SignedTransaction> {
+    let encoded = params
+        .get("signed_tx_base64")
+        .or_else(|| params.get(0))?
+        .as_str()?;
+    let bytes = from_base64(encoded).ok()?;
+    SignedTransaction::try_from_slice(&bytes).ok()
+}
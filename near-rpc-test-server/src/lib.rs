@@ -0,0 +1,24 @@
+//! `near-rpc-test-server` crate provides a local HTTP server speaking the NEAR JSON RPC protocol,
+//! for testing RPC clients such as the `JsonRpcProvider` of `near-providers` end to end without a
+//! network connection.
+//!
+//! The `TestRpcServer` listens on an ephemeral port and dispatches requests by method name
+//! (`status`, `query`, `block`, `chunk`, `send_tx`, `tx`, `broadcast_tx_async`,
+//! `broadcast_tx_commit`, `validators`, `EXPERIMENTAL_protocol_config`) to programmable handlers.
+//! Handlers answer with a result, a JSON RPC error shaped like the ones of a real node, or a bare
+//! HTTP status, so both the serialization of requests and the mapping of errors can be verified.
+//! JSON RPC batches are answered request by request, unless the server is told to reject them.
+//! The `fixtures` module provides canned results for every method.
+//!
+//! The `TestS3Server` stands in for an S3-compatible object storage such as MinIO, answering the
+//! GetObject and ListObjectsV2 requests of path-style URLs from objects stored in memory.
+
+/// Re-export the TestRpcServer
+pub use crate::server::{RpcRequest, RpcResponse, TestRpcServer};
+
+/// Re-export the TestS3Server
+pub use crate::s3::{S3Request, TestS3Server};
+
+pub mod fixtures;
+mod s3;
+mod server;
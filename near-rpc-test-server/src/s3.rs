@@ -0,0 +1,281 @@
+//! The `s3` module implements `TestS3Server`, a local stand-in for an S3-compatible object storage such as MinIO,
+//! serving objects from memory.
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+
+/// The maximum number of keys and common prefixes listed at once, unless the request asks for fewer.
+const MAX_KEYS: usize = 1000;
+
+const XML: &str = "application/xml";
+
+/// An HTTP request received by the `TestS3Server`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct S3Request {
+    pub method: String,
+    /// The decoded path of the request, starting with the bucket, e.g. `/bucket/key`.
+    pub path: String,
+    /// The decoded query parameters of the request.
+    pub query: HashMap<String, String>,
+    /// The HTTP headers of the request, keyed by lowercase name.
+    pub headers: HashMap<String, String>,
+}
+
+#[derive(Default)]
+struct State {
+    /// The objects of every bucket, keyed by bucket and key.
+    objects: Mutex<BTreeMap<(String, String), Vec<u8>>>,
+    requests: Mutex<Vec<S3Request>>,
+}
+
+impl State {
+    fn handle(&self, request: S3Request) -> (StatusCode, &'static str, Vec<u8>) {
+        self.requests.lock().unwrap().push(request.clone());
+        if request.method != Method::GET.as_str() {
+            let (content_type, body) = error("MethodNotAllowed", "Only GET requests are supported");
+            return (StatusCode::METHOD_NOT_ALLOWED, content_type, body);
+        }
+        let path = request.path.trim_start_matches('/');
+        match path.split_once('/') {
+            Some((bucket, key)) if !key.is_empty() => self.get_object(bucket, key),
+            _ if request.query.get("list-type").map(String::as_str) == Some("2") => {
+                self.list_objects(path.trim_end_matches('/'), &request.query)
+            }
+            _ => {
+                let (content_type, body) = error(
+                    "NotImplemented",
+                    "Only GetObject and ListObjectsV2 are supported",
+                );
+                (StatusCode::NOT_IMPLEMENTED, content_type, body)
+            }
+        }
+    }
+
+    fn get_object(&self, bucket: &str, key: &str) -> (StatusCode, &'static str, Vec<u8>) {
+        let objects = self.objects.lock().unwrap();
+        match objects.get(&(bucket.to_string(), key.to_string())) {
+            Some(body) => (StatusCode::OK, "application/octet-stream", body.clone()),
+            None => {
+                let (content_type, body) = error("NoSuchKey", "The specified key does not exist.");
+                (StatusCode::NOT_FOUND, content_type, body)
+            }
+        }
+    }
+
+    /// Lists the keys of `bucket` after `start-after` with the given `prefix`, grouping the keys sharing a prefix up
+    /// to the `delimiter` into common prefixes, as ListObjectsV2 does.
+    fn list_objects(
+        &self,
+        bucket: &str,
+        query: &HashMap<String, String>,
+    ) -> (StatusCode, &'static str, Vec<u8>) {
+        let prefix = query.get("prefix").cloned().unwrap_or_default();
+        let start_after = query.get("start-after").cloned().unwrap_or_default();
+        let delimiter = query
+            .get("delimiter")
+            .filter(|delimiter| !delimiter.is_empty());
+        let max_keys = query
+            .get("max-keys")
+            .and_then(|max_keys| max_keys.parse().ok())
+            .unwrap_or(MAX_KEYS)
+            .min(MAX_KEYS);
+
+        let objects = self.objects.lock().unwrap();
+        let mut contents = Vec::new();
+        let mut common_prefixes = BTreeSet::new();
+        let mut truncated = false;
+        let keys = objects
+            .iter()
+            .filter(|((object_bucket, key), _)| {
+                object_bucket == bucket && key.starts_with(&prefix) && *key > start_after
+            })
+            .map(|((_, key), body)| (key, body.len()));
+        for (key, size) in keys {
+            let common_prefix = delimiter.and_then(|delimiter| {
+                let end = key[prefix.len()..].find(delimiter.as_str())?;
+                Some(key[..prefix.len() + end + delimiter.len()].to_string())
+            });
+            if common_prefix
+                .as_ref()
+                .is_some_and(|common_prefix| common_prefixes.contains(common_prefix))
+            {
+                continue;
+            }
+            if contents.len() + common_prefixes.len() == max_keys {
+                truncated = true;
+                break;
+            }
+            match common_prefix {
+                Some(common_prefix) => {
+                    common_prefixes.insert(common_prefix);
+                }
+                None => contents.push(format!(
+                    "<Contents><Key>{}</Key><Size>{}</Size></Contents>",
+                    key, size
+                )),
+            }
+        }
+
+        let common_prefixes: String = common_prefixes
+            .iter()
+            .map(|prefix| {
+                format!(
+                    "<CommonPrefixes><Prefix>{}</Prefix></CommonPrefixes>",
+                    prefix
+                )
+            })
+            .collect();
+        let body = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+             <ListBucketResult xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">\
+             <Name>{}</Name><Prefix>{}</Prefix><KeyCount>{}</KeyCount><MaxKeys>{}</MaxKeys>\
+             <IsTruncated>{}</IsTruncated>{}{}</ListBucketResult>",
+            bucket,
+            prefix,
+            contents.len() + common_prefixes.len(),
+            max_keys,
+            truncated,
+            contents.concat(),
+            common_prefixes,
+        );
+        (StatusCode::OK, XML, body.into_bytes())
+    }
+}
+
+/// Builds the XML body of an S3 error response.
+fn error(code: &str, message: &str) -> (&'static str, Vec<u8>) {
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?><Error><Code>{}</Code><Message>{}</Message></Error>",
+        code, message
+    );
+    (XML, body.into_bytes())
+}
+
+/// Decodes the percent-encoded characters of a path or query component.
+fn decode(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let hex = bytes
+            .get(index + 1..index + 3)
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (bytes[index], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                index += 1;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// A local server answering the GetObject and ListObjectsV2 requests of an S3-compatible client with path-style
+/// URLs, e.g. `http://127.0.0.1:9000/bucket/key`, listening on an ephemeral port of `127.0.0.1`.
+///
+/// Objects are stored in memory with `put_object`. Requests are not authenticated, but are recorded with their
+/// headers and can be inspected with `requests`. The server shuts down when it is dropped.
+pub struct TestS3Server {
+    addr: SocketAddr,
+    state: Arc<State>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl TestS3Server {
+    /// Starts a server without any objects. Must be called from within a Tokio runtime.
+    pub fn start() -> Self {
+        let state = Arc::new(State::default());
+        let service_state = state.clone();
+        let make_service = make_service_fn(move |_| {
+            let state = service_state.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                    let state = state.clone();
+                    async move {
+                        let query = request
+                            .uri()
+                            .query()
+                            .unwrap_or_default()
+                            .split('&')
+                            .filter(|pair| !pair.is_empty())
+                            .map(|pair| {
+                                let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                                (decode(name), decode(value))
+                            })
+                            .collect();
+                        let headers = request
+                            .headers()
+                            .iter()
+                            .filter_map(|(name, value)| {
+                                Some((name.to_string(), value.to_str().ok()?.to_string()))
+                            })
+                            .collect();
+                        let (status, content_type, body) = state.handle(S3Request {
+                            method: request.method().to_string(),
+                            path: decode(request.uri().path()),
+                            query,
+                            headers,
+                        });
+                        Ok::<_, Infallible>(
+                            Response::builder()
+                                .status(status)
+                                .header("content-type", content_type)
+                                .body(Body::from(body))
+                                .unwrap(),
+                        )
+                    }
+                }))
+            }
+        });
+
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service);
+        let addr = server.local_addr();
+        let (shutdown, shutdown_signal) = oneshot::channel::<()>();
+        tokio::spawn(server.with_graceful_shutdown(async {
+            shutdown_signal.await.ok();
+        }));
+
+        Self {
+            addr,
+            state,
+            shutdown: Some(shutdown),
+        }
+    }
+
+    /// Returns the endpoint URL to configure the S3 client with.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Stores `body` under `key` in `bucket`, replacing any previous object.
+    pub fn put_object(&self, bucket: &str, key: &str, body: impl Into<Vec<u8>>) {
+        let mut objects = self.state.objects.lock().unwrap();
+        objects.insert((bucket.to_string(), key.to_string()), body.into());
+    }
+
+    /// Returns the requests received so far, in order.
+    pub fn requests(&self) -> Vec<S3Request> {
+        self.state.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for TestS3Server {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
@@ -0,0 +1,395 @@
+//! The `server` module implements `TestRpcServer`, a local HTTP server answering NEAR JSON RPC requests
+//! with programmable handlers.
+
+use crate::fixtures;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server, StatusCode};
+use near_primitives::hash::CryptoHash;
+use near_primitives::transaction::SignedTransaction;
+use near_primitives::types::AccountId;
+use near_primitives::views::TxExecutionStatus;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+
+type Handler = Arc<dyn Fn(&Value) -> RpcResponse + Send + Sync>;
+
+/// A JSON RPC request received by the server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcRequest {
+    pub method: String,
+    pub params: Value,
+    /// The HTTP headers of the request, keyed by lowercase name.
+    pub headers: HashMap<String, String>,
+}
+
+/// The answer of a handler to a JSON RPC request.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RpcResponse {
+    /// A successful response carrying `result`.
+    Result(Value),
+    /// An error response carrying the JSON RPC `error` object.
+    Error(Value),
+    /// A response with the given HTTP status code and an empty body, e.g. 429 or 503.
+    Status(u16),
+}
+
+impl RpcResponse {
+    /// An error returned by the handler of the method, e.g. `UNKNOWN_ACCOUNT` for a query.
+    pub fn handler_error(name: &str, info: Value) -> Self {
+        RpcResponse::Error(json!({
+            "name": "HANDLER_ERROR",
+            "cause": { "name": name, "info": info },
+            "code": -32000,
+            "message": "Server error",
+            "data": name,
+        }))
+    }
+
+    /// An error raised by the node while processing the request.
+    pub fn internal_error(error_message: &str) -> Self {
+        RpcResponse::Error(json!({
+            "name": "INTERNAL_ERROR",
+            "cause": { "name": "INTERNAL_ERROR", "info": { "error_message": error_message } },
+            "code": -32000,
+            "message": "Server error",
+            "data": error_message,
+        }))
+    }
+
+    /// The error returned for a method the server has no handler for.
+    pub fn method_not_found(method: &str) -> Self {
+        RpcResponse::Error(json!({
+            "name": "REQUEST_VALIDATION_ERROR",
+            "cause": { "name": "METHOD_NOT_FOUND", "info": { "method_name": method } },
+            "code": -32601,
+            "message": "Method not found",
+            "data": method,
+        }))
+    }
+}
+
+#[derive(Default)]
+struct State {
+    handlers: Mutex<HashMap<String, Handler>>,
+    requests: Mutex<Vec<RpcRequest>>,
+    http_requests: AtomicUsize,
+    reject_batches: AtomicBool,
+}
+
+impl State {
+    fn handle(&self, headers: HashMap<String, String>, payload: &[u8]) -> (StatusCode, Value) {
+        self.http_requests.fetch_add(1, Ordering::SeqCst);
+        let payload: Value = match serde_json::from_slice(payload) {
+            Ok(payload) => payload,
+            Err(err) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    json!({ "jsonrpc": "2.0", "id": null, "error": {
+                        "code": -32700, "message": "Parse error", "data": err.to_string()
+                    }}),
+                )
+            }
+        };
+        let Value::Array(messages) = payload else {
+            return self.handle_message(&headers, &payload);
+        };
+        if self.reject_batches.load(Ordering::SeqCst) {
+            return (
+                StatusCode::OK,
+                json!({ "jsonrpc": "2.0", "id": null, "error": {
+                    "code": -32600, "message": "Invalid Request", "data": "Batch requests are not supported"
+                }}),
+            );
+        }
+        // A handler answering with a bare HTTP status fails the whole batch with it.
+        let mut responses = Vec::with_capacity(messages.len());
+        for message in &messages {
+            match self.handle_message(&headers, message) {
+                (StatusCode::OK, response) => responses.push(response),
+                failure => return failure,
+            }
+        }
+        (StatusCode::OK, Value::Array(responses))
+    }
+
+    fn handle_message(
+        &self,
+        headers: &HashMap<String, String>,
+        payload: &Value,
+    ) -> (StatusCode, Value) {
+        let id = payload["id"].clone();
+        let method = payload["method"].as_str().unwrap_or_default().to_string();
+        let params = payload["params"].clone();
+        self.requests.lock().unwrap().push(RpcRequest {
+            method: method.clone(),
+            params: params.clone(),
+            headers: headers.clone(),
+        });
+
+        let handler = self.handlers.lock().unwrap().get(&method).cloned();
+        let response = match handler {
+            Some(handler) => handler(&params),
+            None => RpcResponse::method_not_found(&method),
+        };
+        match response {
+            RpcResponse::Result(result) => (
+                StatusCode::OK,
+                json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            ),
+            RpcResponse::Error(error) => (
+                StatusCode::OK,
+                json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+            ),
+            RpcResponse::Status(status) => (
+                StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+                Value::Null,
+            ),
+        }
+    }
+}
+
+/// A local JSON RPC server listening on an ephemeral port of `127.0.0.1`.
+///
+/// Requests are dispatched by method name to the handler registered with `on`. Methods without a handler are
+/// answered with a `METHOD_NOT_FOUND` error, as a real node does. Every request is recorded and can be inspected with
+/// `requests`, including each request of a JSON RPC batch. The server shuts down when it is dropped.
+pub struct TestRpcServer {
+    addr: SocketAddr,
+    state: Arc<State>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl TestRpcServer {
+    /// Starts a server without any handlers. Must be called from within a Tokio runtime.
+    pub fn start() -> Self {
+        let state = Arc::new(State::default());
+        let service_state = state.clone();
+        let make_service = make_service_fn(move |_| {
+            let state = service_state.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                    let state = state.clone();
+                    async move {
+                        let headers = request
+                            .headers()
+                            .iter()
+                            .filter_map(|(name, value)| {
+                                Some((name.to_string(), value.to_str().ok()?.to_string()))
+                            })
+                            .collect();
+                        let payload = hyper::body::to_bytes(request.into_body())
+                            .await
+                            .unwrap_or_default();
+                        let (status, body) = state.handle(headers, &payload);
+                        let body = if body.is_null() {
+                            Body::empty()
+                        } else {
+                            Body::from(body.to_string())
+                        };
+                        Ok::<_, Infallible>(
+                            Response::builder()
+                                .status(status)
+                                .header("content-type", "application/json")
+                                .body(body)
+                                .unwrap(),
+                        )
+                    }
+                }))
+            }
+        });
+
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service);
+        let addr = server.local_addr();
+        let (shutdown, shutdown_signal) = oneshot::channel::<()>();
+        tokio::spawn(server.with_graceful_shutdown(async {
+            shutdown_signal.await.ok();
+        }));
+
+        Self {
+            addr,
+            state,
+            shutdown: Some(shutdown),
+        }
+    }
+
+    /// Registers canned handlers for every method used by `JsonRpcProvider`, answering with the `fixtures`.
+    ///
+    /// The chain is at block height 100, queries for access keys return nonce 0, and transactions succeed. The node
+    /// is healthy, connected to a single peer and not archival. Account changes report a balance of 1 NEAR for every
+    /// requested account, and other kinds of changes are empty. Transactions sent to the server can then be looked up
+    /// by hash with `tx` and `EXPERIMENTAL_tx_status`, and their receipt by id with `EXPERIMENTAL_receipt`.
+    pub fn with_fixtures(self) -> Self {
+        let block_hash = CryptoHash::hash_bytes(b"block");
+        self.on_result("status", fixtures::status(100, block_hash));
+        self.on_result("block", fixtures::block(100, block_hash));
+        self.on_result(
+            "chunk",
+            fixtures::chunk(CryptoHash::hash_bytes(b"chunk"), 100),
+        );
+        self.on_result("validators", fixtures::validators(1));
+        self.on_result("EXPERIMENTAL_protocol_config", fixtures::protocol_config());
+        self.on_result("EXPERIMENTAL_genesis_config", fixtures::genesis_config());
+        self.on_result("gas_price", fixtures::gas_price(100_000_000));
+        self.on_result("health", Value::Null);
+        self.on_result("network_info", fixtures::network_info(1));
+        self.on_result("client_config", fixtures::client_config(false));
+        self.on_result(
+            "EXPERIMENTAL_changes_in_block",
+            fixtures::changes_in_block(block_hash, &["test.near".parse().unwrap()]),
+        );
+        self.on("EXPERIMENTAL_changes", move |params| {
+            let account_ids: Vec<AccountId> =
+                serde_json::from_value(params["account_ids"].clone()).unwrap_or_default();
+            match params["changes_type"].as_str() {
+                Some("account_changes") => RpcResponse::Result(fixtures::account_changes(
+                    block_hash,
+                    CryptoHash::hash_bytes(b"transaction"),
+                    &account_ids,
+                    10_u128.pow(24),
+                )),
+                _ => RpcResponse::Result(json!({ "block_hash": block_hash, "changes": [] })),
+            }
+        });
+        self.on("query", move |params| {
+            match params["request_type"].as_str() {
+                Some("view_access_key") => {
+                    RpcResponse::Result(fixtures::access_key(0, 100, block_hash))
+                }
+                Some("view_account") => {
+                    RpcResponse::Result(fixtures::account(10_u128.pow(24), 100, block_hash))
+                }
+                _ => RpcResponse::internal_error("unsupported query request type"),
+            }
+        });
+        // Transactions sent to the server are remembered, so their status and receipt can be looked up by hash.
+        let transactions: Arc<Mutex<HashMap<CryptoHash, SignedTransaction>>> = Default::default();
+        let known = transactions.clone();
+        let transaction = move |params: &Value| -> Result<SignedTransaction, RpcResponse> {
+            if let Some(tx) = fixtures::signed_transaction(params) {
+                known.lock().unwrap().insert(tx.get_hash(), tx.clone());
+                return Ok(tx);
+            }
+            let tx_hash = serde_json::from_value::<CryptoHash>(params["tx_hash"].clone())
+                .map_err(|_| RpcResponse::internal_error("missing signed transaction"))?;
+            known.lock().unwrap().get(&tx_hash).cloned().ok_or_else(|| {
+                RpcResponse::handler_error(
+                    "UNKNOWN_TRANSACTION",
+                    json!({ "requested_transaction_hash": tx_hash }),
+                )
+            })
+        };
+        let transaction = Arc::new(transaction);
+
+        for method in ["send_tx", "tx", "EXPERIMENTAL_tx_status"] {
+            let transaction = transaction.clone();
+            self.on(method, move |params| {
+                let wait_until = serde_json::from_value(params["wait_until"].clone())
+                    .unwrap_or(TxExecutionStatus::ExecutedOptimistic);
+                match transaction(params) {
+                    Ok(tx) if method == "EXPERIMENTAL_tx_status" => RpcResponse::Result(
+                        fixtures::transaction_outcome_with_receipts(&tx, wait_until),
+                    ),
+                    Ok(tx) => RpcResponse::Result(fixtures::transaction_outcome(&tx, wait_until)),
+                    Err(error) => error,
+                }
+            });
+        }
+        let commit = transaction.clone();
+        self.on("broadcast_tx_commit", move |params| match commit(params) {
+            Ok(tx) => RpcResponse::Result(fixtures::final_execution_outcome(&tx)),
+            Err(error) => error,
+        });
+        self.on("broadcast_tx_async", move |params| {
+            match transaction(params) {
+                Ok(tx) => RpcResponse::Result(json!(tx.get_hash())),
+                Err(error) => error,
+            }
+        });
+        self.on("EXPERIMENTAL_receipt", move |params| {
+            let receipt_id = serde_json::from_value::<CryptoHash>(params["receipt_id"].clone());
+            let transactions = transactions.lock().unwrap();
+            let tx = receipt_id.as_ref().ok().and_then(|receipt_id| {
+                transactions
+                    .values()
+                    .find(|tx| fixtures::receipt_id(tx) == *receipt_id)
+            });
+            match tx {
+                Some(tx) => RpcResponse::Result(fixtures::receipt(tx)),
+                None => RpcResponse::handler_error(
+                    "UNKNOWN_RECEIPT",
+                    json!({ "receipt_id": params["receipt_id"] }),
+                ),
+            }
+        });
+        self
+    }
+
+    /// Returns the URL to pass to `JsonRpcProvider::new`.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Returns the address the server listens on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Answers requests for `method` with `handler`, called with the request params. Replaces any previous handler.
+    pub fn on<F>(&self, method: &str, handler: F)
+    where
+        F: Fn(&Value) -> RpcResponse + Send + Sync + 'static,
+    {
+        let mut handlers = self.state.handlers.lock().unwrap();
+        handlers.insert(method.to_string(), Arc::new(handler));
+    }
+
+    /// Answers every request for `method` with `result`.
+    pub fn on_result(&self, method: &str, result: Value) {
+        self.on(method, move |_| RpcResponse::Result(result.clone()));
+    }
+
+    /// Answers every request for `method` with `response`.
+    pub fn respond_with(&self, method: &str, response: RpcResponse) {
+        self.on(method, move |_| response.clone());
+    }
+
+    /// Answers JSON RPC batches with a single `Invalid Request` error, as an endpoint without batch support does.
+    pub fn reject_batches(&self) {
+        self.state.reject_batches.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns the number of HTTP requests received so far, where a JSON RPC batch counts once.
+    pub fn http_request_count(&self) -> usize {
+        self.state.http_requests.load(Ordering::SeqCst)
+    }
+
+    /// Returns the requests received so far, in order.
+    pub fn requests(&self) -> Vec<RpcRequest> {
+        self.state.requests.lock().unwrap().clone()
+    }
+
+    /// Returns the params of the requests for `method` received so far, in order.
+    pub fn requests_for(&self, method: &str) -> Vec<Value> {
+        self.state
+            .requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|request| request.method == method)
+            .map(|request| request.params.clone())
+            .collect()
+    }
+}
+
+impl Drop for TestRpcServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}